@@ -0,0 +1,122 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::decompress::Compression;
+use crate::longpath;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// A tar archive, optionally wrapped in a compression layer (`.tar.gz`, `.tgz`, ...).
+    Tar(Option<Compression>),
+}
+
+impl ArchiveKind {
+    pub fn from_path(path: &Path) -> Option<ArchiveKind> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            return Some(ArchiveKind::Zip);
+        }
+        if name.ends_with(".tar") {
+            return Some(ArchiveKind::Tar(None));
+        }
+        let compression = Compression::from_path(path)?;
+        let stem = Path::new(&name).file_stem()?.to_str()?;
+        let is_short_form = ["tgz", "tbz2", "txz"].iter().any(|ext| name.ends_with(ext));
+        (stem.ends_with(".tar") || is_short_form).then_some(ArchiveKind::Tar(Some(compression)))
+    }
+}
+
+/// Display path of a file stored inside an archive: `archive.zip!inner/path.txt`.
+pub fn entry_path(archive: &Path, inner: &str) -> PathBuf {
+    PathBuf::from(format!("{}!{}", archive.display(), inner))
+}
+
+/// Calls `visit` with the virtual path and a reader for every regular file in
+/// the archive, in archive order.
+pub fn for_each_entry<F>(path: &Path, kind: ArchiveKind, mut visit: F) -> io::Result<()>
+where
+    F: FnMut(PathBuf, &mut dyn Read) -> io::Result<()>,
+{
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(fs::File::open(longpath::for_opening(path))?).map_err(io::Error::other)?;
+            for index in 0..archive.len() {
+                let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let inner = entry.name().map_err(io::Error::other)?.into_owned();
+                visit(entry_path(path, &inner), &mut entry)?;
+            }
+        }
+        ArchiveKind::Tar(compression) => {
+            let file = fs::File::open(longpath::for_opening(path))?;
+            let reader: Box<dyn Read> = match compression {
+                Some(compression) => compression.reader(file)?,
+                None => Box::new(file),
+            };
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let inner = entry.path()?.to_string_lossy().into_owned();
+                visit(entry_path(path, &inner), &mut entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_archive_kind_from_path() {
+        assert_eq!(ArchiveKind::from_path(Path::new("a.zip")), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::from_path(Path::new("a.tar")), Some(ArchiveKind::Tar(None)));
+        assert_eq!(ArchiveKind::from_path(Path::new("a.tar.gz")), Some(ArchiveKind::Tar(Some(Compression::Gzip))));
+        assert_eq!(ArchiveKind::from_path(Path::new("a.tgz")), Some(ArchiveKind::Tar(Some(Compression::Gzip))));
+        assert_eq!(ArchiveKind::from_path(Path::new("app.log.gz")), None);
+        assert_eq!(ArchiveKind::from_path(Path::new("notes.txt")), None);
+    }
+
+    #[test]
+    fn test_for_each_entry_zip_and_tar() {
+        let test_dir = tempdir().unwrap();
+
+        let zip_path = test_dir.path().join("bundle.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        writer.add_directory("docs/", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.start_file("docs/readme.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"zipped text").unwrap();
+        writer.finish().unwrap();
+
+        let tar_path = test_dir.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&tar_path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(10);
+        header.set_cksum();
+        builder.append_data(&mut header, "src/main.rs", &b"tarred rs\n"[..]).unwrap();
+        builder.finish().unwrap();
+
+        for (path, inner, content) in [(&zip_path, "docs/readme.txt", "zipped text"), (&tar_path, "src/main.rs", "tarred rs\n")] {
+            let mut entries = Vec::new();
+            for_each_entry(path, ArchiveKind::from_path(path).unwrap(), |virtual_path, reader| {
+                let mut text = String::new();
+                reader.read_to_string(&mut text)?;
+                entries.push((virtual_path, text));
+                Ok(())
+            }).unwrap();
+            assert_eq!(entries, vec![(entry_path(path, inner), content.to_string())]);
+        }
+
+        test_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+use clap::{Args as ClapArgs, Subcommand};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::SearchResult;
+use crate::json::{self, Value, json_string};
+use crate::output::pattern_label;
+use crate::patterns::PatternSpec;
+
+/// Arguments of `finder baseline`.
+#[derive(ClapArgs, Debug)]
+pub struct BaselineArgs {
+    #[command(subcommand)]
+    pub command: BaselineCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BaselineCommand {
+    /// Run a search and record its matches in FILE, for --baseline to leave them out
+    Update {
+        /// The baseline file to write
+        file: PathBuf,
+
+        /// The paths and options of the search, as given to finder, such as `. -f rules.txt`
+        #[arg(value_name = "SEARCH", trailing_var_arg = true, allow_hyphen_values = true)]
+        search: Vec<OsString>,
+    },
+}
+
+/// A match recorded in a baseline. The line itself is not kept, so that a
+/// baseline of secrets does not disclose them: the fingerprint identifies
+/// it, and still does when lines are added or removed above it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub path: String,
+    /// Where the match was when the baseline was written, for the reader.
+    pub line: usize,
+    pub rule: String,
+    pub fingerprint: String,
+}
+
+/// The matches that a search had when it was adopted, left out of the
+/// results with `--baseline` so that only the new ones are reported.
+#[derive(Debug, Default, PartialEq)]
+pub struct Baseline {
+    pub findings: Vec<Finding>,
+}
+
+/// `path` with `/` separators and without `./`, so that searching `.` and
+/// `src` match the same entries.
+fn normalized_path(path: &Path) -> String {
+    let parts: Vec<String> = path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    parts.join("/")
+}
+
+/// Identifies a match by its rule and its line, leading and trailing
+/// whitespace left out so that reindenting does not make it new.
+fn fingerprint(rule: &str, line: &str) -> String {
+    format!("{:016x}", xxh3_64(format!("{}\0{}", rule, line.trim()).as_bytes()))
+}
+
+fn finding(pattern_specs: &[PatternSpec], result: &SearchResult) -> Finding {
+    let rule = pattern_label(pattern_specs, result);
+    Finding {
+        path: normalized_path(&result.path),
+        line: result.line_number,
+        rule: rule.to_string(),
+        fingerprint: fingerprint(rule, &result.line),
+    }
+}
+
+impl Baseline {
+    /// The baseline of the results of a search.
+    pub fn of(pattern_specs: &[PatternSpec], results: &[SearchResult]) -> Baseline {
+        let mut findings: Vec<Finding> = results.iter().map(|result| finding(pattern_specs, result)).collect();
+        // Results come in the order the threads found them; sorted, the
+        // baseline only changes where the findings do.
+        findings.sort_by(|a, b| (&a.path, a.line, &a.rule).cmp(&(&b.path, b.line, &b.rule)));
+        Baseline { findings }
+    }
+
+    /// Reads a baseline written by `to_json`: a JSON array with an object
+    /// per line. Like the results of `--format json`, the objects are read
+    /// line by line, so this does not read any JSON array.
+    pub fn parse(text: &str) -> Result<Baseline, String> {
+        let mut findings = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim().trim_end_matches(',');
+            if line.is_empty() || line == "[" || line == "]" {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            let object = json::parse_object(line).map_err(error)?;
+            let string = |key: &str| object.get(key).and_then(Value::as_str).map(str::to_string).ok_or_else(|| error(format!("missing `{}`", key)));
+            findings.push(Finding {
+                path: string("path")?,
+                line: object.get("line").and_then(Value::as_usize).unwrap_or_default(),
+                rule: string("rule")?,
+                fingerprint: string("fingerprint")?,
+            });
+        }
+        Ok(Baseline { findings })
+    }
+
+    pub fn to_json(&self) -> String {
+        let lines: Vec<String> = self.findings.iter()
+            .map(|finding| format!(
+                "{{\"path\":{},\"line\":{},\"rule\":{},\"fingerprint\":{}}}",
+                json_string(&finding.path),
+                finding.line,
+                json_string(&finding.rule),
+                json_string(&finding.fingerprint)
+            ))
+            .collect();
+        if lines.is_empty() {
+            return "[\n]\n".to_string();
+        }
+        format!("[\n{}\n]\n", lines.join(",\n"))
+    }
+
+    /// The results that are not in the baseline, and the number of findings
+    /// of the baseline that were not found again. Each finding leaves out a
+    /// single result, so that copying a baselined line is still reported.
+    pub fn suppress(&self, pattern_specs: &[PatternSpec], results: Vec<SearchResult>) -> (Vec<SearchResult>, usize) {
+        let mut remaining: HashMap<(String, String), usize> = HashMap::new();
+        for finding in &self.findings {
+            *remaining.entry((finding.path.clone(), finding.fingerprint.clone())).or_default() += 1;
+        }
+        let kept = results.into_iter()
+            .filter(|result| {
+                let found = finding(pattern_specs, result);
+                match remaining.get_mut(&(found.path, found.fingerprint)) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                }
+            })
+            .collect();
+        (kept, remaining.values().sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, line_number: usize, line: &str, pattern_index: usize) -> SearchResult {
+        SearchResult::new(path, line_number, line, pattern_index)
+    }
+
+    #[test]
+    fn test_baseline_round_trip() {
+        let spec = |name: &str| PatternSpec { name: Some(name.to_string()), ..PatternSpec::new(&name.to_lowercase()) };
+        let pattern_specs = [spec("keys"), spec("TODO")];
+        let results = [result("./src/b.rs", 3, "TODO: later", 1), result("src/a.rs", 9, "  api_key = \"k\"", 0)];
+        let baseline = Baseline::of(&pattern_specs, &results);
+        let json = baseline.to_json();
+        assert!(json.starts_with("[\n{\"path\":\"src/a.rs\",\"line\":9,\"rule\":\"keys\",\"fingerprint\":\""), "{}", json);
+        assert!(json.contains("\"},\n{\"path\":\"src/b.rs\",\"line\":3,\"rule\":\"TODO\",\"fingerprint\":\"") && json.ends_with("\"}\n]\n"));
+        assert!(!json.contains("api_key"));
+        assert_eq!(Baseline::parse(&json).unwrap(), baseline);
+        assert_eq!(Baseline::parse(&Baseline::default().to_json()).unwrap(), Baseline::default());
+        assert_eq!(Baseline::parse("[\n{\"path\":\"a\"}\n]").unwrap_err(), "line 2: missing `rule`");
+    }
+
+    #[test]
+    fn test_suppress() {
+        let pattern_specs = [PatternSpec::new("key")];
+        let baseline = Baseline::of(&pattern_specs, &[result("a.env", 1, "key = 1", 0), result("b.env", 1, "key = 2", 0)]);
+        let results = vec![
+            // Moved down and reindented, the finding is the same.
+            result("./a.env", 5, "    key = 1", 0),
+            // A copy of it is new.
+            result("a.env", 6, "key = 1", 0),
+            result("a.env", 7, "key = 3", 0),
+        ];
+        let (kept, stale) = baseline.suppress(&pattern_specs, results);
+        let lines: Vec<usize> = kept.iter().map(|result| result.line_number).collect();
+        assert_eq!(lines, [6, 7]);
+        assert_eq!(stale, 1);
+    }
+}
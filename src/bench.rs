@@ -0,0 +1,106 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::resources::human_bytes;
+
+/// A stage of the search, timed by `--dry-run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Going through the directories and filtering the entries.
+    Walk,
+    /// Reading the files, decompressing them on the way.
+    Read,
+    Decode,
+    Match,
+}
+
+const STAGES: [(Stage, &str); 4] = [(Stage::Walk, "walk"), (Stage::Read, "read"), (Stage::Decode, "decode"), (Stage::Match, "match")];
+
+/// Time spent in each stage, summed over the walker threads, which all add
+/// to it at once.
+#[derive(Debug, Default)]
+pub struct StageTimings {
+    nanos: [AtomicU64; 4],
+}
+
+impl StageTimings {
+    pub fn add(&self, stage: Stage, elapsed: Duration) {
+        self.nanos[stage as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, stage: Stage) -> Duration {
+        Duration::from_nanos(self.nanos[stage as usize].load(Ordering::Relaxed))
+    }
+}
+
+/// What `--dry-run` reports instead of the results.
+pub struct DryRun<'a> {
+    pub files: usize,
+    pub bytes: u64,
+    pub matches: usize,
+    pub threads: usize,
+    /// Wall time of the walk, which the stages overlap.
+    pub search_time: Duration,
+    /// Time it took to format the results, written nowhere.
+    pub print_time: Duration,
+    pub stages: &'a StageTimings,
+}
+
+impl DryRun<'_> {
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        let seconds = self.search_time.as_secs_f64().max(f64::EPSILON);
+        writeln!(out, "--- Dry run ---")?;
+        writeln!(
+            out,
+            "Searched {} files ({}) in {:.3}s with {} threads: {}/s, {:.0} files/s",
+            self.files,
+            human_bytes(self.bytes),
+            self.search_time.as_secs_f64(),
+            self.threads,
+            human_bytes((self.bytes as f64 / seconds) as u64),
+            self.files as f64 / seconds
+        )?;
+        writeln!(out, "Matches: {} (not printed)", self.matches)?;
+        writeln!(out, "Stages (time summed over the threads):")?;
+        let total: Duration = STAGES.iter().map(|&(stage, _)| self.stages.get(stage)).sum();
+        for (stage, name) in STAGES {
+            let time = self.stages.get(stage);
+            let share = if total.is_zero() { 0.0 } else { time.as_secs_f64() / total.as_secs_f64() * 100.0 };
+            writeln!(out, "  {:<7} {:>9.3}s {:>5.1}%", name, time.as_secs_f64(), share)?;
+        }
+        writeln!(out, "  {:<7} {:>9.3}s", "print", self.print_time.as_secs_f64())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run_report() {
+        let stages = StageTimings::default();
+        stages.add(Stage::Walk, Duration::from_millis(100));
+        stages.add(Stage::Match, Duration::from_millis(250));
+        stages.add(Stage::Match, Duration::from_millis(50));
+        assert_eq!(stages.get(Stage::Match), Duration::from_millis(300));
+
+        let dry_run = DryRun {
+            files: 200,
+            bytes: 4 * 1024 * 1024,
+            matches: 7,
+            threads: 4,
+            search_time: Duration::from_secs(2),
+            print_time: Duration::from_millis(5),
+            stages: &stages,
+        };
+        let mut out = Vec::new();
+        dry_run.write(&mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("Searched 200 files (4.0 MiB) in 2.000s with 4 threads: 2.0 MiB/s, 100 files/s\n"), "{}", report);
+        assert!(report.contains("  walk        0.100s  25.0%\n"), "{}", report);
+        assert!(report.contains("  decode      0.000s   0.0%\n"));
+        assert!(report.ends_with("  print       0.005s\n"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex};
+
+use crate::SearchResult;
+
+/// What the memory of `--max-memory` is spent on, in bytes.
+#[derive(Debug, Default)]
+struct Usage {
+    /// The files the walker threads are reading, counted as their size.
+    buffers: u64,
+    /// The results gathered so far and not spilled.
+    results: u64,
+}
+
+/// The memory a search may use for the files being read and the results
+/// it holds (`--max-memory`).
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    usage: Mutex<Usage>,
+    freed: Condvar,
+}
+
+/// Memory taken from the budget while a file is searched, given back when
+/// dropped.
+pub struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.budget.usage.lock().unwrap().buffers -= self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> MemoryBudget {
+        MemoryBudget { limit, usage: Mutex::new(Usage::default()), freed: Condvar::new() }
+    }
+
+    /// Waits until a file of `bytes` fits in the budget. A file larger than
+    /// the whole budget is still searched, once no other file is.
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let mut usage = self.usage.lock().unwrap();
+        while usage.buffers > 0 && usage.buffers + usage.results + bytes > self.limit {
+            usage = self.freed.wait(usage).unwrap();
+        }
+        usage.buffers += bytes;
+        Reservation { budget: self, bytes }
+    }
+
+    /// Counts `bytes` more of results held in memory, and tells whether
+    /// they should now be spilled to stay within the budget.
+    pub fn add_results(&self, bytes: u64) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        usage.results += bytes;
+        usage.buffers + usage.results > self.limit
+    }
+
+    /// Frees the memory of the results, once they were spilled.
+    pub fn results_spilled(&self) {
+        self.usage.lock().unwrap().results = 0;
+        self.freed.notify_all();
+    }
+}
+
+/// About how much memory `result` takes.
+pub fn result_size(result: &SearchResult) -> u64 {
+    (mem::size_of::<SearchResult>() + result.path.as_os_str().len() + result.pattern.len() + result.line.len()) as u64
+}
+
+/// Results written to an anonymous temporary file, to be read back once
+/// the walk is over and the files it read are no longer in memory.
+pub struct Spill {
+    file: BufWriter<File>,
+    /// The end of the last batch fully written: what a failed write left
+    /// after it is not read back.
+    written: u64,
+}
+
+fn put_bytes(out: &mut impl Write, value: &[u8]) -> io::Result<()> {
+    out.write_all(&(value.len() as u32).to_le_bytes())?;
+    out.write_all(value)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string(input: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(input)? as usize];
+    input.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+impl Spill {
+    pub fn new() -> io::Result<Spill> {
+        Ok(Spill { file: BufWriter::new(tempfile::tempfile()?), written: 0 })
+    }
+
+    /// Appends `results`, all of them or, on error, none.
+    pub fn write(&mut self, results: &[SearchResult]) -> io::Result<()> {
+        for result in results {
+            put_bytes(&mut self.file, result.path.to_string_lossy().as_bytes())?;
+            self.file.write_all(&(result.line_number as u64).to_le_bytes())?;
+            // Line numbers start at 1, so 0 stands for no end line.
+            self.file.write_all(&(result.end_line_number.unwrap_or(0) as u64).to_le_bytes())?;
+            self.file.write_all(&(result.pattern_index as u32).to_le_bytes())?;
+            self.file.write_all(&(result.other_patterns.len() as u32).to_le_bytes())?;
+            for &pattern_index in &result.other_patterns {
+                self.file.write_all(&(pattern_index as u32).to_le_bytes())?;
+            }
+            put_bytes(&mut self.file, result.pattern.as_bytes())?;
+            put_bytes(&mut self.file, result.line.as_bytes())?;
+        }
+        self.file.flush()?;
+        self.written = self.file.get_mut().stream_position()?;
+        Ok(())
+    }
+
+    /// The results written so far, in order.
+    pub fn read_back(self) -> io::Result<Vec<SearchResult>> {
+        let (mut file, _) = self.file.into_parts();
+        file.seek(SeekFrom::Start(0))?;
+        let mut input = BufReader::new(file.take(self.written));
+        let mut results = Vec::new();
+        while !input.fill_buf()?.is_empty() {
+            let path = PathBuf::from(read_string(&mut input)?);
+            let line_number = read_u64(&mut input)? as usize;
+            let end_line_number = read_u64(&mut input)? as usize;
+            let pattern_index = read_u32(&mut input)? as usize;
+            let others = read_u32(&mut input)?;
+            let other_patterns = (0..others).map(|_| read_u32(&mut input).map(|index| index as usize)).collect::<io::Result<_>>()?;
+            let pattern = read_string(&mut input)?;
+            let line = read_string(&mut input)?;
+            results.push(SearchResult {
+                path,
+                line_number,
+                line,
+                pattern,
+                pattern_index,
+                other_patterns,
+                end_line_number: (end_line_number > 0).then_some(end_line_number),
+            });
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reserve_waits_for_room() {
+        let budget = MemoryBudget::new(100);
+        let first = budget.reserve(60);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _second = budget.reserve(50);
+                sender.send(()).unwrap();
+            });
+            assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+            drop(first);
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        });
+        // Larger than the budget, but no other file is being read.
+        let alone = budget.reserve(500);
+        assert!(budget.add_results(1));
+        budget.results_spilled();
+        drop(alone);
+        assert!(!budget.add_results(100));
+    }
+
+    #[test]
+    fn test_spill_round_trip() {
+        let result = |line_number: usize, end_line_number: Option<usize>| SearchResult {
+            pattern: "ERROR".to_string(),
+            end_line_number,
+            ..SearchResult::new("logs/app.log", line_number, &format!("ERROR {}", line_number), 1)
+        };
+        let mut spill = Spill::new().unwrap();
+        spill.write(&[result(3, None)]).unwrap();
+        spill.write(&[result(7, Some(9)), result(12, None)]).unwrap();
+        let results = spill.read_back().unwrap();
+        let read: Vec<(usize, Option<usize>, &str)> = results.iter()
+            .map(|result| (result.line_number, result.end_line_number, result.line.as_str()))
+            .collect();
+        assert_eq!(read, [(3, None, "ERROR 3"), (7, Some(9), "ERROR 7"), (12, None, "ERROR 12")]);
+        assert_eq!((results[0].path.as_path(), results[0].pattern_index), (PathBuf::from("logs/app.log").as_path(), 1));
+    }
+}
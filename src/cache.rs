@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use clap::{Args as ClapArgs, Subcommand};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::SearchResult;
+use crate::error::FinderError;
+use crate::index::modified_nanos;
+
+const MAGIC: &[u8] = b"finder-cache 3\n";
+const EXTENSION: &str = "cache";
+
+/// Arguments of `finder cache`.
+#[derive(ClapArgs, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Delete the results cached by --cache
+    Clear,
+}
+
+/// Where `--cache` keeps its files: `$FINDER_CACHE_DIR`, or else the cache
+/// directory of the user.
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("FINDER_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    base.map(|base| base.join("finder"))
+}
+
+/// A short hash of what the results of a file depend on besides its
+/// contents, naming the cache of the searches that share it.
+pub fn signature(parts: &[&str]) -> String {
+    let mut hasher = Xxh3::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update(b"\0");
+    }
+    format!("{:016x}", hasher.digest())
+}
+
+/// The results of a file, valid while it keeps its size and modification
+/// time.
+#[derive(Debug, Clone)]
+struct CachedFile {
+    size: u64,
+    modified: u128,
+    results: Vec<SearchResult>,
+}
+
+impl CachedFile {
+    fn is_fresh(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len() && self.modified == modified_nanos(metadata)
+    }
+}
+
+/// The results of earlier searches with the same signature, by absolute
+/// path, and those of the files searched again this time (`--cache`).
+#[derive(Debug)]
+pub struct ResultCache {
+    path: PathBuf,
+    files: HashMap<PathBuf, CachedFile>,
+    updated: Mutex<HashMap<PathBuf, CachedFile>>,
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+fn to_bytes(files: &HashMap<PathBuf, CachedFile>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    for (path, file) in files {
+        put_bytes(&mut bytes, path.to_string_lossy().as_bytes());
+        bytes.extend_from_slice(&file.size.to_le_bytes());
+        bytes.extend_from_slice(&file.modified.to_le_bytes());
+        bytes.extend_from_slice(&(file.results.len() as u32).to_le_bytes());
+        for result in &file.results {
+            bytes.extend_from_slice(&(result.line_number as u64).to_le_bytes());
+            // Line numbers start at 1, so 0 stands for no end line.
+            bytes.extend_from_slice(&(result.end_line_number.unwrap_or(0) as u64).to_le_bytes());
+            bytes.extend_from_slice(&(result.pattern_index as u32).to_le_bytes());
+            bytes.extend_from_slice(&(result.other_patterns.len() as u32).to_le_bytes());
+            for &pattern_index in &result.other_patterns {
+                bytes.extend_from_slice(&(pattern_index as u32).to_le_bytes());
+            }
+            put_bytes(&mut bytes, result.pattern.as_bytes());
+            put_bytes(&mut bytes, result.line.as_bytes());
+        }
+    }
+    bytes
+}
+
+fn from_bytes(bytes: &[u8]) -> io::Result<HashMap<PathBuf, CachedFile>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a finder cache, or from another version");
+    let mut rest = bytes.strip_prefix(MAGIC).ok_or_else(invalid)?;
+    let mut take = |count: usize| -> io::Result<&[u8]> {
+        if rest.len() < count {
+            return Err(invalid());
+        }
+        let (taken, remaining) = rest.split_at(count);
+        rest = remaining;
+        Ok(taken)
+    };
+    let mut files = HashMap::new();
+    while let Ok(length) = take(4) {
+        let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+        let path = PathBuf::from(String::from_utf8_lossy(take(length)?).into_owned());
+        let size = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let modified = u128::from_le_bytes(take(16)?.try_into().unwrap());
+        let count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        let mut results = Vec::with_capacity(count);
+        for _ in 0..count {
+            let line_number = u64::from_le_bytes(take(8)?.try_into().unwrap()) as usize;
+            let end_line_number = u64::from_le_bytes(take(8)?.try_into().unwrap()) as usize;
+            let pattern_index = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+            let others = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+            let other_patterns = (0..others)
+                .map(|_| take(4).map(|index| u32::from_le_bytes(index.try_into().unwrap()) as usize))
+                .collect::<io::Result<_>>()?;
+            let length = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+            let pattern = String::from_utf8_lossy(take(length)?).into_owned();
+            let length = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+            let line = String::from_utf8_lossy(take(length)?).into_owned();
+            results.push(SearchResult {
+                path: path.clone(),
+                line_number,
+                line,
+                pattern,
+                pattern_index,
+                other_patterns,
+                end_line_number: (end_line_number > 0).then_some(end_line_number),
+            });
+        }
+        files.insert(path, CachedFile { size, modified, results });
+    }
+    Ok(files)
+}
+
+impl ResultCache {
+    /// Opens the cache of the searches with `signature` in `dir`. A cache
+    /// that cannot be read is started over.
+    pub fn open(dir: &Path, signature: &str) -> ResultCache {
+        let path = dir.join(signature).with_extension(EXTENSION);
+        let files = fs::read(&path).ok().and_then(|bytes| from_bytes(&bytes).ok()).unwrap_or_default();
+        ResultCache { path, files, updated: Mutex::new(HashMap::new()) }
+    }
+
+    /// The results of the file at `path` if it did not change since they
+    /// were cached, reported against `path` as given.
+    pub fn get(&self, path: &Path, metadata: &Metadata) -> Option<Vec<SearchResult>> {
+        let file = self.files.get(&std::path::absolute(path).ok()?).filter(|file| file.is_fresh(metadata))?;
+        Some(file.results.iter().map(|result| SearchResult { path: path.to_path_buf(), ..result.clone() }).collect())
+    }
+
+    /// Caches the results of the file at `path`, as it was before it was
+    /// searched.
+    pub fn insert(&self, path: &Path, metadata: &Metadata, results: &[SearchResult]) {
+        let Ok(absolute) = std::path::absolute(path) else { return };
+        let results = results.iter().map(|result| SearchResult { path: absolute.clone(), ..result.clone() }).collect();
+        let file = CachedFile { size: metadata.len(), modified: modified_nanos(metadata), results };
+        self.updated.lock().unwrap().insert(absolute, file);
+    }
+
+    /// Writes the cache back with the files searched this time, dropping
+    /// the files that no longer exist.
+    pub fn save(&self) -> io::Result<()> {
+        let mut files: HashMap<PathBuf, CachedFile> = self.files.iter()
+            .filter(|(path, _)| path.exists())
+            .map(|(path, file)| (path.clone(), file.clone()))
+            .collect();
+        files.extend(self.updated.lock().unwrap().drain());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        crate::progress::write_atomically(&self.path, to_bytes(&files))
+    }
+}
+
+pub fn run(args: CacheArgs) -> Result<(), FinderError> {
+    match args.command {
+        CacheCommand::Clear => {
+            let dir = cache_dir().ok_or("no cache directory: set FINDER_CACHE_DIR")?;
+            let mut removed = 0;
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    eprintln!("No cache in {}", dir.display());
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == EXTENSION) {
+                    fs::remove_file(&path)?;
+                    removed += 1;
+                }
+            }
+            eprintln!("Removed {} cache files from {}", removed, dir.display());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("app.log");
+        fs::write(&file, "ok\nERROR disk full\n").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        let result = SearchResult {
+            path: file.clone(),
+            pattern: "ERROR".to_string(),
+            end_line_number: Some(3),
+            ..SearchResult::new("", 2, "ERROR disk full", 0)
+        };
+
+        let cache = ResultCache::open(dir.path(), "0123");
+        assert!(cache.get(&file, &metadata).is_none());
+        cache.insert(&file, &metadata, std::slice::from_ref(&result));
+        cache.save().unwrap();
+
+        let cache = ResultCache::open(dir.path(), "0123");
+        let cached = cache.get(&file, &metadata).unwrap();
+        assert_eq!((cached[0].line_number, cached[0].end_line_number, cached[0].line.as_str()), (2, Some(3), "ERROR disk full"));
+        assert_eq!(cached[0].path, file);
+        assert!(ResultCache::open(dir.path(), "4567").get(&file, &metadata).is_none());
+
+        fs::write(&file, "ok\nERROR disk full\nERROR again\n").unwrap();
+        assert!(cache.get(&file, &fs::metadata(&file).unwrap()).is_none());
+        fs::remove_file(&file).unwrap();
+        cache.save().unwrap();
+        assert!(ResultCache::open(dir.path(), "0123").files.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_cache_starts_over() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("0123.cache"), b"finder-cache 2\n\x05\x00").unwrap();
+        assert!(ResultCache::open(dir.path(), "0123").files.is_empty());
+        assert_ne!(signature(&["a", "bc"]), signature(&["ab", "c"]));
+    }
+}
@@ -0,0 +1,67 @@
+use clap::ValueEnum;
+
+use crate::json::json_string;
+use crate::output::OutputFormat;
+
+/// Versions of the formats finder writes and reads back, bumped on
+/// incompatible changes so that wrappers can tell what they get.
+const SCHEMAS: &[(&str, &str)] = &[
+    ("json", "1"),
+    ("stats", "1"),
+    ("manifest", "1"),
+    ("index", "1"),
+    ("serve", "1"),
+    ("sarif", "\"2.1.0\""),
+];
+
+/// What this build of finder can do, as printed by `--capabilities`: the
+/// optional features it was compiled with, the formats it reads and writes
+/// and their versions, and its subcommands.
+pub fn to_json(command: &clap::Command) -> String {
+    let features = [
+        ("pcre2", cfg!(feature = "pcre2")),
+        ("tui", cfg!(feature = "tui")),
+        ("pdf", cfg!(feature = "pdf")),
+        ("serve", cfg!(unix)),
+        ("archives", true),
+        ("documents", true),
+        ("decompression", true),
+        ("watch", true),
+        ("index", true),
+        ("hyperlinks", true),
+    ];
+    let formats = OutputFormat::value_variants().iter().filter_map(|format| format.to_possible_value());
+    let subcommands = command.get_subcommands().map(|subcommand| subcommand.get_name());
+    format!(
+        "{{\"name\":\"finder\",\"version\":{},\"features\":{{{}}},\"formats\":{},\"archives\":{},\"compressions\":{},\"presets\":{},\"subcommands\":{},\"schemas\":{{{}}}}}",
+        json_string(env!("CARGO_PKG_VERSION")),
+        features.iter().map(|(name, enabled)| format!("{}:{}", json_string(name), enabled)).collect::<Vec<_>>().join(","),
+        json_list(formats.map(|format| format.get_name().to_string())),
+        json_list(["zip", "tar"]),
+        json_list(["gzip", "bzip2", "xz", "zstd"]),
+        json_list(["licenses", "pii"]),
+        json_list(subcommands),
+        SCHEMAS.iter().map(|(name, version)| format!("{}:{}", json_string(name), version)).collect::<Vec<_>>().join(","),
+    )
+}
+
+fn json_list<S: AsRef<str>>(items: impl IntoIterator<Item = S>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| json_string(item.as_ref())).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let command = clap::Command::new("finder").subcommand(clap::Command::new("serve"));
+        let capabilities = to_json(&command);
+        assert!(capabilities.starts_with(r#"{"name":"finder","version":""#));
+        assert!(capabilities.contains(r#""formats":["text","sarif","github","json","quickfix","emacs","html","xml"]"#), "{}", capabilities);
+        assert!(capabilities.contains(&format!(r#""pcre2":{}"#, cfg!(feature = "pcre2"))));
+        assert!(capabilities.contains(r#""subcommands":["serve"]"#));
+        assert!(capabilities.ends_with(r#""sarif":"2.1.0"}}"#));
+    }
+}
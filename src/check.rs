@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::Args as ClapArgs;
+use regex::Regex;
+use regex_syntax::ast::{self, ErrorKind};
+use regex_syntax::hir::{Hir, HirKind};
+
+use crate::engine::{self, CaseMode, Engine};
+use crate::error::FinderError;
+use crate::patterns::{self, PatternSpec};
+
+/// Lines of one character each, one of which a useful pattern should fail
+/// to match: a pattern matching all of them matches nearly every line.
+const PROBES: &[&str] = &["a", "Z", "0", " ", "-", "é"];
+
+/// Arguments of `finder check`.
+#[derive(ClapArgs, Debug)]
+pub struct CheckArgs {
+    /// The patterns file(s) to check, in the format of -f
+    #[arg(short = 'f', long = "file", value_name = "FILE", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Regex engine the patterns are meant for
+    #[arg(long, value_enum, default_value_t = Engine::Default)]
+    pub engine: Engine,
+
+    /// Fail on warnings as well as on errors
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with the pattern at `line`, the regex starting at `column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub help: Option<&'static str>,
+}
+
+/// How to fix the syntax errors people make most often.
+fn help_for(kind: &ErrorKind) -> Option<&'static str> {
+    match kind {
+        ErrorKind::GroupUnclosed => Some("close the group with `)`, or write a literal `(` as `\\(`"),
+        ErrorKind::GroupUnopened => Some("write a literal `)` as `\\)`"),
+        ErrorKind::ClassUnclosed => Some("close the class with `]`, or write a literal `[` as `\\[`"),
+        ErrorKind::RepetitionMissing => Some("write a literal `*`, `+` or `?` with a backslash; `.*` matches anything"),
+        ErrorKind::UnsupportedLookAround => Some("look-around needs `--engine pcre2`"),
+        ErrorKind::UnsupportedBackreference => Some("backreferences need `--engine pcre2`"),
+        ErrorKind::EscapeUnrecognized => Some("remove the backslash, or write a literal `\\` as `\\\\`"),
+        _ => None,
+    }
+}
+
+fn has_unbounded_repetition(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Repetition(repetition) => repetition.max.is_none() || has_unbounded_repetition(&repetition.sub),
+        HirKind::Capture(capture) => has_unbounded_repetition(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(has_unbounded_repetition),
+        _ => false,
+    }
+}
+
+/// Whether `hir` repeats without bound something that itself repeats
+/// without bound, like `(\w+\s?)+`.
+fn has_nested_repetition(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Repetition(repetition) => {
+            (repetition.max.is_none() && has_unbounded_repetition(&repetition.sub)) || has_nested_repetition(&repetition.sub)
+        }
+        HirKind::Capture(capture) => has_nested_repetition(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(has_nested_repetition),
+        _ => false,
+    }
+}
+
+/// The error of a pattern that does not compile, located in the line.
+fn syntax_error(line: usize, column: usize, spec: &PatternSpec, engine: Engine) -> Option<Diagnostic> {
+    let error = |offset: usize, message: String, help| Diagnostic { line, column: column + offset, severity: Severity::Error, message, help };
+    if engine == Engine::Pcre2 {
+        return engine::compile_pcre2(std::slice::from_ref(&spec.pattern), CaseMode::Sensitive, false, true)
+            .err()
+            .map(|e| error(0, e.to_string(), None));
+    }
+    if let Err(e) = ast::parse::Parser::new().parse(&spec.pattern) {
+        return Some(error(e.span().start.column - 1, e.kind().to_string(), help_for(e.kind())));
+    }
+    // Errors past the syntax, such as an unknown Unicode class or a regex
+    // too large, are only found by compiling.
+    Regex::new(&spec.pattern).err().map(|e| {
+        let message = e.to_string();
+        let reason = message.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string();
+        error(0, reason, None)
+    })
+}
+
+/// Checks the patterns of a patterns file: whether each one compiles, and
+/// whether it is one that matches about every line, repeats another or can
+/// be slow.
+pub fn check_patterns(content: &str, engine: Engine) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut seen: HashMap<(&str, bool), usize> = HashMap::new();
+    let located = patterns::parse_patterns_located(content);
+    for (line, column, spec) in &located {
+        let warning = |message: String, help| Diagnostic { line: *line, column: *column, severity: Severity::Warning, message, help };
+        if let Some(error) = syntax_error(*line, *column, spec, engine) {
+            diagnostics.push(error);
+            continue;
+        }
+        if let Some(first) = seen.insert((&spec.pattern, spec.negated), *line) {
+            diagnostics.push(warning(format!("same pattern as line {}", first), None));
+        }
+        let Ok(regex) = Regex::new(&spec.pattern) else {
+            // Syntax only PCRE2 understands: the checks below do not apply.
+            continue;
+        };
+        let effect = if spec.negated { "excludes" } else { "matches" };
+        if regex.is_match("") {
+            diagnostics.push(warning(
+                format!("matches the empty string, so it {} every line", effect),
+                Some("use `+` rather than `*` where at least one character is expected"),
+            ));
+        } else if PROBES.iter().all(|probe| regex.is_match(probe)) {
+            diagnostics.push(warning(format!("{} nearly every line", effect), None));
+        }
+        if regex_syntax::Parser::new().parse(&spec.pattern).is_ok_and(|hir| has_nested_repetition(&hir)) {
+            diagnostics.push(warning(
+                "nested unbounded repetition, which can take exponential time with `--engine pcre2`".to_string(),
+                Some("make the inner or the outer repetition bounded, or the two unable to match the same text"),
+            ));
+        }
+    }
+    diagnostics
+}
+
+pub fn run(args: CheckArgs) -> Result<(), FinderError> {
+    let mut out = io::stdout().lock();
+    let (mut errors, mut warnings) = (0, 0);
+    for path in &args.files {
+        let content = crate::read_decoded_exactly(path)?;
+        for diagnostic in check_patterns(&content, args.engine) {
+            let severity = match diagnostic.severity {
+                Severity::Error => {
+                    errors += 1;
+                    "error"
+                }
+                Severity::Warning => {
+                    warnings += 1;
+                    "warning"
+                }
+            };
+            writeln!(out, "{}:{}:{}: {}: {}", path.display(), diagnostic.line, diagnostic.column, severity, diagnostic.message)?;
+            if let Some(help) = diagnostic.help {
+                writeln!(out, "    help: {}", help)?;
+            }
+        }
+    }
+    eprintln!("{} error(s), {} warning(s).", errors, warnings);
+    if errors > 0 || (args.deny_warnings && warnings > 0) {
+        return Err("the patterns have problems".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(content: &str) -> Vec<(usize, usize, Severity, String)> {
+        check_patterns(content, Engine::Default).into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.severity, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let diagnostics = check_patterns("# rules\nkeys: api_(key\nfoo(?=bar)\n\\p{Klingon}\n", Engine::Default);
+        assert_eq!(diagnostics[0], Diagnostic {
+            line: 2,
+            column: 11,
+            severity: Severity::Error,
+            message: "unclosed group".to_string(),
+            help: help_for(&ErrorKind::GroupUnclosed),
+        });
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (3, 4));
+        assert_eq!(diagnostics[1].help, Some("look-around needs `--engine pcre2`"));
+        assert_eq!((diagnostics[2].line, diagnostics[2].severity), (4, Severity::Error));
+        assert_eq!(diagnostics[2].message, "Unicode property not found");
+        assert_eq!(diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_warnings() {
+        assert_eq!(summary("TODO\n[a-z]+\\d\n"), vec![]);
+        assert_eq!(summary("a*\n!.\nTODO\nx: TODO\n(\\w+\\s?)+$\n"), vec![
+            (1, 1, Severity::Warning, "matches the empty string, so it matches every line".to_string()),
+            (2, 2, Severity::Warning, "excludes nearly every line".to_string()),
+            (4, 4, Severity::Warning, "same pattern as line 3".to_string()),
+            (5, 1, Severity::Warning, "nested unbounded repetition, which can take exponential time with `--engine pcre2`".to_string()),
+        ]);
+    }
+}
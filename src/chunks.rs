@@ -0,0 +1,265 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+use crate::bench::Stage;
+use crate::engine::Matcher;
+use crate::{SearchOptions, SearchResult, longpath};
+
+/// How large files are searched (`--chunk-size`): split at line boundaries
+/// into chunks of about `size` bytes, which the thread searching the file
+/// and the threads of the search left idle search at once.
+#[derive(Debug, Clone)]
+pub struct Chunking {
+    pub size: u64,
+    pub threads: Arc<ThreadBudget>,
+}
+
+/// The threads of a search that are not searching a file, which the chunks
+/// of a large file may borrow. A thread searching a file takes one when
+/// there is one left, so that the chunks do not add threads to a search
+/// that keeps all of them busy.
+#[derive(Debug)]
+pub struct ThreadBudget(AtomicUsize);
+
+/// Threads taken from the budget, given back when dropped.
+pub struct Threads<'a> {
+    budget: &'a ThreadBudget,
+    pub count: usize,
+}
+
+impl Drop for Threads<'_> {
+    fn drop(&mut self) {
+        self.budget.0.fetch_add(self.count, Ordering::AcqRel);
+    }
+}
+
+impl ThreadBudget {
+    pub fn new(threads: usize) -> ThreadBudget {
+        ThreadBudget(AtomicUsize::new(threads))
+    }
+
+    /// Takes as many of `wanted` threads as are free, without waiting.
+    pub fn take(&self, wanted: usize) -> Threads<'_> {
+        let mut count = 0;
+        let _ = self.0.fetch_update(Ordering::AcqRel, Ordering::Acquire, |free| {
+            count = free.min(wanted);
+            Some(free - count)
+        });
+        Threads { budget: self, count }
+    }
+}
+
+/// How much is read at a time when looking for the end of a line.
+const SCAN_BUFFER: usize = 64 * 1024;
+
+/// The offset of the start of the first line that begins at or after
+/// `offset`, or `None` if the file ends first.
+fn next_line_start(file: &mut File, offset: u64) -> io::Result<Option<u64>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = vec![0; SCAN_BUFFER];
+    let mut position = offset;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => return Ok(None),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(index) = buffer[..read].iter().position(|&byte| byte == b'\n') {
+            return Ok(Some(position + index as u64 + 1));
+        }
+        position += read as u64;
+    }
+}
+
+/// The byte ranges of the chunks of the `len` bytes of `file` from `start`:
+/// each at least `size` bytes long, the last one excepted, and ending at the
+/// end of a line, so that no line is split.
+pub fn chunk_ranges(file: &mut File, start: u64, len: u64, size: u64) -> io::Result<Vec<(u64, u64)>> {
+    let mut starts = vec![start];
+    let mut offset = start;
+    while offset + size < len {
+        match next_line_start(file, offset + size)? {
+            Some(next) if next < len => {
+                starts.push(next);
+                offset = next;
+            }
+            _ => break,
+        }
+    }
+    let ends = starts.iter().skip(1).copied().chain([len]);
+    Ok(starts.iter().copied().zip(ends).collect())
+}
+
+/// What the search of a chunk found.
+struct ChunkOutcome {
+    /// Numbered from the first line of the chunk.
+    results: Vec<SearchResult>,
+    lines: usize,
+    /// For the `finder:ignore-next-line` comments that silence the first
+    /// line of the next chunk.
+    last_line: String,
+    /// Whether the chunk is valid UTF-8, which the file is only if all of
+    /// its chunks are.
+    utf8: bool,
+}
+
+/// Searches a chunk of `path`, decoded as UTF-8 if the file may be
+/// (`utf8`) and it is, or else from `encoding`, as the whole file would be.
+fn search_chunk<M: Matcher>(
+    path: &Path,
+    encoding: &'static Encoding,
+    utf8: bool,
+    (start, end): (u64, u64),
+    regexes: &[M],
+    options: &SearchOptions,
+) -> io::Result<ChunkOutcome> {
+    let started = Instant::now();
+    let mut file = File::open(longpath::for_opening(path))?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut buffer = vec![0; (end - start) as usize];
+    file.read_exact(&mut buffer)?;
+    let read = Instant::now();
+    let decoded_content = if utf8 { crate::decode_utf8_or(&buffer, encoding) } else { encoding.decode_without_bom_handling(&buffer).0 };
+    let valid_utf8 = matches!(decoded_content, Cow::Borrowed(_));
+    let decoded_content = match options.normalize {
+        Some(normalization) => normalization.apply(&decoded_content),
+        None => Cow::Borrowed(&*decoded_content),
+    };
+    let decoded = Instant::now();
+    let results = crate::search_decoded(path, &decoded_content, regexes, options);
+    if let Some(timings) = &options.timings {
+        timings.add(Stage::Read, read - started);
+        timings.add(Stage::Decode, decoded - read);
+        timings.add(Stage::Match, decoded.elapsed());
+    }
+    Ok(ChunkOutcome {
+        results,
+        lines: buffer.iter().filter(|&&byte| byte == b'\n').count(),
+        last_line: decoded_content.lines().last().unwrap_or_default().to_string(),
+        utf8: valid_utf8,
+    })
+}
+
+/// Runs `search_chunk` on the chunks at `indexes`, on the calling thread
+/// and those it can borrow from `threads`, and gives their outcomes in the
+/// same order. A chunk is left unsearched only after another one failed.
+fn search_chunks<F>(indexes: &[usize], threads: &ThreadBudget, search_chunk: F) -> Vec<Option<io::Result<ChunkOutcome>>>
+where
+    F: Fn(usize) -> io::Result<ChunkOutcome> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<io::Result<ChunkOutcome>>>> = Mutex::new(indexes.iter().map(|_| None).collect());
+    let search = || {
+        loop {
+            let position = next.fetch_add(1, Ordering::Relaxed);
+            let Some(&index) = indexes.get(position) else { break };
+            let outcome = search_chunk(index);
+            let failed = outcome.is_err();
+            outcomes.lock().unwrap()[position] = Some(outcome);
+            if failed {
+                break;
+            }
+        }
+    };
+    let helpers = threads.take(indexes.len().saturating_sub(1));
+    thread::scope(|scope| {
+        for _ in 0..helpers.count {
+            scope.spawn(search);
+        }
+        search();
+    });
+    outcomes.into_inner().unwrap()
+}
+
+/// Searches the `len` bytes of `path` a chunk at a time, on several threads
+/// at once, and gives the results in the order of the file, as searching it
+/// whole would. Returns `None` for files that cannot be split at line
+/// boundaries as bytes: those in UTF-16.
+pub fn search_chunked<M: Matcher>(
+    path: &Path,
+    len: u64,
+    regexes: &[M],
+    options: &SearchOptions,
+    chunking: &Chunking,
+) -> io::Result<Option<Vec<SearchResult>>> {
+    let mut file = File::open(longpath::for_opening(path))?;
+    let mut bom = [0; 3];
+    let sniffed = file.read(&mut bom)?;
+    let (encoding, bom_len) = Encoding::for_bom(&bom[..sniffed]).unwrap_or((WINDOWS_1252, 0));
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        return Ok(None);
+    }
+    let ranges = chunk_ranges(&mut file, bom_len as u64, len, chunking.size)?;
+
+    // A result per file and merged ranges are only known once the chunks
+    // are put back together.
+    let chunk_options = SearchOptions { per_file: None, merge_ranges: false, ..options.clone() };
+    let all: Vec<usize> = (0..ranges.len()).collect();
+    let (ranges, chunk_options) = (&ranges, &chunk_options);
+    let search = |utf8: bool| move |index: usize| search_chunk(path, encoding, utf8, ranges[index], regexes, chunk_options);
+    let mut outcomes = search_chunks(&all, &chunking.threads, search(true));
+    // The chunks are searched as UTF-8 where they are valid, as most files
+    // are. When one is not, neither is the file, which is decoded whole from
+    // `encoding`: the chunks that were valid UTF-8 are searched again so.
+    if outcomes.iter().any(|outcome| matches!(outcome, Some(Ok(outcome)) if !outcome.utf8)) {
+        let again: Vec<usize> = all.into_iter().filter(|&index| matches!(&outcomes[index], Some(Ok(outcome)) if outcome.utf8)).collect();
+        let redone = search_chunks(&again, &chunking.threads, search(false));
+        for (index, outcome) in again.into_iter().zip(redone) {
+            outcomes[index] = outcome;
+        }
+    }
+
+    let mut results = Vec::new();
+    let mut lines_before = 0;
+    let mut previous_line: Option<String> = None;
+    for outcome in outcomes {
+        // A chunk is left unsearched only after another one failed.
+        let Some(outcome) = outcome else { continue };
+        let outcome = outcome?;
+        for mut result in outcome.results {
+            if result.line_number == 1
+                && let Some(previous_line) = &previous_line
+            {
+                // Search the first line again, knowing the line before it.
+                match chunk_options.line_result(regexes, path, 1, Some(previous_line), &result.line) {
+                    Some(first_line) => result = first_line,
+                    None => continue,
+                }
+            }
+            result.line_number += lines_before;
+            results.push(result);
+        }
+        lines_before += outcome.lines;
+        previous_line = Some(outcome.last_line);
+    }
+    let results = crate::keep_per_file(results, options.per_file);
+    Ok(Some(if options.merge_ranges { crate::merge_ranges(results) } else { results }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_chunk_ranges() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("big.log");
+        fs::write(&path, "aaaa\nbb\nc\nddddddddddd\ne").unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(chunk_ranges(&mut file, 0, 23, 6).unwrap(), [(0, 8), (8, 22), (22, 23)]);
+        assert_eq!(chunk_ranges(&mut file, 0, 23, 100).unwrap(), [(0, 23)]);
+        // A chunk goes on to the end of its line, however long.
+        assert_eq!(chunk_ranges(&mut file, 2, 23, 1).unwrap(), [(2, 5), (5, 8), (8, 10), (10, 22), (22, 23)]);
+    }
+}
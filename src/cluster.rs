@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::SearchResult;
+
+/// Length, in characters, of the shingles compared between lines.
+const SHINGLE_LEN: usize = 4;
+/// MinHash signature length: `BANDS` bands of `ROWS` hashes each.
+const BANDS: usize = 16;
+const ROWS: usize = 2;
+const SIGNATURE_LEN: usize = BANDS * ROWS;
+/// Estimated Jaccard similarity above which a line joins a cluster.
+const THRESHOLD: f64 = 0.6;
+
+/// A group of similar matched lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    /// Index in the results of the first line of the cluster, shown for all of them.
+    pub representative: usize,
+    /// Number of results in the cluster.
+    pub count: usize,
+}
+
+type Signature = [u64; SIGNATURE_LEN];
+
+/// Lines differing only by case or numbers (ids, timestamps, counters)
+/// should look identical.
+fn normalize(line: &str) -> Vec<char> {
+    line.trim()
+        .chars()
+        .map(|c| if c.is_ascii_digit() { '0' } else { c.to_ascii_lowercase() })
+        .collect()
+}
+
+fn signature(line: &str) -> Signature {
+    let chars = normalize(line);
+    let mut signature = [u64::MAX; SIGNATURE_LEN];
+    for shingle in chars.windows(SHINGLE_LEN.min(chars.len()).max(1)) {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let hash = hasher.finish();
+        // Cheap independent permutations derived from the one hash.
+        for (seed, min) in signature.iter_mut().enumerate() {
+            let permuted = (hash ^ (seed as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+                .wrapping_mul(0xBF58_476D_1CE4_E5B9)
+                .rotate_left(31);
+            *min = (*min).min(permuted);
+        }
+    }
+    signature
+}
+
+fn similarity(a: &Signature, b: &Signature) -> f64 {
+    a.iter().zip(b).filter(|(a, b)| a == b).count() as f64 / SIGNATURE_LEN as f64
+}
+
+/// Groups the results by line similarity (MinHash over character shingles,
+/// with locality-sensitive hashing to find candidates), largest cluster first.
+pub fn cluster_lines(results: &[SearchResult]) -> Vec<Cluster> {
+    let signatures: Vec<Signature> = results.iter().map(|result| signature(&result.line)).collect();
+    let mut clusters: Vec<(Cluster, Signature)> = Vec::new();
+    let mut buckets: HashMap<(usize, &[u64]), Vec<usize>> = HashMap::new();
+
+    for (index, signature) in signatures.iter().enumerate() {
+        let bands: Vec<(usize, &[u64])> = signature.chunks(ROWS).enumerate().collect();
+        let found = bands.iter()
+            .filter_map(|band| buckets.get(band))
+            .flatten()
+            .copied()
+            .find(|&cluster| similarity(&clusters[cluster].1, signature) >= THRESHOLD);
+        match found {
+            Some(cluster) => clusters[cluster].0.count += 1,
+            None => {
+                for band in bands {
+                    buckets.entry(band).or_default().push(clusters.len());
+                }
+                clusters.push((Cluster { representative: index, count: 1 }, *signature));
+            }
+        }
+    }
+
+    let mut clusters: Vec<Cluster> = clusters.into_iter().map(|(cluster, _)| cluster).collect();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.count));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_lines() {
+        let lines = [
+            "ERROR connection to 10.0.0.12 refused after 3 retries (request 81723)",
+            "WARN disk usage above threshold",
+            "ERROR connection to 10.0.0.7 refused after 5 retries (request 90211)",
+            "ERROR Connection to 192.168.1.40 refused after 3 retries (request 1)",
+            "WARN disk usage above threshold on /var",
+            "ERROR unexpected token in config.yaml",
+        ];
+        let results: Vec<SearchResult> = lines.iter().enumerate().map(|(index, line)| SearchResult {
+            pattern: "ERROR|WARN".to_string(),
+            ..SearchResult::new("app.log", index + 1, line, 0)
+        }).collect();
+        assert_eq!(cluster_lines(&results), vec![
+            Cluster { representative: 0, count: 3 },
+            Cluster { representative: 1, count: 2 },
+            Cluster { representative: 5, count: 1 },
+        ]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args as ClapArgs;
+use ignore::WalkBuilder;
+use indicatif::ProgressBar;
+
+use crate::engine::CaseMode;
+use crate::error::FinderError;
+use crate::filters::FileFilter;
+use crate::patterns::{self, PatternSpec};
+use crate::{SearchOptions, SearchResult};
+
+/// Arguments of `finder compare`.
+#[derive(ClapArgs, Debug)]
+pub struct CompareArgs {
+    /// The baseline tree, e.g. the previous release
+    pub before: PathBuf,
+
+    /// The tree compared with the baseline
+    pub after: PathBuf,
+
+    /// A patterns file whose rules are run over both trees
+    #[arg(short = 'f', long = "input-file")]
+    pub input_file: PathBuf,
+
+    /// Case-insensitive search
+    #[arg(short, long)]
+    pub ignore_case: bool,
+
+    /// Also list the paths whose match count did not change
+    #[arg(long)]
+    pub all: bool,
+}
+
+/// Match counts of one tree, per rule and per path relative to the root.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub rules: BTreeMap<String, usize>,
+    pub paths: BTreeMap<PathBuf, usize>,
+}
+
+impl Profile {
+    pub fn new(root: &Path, pattern_specs: &[PatternSpec], results: &[SearchResult]) -> Profile {
+        let rule = |spec: &PatternSpec| spec.name.clone().unwrap_or_else(|| spec.pattern.clone());
+        let mut profile = Profile::default();
+        for spec in pattern_specs.iter().filter(|spec| !spec.negated) {
+            profile.rules.entry(rule(spec)).or_default();
+        }
+        for result in results {
+            *profile.rules.entry(rule(&pattern_specs[result.pattern_index])).or_default() += 1;
+            let path = result.path.strip_prefix(root).unwrap_or(&result.path);
+            *profile.paths.entry(path.to_path_buf()).or_default() += 1;
+        }
+        profile
+    }
+}
+
+/// `(key, before, after)` for every key of either map, in key order.
+fn deltas<K: Ord + Clone>(before: &BTreeMap<K, usize>, after: &BTreeMap<K, usize>) -> Vec<(K, usize, usize)> {
+    let keys: BTreeSet<&K> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .map(|key| (key.clone(), before.get(key).copied().unwrap_or(0), after.get(key).copied().unwrap_or(0)))
+        .collect()
+}
+
+fn write_table(out: &mut dyn Write, title: &str, rows: &[(String, usize, usize)]) -> io::Result<()> {
+    let width = rows.iter().map(|(key, _, _)| key.chars().count()).chain([title.len()]).max().unwrap_or(0);
+    writeln!(out, "{:<width$}  {:>8}  {:>8}  {:>8}", title, "before", "after", "delta")?;
+    for (key, before, after) in rows {
+        let delta = *after as i64 - *before as i64;
+        writeln!(out, "{:<width$}  {:>8}  {:>8}  {:>+8}", key, before, after, delta)?;
+    }
+    Ok(())
+}
+
+/// Writes the per-rule table, then the per-path table restricted to the
+/// paths whose count changed (unless `all`).
+pub fn write_report(out: &mut dyn Write, before: &Profile, after: &Profile, all: bool) -> io::Result<()> {
+    write_table(out, "Rule", &deltas(&before.rules, &after.rules))?;
+    let paths: Vec<(String, usize, usize)> = deltas(&before.paths, &after.paths).into_iter()
+        .filter(|(_, before, after)| all || before != after)
+        .map(|(path, before, after)| (path.display().to_string(), before, after))
+        .collect();
+    writeln!(out)?;
+    if paths.is_empty() {
+        writeln!(out, "No path changed its match count.")
+    } else {
+        write_table(out, "Path", &paths)
+    }
+}
+
+pub fn run(args: CompareArgs) -> Result<(), FinderError> {
+    let pattern_specs = patterns::parse_patterns(&crate::read_decoded(&args.input_file)?);
+    let patterns: Vec<String> = pattern_specs.iter().map(|spec| spec.pattern.clone()).collect();
+    let regexes = crate::compile_regex_with_cache(&patterns, CaseMode::from_flags(args.ignore_case, false), false)?;
+    let search_options = SearchOptions {
+        negated: pattern_specs.iter().map(|spec| spec.negated).collect(),
+        ..Default::default()
+    };
+
+    let mut profiles = Vec::new();
+    for root in [&args.before, &args.after] {
+        if !root.is_dir() {
+            return Err(FinderError::Usage(format!("{}: not a directory", root.display())));
+        }
+        let outcome = crate::search_walk(
+            &WalkBuilder::new(root),
+            &regexes,
+            &search_options,
+            &FileFilter::default(),
+            &ProgressBar::hidden(),
+        );
+        crate::warn_unreadable(&outcome.errors);
+        profiles.push(Profile::new(root, &pattern_specs, &outcome.results));
+    }
+    write_report(&mut io::stdout().lock(), &profiles[0], &profiles[1], args.all)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, pattern_index: usize) -> SearchResult {
+        SearchResult::new(path, 1, "", pattern_index)
+    }
+
+    #[test]
+    fn test_compare_profiles() {
+        let pattern_specs = patterns::parse_patterns("secrets: api_key\nTODO\n");
+        let before = Profile::new(Path::new("v1"), &pattern_specs, &[
+            result("v1/src/config.rs", 1),
+            result("v1/src/main.rs", 1),
+        ]);
+        let after = Profile::new(Path::new("v2"), &pattern_specs, &[
+            result("v2/src/config.rs", 0),
+            result("v2/src/config.rs", 1),
+            result("v2/src/main.rs", 1),
+        ]);
+        let mut out = Vec::new();
+        write_report(&mut out, &before, &after, false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+Rule       before     after     delta
+TODO            2         2        +0
+secrets         0         1        +1
+
+Path             before     after     delta
+src/config.rs         1         2        +1
+");
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+
+use clap::builder::PossibleValuesParser;
+use clap::{Args as ClapArgs, CommandFactory};
+use clap_complete::Shell;
+
+use crate::config;
+use crate::error::FinderError;
+use crate::types;
+
+/// Arguments of `finder completions`.
+#[derive(ClapArgs, Debug)]
+pub struct CompletionsArgs {
+    /// The shell to write the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+
+    /// Also complete the types defined this way after --type and --type-not, as `name:glob`
+    #[arg(long = "type-add", value_name = "TYPE_SPEC")]
+    pub type_add: Vec<String>,
+}
+
+/// Writes the completion script of finder for `shell`, which completes the
+/// options, the subcommands and their values, including the file type names
+/// of `--type` and `--type-not` and the names of the `profiles`.
+pub fn write_completions(out: &mut dyn Write, shell: Shell, type_add: &[String], profiles: &[String]) -> Result<(), ignore::Error> {
+    // The type and profile names are only known at run time, and only given
+    // to the script: the search itself accepts the types added with
+    // --type-add and the profiles defined since.
+    let names = types::type_names(type_add)?;
+    let mut command = crate::Args::command()
+        .mut_arg("file_type", |arg| arg.value_parser(PossibleValuesParser::new(names.clone())))
+        .mut_arg("type_not", |arg| arg.value_parser(PossibleValuesParser::new(names)));
+    if !profiles.is_empty() {
+        command = command.mut_arg("profile", |arg| arg.value_parser(PossibleValuesParser::new(profiles.to_vec())));
+    }
+    clap_complete::generate(shell, &mut command, "finder", out);
+    Ok(())
+}
+
+pub fn run(args: CompletionsArgs) -> Result<(), FinderError> {
+    let dir = std::env::current_dir().map_err(|e| FinderError::io(".", e))?;
+    let profiles = config::profile_names(&config::config_files(&dir))?;
+    let mut out = io::stdout().lock();
+    write_completions(&mut out, args.shell, &args.type_add, &profiles)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_write_completions() {
+        let script = |shell: Shell| {
+            let mut out = Vec::new();
+            write_completions(&mut out, shell, &["proto:*.proto".to_string()], &["secrets".to_string(), "todo".to_string()]).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("--walk-threads") && bash.contains("completions"));
+        let types_line = bash.lines().find(|line| line.contains("compgen -W \"") && line.contains(" proto ")).unwrap();
+        assert!(types_line.contains(" rust ") && types_line.contains(" js "));
+        assert!(bash.lines().any(|line| line.contains("compgen -W \"secrets todo\"")));
+        for shell in Shell::value_variants() {
+            assert!(script(*shell).contains("proto"));
+        }
+        assert!(write_completions(&mut Vec::new(), Shell::Zsh, &["no glob".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::FinderError;
+use crate::json::{parse_string_array, parse_string_literal};
+use crate::theme;
+
+/// The configuration file of a project, looked for in the current directory
+/// and then in its parents.
+pub const PROJECT_FILE: &str = ".finder.toml";
+
+/// A named set of options of a configuration file, selected with
+/// `--profile`, such as the pattern file and globs of a secrets scan.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    /// Resolved against the directory of the configuration file, so that the
+    /// profile works from anywhere in the project.
+    pub input_file: Option<PathBuf>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub types: Vec<String>,
+    pub type_not: Vec<String>,
+    pub format: Option<String>,
+}
+
+impl Profile {
+    /// The options of the profile as command-line arguments, each with the id
+    /// of the argument it sets. Exclusions are `--include` globs starting
+    /// with `!`.
+    pub fn options(&self) -> Vec<(&'static str, String)> {
+        let mut options = Vec::new();
+        if let Some(input_file) = &self.input_file {
+            options.push(("input_file", format!("--input-file={}", input_file.display())));
+        }
+        options.extend(self.include.iter().map(|glob| ("include", format!("--include={}", glob))));
+        options.extend(self.exclude.iter().map(|glob| ("include", format!("--include=!{}", glob))));
+        options.extend(self.types.iter().map(|name| ("file_type", format!("--type={}", name))));
+        options.extend(self.type_not.iter().map(|name| ("type_not", format!("--type-not={}", name))));
+        if let Some(format) = &self.format {
+            options.push(("format", format!("--format={}", format)));
+        }
+        options
+    }
+}
+
+/// What a configuration file defines.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub profiles: Vec<(String, Profile)>,
+    /// The `--colors` specs of its `[colors]` section, such as
+    /// `path:fg:blue` for `path = "fg:blue"`.
+    pub colors: Vec<String>,
+}
+
+/// What the values of a configuration file can be, for the errors.
+const VALUES: &str = "finder reads double-quoted strings and arrays of them, not the rest of TOML";
+
+/// `line` without its `# comment`, a `#` inside a string left alone.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Whether `value` opens an array it does not close, which then goes on on
+/// the next lines.
+fn is_unclosed_array(value: &str) -> bool {
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut depth = 0i32;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/// Reads a configuration file in `dir`: `[profile.NAME]` sections and a
+/// `[colors]` section, of `key = "value"` and `key = ["value", ...]` lines,
+/// an array possibly going on over several lines, and `#` comments, also at
+/// the end of a line. Like the manifest, this is not a general TOML parser:
+/// numbers, booleans, single-quoted strings and tables are not read.
+pub fn parse_config(toml: &str, dir: &Path) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut in_colors = false;
+    let mut lines = toml.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line = strip_comment(line).trim();
+        let error = |message: String| format!("line {}: {}", index + 1, message);
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            in_colors = section.trim() == "colors";
+            if in_colors {
+                continue;
+            }
+            let name = section.trim().strip_prefix("profile.")
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| error(format!("unknown section `[{}]`, expected `[profile.NAME]` or `[colors]`", section.trim())))?;
+            if config.profiles.iter().any(|(known, _)| known == name) {
+                return Err(error(format!("profile `{}` is defined twice", name)));
+            }
+            config.profiles.push((name.to_string(), Profile::default()));
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| error(format!("expected `key = value`, found `{}`", line)))?;
+        let mut value = value.trim().to_string();
+        while is_unclosed_array(&value) {
+            let Some((_, next)) = lines.next() else { break };
+            value.push('\n');
+            value.push_str(strip_comment(next).trim());
+        }
+        let (key, value) = (key.trim(), value.as_str());
+        let string = || parse_string_literal(value).map_err(|e| error(format!("`{}`: {} ({})", key, e, VALUES)));
+        let list = || parse_string_array(value).map_err(|e| error(format!("`{}`: {} ({})", key, e, VALUES)));
+        if in_colors {
+            if !matches!(key, "path" | "line" | "pattern" | "match") {
+                return Err(error(format!("unknown key `{}`, expected path, line, pattern or match", key)));
+            }
+            let styles = if value.starts_with('[') { list()? } else { vec![string()?] };
+            for style in styles {
+                let spec = format!("{}:{}", key, style);
+                theme::parse_spec(&spec).map_err(error)?;
+                config.colors.push(spec);
+            }
+            continue;
+        }
+        let (_, profile) = config.profiles.last_mut().ok_or_else(|| error("expected a `[profile.NAME]` section first".to_string()))?;
+        match key {
+            "input_file" => profile.input_file = Some(dir.join(string()?)),
+            "include" => profile.include = list()?,
+            "exclude" => profile.exclude = list()?,
+            "type" => profile.types = list()?,
+            "type_not" => profile.type_not = list()?,
+            "format" => profile.format = Some(string()?),
+            _ => return Err(error(format!("unknown key `{}`, expected input_file, include, exclude, type, type_not or format", key))),
+        }
+    }
+    Ok(config)
+}
+
+/// Reads the configuration file at `path`.
+fn read_config(path: &Path) -> Result<Config, FinderError> {
+    let toml = fs::read_to_string(path).map_err(|e| FinderError::io(path, e))?;
+    parse_config(&toml, path.parent().unwrap_or(Path::new(""))).map_err(|e| FinderError::Usage(format!("{}: {}", path.display(), e)))
+}
+
+/// The configuration file of the user: `$FINDER_CONFIG`, or else
+/// `finder/config.toml` in the configuration directory of the user.
+fn user_config() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("FINDER_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join("finder").join("config.toml"))
+}
+
+/// The configuration files that may define profiles, in the order they are
+/// looked in: the `.finder.toml` of `dir` or of its closest parent with one,
+/// then the configuration of the user.
+pub fn config_files(dir: &Path) -> Vec<PathBuf> {
+    let project = dir.ancestors().map(|dir| dir.join(PROJECT_FILE)).find(|path| path.is_file());
+    project.into_iter().chain(user_config()).collect()
+}
+
+/// The profile `name` of the first of `files` that defines it.
+pub fn find_profile(name: &str, files: &[PathBuf]) -> Result<Profile, FinderError> {
+    let mut known = Vec::new();
+    for path in files.iter().filter(|path| path.is_file()) {
+        for (known_name, profile) in read_config(path)?.profiles {
+            if known_name == name {
+                return Ok(profile);
+            }
+            if !known.contains(&known_name) {
+                known.push(known_name);
+            }
+        }
+    }
+    if known.is_empty() {
+        let user = user_config().map_or("the configuration of the user".to_string(), |path| path.display().to_string());
+        return Err(FinderError::Usage(format!(
+            "no profile `{}`: define it as `[profile.{}]` in the {} of the project or in {}",
+            name, name, PROJECT_FILE, user
+        )));
+    }
+    let searched: Vec<String> = files.iter().filter(|path| path.is_file()).map(|path| path.display().to_string()).collect();
+    Err(FinderError::Usage(format!("no profile `{}` in {}; the profiles are: {}", name, searched.join(" or "), known.join(", "))))
+}
+
+/// The `[colors]` specs of `files`, those of the first file last, so that
+/// they take precedence over the others.
+pub fn color_specs(files: &[PathBuf]) -> Result<Vec<String>, FinderError> {
+    let mut specs = Vec::new();
+    for path in files.iter().rev().filter(|path| path.is_file()) {
+        specs.extend(read_config(path)?.colors);
+    }
+    Ok(specs)
+}
+
+/// The names of the profiles `files` define, for the completion of
+/// `--profile`.
+pub fn profile_names(files: &[PathBuf]) -> Result<Vec<String>, FinderError> {
+    let mut names = Vec::new();
+    for path in files.iter().filter(|path| path.is_file()) {
+        for (name, _) in read_config(path)?.profiles {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// `args` without `--profile` and its value.
+pub fn without_profile(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut kept = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            args.next();
+        } else if !arg.to_string_lossy().starts_with("--profile=") {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_config() {
+        let toml = "# Scans of the team\n[profile.secrets]\ninput_file = \"rules/secrets.txt\"\nexclude = [\"vendor/**\", \"*.lock\"]\nformat = \"sarif\"\n\n[profile.todo]\ntype = [\"rust\"]\n";
+        let profiles = parse_config(toml, Path::new("/repo")).unwrap().profiles;
+        assert_eq!(profiles.len(), 2);
+        let (name, secrets) = &profiles[0];
+        assert_eq!(name, "secrets");
+        assert_eq!(secrets.input_file, Some(PathBuf::from("/repo/rules/secrets.txt")));
+        assert_eq!(
+            secrets.options(),
+            [
+                ("input_file", format!("--input-file={}", Path::new("/repo/rules/secrets.txt").display())),
+                ("include", "--include=!vendor/**".to_string()),
+                ("include", "--include=!*.lock".to_string()),
+                ("format", "--format=sarif".to_string()),
+            ]
+        );
+        assert_eq!(profiles[1].1.options(), [("file_type", "--type=rust".to_string())]);
+
+        assert!(parse_config("format = \"json\"\n", Path::new("")).unwrap_err().contains("section first"));
+        assert!(parse_config("[settings]\n", Path::new("")).unwrap_err().contains("unknown section"));
+        assert!(parse_config("[profile.a]\nformats = \"json\"\n", Path::new("")).unwrap_err().starts_with("line 2: unknown key `formats`"));
+        assert!(parse_config("[profile.a]\ntype = \"rust\"\n", Path::new("")).is_err());
+        assert!(parse_config("[profile.a]\n[profile.a]\n", Path::new("")).unwrap_err().contains("twice"));
+        assert!(parse_config("[profile.a]\nformat = 'json'\n", Path::new("")).unwrap_err().contains("not the rest of TOML"));
+    }
+
+    #[test]
+    fn test_parse_config_multiline_arrays_and_comments() {
+        let toml = "[profile.logs] # the logs\ninclude = [\n    \"*.log\", # plain\n    \"*.log.gz\",\n]\nformat = \"json\" # machine\nexclude = [\"#tmp/**\"]\n";
+        let profiles = parse_config(toml, Path::new("")).unwrap().profiles;
+        assert_eq!(profiles[0].0, "logs");
+        assert_eq!(profiles[0].1.include, ["*.log", "*.log.gz"]);
+        assert_eq!(profiles[0].1.format.as_deref(), Some("json"));
+        assert_eq!(profiles[0].1.exclude, ["#tmp/**"]);
+        assert!(parse_config("[profile.a]\ninclude = [\n\"*.log\"\n", Path::new("")).unwrap_err().starts_with("line 2: `include`"));
+    }
+
+    #[test]
+    fn test_find_profile() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("repo");
+        fs::create_dir_all(project.join("src/deep")).unwrap();
+        fs::write(project.join(PROJECT_FILE), "[profile.secrets]\nformat = \"json\"\n").unwrap();
+        let user = dir.path().join("user.toml");
+        fs::write(&user, "[profile.secrets]\nformat = \"sarif\"\n[profile.todo]\ntype = [\"py\"]\n").unwrap();
+
+        assert_eq!(config_files(&project.join("src/deep"))[0], project.join(PROJECT_FILE));
+        let files = [project.join(PROJECT_FILE), user];
+        assert_eq!(find_profile("secrets", &files).unwrap().format.as_deref(), Some("json"));
+        assert_eq!(find_profile("todo", &files).unwrap().types, ["py"]);
+        assert_eq!(profile_names(&files).unwrap(), ["secrets", "todo"]);
+        let error = find_profile("licenses", &files).unwrap_err().to_string();
+        assert!(error.ends_with("; the profiles are: secrets, todo"), "{}", error);
+        let error = find_profile("x", &[dir.path().join("missing.toml")]).unwrap_err().to_string();
+        assert!(error.starts_with("no profile `x`: define it as `[profile.x]` in the .finder.toml of the project or in "), "{}", error);
+    }
+
+    #[test]
+    fn test_colors() {
+        let toml = "[colors]\npath = \"fg:blue\"\nmatch = [\"bg:yellow\", \"style:underline\"]\n[profile.a]\nformat = \"json\"\n";
+        let config = parse_config(toml, Path::new("")).unwrap();
+        assert_eq!(config.colors, ["path:fg:blue", "match:bg:yellow", "match:style:underline"]);
+        assert_eq!(config.profiles.len(), 1);
+        assert!(parse_config("[colors]\nfile = \"fg:blue\"\n", Path::new("")).unwrap_err().starts_with("line 2: unknown key `file`"));
+        assert!(parse_config("[colors]\npath = \"fg:grey\"\n", Path::new("")).unwrap_err().contains("unknown color `grey`"));
+
+        let dir = tempdir().unwrap();
+        let project = dir.path().join(PROJECT_FILE);
+        fs::write(&project, "[colors]\npath = \"fg:cyan\"\n").unwrap();
+        let user = dir.path().join("user.toml");
+        fs::write(&user, "[colors]\npath = \"fg:blue\"\nline = \"none\"\n").unwrap();
+        // The project comes last, to take precedence.
+        assert_eq!(color_specs(&[project, user]).unwrap(), ["path:fg:blue", "line:none", "path:fg:cyan"]);
+    }
+
+    #[test]
+    fn test_without_profile() {
+        let args = without_profile(["src", "--profile", "secrets", "-p", "x", "--profile=todo"].map(OsString::from));
+        assert_eq!(args, ["src", "-p", "x"]);
+    }
+}
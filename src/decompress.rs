@@ -0,0 +1,105 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use bzip2::read::BzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+use crate::longpath;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Picks the decompressor from the file extension (`app.log.3.gz` -> gzip).
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" | "tgz" => Some(Compression::Gzip),
+            "bz2" | "tbz2" => Some(Compression::Bzip2),
+            "xz" | "txz" => Some(Compression::Xz),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn reader<'a, R: Read + 'a>(self, inner: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(inner)),
+            Compression::Bzip2 => Box::new(BzDecoder::new(inner)),
+            Compression::Xz => Box::new(XzDecoder::new_multi_decoder(inner)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(inner)?),
+        })
+    }
+}
+
+/// Opens a file, transparently decompressing it when its extension names a
+/// supported compression format. Other files are read as-is.
+pub fn open_maybe_compressed(path: &Path) -> io::Result<Box<dyn Read>> {
+    let file = fs::File::open(longpath::for_opening(path))?;
+    match Compression::from_path(path) {
+        Some(compression) => compression.reader(file),
+        None => Ok(Box::new(file)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn read_maybe_compressed(path: &Path) -> io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        open_maybe_compressed(path)?.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    #[test]
+    fn test_compression_from_path() {
+        assert_eq!(Compression::from_path(Path::new("app.log.3.gz")), Some(Compression::Gzip));
+        assert_eq!(Compression::from_path(Path::new("dump.BZ2")), Some(Compression::Bzip2));
+        assert_eq!(Compression::from_path(Path::new("data.xz")), Some(Compression::Xz));
+        assert_eq!(Compression::from_path(Path::new("data.zst")), Some(Compression::Zstd));
+        assert_eq!(Compression::from_path(Path::new("plain.log")), None);
+    }
+
+    #[test]
+    fn test_read_maybe_compressed_roundtrip() {
+        let test_dir = tempdir().unwrap();
+        let content = b"first line\nsecond line\n";
+
+        let gz_path = test_dir.path().join("app.log.gz");
+        let mut gz = flate2::write::GzEncoder::new(fs::File::create(&gz_path).unwrap(), flate2::Compression::default());
+        gz.write_all(content).unwrap();
+        gz.finish().unwrap();
+
+        let bz_path = test_dir.path().join("app.log.bz2");
+        let mut bz = bzip2::write::BzEncoder::new(fs::File::create(&bz_path).unwrap(), bzip2::Compression::default());
+        bz.write_all(content).unwrap();
+        bz.finish().unwrap();
+
+        let xz_path = test_dir.path().join("app.log.xz");
+        let mut xz = xz2::write::XzEncoder::new(fs::File::create(&xz_path).unwrap(), 6);
+        xz.write_all(content).unwrap();
+        xz.finish().unwrap();
+
+        let zst_path = test_dir.path().join("app.log.zst");
+        fs::write(&zst_path, zstd::encode_all(&content[..], 0).unwrap()).unwrap();
+
+        let plain_path = test_dir.path().join("app.log");
+        fs::write(&plain_path, content).unwrap();
+
+        for path in [gz_path, bz_path, xz_path, zst_path, plain_path] {
+            assert_eq!(read_maybe_compressed(&path).unwrap(), content, "{}", path.display());
+        }
+
+        test_dir.close().unwrap();
+    }
+}
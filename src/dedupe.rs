@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::SearchResult;
+use crate::longpath;
+
+/// Files with the same contents, of which `--dedupe` only searches the
+/// first in path order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub original: PathBuf,
+    pub copies: Vec<PathBuf>,
+}
+
+fn content_hash(path: &Path) -> io::Result<u128> {
+    let mut reader = BufReader::new(File::open(longpath::for_opening(path))?);
+    let mut hasher = Xxh3::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.digest128());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// Groups the files with identical contents. Only files that share their
+/// size with another one are read; files that cannot be read are left alone.
+pub fn find_duplicates(files: &[PathBuf]) -> Vec<DuplicateGroup> {
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for path in files {
+        if let Ok(metadata) = fs::metadata(longpath::for_opening(path)) {
+            by_size.entry(metadata.len()).or_default().push(path);
+        }
+    }
+    let mut by_content: HashMap<(u64, u128), Vec<&PathBuf>> = HashMap::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        for path in paths {
+            if let Ok(hash) = content_hash(path) {
+                by_content.entry((size, hash)).or_default().push(path);
+            }
+        }
+    }
+    let mut groups: Vec<DuplicateGroup> = by_content.into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|mut paths| {
+            paths.sort();
+            DuplicateGroup {
+                original: paths[0].clone(),
+                copies: paths[1..].iter().map(|path| path.to_path_buf()).collect(),
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.original.cmp(&b.original));
+    groups
+}
+
+/// Reports every result found in an original against each of its copies
+/// too, including the results found inside an original archive.
+pub fn fan_out(results: Vec<SearchResult>, groups: &[DuplicateGroup]) -> Vec<SearchResult> {
+    let copies_of: HashMap<&Path, &[PathBuf]> = groups.iter()
+        .map(|group| (group.original.as_path(), group.copies.as_slice()))
+        .collect();
+    let mut fanned_out = Vec::with_capacity(results.len());
+    for result in results {
+        let path = result.path.to_string_lossy().into_owned();
+        let (file, inner) = match path.split_once('!') {
+            Some((file, inner)) if !copies_of.contains_key(result.path.as_path()) => (file, Some(inner)),
+            _ => (path.as_str(), None),
+        };
+        let copies = copies_of.get(Path::new(file)).copied().unwrap_or_default();
+        let fanned: Vec<SearchResult> = copies.iter()
+            .map(|copy| {
+                let path = match inner {
+                    Some(inner) => PathBuf::from(format!("{}!{}", copy.display(), inner)),
+                    None => copy.clone(),
+                };
+                SearchResult { path, ..result.clone() }
+            })
+            .collect();
+        fanned_out.push(result);
+        fanned_out.extend(fanned);
+    }
+    fanned_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn result(path: &str) -> SearchResult {
+        SearchResult { pattern: "x".to_string(), ..SearchResult::new(path, 1, "x", 0) }
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let files = vec![
+            write("c.js", "same"),
+            write("a.js", "same"),
+            write("b.js", "same"),
+            write("d.js", "sane"),
+            write("e.js", "other"),
+        ];
+        assert_eq!(find_duplicates(&files), vec![DuplicateGroup {
+            original: dir.path().join("a.js"),
+            copies: vec![dir.path().join("b.js"), dir.path().join("c.js")],
+        }]);
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let groups = [DuplicateGroup { original: PathBuf::from("a.zip"), copies: vec![PathBuf::from("vendor/a.zip")] }];
+        let paths: Vec<PathBuf> = fan_out(vec![result("a.zip!lib/x.js"), result("other.js")], &groups)
+            .into_iter()
+            .map(|result| result.path)
+            .collect();
+        assert_eq!(paths, vec![PathBuf::from("a.zip!lib/x.js"), PathBuf::from("vendor/a.zip!lib/x.js"), PathBuf::from("other.js")]);
+    }
+}
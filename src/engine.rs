@@ -0,0 +1,368 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use regex_syntax::ast::{self, Ast, ClassSet, ClassSetItem, LiteralKind, Span};
+
+use crate::error::FinderError;
+
+/// Regex backend used to compile the patterns.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Rust's `regex` crate: linear time, no look-around or backreferences
+    #[default]
+    Default,
+    /// PCRE2: supports look-around and backreferences (requires the `pcre2` feature)
+    Pcre2,
+}
+
+/// How letter case is matched.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    /// `-i`
+    Insensitive,
+    /// `-S`: insensitive unless the pattern contains an uppercase letter.
+    Smart,
+}
+
+impl CaseMode {
+    /// `-i` wins over `-S`.
+    pub fn from_flags(ignore_case: bool, smart_case: bool) -> CaseMode {
+        match (ignore_case, smart_case) {
+            (true, _) => CaseMode::Insensitive,
+            (false, true) => CaseMode::Smart,
+            (false, false) => CaseMode::Sensitive,
+        }
+    }
+
+    /// Whether `pattern` is compiled case-insensitively.
+    pub fn ignore_case(self, pattern: &str) -> bool {
+        match self {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !has_uppercase_literal(pattern),
+        }
+    }
+}
+
+/// Whether `pattern` has an uppercase letter among its literals and class
+/// ranges, so that `\S`, `\PL`, `(?P<Name>...)` or `\p{Lu}` do not make a
+/// lowercase pattern case-sensitive. A pattern that does not parse is
+/// scanned as text, for compiling it to report the error.
+fn has_uppercase_literal(pattern: &str) -> bool {
+    match ast::parse::Parser::new().parse(pattern) {
+        Ok(ast) => ast_has_uppercase(&ast),
+        Err(_) => pattern.chars().any(char::is_uppercase),
+    }
+}
+
+fn ast_has_uppercase(ast: &Ast) -> bool {
+    match ast {
+        Ast::Literal(literal) => literal.c.is_uppercase(),
+        Ast::ClassBracketed(class) => class_has_uppercase(&class.kind),
+        Ast::Repetition(repetition) => ast_has_uppercase(&repetition.ast),
+        Ast::Group(group) => ast_has_uppercase(&group.ast),
+        Ast::Alternation(alternation) => alternation.asts.iter().any(ast_has_uppercase),
+        Ast::Concat(concat) => concat.asts.iter().any(ast_has_uppercase),
+        _ => false,
+    }
+}
+
+fn class_has_uppercase(set: &ClassSet) -> bool {
+    match set {
+        ClassSet::BinaryOp(op) => class_has_uppercase(&op.lhs) || class_has_uppercase(&op.rhs),
+        ClassSet::Item(item) => item_has_uppercase(item),
+    }
+}
+
+fn item_has_uppercase(item: &ClassSetItem) -> bool {
+    match item {
+        ClassSetItem::Literal(literal) => literal.c.is_uppercase(),
+        ClassSetItem::Range(range) => range.start.c.is_uppercase() || range.end.c.is_uppercase(),
+        ClassSetItem::Bracketed(class) => class_has_uppercase(&class.kind),
+        ClassSetItem::Union(union) => union.items.iter().any(item_has_uppercase),
+        _ => false,
+    }
+}
+
+/// The offset of the character after the one at `offset`, or past the end
+/// of `haystack` when there is none, for resuming a search there.
+pub fn next_char_boundary(haystack: &str, offset: usize) -> usize {
+    haystack[offset..].chars().next().map_or(haystack.len() + 1, |c| offset + c.len_utf8())
+}
+
+/// A compiled pattern, whatever the engine behind it.
+pub trait Matcher: Send + Sync {
+    /// The pattern as written by the user.
+    fn as_str(&self) -> &str;
+
+    fn is_match(&self, haystack: &str) -> bool;
+
+    /// Byte ranges of all successive non-overlapping matches.
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>>;
+
+    /// First match starting at or after `start`, with the text before `start`
+    /// still visible to anchors and word boundaries.
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>>;
+}
+
+impl Matcher for Regex {
+    fn as_str(&self) -> &str {
+        Regex::as_str(self)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        Regex::is_match(self, haystack)
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        self.find_iter(haystack).map(|m| m.range()).collect()
+    }
+
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        Regex::find_at(self, haystack, start).map(|m| m.range())
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn as_str(&self) -> &str {
+        (**self).as_str()
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        (**self).is_match(haystack)
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        (**self).find_spans(haystack)
+    }
+
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        (**self).find_at(haystack, start)
+    }
+}
+
+/// A regex compiled with Unicode off (`--no-unicode`), which keeps the
+/// pattern as written rather than as rewritten by `ascii_pattern`.
+struct AsciiRegex {
+    pattern: String,
+    regex: Regex,
+}
+
+impl Matcher for AsciiRegex {
+    fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.regex.is_match(haystack)
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        self.regex.find_spans(haystack)
+    }
+
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        Matcher::find_at(&self.regex, haystack, start)
+    }
+}
+
+/// Whether a class needs Unicode on: with it off, it would match bytes in
+/// the middle of a character, or does not compile.
+fn class_needs_unicode(set: &ClassSet) -> bool {
+    match set {
+        ClassSet::BinaryOp(op) => class_needs_unicode(&op.lhs) || class_needs_unicode(&op.rhs),
+        ClassSet::Item(item) => item_needs_unicode(item),
+    }
+}
+
+fn item_needs_unicode(item: &ClassSetItem) -> bool {
+    match item {
+        ClassSetItem::Literal(literal) => !literal.c.is_ascii(),
+        ClassSetItem::Range(range) => !range.start.c.is_ascii() || !range.end.c.is_ascii(),
+        ClassSetItem::Ascii(class) => class.negated,
+        ClassSetItem::Unicode(_) => true,
+        ClassSetItem::Perl(class) => class.negated,
+        ClassSetItem::Bracketed(class) => class.negated || class_needs_unicode(&class.kind),
+        ClassSetItem::Union(union) => union.items.iter().any(item_needs_unicode),
+        ClassSetItem::Empty(_) => false,
+    }
+}
+
+/// The spans of `ast`, left to right, that need Unicode on.
+fn unicode_spans(ast: &Ast, spans: &mut Vec<Span>) {
+    match ast {
+        Ast::Dot(span) => spans.push(**span),
+        Ast::Literal(literal) if !literal.c.is_ascii() && literal.kind != LiteralKind::Verbatim => spans.push(literal.span),
+        Ast::ClassUnicode(class) => spans.push(class.span),
+        Ast::ClassPerl(class) if class.negated => spans.push(class.span),
+        Ast::ClassBracketed(class) if class.negated || class_needs_unicode(&class.kind) => spans.push(class.span),
+        Ast::Repetition(repetition) => unicode_spans(&repetition.ast, spans),
+        Ast::Group(group) => unicode_spans(&group.ast, spans),
+        Ast::Alternation(alternation) => alternation.asts.iter().for_each(|ast| unicode_spans(ast, spans)),
+        Ast::Concat(concat) => concat.asts.iter().for_each(|ast| unicode_spans(ast, spans)),
+        _ => {}
+    }
+}
+
+/// Rewrites `pattern` to be compiled with Unicode off, so that `\w`, `\d`,
+/// `\s`, `\b` and case folding only know ASCII. The `regex` crate refuses
+/// what could then match part of a character, such as `.` or `[^a]`, so
+/// that keeps Unicode on in a `(?u:...)` group, as do `\p{...}` classes.
+/// A pattern that does not parse is returned as is, for compiling it to
+/// report the error.
+pub fn ascii_pattern(pattern: &str) -> String {
+    let Ok(ast) = ast::parse::Parser::new().parse(pattern) else {
+        return pattern.to_string();
+    };
+    let mut spans = Vec::new();
+    unicode_spans(&ast, &mut spans);
+    let mut rewritten = String::with_capacity(pattern.len() + 5 * spans.len());
+    let mut last_end = 0;
+    for span in spans {
+        rewritten.push_str(&pattern[last_end..span.start.offset]);
+        rewritten.push_str("(?u:");
+        rewritten.push_str(&pattern[span.start.offset..span.end.offset]);
+        rewritten.push(')');
+        last_end = span.end.offset;
+    }
+    rewritten.push_str(&pattern[last_end..]);
+    rewritten
+}
+
+/// Compiles `patterns` with the `regex` crate and Unicode off
+/// (`--no-unicode`).
+pub fn compile_ascii(patterns: &[String], case: CaseMode, multiline: bool) -> Result<Vec<Box<dyn Matcher>>, regex::Error> {
+    patterns.iter()
+        .map(|pattern| {
+            let regex = RegexBuilder::new(&ascii_pattern(pattern))
+                .unicode(false)
+                .case_insensitive(case.ignore_case(pattern))
+                .multi_line(multiline)
+                .crlf(multiline)
+                .build()?;
+            Ok(Box::new(AsciiRegex { pattern: pattern.clone(), regex }) as Box<dyn Matcher>)
+        })
+        .collect()
+}
+
+#[cfg(feature = "pcre2")]
+mod pcre2_engine {
+    use super::*;
+
+    pub struct Pcre2Matcher(pub pcre2::bytes::Regex);
+
+    // A match-limit error is reported by PCRE2 as an error rather than a
+    // non-match; for searching purposes both mean "no hit on this text".
+    impl Matcher for Pcre2Matcher {
+        fn as_str(&self) -> &str {
+            self.0.as_str()
+        }
+
+        fn is_match(&self, haystack: &str) -> bool {
+            self.0.is_match(haystack.as_bytes()).unwrap_or(false)
+        }
+
+        fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+            self.0
+                .find_iter(haystack.as_bytes())
+                .map_while(Result::ok)
+                .map(|m| m.start()..m.end())
+                .collect()
+        }
+
+        fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+            self.0.find_at(haystack.as_bytes(), start).ok().flatten().map(|m| m.start()..m.end())
+        }
+    }
+
+    pub fn compile(pattern: &str, ignore_case: bool, multiline: bool, unicode: bool) -> Result<Box<dyn Matcher>, pcre2::Error> {
+        let regex = pcre2::bytes::RegexBuilder::new()
+            .utf(true)
+            .ucp(unicode)
+            .jit_if_available(true)
+            .caseless(ignore_case)
+            .multi_line(multiline)
+            .crlf(multiline)
+            .build(pattern)?;
+        Ok(Box::new(Pcre2Matcher(regex)))
+    }
+}
+
+/// Compiles `patterns` with PCRE2. Without `unicode`, `\w`, `\d`, `\s` and
+/// `\b` only know ASCII.
+#[cfg(feature = "pcre2")]
+pub fn compile_pcre2(patterns: &[String], case: CaseMode, multiline: bool, unicode: bool) -> Result<Vec<Box<dyn Matcher>>, FinderError> {
+    patterns.iter()
+        .map(|p| pcre2_engine::compile(p, case.ignore_case(p), multiline, unicode).map_err(Into::into))
+        .collect()
+}
+
+#[cfg(not(feature = "pcre2"))]
+pub fn compile_pcre2(_patterns: &[String], _case: CaseMode, _multiline: bool, _unicode: bool) -> Result<Vec<Box<dyn Matcher>>, FinderError> {
+    Err("finder was built without PCRE2 support (rebuild with `--features pcre2`)".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_matcher_spans() {
+        let re = Regex::new("o").unwrap();
+        assert!(Matcher::is_match(&re, "foo"));
+        assert_eq!(re.find_spans("foo boo"), vec![1..2, 2..3, 5..6, 6..7]);
+        let boxed: Box<dyn Matcher> = Box::new(re);
+        assert_eq!(boxed.as_str(), "o");
+    }
+
+    #[test]
+    fn test_smart_case() {
+        let smart = CaseMode::from_flags(false, true);
+        assert!(smart.ignore_case("error"));
+        assert!(!smart.ignore_case("Error"));
+        assert!(smart.ignore_case(r"\bfoo\S+\W\p{Lu}\pL"));
+        assert!(!smart.ignore_case(r"\bfoo ÉTÉ"));
+        assert!(smart.ignore_case(r"(?P<Name>foo)\PL(?-U:x)[[:^alpha:]\D]"));
+        assert!(!smart.ignore_case("[a-zA-Z]+"));
+        assert!(CaseMode::from_flags(true, true).ignore_case("Error"));
+        assert!(!CaseMode::from_flags(false, false).ignore_case("error"));
+    }
+
+    #[test]
+    fn test_ascii_pattern() {
+        assert_eq!(ascii_pattern(r"\w+\s\d"), r"\w+\s\d");
+        assert_eq!(ascii_pattern(r"a.*[^b]\W(\p{Greek}|[é-ü]x)"), r"a(?u:.)*(?u:[^b])(?u:\W)((?u:\p{Greek})|(?u:[é-ü])x)");
+        assert_eq!(ascii_pattern(r"é\x{e9}\xe9[a[:^digit:]]"), r"é(?u:\x{e9})(?u:\xe9)(?u:[a[:^digit:]])");
+        assert_eq!(ascii_pattern("(unclosed"), "(unclosed");
+    }
+
+    #[test]
+    fn test_compile_ascii() {
+        let patterns = [r"\w+".to_string(), "straße".to_string(), ".+".to_string()];
+        let matchers = compile_ascii(&patterns, CaseMode::Insensitive, false).unwrap();
+        assert_eq!(matchers[0].find_spans("école k"), vec![2..6, 7..8]);
+        assert_eq!(matchers[0].as_str(), r"\w+");
+        // Case folding is ASCII-only: `ß` no longer matches `ẞ`.
+        assert!(matchers[1].is_match("STRAße"));
+        assert!(!matchers[1].is_match("STRAẞE"));
+        assert_eq!(matchers[2].find_spans("été"), vec![0..5]);
+    }
+
+    #[cfg(feature = "pcre2")]
+    #[test]
+    fn test_pcre2_look_around_and_backreferences() {
+        let matchers = compile_pcre2(&[r"(?<=\$)\d+".to_string(), r"(\w)\1".to_string()], CaseMode::Sensitive, false, true).unwrap();
+        assert_eq!(matchers[0].find_spans("cost: $42"), vec![7..9]);
+        assert!(matchers[1].is_match("hello"));
+        assert!(!matchers[1].is_match("helo"));
+    }
+
+    #[cfg(not(feature = "pcre2"))]
+    #[test]
+    fn test_pcre2_unavailable_without_feature() {
+        assert!(compile_pcre2(&["a".to_string()], CaseMode::Sensitive, false, true).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Everything that can go wrong while searching.
+#[derive(Debug)]
+pub enum FinderError {
+    /// An I/O operation failed, on `path` when it is known.
+    Io { path: Option<PathBuf>, source: io::Error },
+    /// A pattern did not compile.
+    Regex(regex::Error),
+    #[cfg(feature = "pcre2")]
+    Pcre2(pcre2::Error),
+    /// A file that must be read exactly, such as a patterns file, is not
+    /// valid in the encoding its byte order mark announces.
+    Encoding { path: PathBuf, encoding: &'static str },
+    /// A glob, a file type or a directory entry the walk could not handle.
+    Walk(ignore::Error),
+    /// A symbolic link to a directory that the walk goes through already,
+    /// such as one above it, which was not followed (`--follow`).
+    SymlinkCycle { link: PathBuf, target: PathBuf },
+    Watch(notify::Error),
+    /// Options that cannot be honored.
+    Usage(String),
+}
+
+impl FinderError {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> FinderError {
+        FinderError::Io { path: Some(path.into()), source }
+    }
+
+    pub fn is_symlink_cycle(&self) -> bool {
+        matches!(self, FinderError::SymlinkCycle { .. })
+    }
+
+    /// The file or directory the error is about, if any, and the reason on
+    /// its own, so that errors can be grouped by reason.
+    pub fn path_and_reason(&self) -> (Option<&Path>, String) {
+        match self {
+            FinderError::Io { path, source } => (path.as_deref(), source.to_string()),
+            FinderError::Encoding { path, encoding } => (Some(path), format!("not valid {}", encoding)),
+            FinderError::Walk(e) => walk_path_and_reason(e),
+            FinderError::SymlinkCycle { link, .. } => (Some(link), "symbolic link to a directory already searched".to_string()),
+            _ => (None, self.to_string()),
+        }
+    }
+}
+
+fn walk_path_and_reason(e: &ignore::Error) -> (Option<&Path>, String) {
+    match e {
+        ignore::Error::WithPath { path, err } => (Some(path), walk_path_and_reason(err).1),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => walk_path_and_reason(err),
+        _ => (None, e.to_string()),
+    }
+}
+
+/// The reasons `errors` happened, the most frequent first, each with the
+/// paths it happened to.
+pub fn group_by_reason<'e>(errors: impl IntoIterator<Item = &'e FinderError>) -> Vec<(String, Vec<String>)> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for e in errors {
+        let (path, reason) = e.path_and_reason();
+        let path = path.map_or_else(String::new, |path| path.display().to_string());
+        match groups.iter_mut().find(|(known, _)| *known == reason) {
+            Some((_, paths)) => paths.push(path),
+            None => groups.push((reason, vec![path])),
+        }
+    }
+    groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+    groups
+}
+
+impl fmt::Display for FinderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FinderError::Io { path: Some(path), source } => write!(f, "{}: {}", path.display(), source),
+            FinderError::Io { path: None, source } => source.fmt(f),
+            FinderError::Regex(e) => write!(f, "invalid pattern: {}", e),
+            #[cfg(feature = "pcre2")]
+            FinderError::Pcre2(e) => write!(f, "invalid pattern: {}", e),
+            FinderError::Encoding { path, encoding } => write!(f, "{}: not valid {}", path.display(), encoding),
+            FinderError::Walk(e) => e.fmt(f),
+            FinderError::SymlinkCycle { link, target } => write!(f, "{}: symbolic link to {}, already searched", link.display(), target.display()),
+            FinderError::Watch(e) => write!(f, "cannot watch for changes: {}", e),
+            FinderError::Usage(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for FinderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FinderError::Io { source, .. } => Some(source),
+            FinderError::Regex(e) => Some(e),
+            #[cfg(feature = "pcre2")]
+            FinderError::Pcre2(e) => Some(e),
+            FinderError::Walk(e) => Some(e),
+            FinderError::Watch(e) => Some(e),
+            FinderError::Encoding { .. } | FinderError::SymlinkCycle { .. } | FinderError::Usage(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for FinderError {
+    fn from(source: io::Error) -> FinderError {
+        FinderError::Io { path: None, source }
+    }
+}
+
+impl From<regex::Error> for FinderError {
+    fn from(e: regex::Error) -> FinderError {
+        FinderError::Regex(e)
+    }
+}
+
+#[cfg(feature = "pcre2")]
+impl From<pcre2::Error> for FinderError {
+    fn from(e: pcre2::Error) -> FinderError {
+        FinderError::Pcre2(e)
+    }
+}
+
+/// The link and the directory above it of a loop the walker found.
+fn symlink_loop(e: &ignore::Error) -> Option<(&Path, &Path)> {
+    match e {
+        ignore::Error::Loop { ancestor, child } => Some((child, ancestor)),
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => symlink_loop(err),
+        _ => None,
+    }
+}
+
+impl From<ignore::Error> for FinderError {
+    fn from(e: ignore::Error) -> FinderError {
+        match symlink_loop(&e) {
+            Some((link, target)) => FinderError::SymlinkCycle { link: link.to_path_buf(), target: target.to_path_buf() },
+            None => FinderError::Walk(e),
+        }
+    }
+}
+
+impl From<notify::Error> for FinderError {
+    fn from(e: notify::Error) -> FinderError {
+        FinderError::Watch(e)
+    }
+}
+
+impl From<&str> for FinderError {
+    fn from(message: &str) -> FinderError {
+        FinderError::Usage(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let missing = FinderError::io("logs/app.log", io::Error::new(io::ErrorKind::NotFound, "No such file or directory"));
+        assert_eq!(missing.to_string(), "logs/app.log: No such file or directory");
+        assert!(missing.source().is_some());
+
+        let unclosed = "(";
+        let regex = FinderError::from(regex::Regex::new(unclosed).unwrap_err());
+        assert!(regex.to_string().starts_with("invalid pattern: regex parse error"));
+
+        let encoding = FinderError::Encoding { path: PathBuf::from("rules.txt"), encoding: "UTF-8" };
+        assert_eq!(encoding.to_string(), "rules.txt: not valid UTF-8");
+
+        let cycle = FinderError::from(ignore::Error::WithDepth {
+            depth: 3,
+            err: Box::new(ignore::Error::Loop { ancestor: PathBuf::from("src"), child: PathBuf::from("src/lib/up") }),
+        });
+        assert!(cycle.is_symlink_cycle());
+        assert_eq!(cycle.to_string(), "src/lib/up: symbolic link to src, already searched");
+    }
+
+    #[test]
+    fn test_group_by_reason() {
+        let denied = || io::Error::from(io::ErrorKind::PermissionDenied);
+        let walk = ignore::Error::WithDepth {
+            depth: 2,
+            err: Box::new(ignore::Error::WithPath { path: PathBuf::from("secret"), err: Box::new(ignore::Error::Io(denied())) }),
+        };
+        let errors = [
+            FinderError::io("b.gz", io::Error::new(io::ErrorKind::InvalidData, "invalid gzip header")),
+            FinderError::io("a.txt", denied()),
+            FinderError::Walk(walk),
+        ];
+        let reason = denied().to_string();
+        assert_eq!(group_by_reason(&errors), vec![
+            (reason, vec!["a.txt".to_string(), "secret".to_string()]),
+            ("invalid gzip header".to_string(), vec!["b.gz".to_string()]),
+        ]);
+    }
+}
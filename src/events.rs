@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::SearchResult;
+use crate::engine::Matcher;
+use crate::json::json_string;
+use crate::longpath;
+use crate::output::{self, Printer};
+use crate::stats::SearchStats;
+
+/// A duration the way `rg --json` writes it.
+fn duration(elapsed: Duration) -> String {
+    format!(
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:.6}s\"}}",
+        elapsed.as_secs(),
+        elapsed.subsec_nanos(),
+        elapsed.as_secs_f64()
+    )
+}
+
+/// The counters of an `end` or `summary` event.
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    searches: usize,
+    searches_with_match: usize,
+    bytes_searched: u64,
+    bytes_printed: u64,
+    matched_lines: usize,
+    matches: usize,
+}
+
+impl Counts {
+    fn to_json(self, elapsed: Duration) -> String {
+        format!(
+            "{{\"elapsed\":{},\"searches\":{},\"searches_with_match\":{},\"bytes_searched\":{},\"bytes_printed\":{},\"matched_lines\":{},\"matches\":{}}}",
+            duration(elapsed),
+            self.searches,
+            self.searches_with_match,
+            self.bytes_searched,
+            self.bytes_printed,
+            self.matched_lines,
+            self.matches
+        )
+    }
+}
+
+/// The text of a matching line as printed, masked with `--redact`, and
+/// where its matches are in it.
+fn line_and_spans<M: Matcher>(printer: &Printer<M>, result: &SearchResult) -> (String, Vec<Range<usize>>) {
+    let spans: Vec<Range<usize>> = printer.line_matches(&result.line).into_iter().map(|(_, span)| span).collect();
+    if !printer.options.redact {
+        return (result.line.clone(), spans);
+    }
+    // Masking can shorten a match, which moves the ones after it.
+    let mut line = String::with_capacity(result.line.len());
+    let mut masked_spans = Vec::with_capacity(spans.len());
+    let mut last_end = 0;
+    for span in spans {
+        line.push_str(&result.line[last_end..span.start]);
+        let start = line.len();
+        line.push_str(&output::mask(&result.line[span.clone()]));
+        masked_spans.push(start..line.len());
+        last_end = span.end;
+    }
+    line.push_str(&result.line[last_end..]);
+    (line, masked_spans)
+}
+
+/// Writes `event` and counts its bytes.
+fn emit(out: &mut dyn Write, counts: &mut Counts, event: String) -> io::Result<()> {
+    counts.bytes_printed += event.len() as u64 + 1;
+    writeln!(out, "{}", event)
+}
+
+/// Writes the results as the event stream of `rg --json`, so that the
+/// tools built for it can read them: for each file with matches a `begin`
+/// event, a `match` event per result and an `end` event, then a `summary`
+/// of the whole search. `offsets` are the byte offsets of the matching
+/// lines, as `line_offsets` finds them; the lines not in them have a null
+/// `absolute_offset`.
+pub fn write_events<M: Matcher>(
+    out: &mut dyn Write,
+    printer: &Printer<M>,
+    results: &[SearchResult],
+    stats: &SearchStats,
+    offsets: &HashMap<PathBuf, HashMap<usize, u64>>,
+    elapsed_total: Duration,
+) -> io::Result<()> {
+    let times: HashMap<&Path, Duration> = stats.timings().iter().map(|(path, elapsed, _)| (path.as_path(), *elapsed)).collect();
+    // By file, in the order the results come in.
+    let mut files: Vec<(&Path, Vec<&SearchResult>)> = Vec::new();
+    let mut positions: HashMap<&Path, usize> = HashMap::new();
+    for result in results {
+        let position = *positions.entry(&result.path).or_insert_with(|| {
+            files.push((&result.path, Vec::new()));
+            files.len() - 1
+        });
+        files[position].1.push(result);
+    }
+
+    let mut total = Counts { searches: stats.files_searched, bytes_searched: stats.bytes_scanned, ..Counts::default() };
+    let mut search_time = Duration::ZERO;
+    for (path, results) in files {
+        let path_json = format!("{{\"text\":{}}}", json_string(&path.to_string_lossy()));
+        let mut counts = Counts {
+            searches: 1,
+            searches_with_match: 1,
+            bytes_searched: fs::metadata(longpath::for_opening(path)).map_or(0, |metadata| metadata.len()),
+            ..Counts::default()
+        };
+        emit(out, &mut counts, format!("{{\"type\":\"begin\",\"data\":{{\"path\":{}}}}}", path_json))?;
+        for result in results {
+            let (line, spans) = line_and_spans(printer, result);
+            let submatches: Vec<String> = spans.iter()
+                .map(|span| format!("{{\"match\":{{\"text\":{}}},\"start\":{},\"end\":{}}}", json_string(&line[span.clone()]), span.start, span.end))
+                .collect();
+            let offset = offsets.get(&result.path).and_then(|offsets| offsets.get(&result.line_number));
+            counts.matched_lines += 1;
+            counts.matches += spans.len();
+            emit(out, &mut counts, format!(
+                "{{\"type\":\"match\",\"data\":{{\"path\":{},\"lines\":{{\"text\":{}}},\"line_number\":{},\"absolute_offset\":{},\"submatches\":[{}]}}}}",
+                path_json,
+                json_string(&format!("{}\n", line)),
+                result.line_number,
+                offset.map_or("null".to_string(), u64::to_string),
+                submatches.join(","),
+            ))?;
+        }
+        let elapsed = times.get(path).copied().unwrap_or_default();
+        search_time += elapsed;
+        // As with rg, the end event is in the bytes printed of the summary,
+        // not in its own.
+        let end = format!(
+            "{{\"type\":\"end\",\"data\":{{\"path\":{},\"binary_offset\":null,\"stats\":{}}}}}",
+            path_json,
+            counts.to_json(elapsed)
+        );
+        total.searches_with_match += 1;
+        total.matched_lines += counts.matched_lines;
+        total.matches += counts.matches;
+        total.bytes_printed += counts.bytes_printed + end.len() as u64 + 1;
+        writeln!(out, "{}", end)?;
+    }
+    writeln!(
+        out,
+        "{{\"type\":\"summary\",\"data\":{{\"elapsed_total\":{},\"stats\":{}}}}}",
+        duration(elapsed_total),
+        total.to_json(search_time)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputOptions;
+    use crate::patterns::PatternSpec;
+    use regex::Regex;
+
+    #[test]
+    fn test_write_events() {
+        let result = |line_number: usize, line: &str| SearchResult { pattern: "key=\\w+".to_string(), ..SearchResult::new("src/app.py", line_number, line, 0) };
+        let results = [result(3, "é key=abc key=d"), result(9, "key=x")];
+        let pattern_specs = [PatternSpec::new("key=\\w+")];
+        let matchers = [Regex::new("key=\\w+").unwrap()];
+        let mut stats = SearchStats::new(1);
+        stats.record_file(Path::new("src/app.py"), 120, Duration::from_millis(2), &results);
+        stats.record_file(Path::new("src/other.py"), 30, Duration::from_millis(1), &[]);
+        let offsets = HashMap::from([(PathBuf::from("src/app.py"), HashMap::from([(3, 40)]))]);
+        let render = |redact: bool| {
+            let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions { redact, ..Default::default() } };
+            let mut out = Vec::new();
+            write_events(&mut out, &printer, &results, &stats, &offsets, Duration::from_millis(5)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let events = render(false);
+        let lines: Vec<&str> = events.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], r#"{"type":"begin","data":{"path":{"text":"src/app.py"}}}"#);
+        assert_eq!(
+            lines[1],
+            r#"{"type":"match","data":{"path":{"text":"src/app.py"},"lines":{"text":"é key=abc key=d\n"},"line_number":3,"absolute_offset":40,"submatches":[{"match":{"text":"key=abc"},"start":3,"end":10},{"match":{"text":"key=d"},"start":11,"end":16}]}}"#
+        );
+        assert!(lines[2].contains(r#""line_number":9,"absolute_offset":null,"#));
+        assert!(lines[3].starts_with(r#"{"type":"end","data":{"path":{"text":"src/app.py"},"binary_offset":null,"stats":{"elapsed":{"secs":0,"nanos":2000000,"human":"0.002000s"},"searches":1,"searches_with_match":1,"bytes_searched":0,"#));
+        assert!(lines[3].ends_with(r#""matched_lines":2,"matches":3}}}"#));
+        assert!(lines[4].starts_with(r#"{"type":"summary","data":{"elapsed_total":{"secs":0,"nanos":5000000,"human":"0.005000s"},"stats":{"elapsed":{"secs":0,"nanos":2000000,"human":"0.002000s"},"searches":2,"searches_with_match":1,"bytes_searched":150,"bytes_printed":"#));
+        let printed: usize = lines[..4].iter().map(|line| line.len() + 1).sum();
+        assert!(lines[4].contains(&format!(r#""bytes_printed":{},"#, printed)));
+
+        let redacted = render(true);
+        assert!(redacted.contains(r#""lines":{"text":"é ***=*** ***=*\n"},"line_number":3,"absolute_offset":40,"submatches":[{"match":{"text":"***=***"},"start":3,"end":10}"#));
+    }
+}
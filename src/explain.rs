@@ -0,0 +1,303 @@
+use std::io::{self, IsTerminal, Read, Write};
+use std::ops::Range;
+
+use clap::Args as ClapArgs;
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::Hir;
+use regex_syntax::hir::literal::{ExtractKind, Extractor, Seq};
+
+use crate::engine::{CaseMode, Matcher};
+use crate::error::FinderError;
+use crate::word::{self, WordChars, WordMatcher};
+
+/// The size limit of the `regex` crate, past which a pattern does not
+/// compile.
+const SIZE_LIMIT: usize = 10 * (1 << 20);
+
+/// How many literals of a set are shown.
+const LITERALS_SHOWN: usize = 8;
+
+/// Arguments of `finder explain`.
+#[derive(ClapArgs, Debug)]
+pub struct ExplainArgs {
+    /// The pattern to explain (can be repeated)
+    #[arg(short = 'p', long, required = true)]
+    pub pattern: Vec<String>,
+
+    /// Case-insensitive search
+    #[arg(short, long)]
+    pub ignore_case: bool,
+
+    /// Case-insensitive unless the pattern contains an uppercase letter
+    #[arg(short = 'S', long)]
+    pub smart_case: bool,
+
+    /// Only match whole words, as with -w
+    #[arg(short = 'w', long)]
+    pub word_regexp: bool,
+
+    /// Regex character class of word characters for -w
+    #[arg(long, value_name = "CLASS", requires = "word_regexp")]
+    pub word_chars: Option<String>,
+
+    /// Allow the pattern to match across line boundaries
+    #[arg(short = 'U', long)]
+    pub multiline: bool,
+}
+
+/// What the options of a search do to the patterns.
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub case: CaseMode,
+    /// `-w`, and the characters that make up words.
+    pub word_chars: Option<WordChars>,
+    pub multiline: bool,
+}
+
+impl Settings {
+    fn compile(&self, pattern: &str, ignore_case: bool) -> Result<Regex, regex::Error> {
+        self.builder(pattern, ignore_case).build()
+    }
+
+    fn builder(&self, pattern: &str, ignore_case: bool) -> RegexBuilder {
+        let mut builder = RegexBuilder::new(pattern);
+        builder.case_insensitive(ignore_case).multi_line(self.multiline).crlf(self.multiline);
+        builder
+    }
+
+    /// How the case of `pattern` is matched, and why.
+    fn describe_case(&self, pattern: &str) -> &'static str {
+        match (self.case, self.case.ignore_case(pattern)) {
+            (CaseMode::Sensitive, _) => "sensitive",
+            (CaseMode::Insensitive, _) => "insensitive (-i): letters match in either case, Unicode case folding included",
+            (CaseMode::Smart, true) => "insensitive (-S, as the pattern has no uppercase letter)",
+            (CaseMode::Smart, false) => "sensitive (-S, as the pattern has an uppercase letter)",
+        }
+    }
+}
+
+/// The smallest size limit `pattern` compiles with, about the size of its
+/// compiled automaton in bytes.
+fn automaton_size(settings: &Settings, pattern: &str, ignore_case: bool) -> usize {
+    let (mut low, mut high) = (0, SIZE_LIMIT);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if settings.builder(pattern, ignore_case).size_limit(middle).build().is_ok() {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    high
+}
+
+/// A set of literals the way `explain` shows it, e.g. `"foo", "bar" (exact)`;
+/// the matches `end` with them, `starts` or `ends`.
+fn describe_literals(seq: &Seq, end: &str) -> String {
+    let Some(literals) = seq.literals() else {
+        return "any (too many to use)".to_string();
+    };
+    if literals.is_empty() || literals.iter().all(|literal| literal.as_bytes().is_empty()) {
+        return "none".to_string();
+    }
+    let mut shown: Vec<String> = literals.iter()
+        .take(LITERALS_SHOWN)
+        .map(|literal| format!("{:?}", String::from_utf8_lossy(literal.as_bytes())))
+        .collect();
+    if literals.len() > LITERALS_SHOWN {
+        shown.push(format!("... {} in all", literals.len()));
+    }
+    if seq.is_exact() {
+        format!("{} (exact: a match is one of them)", shown.join(", "))
+    } else {
+        format!("{} (inexact: a match {} with one of them)", shown.join(", "), end)
+    }
+}
+
+/// Writes how `pattern` is compiled with `settings`.
+pub fn write_explanation(out: &mut dyn Write, settings: &Settings, pattern: &str) -> io::Result<()> {
+    writeln!(out, "pattern: {}", pattern)?;
+    let ignore_case = settings.case.ignore_case(pattern);
+    let hir = match regex_syntax::ParserBuilder::new()
+        .case_insensitive(ignore_case)
+        .multi_line(settings.multiline)
+        .build()
+        .parse(pattern)
+    {
+        Ok(hir) => hir,
+        Err(e) => {
+            writeln!(out, "  error: {}", e.to_string().lines().last().unwrap_or_default().trim_start_matches("error: "))?;
+            return writeln!(out, "  help: `finder check` locates the error and suggests fixes");
+        }
+    };
+    writeln!(out, "  case: {}", settings.describe_case(pattern))?;
+    match &settings.word_chars {
+        None => writeln!(out, "  word boundaries: none added")?,
+        Some(WordChars::Alphanumeric) => writeln!(out, "  word boundaries (-w): a match must not touch a letter or digit on either side; `_` separates words")?,
+        Some(WordChars::Class { source, .. }) => {
+            writeln!(out, "  word boundaries (-w): a match must not touch a character of {} on either side", source)?;
+        }
+    }
+    if settings.multiline {
+        writeln!(out, "  multiline (-U): `^` and `$` match at line boundaries, `.` still stops at newlines")?;
+    }
+    // The compiled form itself is unreadable as soon as a class is
+    // expanded to its Unicode ranges; what it can match is not.
+    let properties = hir.properties();
+    match (properties.minimum_len(), properties.maximum_len()) {
+        (Some(0), _) => writeln!(out, "  length: can match the empty string, and so every line")?,
+        (Some(min), Some(max)) if min == max => writeln!(out, "  length: {} bytes", min)?,
+        (Some(min), Some(max)) => writeln!(out, "  length: {} to {} bytes", min, max)?,
+        (Some(min), None) => writeln!(out, "  length: {} bytes or more", min)?,
+        (None, _) => writeln!(out, "  length: cannot match anything")?,
+    }
+    writeln!(out, "  prefix literals: {}", describe_literals(&literals(&hir, ExtractKind::Prefix), "starts"))?;
+    writeln!(out, "  suffix literals: {}", describe_literals(&literals(&hir, ExtractKind::Suffix), "ends"))?;
+    match settings.compile(pattern, ignore_case).map(|_| automaton_size(settings, pattern, ignore_case)) {
+        Ok(0) => writeln!(out, "  automaton size: none, the literals are looked for directly"),
+        Ok(size) => writeln!(out, "  automaton size: about {} bytes (the limit is {} bytes)", size, SIZE_LIMIT),
+        Err(e) => writeln!(out, "  error: {}", e.to_string().lines().last().unwrap_or_default().trim_start_matches("error: ")),
+    }
+}
+
+fn literals(hir: &Hir, kind: ExtractKind) -> Seq {
+    let mut seq = Extractor::new().kind(kind.clone()).extract(hir);
+    match kind {
+        ExtractKind::Prefix => seq.optimize_for_prefix_by_preference(),
+        _ => seq.optimize_for_suffix_by_preference(),
+    }
+    seq
+}
+
+/// The matches of `matcher` in `line`, as text and span.
+fn describe_spans(line: &str, spans: &[Range<usize>]) -> String {
+    spans.iter().map(|span| format!("{:?} at {}..{}", &line[span.clone()], span.start, span.end)).collect::<Vec<_>>().join(", ")
+}
+
+/// Why `line` does not match, when it is one option away from matching.
+fn near_miss(settings: &Settings, pattern: &str, line: &str) -> Option<String> {
+    let ignore_case = settings.case.ignore_case(pattern);
+    let regex = settings.compile(pattern, ignore_case).ok()?;
+    if settings.word_chars.is_some() && let Some(found) = regex.find(line) {
+        return Some(format!("{:?} at {}..{} touches a word character, which -w rejects", found.as_str(), found.start(), found.end()));
+    }
+    if !ignore_case && settings.compile(pattern, true).is_ok_and(|regex| regex.is_match(line)) {
+        return Some("it would with -i".to_string());
+    }
+    None
+}
+
+/// Writes whether and where `pattern` matches each line of `sample`, or
+/// the whole of it with -U.
+pub fn write_sample(out: &mut dyn Write, settings: &Settings, pattern: &str, sample: &str) -> io::Result<()> {
+    let ignore_case = settings.case.ignore_case(pattern);
+    let Ok(regex) = settings.compile(pattern, ignore_case) else { return Ok(()) };
+    let matcher: Box<dyn Matcher> = match &settings.word_chars {
+        Some(word_chars) => match word::compile_bounded(pattern, word_chars, ignore_case, settings.multiline, true) {
+            Some(bounded) => Box::new(WordMatcher::new(regex, word_chars.clone()).with_bounded(bounded)),
+            None => Box::new(WordMatcher::new(regex, word_chars.clone())),
+        },
+        None => Box::new(regex),
+    };
+    if settings.multiline {
+        let spans = matcher.find_spans(sample);
+        if spans.is_empty() {
+            let reason = near_miss(settings, pattern, sample).map_or(String::new(), |reason| format!(" ({})", reason));
+            return writeln!(out, "  sample: no match{}", reason);
+        }
+        let line_of = |offset: usize| sample[..offset].matches('\n').count() + 1;
+        for span in &spans {
+            writeln!(out, "  sample: line {}: {}", line_of(span.start), describe_spans(sample, std::slice::from_ref(span)))?;
+        }
+        return Ok(());
+    }
+    let mut matched = 0;
+    let mut total = 0;
+    for (index, line) in sample.lines().enumerate() {
+        total += 1;
+        let spans = matcher.find_spans(line);
+        if spans.is_empty() {
+            let reason = near_miss(settings, pattern, line).map_or(String::new(), |reason| format!(" ({})", reason));
+            writeln!(out, "  line {}: no match{}", index + 1, reason)?;
+        } else {
+            matched += 1;
+            writeln!(out, "  line {}: {}", index + 1, describe_spans(line, &spans))?;
+        }
+    }
+    writeln!(out, "  {} of {} sample lines match", matched, total)
+}
+
+pub fn run(args: ExplainArgs) -> Result<(), FinderError> {
+    let settings = Settings {
+        case: CaseMode::from_flags(args.ignore_case, args.smart_case),
+        word_chars: match (&args.word_chars, args.word_regexp) {
+            (Some(class), _) => Some(WordChars::from_class(class)?),
+            (None, true) => Some(WordChars::Alphanumeric),
+            (None, false) => None,
+        },
+        multiline: args.multiline,
+    };
+    // Sample text is only read when piped in, so that `finder explain -p X`
+    // on its own does not wait for input.
+    let mut sample = None;
+    if !io::stdin().is_terminal() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        sample = Some(text);
+    }
+    let mut out = io::stdout().lock();
+    for (index, pattern) in args.pattern.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        write_explanation(&mut out, &settings, pattern)?;
+        if let Some(sample) = &sample {
+            write_sample(&mut out, &settings, pattern, sample)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explain(settings: &Settings, pattern: &str, sample: &str) -> String {
+        let mut out = Vec::new();
+        write_explanation(&mut out, settings, pattern).unwrap();
+        write_sample(&mut out, settings, pattern, sample).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_explanation() {
+        let settings = Settings { case: CaseMode::Smart, word_chars: Some(WordChars::Alphanumeric), multiline: false };
+        let explanation = explain(&settings, r"api_key=\w+", "API_KEY=abc\napi_key=x1 api_key=y\nmyapi_key=z\nnothing\n");
+        let lines: Vec<&str> = explanation.lines().collect();
+        assert_eq!(lines[0], r"pattern: api_key=\w+");
+        assert_eq!(lines[1], "  case: insensitive (-S, as the pattern has no uppercase letter)");
+        assert!(lines[2].starts_with("  word boundaries (-w): a match must not touch a letter or digit"));
+        assert_eq!(lines[3], "  length: 9 bytes or more");
+        assert!(lines[4].starts_with(r#"  prefix literals: "API_", "APi_", "#) && lines[4].ends_with("(inexact: a match starts with one of them)"));
+        assert_eq!(lines[5], "  suffix literals: any (too many to use)");
+        let size: usize = lines[6].trim_start_matches("  automaton size: about ").split(' ').next().unwrap().parse().unwrap();
+        assert!(size > 0 && size < SIZE_LIMIT);
+        assert_eq!(&lines[7..], [
+            r#"  line 1: "API_KEY=abc" at 0..11"#,
+            r#"  line 2: "api_key=x1" at 0..10, "api_key=y" at 11..20"#,
+            r#"  line 3: no match ("api_key=z" at 2..11 touches a word character, which -w rejects)"#,
+            "  line 4: no match",
+            "  2 of 4 sample lines match",
+        ]);
+
+        let sensitive = Settings::default();
+        let explanation = explain(&sensitive, "TODO", "todo: later\n");
+        assert!(explanation.contains("  length: 4 bytes\n  prefix literals: \"TODO\" (exact: a match is one of them)\n"));
+        assert!(explanation.contains("  automaton size: none, the literals are looked for directly\n"));
+        assert!(explanation.ends_with("  line 1: no match (it would with -i)\n  0 of 1 sample lines match\n"));
+
+        let multiline = Settings { multiline: true, ..Settings::default() };
+        assert!(explain(&multiline, r"BEGIN\nkey", "x\nBEGIN\nkey\n").ends_with("  sample: line 2: \"BEGIN\\nkey\" at 2..11\n"));
+        assert!(explain(&sensitive, "a(b", "").contains("  error: unclosed group\n"));
+    }
+}
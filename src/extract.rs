@@ -0,0 +1,346 @@
+use std::io::{self, Read};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::archive;
+use crate::longpath;
+
+/// A document whose text finder extracts before matching (`--documents`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    /// Needs the `pdf` feature.
+    Pdf,
+    Docx,
+    Xlsx,
+}
+
+impl DocumentKind {
+    pub fn from_path(path: &Path) -> Option<DocumentKind> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "pdf" => Some(DocumentKind::Pdf),
+            "docx" => Some(DocumentKind::Docx),
+            "xlsx" => Some(DocumentKind::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+/// The text of a document, one line per entry with the number it is
+/// reported at: its page in a PDF, its paragraph in a Word document and its
+/// row in a spreadsheet, whose sheets are reported as `book.xlsx!Sheet1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extracted {
+    pub path: PathBuf,
+    pub lines: Vec<(usize, String)>,
+}
+
+pub fn extract(path: &Path, kind: DocumentKind) -> io::Result<Vec<Extracted>> {
+    match kind {
+        DocumentKind::Pdf => extract_pdf(path),
+        DocumentKind::Docx => extract_docx(path),
+        DocumentKind::Xlsx => extract_xlsx(path),
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn extract_pdf(path: &Path) -> io::Result<Vec<Extracted>> {
+    let bytes = fs::read(longpath::for_opening(path))?;
+    // pdf-extract panics on some malformed documents rather than failing.
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&bytes))
+        .map_err(|_| io::Error::other("cannot extract the text of this PDF"))?
+        .map_err(io::Error::other)?;
+    let lines = pages.iter().enumerate()
+        .flat_map(|(index, page)| {
+            page.lines().filter(|line| !line.trim().is_empty()).map(move |line| (index + 1, line.to_string()))
+        })
+        .collect();
+    Ok(vec![Extracted { path: path.to_path_buf(), lines }])
+}
+
+#[cfg(not(feature = "pdf"))]
+fn extract_pdf(_path: &Path) -> io::Result<Vec<Extracted>> {
+    Err(io::Error::other("finder was built without PDF support (rebuild with `--features pdf`)"))
+}
+
+/// Reads the part `name` of an Office Open XML package, which is a zip.
+fn read_part(package: &mut zip::ZipArchive<fs::File>, name: &str) -> io::Result<String> {
+    let mut part = package.by_name(name).map_err(|e| io::Error::other(format!("{}: {}", name, e)))?;
+    let mut xml = String::new();
+    part.read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+fn open_package(path: &Path) -> io::Result<zip::ZipArchive<fs::File>> {
+    zip::ZipArchive::new(fs::File::open(longpath::for_opening(path))?).map_err(io::Error::other)
+}
+
+/// Replaces the predefined entities and the character references of XML
+/// text.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => reference.strip_prefix("#x").map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| reference.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Calls `visit` with each tag of `xml`, as written between `<` and `>`, and
+/// the unescaped text that follows it up to the next tag.
+fn for_each_tag(xml: &str, mut visit: impl FnMut(&str, String)) {
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else { break };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+        visit(tag, unescape(text));
+    }
+}
+
+/// `w:t` for `<w:t xml:space="preserve">`, `/w:p` for `</w:p>`.
+fn tag_name(tag: &str) -> &str {
+    tag.split(|c: char| c.is_whitespace() || c == '/' && !tag.starts_with('/')).next().unwrap_or_default()
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = tag[start..].find('"')?;
+    Some(unescape(&tag[start..start + end]))
+}
+
+/// The paragraphs of `word/document.xml`, numbered from 1, empty ones
+/// included so that the numbers match the document.
+fn docx_paragraphs(xml: &str) -> Vec<(usize, String)> {
+    let mut paragraphs = Vec::new();
+    let mut number = 0;
+    let mut paragraph = String::new();
+    for_each_tag(xml, |tag, text| match tag_name(tag) {
+        "w:t" => paragraph.push_str(&text),
+        "w:tab" => paragraph.push('\t'),
+        "w:br" | "w:cr" => paragraph.push(' '),
+        "w:p" if tag.ends_with('/') => number += 1,
+        "/w:p" => {
+            number += 1;
+            let text = std::mem::take(&mut paragraph);
+            if !text.trim().is_empty() {
+                paragraphs.push((number, text));
+            }
+        }
+        _ => {}
+    });
+    paragraphs
+}
+
+fn extract_docx(path: &Path) -> io::Result<Vec<Extracted>> {
+    let xml = read_part(&mut open_package(path)?, "word/document.xml")?;
+    Ok(vec![Extracted { path: path.to_path_buf(), lines: docx_paragraphs(&xml) }])
+}
+
+/// The strings of `xl/sharedStrings.xml`, which cells refer to by index.
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut string = String::new();
+    for_each_tag(xml, |tag, text| match tag_name(tag) {
+        "t" => string.push_str(&text),
+        "/si" => strings.push(std::mem::take(&mut string)),
+        _ => {}
+    });
+    strings
+}
+
+/// The rows of a worksheet with their number, the values of their cells
+/// separated by tabs.
+fn sheet_rows(xml: &str, shared_strings: &[String]) -> Vec<(usize, String)> {
+    let mut rows = Vec::new();
+    let mut number = 0;
+    let mut cells: Vec<String> = Vec::new();
+    let mut cell_type = String::new();
+    for_each_tag(xml, |tag, text| match tag_name(tag) {
+        "row" => {
+            number = attribute(tag, "r").and_then(|r| r.parse().ok()).unwrap_or(number + 1);
+            cells.clear();
+        }
+        "c" => cell_type = attribute(tag, "t").unwrap_or_default(),
+        "v" if cell_type == "s" => cells.extend(text.parse::<usize>().ok().and_then(|index| shared_strings.get(index)).cloned()),
+        "v" => cells.push(text),
+        "t" if cell_type == "inlineStr" => cells.push(text),
+        "/row" if !cells.is_empty() => rows.push((number, cells.join("\t"))),
+        _ => {}
+    });
+    rows
+}
+
+/// The sheets of `xl/workbook.xml` in order, with the part holding each,
+/// found through the workbook's relationships.
+fn sheets(workbook: &str, relationships: &str) -> Vec<(String, String)> {
+    let mut targets = Vec::new();
+    for_each_tag(relationships, |tag, _| {
+        if tag_name(tag) == "Relationship"
+            && let (Some(id), Some(target)) = (attribute(tag, "Id"), attribute(tag, "Target"))
+        {
+            // Targets are relative to `xl/`, or absolute within the package.
+            let part = target.strip_prefix('/').map_or_else(|| format!("xl/{}", target), str::to_string);
+            targets.push((id, part));
+        }
+    });
+    let mut sheets = Vec::new();
+    for_each_tag(workbook, |tag, _| {
+        if tag_name(tag) == "sheet"
+            && let (Some(name), Some(id)) = (attribute(tag, "name"), attribute(tag, "r:id"))
+            && let Some((_, part)) = targets.iter().find(|(target_id, _)| *target_id == id)
+        {
+            sheets.push((name, part.clone()));
+        }
+    });
+    sheets
+}
+
+fn extract_xlsx(path: &Path) -> io::Result<Vec<Extracted>> {
+    let mut package = open_package(path)?;
+    // Workbooks without any text cell have no shared strings.
+    let strings = read_part(&mut package, "xl/sharedStrings.xml").map_or_else(|_| Vec::new(), |xml| shared_strings(&xml));
+    let workbook = read_part(&mut package, "xl/workbook.xml")?;
+    let relationships = read_part(&mut package, "xl/_rels/workbook.xml.rels")?;
+    sheets(&workbook, &relationships).into_iter()
+        .map(|(name, part)| {
+            let rows = sheet_rows(&read_part(&mut package, &part)?, &strings);
+            Ok(Extracted { path: archive::entry_path(path, &name), lines: rows })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_package(path: &Path, parts: &[(&str, &str)]) {
+        let mut package = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in parts {
+            package.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            package.write_all(content.as_bytes()).unwrap();
+        }
+        package.finish().unwrap();
+    }
+
+    #[test]
+    fn test_unescape_and_tags() {
+        assert_eq!(unescape("a &amp; b &lt;&#233;&#xE9;&gt; &bogus; &"), "a & b <éé> &bogus; &");
+        assert_eq!(tag_name("w:t xml:space=\"preserve\""), "w:t");
+        assert_eq!(tag_name("/w:p"), "/w:p");
+        assert_eq!(tag_name("w:tab/"), "w:tab");
+        assert_eq!(attribute(r#"sheet name="Q&amp;A" r:id="rId2"/"#, "name").as_deref(), Some("Q&A"));
+    }
+
+    #[test]
+    fn test_extract_docx() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("memo.docx");
+        write_package(&path, &[("word/document.xml", concat!(
+            r#"<?xml version="1.0"?><w:document><w:body>"#,
+            r#"<w:p><w:r><w:t>Dear </w:t></w:r><w:r><w:t xml:space="preserve">team,</w:t></w:r></w:p>"#,
+            r#"<w:p/><w:p><w:r><w:t>IBAN:</w:t><w:tab/><w:t>FR76 &amp; more</w:t></w:r></w:p>"#,
+            r#"</w:body></w:document>"#,
+        ))]);
+        assert_eq!(extract(&path, DocumentKind::Docx).unwrap(), vec![Extracted {
+            path: path.clone(),
+            lines: vec![(1, "Dear team,".to_string()), (3, "IBAN:\tFR76 & more".to_string())],
+        }]);
+    }
+
+    #[test]
+    fn test_extract_xlsx() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("book.xlsx");
+        write_package(&path, &[
+            ("xl/workbook.xml", r#"<workbook><sheets><sheet name="Clients" sheetId="1" r:id="rId1"/><sheet name="Empty" sheetId="2" r:id="rId2"/></sheets></workbook>"#),
+            ("xl/_rels/workbook.xml.rels", r#"<Relationships><Relationship Id="rId1" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Target="/xl/worksheets/sheet2.xml"/></Relationships>"#),
+            ("xl/sharedStrings.xml", r#"<sst><si><t>Name</t></si><si><r><t>Jane </t></r><r><t>Doe</t></r></si></sst>"#),
+            ("xl/worksheets/sheet1.xml", concat!(
+                r#"<worksheet><sheetData><row r="1"><c r="A1" t="s"><v>0</v></c></row>"#,
+                r#"<row r="4"><c r="A4" t="s"><v>1</v></c><c r="B4"><v>42</v></c><c r="C4" t="inlineStr"><is><t>jane@example.com</t></is></c></row>"#,
+                r#"</sheetData></worksheet>"#,
+            )),
+            ("xl/worksheets/sheet2.xml", "<worksheet><sheetData/></worksheet>"),
+        ]);
+        let sheets = extract(&path, DocumentKind::Xlsx).unwrap();
+        assert_eq!(sheets[0].path, archive::entry_path(&path, "Clients"));
+        assert_eq!(sheets[0].lines, vec![(1, "Name".to_string()), (4, "Jane Doe\t42\tjane@example.com".to_string())]);
+        assert!(sheets[1].lines.is_empty());
+    }
+
+    /// A PDF with one line of Helvetica text per page.
+    #[cfg(feature = "pdf")]
+    fn pdf(pages: &[&str]) -> Vec<u8> {
+        let kids: Vec<String> = (0..pages.len()).map(|index| format!("{} 0 R", 3 + 2 * index)).collect();
+        let font = 3 + 2 * pages.len();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()),
+        ];
+        for (index, text) in pages.iter().enumerate() {
+            objects.push(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 {} 0 R >> >> /Contents {} 0 R >>", font, 4 + 2 * index));
+            let content = format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text);
+            objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+        }
+        objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string());
+        let mut pdf = "%PDF-1.4\n".to_string();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+        }
+        let xref = pdf.len();
+        pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+        for offset in offsets {
+            pdf.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        pdf.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref));
+        pdf.into_bytes()
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_extract_pdf() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        fs::write(&path, pdf(&["Quarterly report", "Account 12345 is confidential"])).unwrap();
+        let documents = extract(&path, DocumentKind::Pdf).unwrap();
+        assert_eq!(documents[0].lines, vec![(1, "Quarterly report".to_string()), (2, "Account 12345 is confidential".to_string())]);
+        fs::write(&path, b"%PDF-1.4 truncated").unwrap();
+        assert!(extract(&path, DocumentKind::Pdf).is_err());
+    }
+
+    #[cfg(not(feature = "pdf"))]
+    #[test]
+    fn test_pdf_needs_feature() {
+        assert!(extract(Path::new("report.pdf"), DocumentKind::Pdf).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args as ClapArgs;
+use ignore::overrides::{Override, OverrideBuilder};
+use regex::Regex;
+
+use crate::engine::CaseMode;
+use crate::error::FinderError;
+use crate::json::{self, Value};
+use crate::rules::Severity;
+
+/// Arguments of `finder filter`.
+#[derive(ClapArgs, Debug)]
+pub struct FilterArgs {
+    /// A results file written by `--format json`, or `-` for stdin
+    pub input: PathBuf,
+
+    /// Only keep results whose line matches PATTERN (can be repeated)
+    #[arg(short = 'p', long, value_name = "PATTERN")]
+    pub pattern: Vec<String>,
+
+    /// Only keep results whose path matches GLOB, as with --include (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    pub path_glob: Vec<String>,
+
+    /// Only keep results of the rule named NAME, or of the raw pattern when it has no name (can be repeated)
+    #[arg(long, value_name = "NAME")]
+    pub rule: Vec<String>,
+
+    /// Only keep results of severity LEVEL or above; results without a severity are warn
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    pub min_severity: Option<Severity>,
+
+    /// Case-insensitive --pattern
+    #[arg(short, long)]
+    pub ignore_case: bool,
+
+    /// Case-insensitive --pattern unless it contains an uppercase letter
+    #[arg(short = 'S', long)]
+    pub smart_case: bool,
+
+    /// Write the kept results as JSON Lines, like the input, instead of text
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// A result read back from a `--format json` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub path: String,
+    pub line: usize,
+    pub end_line: Option<usize>,
+    pub rule: String,
+    /// The other rules that match the line.
+    pub other_rules: Vec<String>,
+    pub severity: Option<Severity>,
+    pub text: String,
+}
+
+impl Record {
+    pub fn parse(line: &str) -> Result<Record, String> {
+        let object = json::parse_object(line)?;
+        let string = |key: &str| object.get(key).and_then(Value::as_str).map(str::to_string).ok_or(format!("missing `{}`", key));
+        Ok(Record {
+            path: string("path")?,
+            line: object.get("line").and_then(Value::as_usize).ok_or("missing `line`")?,
+            end_line: object.get("end_line").and_then(Value::as_usize),
+            rule: string("rule")?,
+            other_rules: object.get("other_rules").and_then(Value::as_strings).unwrap_or_default().to_vec(),
+            severity: match object.get("severity").and_then(Value::as_str) {
+                Some(severity) => Some(Severity::parse(severity).ok_or(format!("unknown severity `{}`", severity))?),
+                None => None,
+            },
+            text: string("text")?,
+        })
+    }
+}
+
+/// What a record must satisfy to be kept; an empty list accepts everything.
+pub struct RecordFilter {
+    pub patterns: Vec<Regex>,
+    pub paths: Option<Override>,
+    pub rules: Vec<String>,
+    pub min_severity: Option<Severity>,
+}
+
+impl RecordFilter {
+    pub fn accepts(&self, record: &Record) -> bool {
+        (self.patterns.is_empty() || self.patterns.iter().any(|re| re.is_match(&record.text)))
+            && self.paths.as_ref().is_none_or(|paths| paths.matched(Path::new(&record.path), false).is_whitelist())
+            && (self.rules.is_empty() || self.rules.iter().any(|rule| *rule == record.rule || record.other_rules.contains(rule)))
+            && self.min_severity.is_none_or(|min| record.severity.unwrap_or(Severity::Warn) >= min)
+    }
+}
+
+fn path_globs(globs: &[String]) -> Result<Option<Override>, ignore::Error> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut overrides = OverrideBuilder::new(".");
+    for glob in globs {
+        overrides.add(glob)?;
+    }
+    overrides.build().map(Some)
+}
+
+pub fn run(args: FilterArgs) -> Result<(), FinderError> {
+    let content = if args.input == Path::new("-") {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        fs::read_to_string(&args.input)?
+    };
+    let case = CaseMode::from_flags(args.ignore_case, args.smart_case);
+    let filter = RecordFilter {
+        patterns: crate::compile_regex_with_cache(&args.pattern, case, false)?,
+        paths: path_globs(&args.path_glob)?,
+        rules: args.rule,
+        min_severity: args.min_severity,
+    };
+
+    let mut out = io::stdout().lock();
+    for (index, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let record = Record::parse(line)
+            .map_err(|e| FinderError::Usage(format!("{}:{}: not a `--format json` result: {}", args.input.display(), index + 1, e)))?;
+        if !filter.accepts(&record) {
+            continue;
+        }
+        if args.json {
+            writeln!(out, "{}", line)?;
+        } else {
+            let lines = match record.end_line {
+                Some(end) => format!("{}-{}", record.line, end),
+                None => record.line.to_string(),
+            };
+            writeln!(out, "{}:{}:{}:{}", record.path, lines, record.rule, record.text.trim())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_records() {
+        let records: Vec<Record> = [
+            r#"{"path":"src/main.rs","line":3,"rule":"secrets","pattern":"key","text":"api_key = 1"}"#,
+            r#"{"path":"docs/notes.md","line":9,"rule":"secrets","other_rules":["TODO"],"pattern":"key","text":"key rotation TODO"}"#,
+            r#"{"path":"src/lib.rs","line":1,"end_line":2,"rule":"TODO","severity":"info","pattern":"TODO","text":"TODO: key"}"#,
+            r#"{"path":"src/db.rs","line":7,"rule":"secrets","severity":"error","pattern":"key","text":"db_key = 2"}"#,
+        ].iter().map(|line| Record::parse(line).unwrap()).collect();
+        assert_eq!(records[2].end_line, Some(2));
+
+        let kept = |filter: &RecordFilter| -> Vec<usize> {
+            records.iter().filter(|record| filter.accepts(record)).map(|record| record.line).collect()
+        };
+        let everything = RecordFilter { patterns: Vec::new(), paths: None, rules: Vec::new(), min_severity: None };
+        assert_eq!(kept(&everything), vec![3, 9, 1, 7]);
+        let rust = RecordFilter { paths: path_globs(&["*.rs".to_string()]).unwrap(), ..everything };
+        assert_eq!(kept(&rust), vec![3, 1, 7]);
+        let secrets_in_rust = RecordFilter { rules: vec!["secrets".to_string()], ..rust };
+        assert_eq!(kept(&secrets_in_rust), vec![3, 7]);
+        let assignments = RecordFilter { patterns: vec![Regex::new(r"=\s*\d").unwrap()], paths: None, rules: Vec::new(), min_severity: None };
+        assert_eq!(kept(&assignments), vec![3, 7]);
+        // Results without a severity are warn.
+        let warnings = RecordFilter { patterns: Vec::new(), paths: None, rules: Vec::new(), min_severity: Some(Severity::Warn) };
+        assert_eq!(kept(&warnings), vec![3, 9, 7]);
+        let errors = RecordFilter { min_severity: Some(Severity::Error), ..warnings };
+        assert_eq!(kept(&errors), vec![7]);
+        let todos = RecordFilter { rules: vec!["TODO".to_string()], min_severity: None, ..errors };
+        assert_eq!(kept(&todos), vec![9, 1]);
+
+        assert!(Record::parse(r#"{"path":"a.rs"}"#).is_err());
+        assert!(Record::parse(r#"{"path":"a.rs","line":1,"rule":"r","severity":"fatal","text":""}"#).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// Parses a date as `2024-01-01` or `2024-01-01T12:00:00` (UTC).
+pub fn parse_date(value: &str) -> Result<SystemTime, String> {
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(Default::default())))
+        .map_err(|_| format!("invalid date `{}`, expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS", value))?;
+    let seconds = datetime.and_utc().timestamp();
+    Ok(UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64))
+}
+
+/// Parses a duration such as `30s`, `15m`, `12h`, `2d` or `1w`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let trimmed = value.trim();
+    let invalid = || format!("invalid duration `{}`, expected e.g. 2d", value);
+    let unit = trimmed.chars().last().ok_or_else(invalid)?;
+    let number: u64 = trimmed[..trimmed.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(number.saturating_mul(seconds)))
+}
+
+/// Size and modification time bounds on the searched files
+/// (`--min-filesize`, `--max-filesize`, `--newer-than`, `--older-than`,
+/// `--changed-within`).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileFilter {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub newer_than: Option<SystemTime>,
+    pub older_than: Option<SystemTime>,
+}
+
+impl FileFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_size.is_none() && self.max_size.is_none() && self.newer_than.is_none() && self.older_than.is_none()
+    }
+
+    pub fn accepts(&self, metadata: &Metadata) -> bool {
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        if self.newer_than.is_none() && self.older_than.is_none() {
+            return true;
+        }
+        // Without a modification time, a file cannot be shown to be in range.
+        let Ok(modified) = metadata.modified() else { return false };
+        self.newer_than.is_none_or(|bound| modified >= bound) && self.older_than.is_none_or(|bound| modified < bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_date_and_duration() {
+        assert_eq!(parse_date("1970-01-02"), Ok(UNIX_EPOCH + Duration::from_secs(86_400)));
+        assert_eq!(parse_date("1970-01-01T00:01:00"), Ok(UNIX_EPOCH + Duration::from_secs(60)));
+        assert!(parse_date("yesterday").is_err());
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(2 * 86_400)));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("d").is_err());
+    }
+
+    #[test]
+    fn test_file_filter() {
+        let test_dir = tempdir().unwrap();
+        let path = test_dir.path().join("app.log");
+        fs::write(&path, "0123456789").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let now = SystemTime::now();
+        let hour = Duration::from_secs(3600);
+
+        assert!(FileFilter { max_size: Some(10), ..Default::default() }.accepts(&metadata));
+        assert!(!FileFilter { max_size: Some(9), ..Default::default() }.accepts(&metadata));
+        assert!(!FileFilter { min_size: Some(11), ..Default::default() }.accepts(&metadata));
+        assert!(FileFilter { newer_than: Some(now - hour), ..Default::default() }.accepts(&metadata));
+        assert!(!FileFilter { older_than: Some(now - hour), ..Default::default() }.accepts(&metadata));
+
+        test_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Which files of their git repositories the search is restricted to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitScope {
+    /// Changed in the working tree or the index, or untracked (`--changed`).
+    Changed,
+    /// Changed in the index (`--staged`).
+    Staged,
+    /// Changed since the common ancestor of this revision and HEAD,
+    /// including uncommitted changes (`--since`).
+    Since(String),
+}
+
+/// The files of a scope in the repositories of the searched paths, and the
+/// directories that lead to them, all canonical.
+#[derive(Debug, Default)]
+pub struct ScopedFiles {
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("cannot run git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git {}` failed in {}: {}", args.join(" "), dir.display(), stderr.lines().next().unwrap_or_default()));
+    }
+    Ok(output.stdout)
+}
+
+/// The paths of `git diff --name-only -z`.
+fn parse_names(output: &[u8]) -> Vec<String> {
+    output.split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+/// The paths of `git status --porcelain -z` that still exist: the
+/// original path that follows a rename or a copy is left out.
+fn parse_status(output: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut entries = output.split(|&b| b == 0).filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        let Some((status, name)) = entry.split_at_checked(3) else { continue };
+        if matches!(status[0], b'R' | b'C') {
+            entries.next();
+        }
+        if status[0] != b'D' && status[1] != b'D' {
+            names.push(String::from_utf8_lossy(name).into_owned());
+        }
+    }
+    names
+}
+
+impl ScopedFiles {
+    /// Asks git for the files of `scope` in the repository of each of
+    /// `roots`.
+    pub fn find(roots: &[PathBuf], scope: &GitScope) -> Result<ScopedFiles, String> {
+        let mut toplevels = HashSet::new();
+        for root in roots {
+            let dir = if root.is_dir() { root.as_path() } else { root.parent().unwrap_or(Path::new(".")) };
+            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+            let toplevel = git(dir, &["rev-parse", "--show-toplevel"])
+                .map_err(|_| format!("{} is not in a git repository", root.display()))?;
+            toplevels.insert(PathBuf::from(String::from_utf8_lossy(&toplevel).trim_end()));
+        }
+        let mut scoped = ScopedFiles::default();
+        for toplevel in toplevels {
+            let toplevel = fs::canonicalize(&toplevel).unwrap_or(toplevel);
+            let names = match scope {
+                GitScope::Changed => parse_status(&git(&toplevel, &["status", "--porcelain", "-z", "--untracked-files=all"])?),
+                GitScope::Staged => parse_names(&git(&toplevel, &["diff", "--cached", "--name-only", "-z", "--diff-filter=d"])?),
+                GitScope::Since(revision) => parse_names(&git(&toplevel, &["diff", "--merge-base", revision, "--name-only", "-z", "--diff-filter=d", "--"])?),
+            };
+            for name in names {
+                let path = toplevel.join(name);
+                scoped.dirs.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+                scoped.files.insert(path);
+            }
+        }
+        Ok(scoped)
+    }
+
+    /// Whether the walk goes into `path`: one of the files, or a directory
+    /// that holds some.
+    pub fn keeps(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(path) = fs::canonicalize(path) else { return false };
+        if is_dir { self.dirs.contains(&path) } else { self.files.contains(&path) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_status() {
+        let output = b" M src/main.rs\0R  new.rs\0old.rs\0D  gone.rs\0?? notes.txt\0AM added.rs\0";
+        assert_eq!(parse_status(output), ["src/main.rs", "new.rs", "notes.txt", "added.rs"]);
+        assert_eq!(parse_names(b"a.rs\0b c.rs\0"), ["a.rs", "b c.rs"]);
+    }
+
+    #[test]
+    fn test_scoped_files() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path();
+        let run = |args: &[&str]| git(repo, args).unwrap();
+        run(&["init", "-q"]);
+        fs::create_dir(repo.join("src")).unwrap();
+        fs::write(repo.join("src/kept.rs"), "old").unwrap();
+        fs::write(repo.join("src/edited.rs"), "old").unwrap();
+        run(&["add", "."]);
+        run(&["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-qm", "first"]);
+        fs::write(repo.join("src/edited.rs"), "new").unwrap();
+        fs::write(repo.join("staged.rs"), "new").unwrap();
+        run(&["add", "staged.rs"]);
+        fs::write(repo.join("untracked.rs"), "new").unwrap();
+
+        let root = [repo.to_path_buf()];
+        let changed = ScopedFiles::find(&root, &GitScope::Changed).unwrap();
+        assert_eq!(changed.files.len(), 3);
+        assert!(changed.keeps(&repo.join("src"), true) && changed.keeps(&repo.join("src/edited.rs"), false));
+        assert!(!changed.keeps(&repo.join("src/kept.rs"), false));
+        let staged = ScopedFiles::find(&root, &GitScope::Staged).unwrap();
+        assert!(staged.keeps(&repo.join("staged.rs"), false) && !staged.keeps(&repo.join("src"), true));
+        let since = ScopedFiles::find(&root, &GitScope::Since("HEAD".to_string())).unwrap();
+        assert!(since.keeps(&repo.join("src/edited.rs"), false) && since.keeps(&repo.join("staged.rs"), false));
+        assert!(!since.keeps(&repo.join("untracked.rs"), false));
+
+        let outside = tempdir().unwrap();
+        assert!(ScopedFiles::find(&[outside.path().to_path_buf()], &GitScope::Changed).unwrap_err().contains("not in a git repository"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{self, Write};
+
+use crate::SearchResult;
+use crate::engine::Matcher;
+use crate::output::{label_of, match_columns};
+use crate::patterns::PatternSpec;
+use crate::rules::Severity;
+
+/// Severity of the annotations of `--format github`.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    Notice,
+    #[default]
+    Warning,
+    Error,
+}
+
+impl AnnotationLevel {
+    fn command(self) -> &'static str {
+        match self {
+            AnnotationLevel::Notice => "notice",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Error => "error",
+        }
+    }
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property value of a workflow command, where `:` and `,` are
+/// separators.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Writes one `::warning file=...,line=...::...` workflow command per
+/// result, which GitHub Actions shows inline on the diff of a pull request.
+/// The results of a rule with a severity are annotated at its level rather
+/// than at `level`.
+pub fn write_annotations<M: Matcher>(
+    out: &mut dyn Write,
+    pattern_specs: &[PatternSpec],
+    matchers: &[M],
+    results: &[SearchResult],
+    level: AnnotationLevel,
+) -> io::Result<()> {
+    for (result, pattern_index) in results.iter().flat_map(|result| result.pattern_indexes().map(move |pattern_index| (result, pattern_index))) {
+        let path = result.path.to_string_lossy();
+        let mut properties = format!("file={},line={}", escape_property(path.trim_start_matches("./")), result.line_number);
+        if let Some(end) = result.end_line_number {
+            properties.push_str(&format!(",endLine={}", end));
+        }
+        if let Some((start_column, end_column)) = match_columns(&matchers[pattern_index], result) {
+            properties.push_str(&format!(",col={},endColumn={}", start_column, end_column));
+        }
+        let label = label_of(pattern_specs, result, pattern_index);
+        let level = pattern_specs[pattern_index].severity.map_or(level, Severity::annotation_level);
+        writeln!(
+            out,
+            "::{} {},title={}::{}",
+            level.command(),
+            properties,
+            escape_property(&format!("finder: {}", label)),
+            escape_data(&format!("{} matched", label)),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_write_annotations() {
+        let pattern_specs = [PatternSpec { name: Some("key, legacy".to_string()), ..PatternSpec::new("KEY_[0-9]+") }];
+        let matchers = [Regex::new("KEY_[0-9]+").unwrap()];
+        let result = |path: &str, end_line_number: Option<usize>| SearchResult {
+            pattern: "KEY_[0-9]+".to_string(),
+            end_line_number,
+            ..SearchResult::new(path, 3, "x = KEY_42", 0)
+        };
+        let mut out = Vec::new();
+        write_annotations(&mut out, &pattern_specs, &matchers, &[result("./src/a.rs", None), result("b.rs", Some(5))], AnnotationLevel::Error).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+::error file=src/a.rs,line=3,col=5,endColumn=11,title=finder%3A key%2C legacy::key, legacy matched
+::error file=b.rs,line=3,endLine=5,title=finder%3A key%2C legacy::key, legacy matched
+");
+
+        let pattern_specs = [PatternSpec { severity: Some(Severity::Info), ..pattern_specs[0].clone() }];
+        let mut out = Vec::new();
+        write_annotations(&mut out, &pattern_specs, &matchers, &[result("b.rs", None)], AnnotationLevel::Error).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("::notice file=b.rs,"));
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use chrono::{DateTime, SecondsFormat};
+use clap::Args as ClapArgs;
+use regex::Regex;
+
+use crate::SearchOptions;
+use crate::engine::CaseMode;
+use crate::error::FinderError;
+use crate::json::json_string;
+use crate::patterns::{self, PatternSpec};
+
+/// Starts the header line of each commit in the `git log` output, which no
+/// diff line can start with.
+const COMMIT_MARK: char = '\u{1}';
+
+/// Arguments of `finder history`.
+#[derive(ClapArgs, Debug)]
+pub struct HistoryArgs {
+    /// The string to search for (can be repeated, and added to the patterns of -f)
+    #[arg(short = 'p', long, required_unless_present = "input_file")]
+    pub pattern: Vec<String>,
+
+    /// A file containing patterns to search for, one per line, optionally named as `name: regex`
+    #[arg(short = 'f', long = "input-file")]
+    pub input_file: Option<PathBuf>,
+
+    /// Case-insensitive search
+    #[arg(short, long)]
+    pub ignore_case: bool,
+
+    /// Only search the commits after REV (a commit, a tag or a branch) up to HEAD, instead of the whole history
+    #[arg(long, value_name = "REV")]
+    pub since: Option<String>,
+
+    /// Format of the changes
+    #[arg(long, value_enum, default_value_t = HistoryFormat::Text)]
+    pub format: HistoryFormat,
+
+    /// Only search the changes to these files or directories
+    pub paths: Vec<PathBuf>,
+}
+
+/// The format of `finder history`.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    #[default]
+    Text,
+    /// One JSON object per change (JSON Lines)
+    Json,
+}
+
+/// A commit, as `git log` describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub id: String,
+    /// Seconds since the epoch.
+    pub time: i64,
+    pub author: String,
+}
+
+/// A matching line that a commit added or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub commit: usize,
+    pub added: bool,
+    pub path: String,
+    /// In the new version of the file for an added line, in the old one for
+    /// a removed line.
+    pub line_number: usize,
+    pub pattern_index: usize,
+    pub line: String,
+}
+
+/// The start of each side of a hunk header, `@@ -12,3 +14,0 @@`, and
+/// their line counts.
+fn parse_hunk(header: &str) -> Option<(usize, usize, usize, usize)> {
+    let mut ranges = header.strip_prefix("@@ -")?.split(' ');
+    let side = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = side(ranges.next()?)?;
+    let (new_start, new_count) = side(ranges.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+/// The path of a `--- a/path` or `+++ b/path` line, `None` for `/dev/null`.
+fn diff_path(line: &str) -> Option<String> {
+    let path = &line[4..];
+    (path != "/dev/null").then(|| path.get(2..).unwrap_or(path).to_string())
+}
+
+/// Reads the commits and their changed lines out of `git log -p
+/// --unified=0`, keeping the lines that match. The lines of a plain `git
+/// diff`, without commits, are all given to commit 0.
+pub fn parse_log<F: FnMut(&str) -> Option<usize>>(log: &str, mut hit: F) -> (Vec<Commit>, Vec<Change>) {
+    let mut commits = Vec::new();
+    let mut changes = Vec::new();
+    let (mut old_path, mut new_path): (Option<String>, Option<String>) = (None, None);
+    // The lines of the current hunk still to come on each side.
+    let (mut old_line, mut old_left, mut new_line, mut new_left) = (0, 0, 0, 0);
+    for line in log.lines() {
+        if old_left > 0 || new_left > 0 {
+            let (added, path, line_number) = match line.as_bytes().first() {
+                Some(b'-') => {
+                    old_left -= 1;
+                    old_line += 1;
+                    (false, &old_path, old_line - 1)
+                }
+                Some(b'+') => {
+                    new_left -= 1;
+                    new_line += 1;
+                    (true, &new_path, new_line - 1)
+                }
+                Some(b' ') => {
+                    old_left -= 1;
+                    new_left -= 1;
+                    old_line += 1;
+                    new_line += 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            let text = &line[1..];
+            if let (Some(path), Some(pattern_index)) = (path, hit(text)) {
+                changes.push(Change {
+                    commit: commits.len().saturating_sub(1),
+                    added,
+                    path: path.clone(),
+                    line_number,
+                    pattern_index,
+                    line: text.to_string(),
+                });
+            }
+        } else if let Some(header) = line.strip_prefix(COMMIT_MARK) {
+            let mut fields = header.splitn(3, '\t');
+            let (Some(id), Some(time), Some(author)) = (fields.next(), fields.next(), fields.next()) else { continue };
+            commits.push(Commit { id: id.to_string(), time: time.parse().unwrap_or(0), author: author.to_string() });
+        } else if line.starts_with("diff ") {
+            (old_path, new_path) = (None, None);
+        } else if line.starts_with("--- ") {
+            old_path = diff_path(line);
+        } else if line.starts_with("+++ ") {
+            new_path = diff_path(line);
+        } else if let Some((old_start, old_count, new_start, new_count)) = parse_hunk(line) {
+            (old_line, old_left, new_line, new_left) = (old_start, old_count, new_start, new_count);
+        }
+    }
+    (commits, changes)
+}
+
+/// Leaves out the lines that a commit both removed and added again in the
+/// same file with the same match, such as a line that only moved or whose
+/// other text changed: the match was neither introduced nor removed.
+pub fn drop_moves(changes: Vec<Change>, matched: impl Fn(&Change) -> String) -> Vec<Change> {
+    let key = |change: &Change| (change.commit, change.path.clone(), change.pattern_index, matched(change));
+    let mut removed: HashMap<_, usize> = HashMap::new();
+    for change in changes.iter().filter(|change| !change.added) {
+        *removed.entry(key(change)).or_default() += 1;
+    }
+    let mut moved: HashMap<_, usize> = HashMap::new();
+    for change in changes.iter().filter(|change| change.added) {
+        let key = key(change);
+        if let Some(count) = removed.get_mut(&key).filter(|count| **count > 0) {
+            *count -= 1;
+            *moved.entry(key).or_default() += 1;
+        }
+    }
+    // Both sides of each move are dropped.
+    let mut left = moved.clone();
+    changes.into_iter()
+        .filter(|change| {
+            let counts = if change.added { &mut left } else { &mut moved };
+            match counts.get_mut(&key(change)).filter(|count| **count > 0) {
+                Some(count) => {
+                    *count -= 1;
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect()
+}
+
+fn label(pattern_specs: &[PatternSpec], change: &Change) -> String {
+    let spec = &pattern_specs[change.pattern_index];
+    spec.name.clone().unwrap_or_else(|| spec.pattern.clone())
+}
+
+fn date(commit: &Commit) -> String {
+    DateTime::from_timestamp(commit.time, 0).map_or(String::new(), |time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Writes one line per change, oldest commit first: the commit, its date
+/// and author, whether the match was added or removed, and where.
+pub fn write_text(out: &mut dyn Write, pattern_specs: &[PatternSpec], commits: &[Commit], changes: &[Change]) -> io::Result<()> {
+    for change in changes {
+        let commit = &commits[change.commit];
+        let date = date(commit);
+        writeln!(
+            out,
+            "{} {} {} {} {}:{}:{}:{}",
+            commit.id.get(..10).unwrap_or(&commit.id),
+            date.get(..10).unwrap_or(&date),
+            commit.author,
+            if change.added { "added" } else { "removed" },
+            change.path,
+            change.line_number,
+            label(pattern_specs, change),
+            change.line.trim(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes one JSON object per change (JSON Lines): `commit`, `date`,
+/// `author`, `change` (`added` or `removed`), `path`, `line`, `rule` and
+/// `text`.
+pub fn write_json(out: &mut dyn Write, pattern_specs: &[PatternSpec], commits: &[Commit], changes: &[Change]) -> io::Result<()> {
+    for change in changes {
+        let commit = &commits[change.commit];
+        writeln!(
+            out,
+            "{{\"commit\":{},\"date\":{},\"author\":{},\"change\":\"{}\",\"path\":{},\"line\":{},\"rule\":{},\"text\":{}}}",
+            json_string(&commit.id),
+            json_string(&date(commit)),
+            json_string(&commit.author),
+            if change.added { "added" } else { "removed" },
+            json_string(&change.path),
+            change.line_number,
+            json_string(&label(pattern_specs, change)),
+            json_string(&change.line),
+        )?;
+    }
+    Ok(())
+}
+
+pub fn run(args: HistoryArgs) -> Result<(), FinderError> {
+    let mut pattern_specs: Vec<PatternSpec> = args.pattern.iter().map(|pattern| PatternSpec::new(pattern)).collect();
+    if let Some(input_file) = &args.input_file {
+        pattern_specs.extend(patterns::parse_patterns(&crate::read_decoded(input_file)?));
+    }
+    let patterns: Vec<String> = pattern_specs.iter().map(|spec| spec.pattern.clone()).collect();
+    let regexes: Vec<Regex> = crate::compile_regex_with_cache(&patterns, CaseMode::from_flags(args.ignore_case, false), false)?;
+    let search_options = SearchOptions {
+        negated: pattern_specs.iter().map(|spec| spec.negated).collect(),
+        ..Default::default()
+    };
+
+    let mut command = Command::new("git");
+    command
+        .args(["-c", "core.quotePath=false", "log", "--reverse", "--no-color", "--no-ext-diff", "--no-renames", "-p", "--unified=0"])
+        .arg(format!("--format=format:{}%H%x09%at%x09%an", COMMIT_MARK));
+    if let Some(since) = &args.since {
+        command.arg(format!("{}..HEAD", since));
+    }
+    command.arg("--").args(&args.paths);
+    let output = command.output().map_err(|e| FinderError::Usage(format!("cannot run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(FinderError::Usage(format!("git log failed: {}", String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or_default())));
+    }
+    let log = String::from_utf8_lossy(&output.stdout);
+    let (commits, changes) = parse_log(&log, |line| search_options.hit(&regexes, line).map(|(pattern_index, _)| pattern_index));
+    let changes = drop_moves(changes, |change| {
+        regexes[change.pattern_index].find(&change.line).map_or(String::new(), |found| found.as_str().to_string())
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    match args.format {
+        HistoryFormat::Text => write_text(&mut out, &pattern_specs, &commits, &changes)?,
+        HistoryFormat::Json => write_json(&mut out, &pattern_specs, &commits, &changes)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\u{1}aaaaaaaaaaaa\t1700000000\tAlice
+diff --git a/config.py b/config.py
+new file mode 100644
+--- /dev/null
++++ b/config.py
+@@ -0,0 +1,3 @@
++DEBUG = True
++API_KEY = \"abc\"
++--- not a header
+\u{1}bbbbbbbbbbbb\t1710000000\tBob
+diff --git a/config.py b/config.py
+--- a/config.py
++++ b/config.py
+@@ -2 +1,0 @@
+-API_KEY = \"abc\"
+@@ -3 +3 @@
+---- not a header
++--- still not a header
+diff --git a/app.py b/app.py
+--- a/app.py
++++ b/app.py
+@@ -4 +4 @@
+-key = API_KEY  # old
++key = API_KEY  # new
+";
+
+    #[test]
+    fn test_parse_log() {
+        let key = Regex::new("API_KEY").unwrap();
+        let (commits, changes) = parse_log(LOG, |line| key.is_match(line).then_some(0));
+        assert_eq!(commits[1], Commit { id: "bbbbbbbbbbbb".to_string(), time: 1_710_000_000, author: "Bob".to_string() });
+        let summary: Vec<(usize, bool, &str, usize)> = changes.iter()
+            .map(|change| (change.commit, change.added, change.path.as_str(), change.line_number))
+            .collect();
+        assert_eq!(summary, [(0, true, "config.py", 2), (1, false, "config.py", 2), (1, false, "app.py", 4), (1, true, "app.py", 4)]);
+
+        let (_, dashes) = parse_log(LOG, |line| line.contains("header").then_some(0));
+        assert_eq!(dashes.iter().map(|change| change.line_number).collect::<Vec<_>>(), [3, 3, 3]);
+
+        let changes = drop_moves(changes, |change| key.find(&change.line).unwrap().as_str().to_string());
+        assert_eq!(changes.iter().map(|change| (change.commit, change.added)).collect::<Vec<_>>(), [(0, true), (1, false)]);
+
+        let pattern_specs = [PatternSpec { name: Some("keys".to_string()), ..PatternSpec::new("API_KEY") }];
+        let mut out = Vec::new();
+        write_text(&mut out, &pattern_specs, &commits, &changes).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+aaaaaaaaaa 2023-11-14 Alice added config.py:2:keys:API_KEY = \"abc\"
+bbbbbbbbbb 2024-03-09 Bob removed config.py:2:keys:API_KEY = \"abc\"
+");
+        let mut out = Vec::new();
+        write_json(&mut out, &pattern_specs, &commits, &changes[..1]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"commit\":\"aaaaaaaaaaaa\",\"date\":\"2023-11-14T22:13:20Z\",\"author\":\"Alice\",\"change\":\"added\",\"path\":\"config.py\",\"line\":2,\"rule\":\"keys\",\"text\":\"API_KEY = \\\"abc\\\"\"}\n"
+        );
+    }
+}
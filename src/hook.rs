@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use clap::{Args as ClapArgs, Subcommand};
+use colored::Colorize;
+use regex::Regex;
+
+use crate::baseline::Baseline;
+use crate::engine::CaseMode;
+use crate::error::FinderError;
+use crate::history::{self, Change};
+use crate::ignores::InlineIgnores;
+use crate::output::rule_label;
+use crate::patterns::{self, PatternSpec};
+use crate::rules::Severity;
+use crate::{SearchOptions, SearchResult};
+
+/// The rules file of `finder hook`, relative to the root of the repository.
+const DEFAULT_RULES: &str = ".finder-rules";
+
+/// Tells the hooks that `finder hook install` wrote from those of others.
+const MARKER: &str = "# Installed by `finder hook install`.";
+
+/// Arguments of `finder hook`.
+#[derive(ClapArgs, Debug)]
+pub struct HookArgs {
+    #[command(subcommand)]
+    pub command: HookCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HookCommand {
+    /// Write a pre-commit hook that blocks the commits adding lines that match the rules
+    Install {
+        /// The patterns file the hook checks, relative to the root of the repository
+        #[arg(short = 'f', long = "input-file", default_value = DEFAULT_RULES)]
+        input_file: PathBuf,
+
+        /// Case-insensitive search
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Only block the commits adding matches of the rules of at least this severity
+        #[arg(long, value_enum, value_name = "LEVEL", default_value = "warn")]
+        fail_on: Severity,
+
+        /// Leave out the matches recorded in FILE by `finder baseline update`, relative to the root of the repository
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+
+        /// Replace a pre-commit hook that finder did not write
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the staged changes against the rules, failing if some added line matches
+    Run {
+        /// The patterns file to check the staged changes against
+        #[arg(short = 'f', long = "input-file", default_value = DEFAULT_RULES)]
+        input_file: PathBuf,
+
+        /// Case-insensitive search
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Only fail on the matches of the rules of at least this severity; the others are listed
+        #[arg(long, value_enum, value_name = "LEVEL", default_value = "warn")]
+        fail_on: Severity,
+
+        /// Leave out the matches recorded in FILE by `finder baseline update`
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+    },
+}
+
+/// How `finder hook run` checks the staged changes.
+#[derive(Debug)]
+pub struct CheckOptions<'a> {
+    pub input_file: &'a Path,
+    pub ignore_case: bool,
+    pub fail_on: Severity,
+    pub baseline: Option<&'a Path>,
+}
+
+fn git(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git").args(args).output().map_err(|e| format!("cannot run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("`git {}` failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or_default()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Quotes `text` for a POSIX shell.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The pre-commit hook that runs `finder` on the rules.
+pub fn hook_script(finder: &Path, options: &CheckOptions) -> String {
+    let mut command = format!(
+        "{} hook run -f {}",
+        shell_quote(&finder.to_string_lossy()),
+        shell_quote(&options.input_file.to_string_lossy())
+    );
+    if options.ignore_case {
+        command.push_str(" -i");
+    }
+    if options.fail_on != Severity::Warn {
+        command.push_str(&format!(" --fail-on {}", options.fail_on.as_str()));
+    }
+    if let Some(baseline) = options.baseline {
+        command.push_str(&format!(" --baseline {}", shell_quote(&baseline.to_string_lossy())));
+    }
+    format!("#!/bin/sh\n{}\nexec {}\n", MARKER, command)
+}
+
+/// The lines that the staged changes add and that match, leaving out those
+/// that were only moved.
+pub fn staged_additions(diff: &str, regexes: &[Regex], search_options: &SearchOptions) -> Vec<Change> {
+    let (_, changes) = history::parse_log(diff, |line| search_options.hit(regexes, line).map(|(pattern_index, _)| pattern_index));
+    let changes = history::drop_moves(changes, |change| {
+        regexes[change.pattern_index].find(&change.line).map_or(String::new(), |found| found.as_str().to_string())
+    });
+    changes.into_iter().filter(|change| change.added).collect()
+}
+
+/// The findings of the staged changes: their added lines that match, but
+/// for those that inline comments silence, `previous` giving the line
+/// before one as staged, and those of `rules_file`, the rules themselves.
+pub fn staged_findings<F: FnMut(&str, usize) -> Option<String>>(
+    diff: &str,
+    regexes: &[Regex],
+    search_options: &SearchOptions,
+    rules_file: Option<&str>,
+    mut previous: F,
+) -> Vec<SearchResult> {
+    staged_additions(diff, regexes, search_options).into_iter()
+        .filter(|change| Some(change.path.as_str()) != rules_file)
+        .filter_map(|change| {
+            // The diff leaves out the line before, where a
+            // `finder:ignore-next-line` comment may be.
+            let previous = previous(&change.path, change.line_number);
+            search_options.line_result(regexes, Path::new(&change.path), change.line_number, previous.as_deref(), &change.line)
+        })
+        .collect()
+}
+
+/// `path` relative to the root of the repository, as the diff names it.
+fn repository_path(toplevel: &Path, path: &Path) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    let relative = path.strip_prefix(fs::canonicalize(toplevel).ok()?).ok()?;
+    let parts: Vec<String> = relative.components().map(|component| component.as_os_str().to_string_lossy().into_owned()).collect();
+    Some(parts.join("/"))
+}
+
+fn install(options: &CheckOptions, force: bool) -> Result<(), FinderError> {
+    let input_file = options.input_file;
+    let hooks = PathBuf::from(git(&["rev-parse", "--git-path", "hooks"]).map_err(FinderError::Usage)?.trim_end());
+    let path = hooks.join("pre-commit");
+    if let Ok(existing) = fs::read_to_string(&path)
+        && !existing.contains(MARKER)
+        && !force
+    {
+        return Err(FinderError::Usage(format!("{} already exists; chain `finder hook run` from it, or replace it with --force", path.display())));
+    }
+    let toplevel = PathBuf::from(git(&["rev-parse", "--show-toplevel"]).map_err(FinderError::Usage)?.trim_end());
+    if !toplevel.join(input_file).is_file() {
+        eprintln!("{} {} does not exist yet: the hook will fail until it does", "warning:".yellow().bold(), input_file.display());
+    }
+    // The hook keeps working when finder is not on the PATH of the tool
+    // that commits.
+    let finder = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("finder"));
+    fs::create_dir_all(&hooks)?;
+    fs::write(&path, hook_script(&finder, options))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    eprintln!("Installed {}, checking the staged changes against {}", path.display(), input_file.display());
+    Ok(())
+}
+
+fn check(options: &CheckOptions) -> Result<(), FinderError> {
+    let input_file = options.input_file;
+    let pattern_specs: Vec<PatternSpec> = patterns::parse_patterns(&crate::read_decoded(input_file)?);
+    let patterns: Vec<String> = pattern_specs.iter().map(|spec| spec.pattern.clone()).collect();
+    let regexes = crate::compile_regex_with_cache(&patterns, CaseMode::from_flags(options.ignore_case, false), false)?;
+    let search_options = SearchOptions {
+        negated: pattern_specs.iter().map(|spec| spec.negated).collect(),
+        inline_ignores: Some(Arc::new(InlineIgnores::new(&pattern_specs))),
+        ..Default::default()
+    };
+    let toplevel = PathBuf::from(git(&["rev-parse", "--show-toplevel"]).map_err(FinderError::Usage)?.trim_end());
+    let diff = git(&["-c", "core.quotePath=false", "diff", "--cached", "--no-color", "--no-ext-diff", "--no-renames", "--unified=0"]).map_err(FinderError::Usage)?;
+    let mut staged: HashMap<String, String> = HashMap::new();
+    let findings = staged_findings(&diff, &regexes, &search_options, repository_path(&toplevel, input_file).as_deref(), |path, line_number| {
+        let content = staged.entry(path.to_string()).or_insert_with(|| git(&["show", &format!(":{}", path)]).unwrap_or_default());
+        content.lines().nth(line_number.checked_sub(2)?).map(str::to_string)
+    });
+    let findings = match options.baseline {
+        Some(path) => {
+            let baseline = Baseline::parse(&crate::read_decoded(path)?).map_err(|e| FinderError::Usage(format!("{}: {}", path.display(), e)))?;
+            baseline.suppress(&pattern_specs, findings).0
+        }
+        None => findings,
+    };
+    if findings.is_empty() {
+        return Ok(());
+    }
+    let mut out = io::stdout().lock();
+    for finding in &findings {
+        writeln!(out, "{}:{}:{}:{}", finding.path.display(), finding.line_number, rule_label(&pattern_specs, finding), finding.line.trim())?;
+    }
+    out.flush()?;
+    let blocking = findings.iter()
+        .filter(|finding| finding.pattern_indexes().any(|pattern_index| pattern_specs[pattern_index].level() >= options.fail_on))
+        .count();
+    if blocking == 0 {
+        eprintln!("{} the staged changes add {} lines matching rules below {}", "note:".yellow().bold(), findings.len(), options.fail_on.as_str());
+        return Ok(());
+    }
+    eprintln!(
+        "{} the staged changes add {} lines matching {}; fix them, or commit with --no-verify",
+        "error:".red().bold(),
+        blocking,
+        input_file.display()
+    );
+    std::process::exit(1);
+}
+
+pub fn run(args: HookArgs) -> Result<(), FinderError> {
+    match args.command {
+        HookCommand::Install { input_file, ignore_case, fail_on, baseline, force } => {
+            install(&CheckOptions { input_file: &input_file, ignore_case, fail_on, baseline: baseline.as_deref() }, force)
+        }
+        HookCommand::Run { input_file, ignore_case, fail_on, baseline } => {
+            check(&CheckOptions { input_file: &input_file, ignore_case, fail_on, baseline: baseline.as_deref() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_script() {
+        let options = CheckOptions { input_file: Path::new(".finder-rules"), ignore_case: true, fail_on: Severity::Warn, baseline: None };
+        assert_eq!(
+            hook_script(Path::new("/opt/finder's/finder"), &options),
+            "#!/bin/sh\n# Installed by `finder hook install`.\nexec '/opt/finder'\\''s/finder' hook run -f '.finder-rules' -i\n"
+        );
+        let options = CheckOptions { ignore_case: false, fail_on: Severity::Error, baseline: Some(Path::new("baseline.json")), ..options };
+        assert!(hook_script(Path::new("finder"), &options).ends_with("hook run -f '.finder-rules' --fail-on error --baseline 'baseline.json'\n"));
+    }
+
+    #[test]
+    fn test_staged_additions() {
+        let diff = "\
+diff --git a/app.py b/app.py
+--- a/app.py
++++ b/app.py
+@@ -3 +3,2 @@
+-    print(\"debug\")  # old
++    print(\"debug\")  # moved
++password = \"hunter2\"
+diff --git a/.env.example b/.env.example
+new file mode 100644
+--- /dev/null
++++ b/.env.example
+@@ -0,0 +1 @@
++password = \"changeme\"
+";
+        let regexes = [Regex::new(r"print\(").unwrap(), Regex::new("password = ").unwrap(), Regex::new("changeme").unwrap()];
+        let search_options = SearchOptions { negated: vec![false, false, true], ..Default::default() };
+        let additions = staged_additions(diff, &regexes, &search_options);
+        let found: Vec<(&str, usize, usize)> = additions.iter().map(|change| (change.path.as_str(), change.line_number, change.pattern_index)).collect();
+        assert_eq!(found, [("app.py", 4, 1)]);
+    }
+
+    #[test]
+    fn test_staged_findings() {
+        let diff = "\
+diff --git a/app.py b/app.py
+--- a/app.py
++++ b/app.py
+@@ -0,0 +1 @@
++token = 1  # finder:ignore
+@@ -1,0 +3,2 @@
++token = 2
++token = 3
+diff --git a/.finder-rules b/.finder-rules
+--- a/.finder-rules
++++ b/.finder-rules
+@@ -0,0 +1 @@
++token = \\d
+";
+        let pattern_specs = [PatternSpec::new("token = \\d")];
+        let regexes = [Regex::new(&pattern_specs[0].pattern).unwrap()];
+        let search_options = SearchOptions { inline_ignores: Some(Arc::new(InlineIgnores::new(&pattern_specs))), ..Default::default() };
+        let staged = ["token = 1  # finder:ignore", "# finder:ignore-next-line", "token = 2", "token = 3"];
+        let findings = staged_findings(diff, &regexes, &search_options, Some(".finder-rules"), |path, line_number| {
+            assert_eq!(path, "app.py");
+            staged.get(line_number.checked_sub(2)?).map(|line| line.to_string())
+        });
+        let found: Vec<usize> = findings.iter().map(|finding| finding.line_number).collect();
+        assert_eq!(found, [4]);
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+
+/// Where the paths of results link to in terminals that support OSC 8
+/// hyperlinks (`--hyperlink-format`), e.g. `vscode://file{path}:{line}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperlinkFormat(String);
+
+impl Default for HyperlinkFormat {
+    fn default() -> HyperlinkFormat {
+        HyperlinkFormat("file://{path}".to_string())
+    }
+}
+
+impl HyperlinkFormat {
+    /// A format with `{path}`, `{line}` and `{column}` placeholders, or the
+    /// name of a known editor: `file`, `vscode`, `cursor`.
+    pub fn parse(value: &str) -> Result<HyperlinkFormat, String> {
+        let format = match value {
+            "file" | "default" => return Ok(HyperlinkFormat::default()),
+            "vscode" => "vscode://file{path}:{line}:{column}",
+            "cursor" => "cursor://file{path}:{line}:{column}",
+            format => format,
+        };
+        if !format.contains("{path}") {
+            return Err(format!("`{}` has no {{path}} placeholder", format));
+        }
+        Ok(HyperlinkFormat(format.to_string()))
+    }
+
+    /// The link to `line` and `column` (both 1-based) of `path`. The path is
+    /// made absolute, written with `/` and percent-encoded, as URLs expect.
+    pub fn url(&self, path: &Path, line: usize, column: usize) -> String {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut path = absolute.to_string_lossy().replace('\\', "/");
+        // `C:/dir` on Windows.
+        if !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        self.0
+            .replace("{path}", &percent_encode(&path))
+            .replace("{line}", &line.to_string())
+            .replace("{column}", &column.to_string())
+    }
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `text` wrapped in an OSC 8 escape sequence that links it to `url`.
+pub fn wrap(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperlink_format() {
+        let vscode = HyperlinkFormat::parse("vscode").unwrap();
+        assert_eq!(vscode.url(Path::new("/src/my file.rs"), 12, 5), "vscode://file/src/my%20file.rs:12:5");
+        assert_eq!(HyperlinkFormat::parse("file").unwrap().url(Path::new("/a/é.txt"), 1, 1), "file:///a/%C3%A9.txt");
+        let custom = HyperlinkFormat::parse("idea://open?file={path}&line={line}").unwrap();
+        assert_eq!(custom.url(Path::new("/x.rs"), 3, 1), "idea://open?file=/x.rs&line=3");
+        assert!(HyperlinkFormat::parse("editor://{line}").is_err());
+        assert!(HyperlinkFormat::default().url(Path::new("relative.txt"), 1, 1).ends_with("/relative.txt"));
+        assert_eq!(wrap("file:///x", "x"), "\x1b]8;;file:///x\x1b\\x\x1b]8;;\x1b\\");
+    }
+}
@@ -0,0 +1,121 @@
+use crate::patterns::PatternSpec;
+
+/// The comment that leaves the matches of its own line out.
+pub const IGNORE: &str = "finder:ignore";
+
+/// The comment that leaves the matches of the line after it out.
+pub const IGNORE_NEXT_LINE: &str = "finder:ignore-next-line";
+
+/// What the inline comments leave out of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ignored<'a> {
+    Nothing,
+    /// The matches of every rule.
+    All,
+    /// The matches of the rules of these names.
+    Rules(Vec<&'a str>),
+}
+
+impl<'a> Ignored<'a> {
+    fn add(&mut self, other: Ignored<'a>) {
+        match (&mut *self, other) {
+            (Ignored::All, _) | (_, Ignored::Nothing) => {}
+            (Ignored::Rules(rules), Ignored::Rules(others)) => rules.extend(others),
+            (this, other) => *this = other,
+        }
+    }
+
+    pub fn covers(&self, rule: &str) -> bool {
+        match self {
+            Ignored::Nothing => false,
+            Ignored::All => true,
+            Ignored::Rules(rules) => rules.contains(&rule),
+        }
+    }
+}
+
+/// The rules that the `comment` directives of `line` name: the
+/// comma-separated names in brackets right after the directive, as in
+/// `finder:ignore[aws-key,jwt]`, or every rule when it is followed by
+/// anything else, such as a reason, the end of the line or of the comment.
+fn directives<'a>(line: &'a str, comment: &str) -> Ignored<'a> {
+    let mut ignored = Ignored::Nothing;
+    for (start, _) in line.match_indices(comment) {
+        let rest = &line[start + comment.len()..];
+        // `finder:ignored` or `finder:ignore-next-line` are other words.
+        if rest.starts_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+            continue;
+        }
+        let is_name = |name: &str| name.starts_with(char::is_alphanumeric) && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        match rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some((names, _)) if names.split(',').map(str::trim).all(is_name) => {
+                ignored.add(Ignored::Rules(names.split(',').map(str::trim).collect()));
+            }
+            _ => ignored.add(Ignored::All),
+        }
+    }
+    ignored
+}
+
+/// What the `finder:ignore` comments of `line` and the
+/// `finder:ignore-next-line` comments of the line before it leave out of
+/// `line`.
+pub fn ignored<'a>(previous: Option<&'a str>, line: &'a str) -> Ignored<'a> {
+    let mut ignored = Ignored::Nothing;
+    if line.contains(IGNORE) {
+        ignored.add(directives(line, IGNORE));
+    }
+    if let Some(previous) = previous.filter(|previous| previous.contains(IGNORE_NEXT_LINE)) {
+        ignored.add(directives(previous, IGNORE_NEXT_LINE));
+    }
+    ignored
+}
+
+/// Leaves out the matches that inline comments silence, unless
+/// `--no-inline-ignores`.
+#[derive(Debug, Clone, Default)]
+pub struct InlineIgnores {
+    /// The rule of each pattern, by index, as the comments name it: its
+    /// name, or the pattern itself when it has none.
+    rules: Vec<String>,
+}
+
+impl InlineIgnores {
+    pub fn new(pattern_specs: &[PatternSpec]) -> InlineIgnores {
+        InlineIgnores { rules: pattern_specs.iter().map(|spec| spec.name.clone().unwrap_or_else(|| spec.pattern.clone())).collect() }
+    }
+
+    /// Whether `ignored` covers the pattern at `pattern_index`.
+    pub fn ignores(&self, ignored: &Ignored, pattern_index: usize) -> bool {
+        self.rules.get(pattern_index).is_some_and(|rule| ignored.covers(rule))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignored() {
+        assert_eq!(ignored(None, "let key = 1;"), Ignored::Nothing);
+        assert_eq!(ignored(None, "let key = 1; // finder:ignore"), Ignored::All);
+        assert_eq!(ignored(None, "key = 1 /* finder:ignore */"), Ignored::All);
+        assert_eq!(ignored(None, "key = 1 # finder:ignore[secrets, jwt] test fixture"), Ignored::Rules(vec!["secrets", "jwt"]));
+        assert_eq!(ignored(None, "key = 1 # finder:ignore -- test fixture"), Ignored::All);
+        // Words after the directive are a reason, not rule names.
+        assert_eq!(ignored(None, "key = 1 // finder:ignore false positive"), Ignored::All);
+        assert_eq!(ignored(None, "key = 1 // finder:ignore[not a name]"), Ignored::All);
+        assert_eq!(ignored(None, "// finder:ignored"), Ignored::Nothing);
+        // The directive of the line before only applies to the next line.
+        assert_eq!(ignored(None, "// finder:ignore-next-line"), Ignored::Nothing);
+        assert_eq!(ignored(Some("// finder:ignore-next-line[aws-key]"), "key = 1"), Ignored::Rules(vec!["aws-key"]));
+        assert_eq!(ignored(Some("<!-- finder:ignore-next-line -->"), "key = 1 // finder:ignore[jwt]"), Ignored::All);
+        assert_eq!(ignored(Some("// finder:ignore"), "key = 1"), Ignored::Nothing);
+
+        let inline_ignores = InlineIgnores::new(&[PatternSpec { name: Some("jwt".to_string()), ..PatternSpec::new("eyJ") }, PatternSpec::new("TODO")]);
+        let ignored = Ignored::Rules(vec!["jwt"]);
+        assert!(inline_ignores.ignores(&ignored, 0));
+        assert!(!inline_ignores.ignores(&ignored, 1));
+        assert!(inline_ignores.ignores(&Ignored::All, 1));
+    }
+}
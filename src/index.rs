@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::UNIX_EPOCH;
+
+use clap::{Args as ClapArgs, Subcommand};
+use ignore::{WalkBuilder, WalkState};
+use regex_syntax::hir::{Hir, HirKind};
+
+use crate::error::FinderError;
+use crate::longpath;
+
+/// Where `finder index build <DIR>` writes the index, and where
+/// `--use-index` looks for it. Hidden, so searches do not walk into it.
+pub const INDEX_FILE_NAME: &str = ".finder-index";
+
+const MAGIC: &[u8] = b"finder-index 2\n";
+
+/// Arguments of `finder index`.
+#[derive(ClapArgs, Debug)]
+pub struct IndexArgs {
+    #[command(subcommand)]
+    pub command: IndexCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IndexCommand {
+    /// Index the trigrams of every file under DIR into DIR/.finder-index
+    Build {
+        /// The directory to index
+        dir: PathBuf,
+    },
+}
+
+/// The distinct trigrams of `content`, ASCII letters lowercased so that the
+/// index also serves case-insensitive searches, packed into `u32`s and
+/// sorted.
+pub fn trigrams(content: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = content.windows(3)
+        .map(|window| {
+            let [a, b, c] = [window[0], window[1], window[2]].map(|byte| byte.to_ascii_lowercase() as u32);
+            (a << 16) | (b << 8) | c
+        })
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+/// What a file must contain for a pattern to possibly match in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Every trigram.
+    Trigrams(Vec<u32>),
+    /// Every sub-query; `All(vec![])` holds for any file.
+    All(Vec<Query>),
+    /// At least one sub-query.
+    Any(Vec<Query>),
+}
+
+impl Query {
+    /// The query of a regex: the literal runs it cannot match without. A
+    /// pattern that cannot be analysed gives a query every file satisfies.
+    pub fn of(pattern: &str, ignore_case: bool) -> Query {
+        match regex_syntax::Parser::new().parse(pattern) {
+            Ok(hir) => Query::of_hir(&hir, ignore_case),
+            Err(_) => Query::All(Vec::new()),
+        }
+    }
+
+    fn of_literal(bytes: &[u8], ignore_case: bool) -> Query {
+        let mut required = trigrams(bytes);
+        // Unicode case folding matches letters whose bytes differ: non-ASCII
+        // ones, and `k` and `s` with the Kelvin sign and the long s.
+        if ignore_case {
+            required.retain(|trigram| trigram.to_be_bytes()[1..].iter().all(|byte| byte.is_ascii() && !matches!(byte, b'k' | b's')));
+        }
+        Query::Trigrams(required)
+    }
+
+    fn of_hir(hir: &Hir, ignore_case: bool) -> Query {
+        match hir.kind() {
+            HirKind::Literal(literal) => Query::of_literal(&literal.0, ignore_case),
+            HirKind::Capture(capture) => Query::of_hir(&capture.sub, ignore_case),
+            HirKind::Repetition(repetition) if repetition.min > 0 => Query::of_hir(&repetition.sub, ignore_case),
+            HirKind::Concat(subs) => {
+                // Adjacent literals form a single run, whose trigrams include
+                // the ones across their boundaries.
+                let mut queries = Vec::new();
+                let mut run = Vec::new();
+                for sub in subs {
+                    match sub.kind() {
+                        HirKind::Literal(literal) => run.extend_from_slice(&literal.0),
+                        _ => {
+                            queries.push(Query::of_literal(&run, ignore_case));
+                            run.clear();
+                            queries.push(Query::of_hir(sub, ignore_case));
+                        }
+                    }
+                }
+                queries.push(Query::of_literal(&run, ignore_case));
+                Query::All(queries)
+            }
+            HirKind::Alternation(subs) => Query::Any(subs.iter().map(|sub| Query::of_hir(sub, ignore_case)).collect()),
+            _ => Query::All(Vec::new()),
+        }
+    }
+
+    /// Whether a file with these (sorted) trigrams may contain a match.
+    pub fn matches(&self, trigrams: &[u32]) -> bool {
+        match self {
+            Query::Trigrams(required) => required.iter().all(|trigram| trigrams.binary_search(trigram).is_ok()),
+            Query::All(queries) => queries.iter().all(|query| query.matches(trigrams)),
+            Query::Any(queries) => queries.iter().any(|query| query.matches(trigrams)),
+        }
+    }
+}
+
+/// A file as it was when the index was built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    pub size: u64,
+    /// Modification time, in nanoseconds since the Unix epoch.
+    pub modified: u128,
+    pub trigrams: Vec<u32>,
+}
+
+pub fn modified_nanos(metadata: &Metadata) -> u128 {
+    metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos())
+}
+
+impl IndexedFile {
+    /// Whether the file still has the size and modification time it had
+    /// when indexed.
+    fn is_fresh(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len() && self.modified == modified_nanos(metadata)
+    }
+
+    /// Indexes the file at `path`, whose metadata is `metadata`.
+    fn read(path: &Path, metadata: &Metadata) -> io::Result<IndexedFile> {
+        let content = fs::read(longpath::for_opening(path))?;
+        // Searches look at the decoded text, so index that.
+        Ok(IndexedFile {
+            size: metadata.len(),
+            modified: modified_nanos(metadata),
+            trigrams: trigrams(crate::decode_bytes(&content).as_bytes()),
+        })
+    }
+}
+
+/// The trigram index of a directory, keyed by paths relative to it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Index {
+    pub files: HashMap<PathBuf, IndexedFile>,
+}
+
+impl Index {
+    /// Indexes every file under `dir` that a search would go through.
+    pub fn build(dir: &Path) -> Index {
+        let (sender, receiver) = mpsc::channel();
+        WalkBuilder::new(dir).build_parallel().run(|| {
+            let sender = sender.clone();
+            Box::new(move |entry| {
+                let Some(entry) = entry.ok().filter(|e| e.file_type().is_some_and(|ft| ft.is_file())) else {
+                    return WalkState::Continue;
+                };
+                let Some(file) = entry.metadata().ok().and_then(|metadata| IndexedFile::read(entry.path(), &metadata).ok()) else {
+                    return WalkState::Continue;
+                };
+                let Ok(relative) = entry.path().strip_prefix(dir) else { return WalkState::Continue };
+                let _ = sender.send((relative.to_path_buf(), file));
+                WalkState::Continue
+            })
+        });
+        drop(sender);
+        Index { files: receiver.into_iter().collect() }
+    }
+
+    /// Indexes `path` under `dir` again if it changed since it was indexed,
+    /// or forgets it, and the files under it for a directory, if it can no
+    /// longer be read. Returns whether the index changed.
+    pub fn update(&mut self, dir: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(dir) else { return false };
+        let metadata = fs::metadata(longpath::for_opening(path));
+        if let (Some(file), Ok(metadata)) = (self.files.get(relative), &metadata) && file.is_fresh(metadata) {
+            return false;
+        }
+        if metadata.as_ref().is_ok_and(|metadata| metadata.is_dir()) {
+            return false;
+        }
+        match metadata.and_then(|metadata| IndexedFile::read(path, &metadata)) {
+            Ok(file) => {
+                self.files.insert(relative.to_path_buf(), file);
+                true
+            }
+            // A removed directory takes the files under it along.
+            Err(_) => {
+                let count = self.files.len();
+                self.files.retain(|file, _| !file.starts_with(relative));
+                self.files.len() != count
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        let mut paths: Vec<&PathBuf> = self.files.keys().collect();
+        paths.sort();
+        for path in paths {
+            let file = &self.files[path];
+            let path = path.to_string_lossy();
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&file.size.to_le_bytes());
+            bytes.extend_from_slice(&file.modified.to_le_bytes());
+            bytes.extend_from_slice(&(file.trigrams.len() as u32).to_le_bytes());
+            for trigram in &file.trigrams {
+                bytes.extend_from_slice(&trigram.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Index> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a finder index, or from another version");
+        let mut rest = bytes.strip_prefix(MAGIC).ok_or_else(invalid)?;
+        let mut take = |count: usize| -> io::Result<&[u8]> {
+            if rest.len() < count {
+                return Err(invalid());
+            }
+            let (taken, remaining) = rest.split_at(count);
+            rest = remaining;
+            Ok(taken)
+        };
+        let mut index = Index::default();
+        while let Ok(length) = take(4) {
+            let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+            let path = PathBuf::from(String::from_utf8_lossy(take(length)?).into_owned());
+            let size = u64::from_le_bytes(take(8)?.try_into().unwrap());
+            let modified = u128::from_le_bytes(take(16)?.try_into().unwrap());
+            let count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+            let trigrams = take(count * 4)?.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect();
+            index.files.insert(path, IndexedFile { size, modified, trigrams });
+        }
+        Ok(index)
+    }
+}
+
+/// Rules out, with the indexes of the searched directories, the files that
+/// cannot match any pattern (`--use-index`).
+#[derive(Debug)]
+pub struct IndexFilter {
+    indexes: Arc<Vec<(PathBuf, Index)>>,
+    queries: Vec<Query>,
+    /// Files searched because they are missing from the index or changed
+    /// since it was built.
+    stale: AtomicUsize,
+}
+
+/// Loads `.finder-index` from each directory of `roots` that has one,
+/// with the directory it indexes.
+pub fn load_indexes(roots: &[PathBuf]) -> io::Result<Vec<(PathBuf, Index)>> {
+    let mut indexes = Vec::new();
+    for root in roots.iter().filter(|root| root.is_dir()) {
+        let path = root.join(INDEX_FILE_NAME);
+        let mut bytes = Vec::new();
+        match fs::File::open(longpath::for_opening(&path)) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let index = Index::from_bytes(&bytes)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        indexes.push((root.clone(), index));
+    }
+    Ok(indexes)
+}
+
+/// Keeps the indexes of the watched directories up to date as their files
+/// change (`--watch`), instead of leaving them to be rebuilt.
+pub struct IndexUpdater {
+    /// Each index, with its directory and whether it changed since it was
+    /// last written.
+    indexes: Vec<(PathBuf, Index, bool)>,
+}
+
+impl IndexUpdater {
+    pub fn load(roots: &[PathBuf]) -> io::Result<IndexUpdater> {
+        let indexes = load_indexes(roots)?.into_iter().map(|(root, index)| (root, index, false)).collect();
+        Ok(IndexUpdater { indexes })
+    }
+
+    /// Indexes the changed file at `path` again, in the index of the
+    /// directory it is in.
+    pub fn update(&mut self, path: &Path) {
+        for (root, index, dirty) in &mut self.indexes {
+            if index.update(root, path) {
+                *dirty = true;
+            }
+        }
+    }
+
+    /// Writes the indexes that changed since the last checkpoint, so that
+    /// stopping the watch loses at most the last batch of changes.
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        for (root, index, dirty) in &mut self.indexes {
+            if std::mem::take(dirty) {
+                crate::progress::write_atomically(&root.join(INDEX_FILE_NAME), index.to_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IndexFilter {
+    pub fn new(indexes: Arc<Vec<(PathBuf, Index)>>, queries: Vec<Query>) -> IndexFilter {
+        IndexFilter { indexes, queries, stale: AtomicUsize::new(0) }
+    }
+
+    /// Loads the indexes of `roots` for a single search.
+    pub fn load(roots: &[PathBuf], queries: Vec<Query>) -> io::Result<IndexFilter> {
+        Ok(IndexFilter::new(Arc::new(load_indexes(roots)?), queries))
+    }
+
+    /// Whether no searched directory has an index.
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// Whether the file at `path` has to be searched: it is not indexed, it
+    /// changed since, or its trigrams allow a match.
+    pub fn may_match(&self, path: &Path, metadata: Option<&Metadata>) -> bool {
+        let indexed = self.indexes.iter().find_map(|(root, index)| index.files.get(path.strip_prefix(root).ok()?));
+        match (indexed, metadata) {
+            (Some(file), Some(metadata)) if file.is_fresh(metadata) => {
+                self.queries.iter().any(|query| query.matches(&file.trigrams))
+            }
+            _ => {
+                self.stale.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+        }
+    }
+
+    pub fn stale(&self) -> usize {
+        self.stale.load(Ordering::Relaxed)
+    }
+}
+
+pub fn run(args: IndexArgs) -> Result<(), FinderError> {
+    match args.command {
+        IndexCommand::Build { dir } => {
+            if !dir.is_dir() {
+                return Err(FinderError::Usage(format!("{}: not a directory", dir.display())));
+            }
+            let index = Index::build(&dir);
+            let path = dir.join(INDEX_FILE_NAME);
+            crate::progress::write_atomically(&path, index.to_bytes())?;
+            eprintln!("Indexed {} files into {}", index.files.len(), path.display());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn may_match(pattern: &str, ignore_case: bool, content: &str) -> bool {
+        Query::of(pattern, ignore_case).matches(&trigrams(content.as_bytes()))
+    }
+
+    #[test]
+    fn test_queries() {
+        let content = "fn parse_config(path: &Path) -> Config";
+        assert!(may_match("parse_config", false, content));
+        assert!(!may_match("parse_settings", false, content));
+        assert!(may_match(r"fn \w+\(path", false, content));
+        assert!(!may_match(r"fn \w+\(file", false, content));
+        assert!(may_match("settings|config", false, content));
+        assert!(!may_match("settings|options", false, content));
+        assert!(may_match("(?:load)?_config", false, content));
+        assert!(may_match("PARSE_CONFIG", true, content));
+        assert!(may_match("(?i)PARSE", false, content));
+        assert!(may_match("x", false, ""));
+        assert!(may_match("unclosed(", false, ""));
+    }
+
+    #[test]
+    fn test_index_round_trip_and_freshness() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn parse_config() {}").unwrap();
+        fs::write(dir.path().join("b.rs"), "fn main() {}").unwrap();
+        let index = Index::build(dir.path());
+        assert_eq!(index.files.len(), 2);
+        assert_eq!(Index::from_bytes(&index.to_bytes()).unwrap(), index);
+        assert!(Index::from_bytes(b"garbage").is_err());
+
+        fs::write(dir.path().join(INDEX_FILE_NAME), index.to_bytes()).unwrap();
+        let filter = IndexFilter::load(&[dir.path().to_path_buf()], vec![Query::of("parse_config", false)]).unwrap();
+        let check = |name: &str| {
+            let path = dir.path().join(name);
+            filter.may_match(&path, fs::metadata(longpath::for_opening(&path)).ok().as_ref())
+        };
+        assert!(check("a.rs"));
+        assert!(!check("b.rs"));
+        assert_eq!(filter.stale(), 0);
+
+        // A changed file is searched whatever its indexed trigrams say.
+        fs::write(dir.path().join("b.rs"), "fn main() { parse_config() }").unwrap();
+        assert!(check("b.rs"));
+        fs::write(dir.path().join("c.rs"), "").unwrap();
+        assert!(check("c.rs"));
+        assert_eq!(filter.stale(), 2);
+    }
+
+    #[test]
+    fn test_index_update() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join(INDEX_FILE_NAME), Index::build(dir.path()).to_bytes()).unwrap();
+        let mut updater = IndexUpdater::load(&[dir.path().to_path_buf()]).unwrap();
+        let query = Query::of("parse_config", false);
+
+        // An unchanged file leaves the index alone.
+        updater.update(&dir.path().join("a.rs"));
+        assert!(!updater.indexes[0].2);
+
+        fs::write(dir.path().join("a.rs"), "fn main() { parse_config() }").unwrap();
+        fs::write(dir.path().join("b.rs"), "fn parse_config() {}").unwrap();
+        updater.update(&dir.path().join("a.rs"));
+        updater.update(&dir.path().join("b.rs"));
+        updater.update(Path::new("/elsewhere/c.rs"));
+        updater.checkpoint().unwrap();
+        assert!(!updater.indexes[0].2);
+
+        let (_, index) = load_indexes(&[dir.path().to_path_buf()]).unwrap().remove(0);
+        assert_eq!(index, updater.indexes[0].1);
+        assert_eq!(index.files.len(), 2);
+        assert!(index.files.values().all(|file| query.matches(&file.trigrams)));
+
+        fs::remove_file(dir.path().join("b.rs")).unwrap();
+        assert!(updater.indexes[0].1.update(dir.path(), &dir.path().join("b.rs")));
+        assert_eq!(updater.indexes[0].1.files.len(), 1);
+
+        // A directory renamed away is reported once, for the directory.
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/c.rs"), "fn c() {}").unwrap();
+        assert!(updater.indexes[0].1.update(dir.path(), &dir.path().join("sub/c.rs")));
+        assert!(!updater.indexes[0].1.update(dir.path(), &dir.path().join("sub")));
+        fs::rename(dir.path().join("sub"), dir.path().join("moved")).unwrap();
+        assert!(updater.indexes[0].1.update(dir.path(), &dir.path().join("sub")));
+        assert_eq!(updater.indexes[0].1.files.len(), 1);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// `text` as a JSON string literal.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A scalar JSON value, or an array of strings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Strings(Vec<String>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_strings(&self) -> Option<&[String]> {
+        match self {
+            Value::Strings(strings) => Some(strings),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as usize),
+            _ => None,
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!("expected `{}`, found `{}`", expected, c)),
+        None => Err(format!("expected `{}`, found the end of the line", expected)),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut text = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(text),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => text.push('\n'),
+                'r' => text.push('\r'),
+                't' => text.push('\t'),
+                'b' => text.push('\u{8}'),
+                'f' => text.push('\u{c}'),
+                'u' => {
+                    let mut code = parse_hex4(chars)?;
+                    // A surrogate pair encodes a character outside the basic plane.
+                    if (0xD800..0xDC00).contains(&code) {
+                        let mut lookahead = chars.clone();
+                        if lookahead.next() == Some('\\') && lookahead.next() == Some('u') {
+                            *chars = lookahead;
+                            let low = parse_hex4(chars)?;
+                            code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                        }
+                    }
+                    text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                c => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let digits: String = chars.by_ref().take(4).collect();
+    u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape `\\u{}`", digits))
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('"') => parse_string(chars).map(Value::String),
+        Some('[') => parse_strings(chars).map(Value::Strings),
+        Some('{') => Err("nested objects are not supported".to_string()),
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}') && !c.is_whitespace()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => word.parse().map(Value::Number).map_err(|_| format!("invalid value `{}`", word)),
+            }
+        }
+        None => Err("expected a value, found the end of the line".to_string()),
+    }
+}
+
+/// Parses a JSON string literal that makes up the whole of `text`, such as
+/// one written by `json_string`.
+pub fn parse_string_literal(text: &str) -> Result<String, String> {
+    let mut chars = text.chars().peekable();
+    let string = parse_string(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(string),
+        Some(c) => Err(format!("unexpected `{}` after the string", c)),
+    }
+}
+
+fn parse_strings(chars: &mut Peekable<Chars>) -> Result<Vec<String>, String> {
+    let mut strings = Vec::new();
+    expect(chars, '[')?;
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_none() {
+        loop {
+            strings.push(parse_string(chars)?);
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(',') => skip_whitespace(chars),
+                Some(']') => break,
+                _ => return Err("expected `,` or `]`".to_string()),
+            }
+            // A trailing comma is allowed.
+            if chars.next_if_eq(&']').is_some() {
+                break;
+            }
+        }
+    }
+    Ok(strings)
+}
+
+/// Parses an array of string literals that makes up the whole of `text`,
+/// such as `["*.rs", "!vendor/**"]`.
+pub fn parse_string_array(text: &str) -> Result<Vec<String>, String> {
+    let mut chars = text.chars().peekable();
+    let strings = parse_strings(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(strings),
+        Some(c) => Err(format!("unexpected `{}` after the array", c)),
+    }
+}
+
+/// Parses a JSON object whose values are all scalars or arrays of strings,
+/// such as a line of `--format json`.
+pub fn parse_object(text: &str) -> Result<HashMap<String, Value>, String> {
+    let mut chars = text.chars().peekable();
+    let mut object = HashMap::new();
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_none() {
+        loop {
+            let key = parse_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            object.insert(key, parse_value(&mut chars)?);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => skip_whitespace(&mut chars),
+                Some('}') => break,
+                _ => return Err("expected `,` or `}`".to_string()),
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(object),
+        Some(c) => Err(format!("unexpected `{}` after the object", c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_round_trip() {
+        let text = "tab\there \"quoted\" back\\slash\nbell\u{7} é 😀";
+        let object = parse_object(&format!("{{\"text\":{}}}", json_string(text))).unwrap();
+        assert_eq!(object["text"].as_str(), Some(text));
+        let object = parse_object(r#"{"raw":"😀","escaped":"\ud83d\ude00 \u00e9"}"#).unwrap();
+        assert_eq!(object["raw"].as_str(), Some("😀"));
+        assert_eq!(object["escaped"].as_str(), Some("😀 é"));
+    }
+
+    #[test]
+    fn test_parse_object() {
+        let object = parse_object(r#" { "path": "a.rs", "line": 12, "end": null, "ok": true } "#).unwrap();
+        assert_eq!(object["path"], Value::String("a.rs".to_string()));
+        assert_eq!(object["line"].as_usize(), Some(12));
+        assert_eq!(object["end"], Value::Null);
+        assert_eq!(object["ok"], Value::Bool(true));
+        assert!(parse_object("{}").unwrap().is_empty());
+        assert_eq!(parse_object(r#"{"a":["x", "y"]}"#).unwrap()["a"].as_strings(), Some(&["x".to_string(), "y".to_string()][..]));
+        assert!(parse_object(r#"{"a":[1]}"#).is_err());
+        assert!(parse_object(r#"{"a":{}}"#).is_err());
+        assert!(parse_object(r#"{"a":1"#).is_err());
+        assert!(parse_object(r#"{"a":1} x"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_string_array() {
+        assert_eq!(parse_string_array(r#"["*.rs", "!vendor/**",]"#).unwrap(), ["*.rs", "!vendor/**"]);
+        assert!(parse_string_array(" [ ] ").unwrap().is_empty());
+        assert!(parse_string_array(r#"["a" "b"]"#).is_err());
+        assert!(parse_string_array(r#"["a", 1]"#).is_err());
+        assert!(parse_string_array(r#""a""#).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+/// The part of each file that is searched (`--line-range`, `--head`, `--tail`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineSelection {
+    /// Lines `first..=last` (1-based); `last: None` runs to the end of the file.
+    Range { first: usize, last: Option<usize> },
+    /// The last N lines.
+    Tail(usize),
+}
+
+impl LineSelection {
+    pub fn head(count: usize) -> LineSelection {
+        LineSelection::Range { first: 1, last: Some(count) }
+    }
+
+    /// Parses `100-500`, `100-` (to the end) or `-500` (from the start).
+    pub fn parse_range(value: &str) -> Result<LineSelection, String> {
+        let (first, last) = value.split_once('-').ok_or_else(|| format!("invalid line range `{}`, expected START-END", value))?;
+        let parse = |bound: &str| bound.trim().parse::<usize>().map_err(|_| format!("invalid line number `{}` in `{}`", bound, value));
+        let first = if first.trim().is_empty() { 1 } else { parse(first)? };
+        let last = if last.trim().is_empty() { None } else { Some(parse(last)?) };
+        if first == 0 || last.is_some_and(|last| last < first) {
+            return Err(format!("invalid line range `{}`", value));
+        }
+        Ok(LineSelection::Range { first, last })
+    }
+
+    /// Returns the selected slice of `content` and the number of lines before it.
+    pub fn select<'a>(&self, content: &'a str) -> (&'a str, usize) {
+        let (first, last) = match *self {
+            LineSelection::Range { first, last } => (first, last),
+            LineSelection::Tail(count) => {
+                let total = content.lines().count();
+                (total.saturating_sub(count) + 1, None)
+            }
+        };
+        let start = line_start(content, first - 1);
+        let end = last.map_or(content.len(), |last| line_start(content, last));
+        (&content[start..end.max(start)], first - 1)
+    }
+}
+
+/// Byte offset at which the line with 0-based index `index` starts (or the end of `content`).
+fn line_start(content: &str, index: usize) -> usize {
+    if index == 0 {
+        return 0;
+    }
+    content.match_indices('\n').nth(index - 1).map_or(content.len(), |(offset, _)| offset + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(LineSelection::parse_range("100-500"), Ok(LineSelection::Range { first: 100, last: Some(500) }));
+        assert_eq!(LineSelection::parse_range("100-"), Ok(LineSelection::Range { first: 100, last: None }));
+        assert_eq!(LineSelection::parse_range("-5"), Ok(LineSelection::Range { first: 1, last: Some(5) }));
+        assert!(LineSelection::parse_range("5-1").is_err());
+        assert!(LineSelection::parse_range("0-3").is_err());
+        assert!(LineSelection::parse_range("12").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(LineSelection::parse_range("2-3").unwrap().select(CONTENT), ("two\nthree\n", 1));
+        assert_eq!(LineSelection::head(2).select(CONTENT), ("one\ntwo\n", 0));
+        assert_eq!(LineSelection::Tail(2).select(CONTENT), ("four\nfive\n", 3));
+        assert_eq!(LineSelection::Tail(10).select(CONTENT), (CONTENT, 0));
+        assert_eq!(LineSelection::parse_range("9-12").unwrap().select(CONTENT), ("", 8));
+        assert_eq!(LineSelection::Tail(1).select("a\r\nb"), ("b", 1));
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The directories a walk that follows symbolic links (`--follow`) already
+/// goes through, so that a link to one of them is not walked again.
+///
+/// The walker itself catches the links to a directory above them, by file
+/// identity (device and inode on Unix, file ID on Windows); this catches the
+/// links to the rest of the searched tree, to a directory the tree is in,
+/// which would lead back into it, and to a directory outside of it that
+/// another link leads to. Directories are told apart by their canonical
+/// path, which resolves every link on the way and, as directories cannot be
+/// hard-linked, names each of them once.
+#[derive(Debug)]
+pub struct VisitedLinks {
+    /// The canonical roots of the walk, which it goes through anyway.
+    roots: Vec<PathBuf>,
+    /// The directories outside the roots that a link led to.
+    entered: Mutex<HashSet<PathBuf>>,
+}
+
+impl VisitedLinks {
+    pub fn new(roots: &[PathBuf]) -> VisitedLinks {
+        VisitedLinks {
+            roots: roots.iter().map(|root| fs::canonicalize(root).unwrap_or_else(|_| root.clone())).collect(),
+            entered: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// For `link`, a symbolic link to a directory, the directory it leads to
+    /// when the walk already goes there and skips it; `None` when the walk
+    /// should follow it.
+    pub fn already_walked(&self, link: &Path) -> Option<PathBuf> {
+        let target = fs::canonicalize(link).ok()?;
+        let walked = self.roots.iter().any(|root| target.starts_with(root) || root.starts_with(&target));
+        if walked || !self.entered.lock().unwrap().insert(target.clone()) {
+            Some(target)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_already_walked() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir(&outside).unwrap();
+        symlink(root.join("src"), root.join("to_src")).unwrap();
+        symlink(&outside, root.join("to_outside")).unwrap();
+        symlink(&outside, root.join("src/to_outside")).unwrap();
+        symlink("..", root.join("src/to_parent")).unwrap();
+        symlink(dir.path(), root.join("to_above")).unwrap();
+
+        let links = VisitedLinks::new(std::slice::from_ref(&root));
+        let outside = fs::canonicalize(&outside).unwrap();
+        assert_eq!(links.already_walked(&root.join("to_src")), Some(fs::canonicalize(root.join("src")).unwrap()));
+        assert_eq!(links.already_walked(&root.join("to_outside")), None);
+        assert_eq!(links.already_walked(&root.join("src/to_outside")), Some(outside));
+        assert_eq!(links.already_walked(&root.join("missing")), None);
+        assert_eq!(links.already_walked(&root.join("src/to_parent")), Some(fs::canonicalize(&root).unwrap()));
+        assert_eq!(links.already_walked(&root.join("to_above")), Some(fs::canonicalize(dir.path()).unwrap()));
+    }
+}
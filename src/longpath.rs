@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+/// The path to give the operating system to open `path`. On Windows, that
+/// is the `\\?\` extended-length form, which lifts the 260-character limit
+/// and also works for `\\server\share` UNC paths; results keep showing
+/// `path` as given.
+#[cfg(windows)]
+pub fn for_opening(path: &Path) -> Cow<'_, Path> {
+    let absolute = match std::path::absolute(path) {
+        Ok(absolute) => absolute,
+        Err(_) => return Cow::Borrowed(path),
+    };
+    match absolute.to_str().and_then(extended_length) {
+        Some(extended) => Cow::Owned(std::path::PathBuf::from(extended)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn for_opening(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// The `\\?\` form of an absolute Windows path, `None` when it already is
+/// one or is not absolute. Windows does not normalize such paths, so `/`
+/// separators and `.` and `..` components are resolved here.
+#[cfg(any(windows, test))]
+fn extended_length(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', r"\");
+    let (prefix, rest, root_components) = if let Some(unc) = path.strip_prefix(r"\\") {
+        // `..` cannot climb above `\\server\share`.
+        (r"\\?\UNC\", unc, 2)
+    } else if path.as_bytes().get(1) == Some(&b':') && path.as_bytes().get(2) == Some(&b'\\') && path.as_bytes()[0].is_ascii_alphabetic() {
+        (r"\\?\", path.as_str(), 1)
+    } else {
+        return None;
+    };
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." if components.len() > root_components => {
+                components.pop();
+            }
+            ".." => {}
+            component => components.push(component),
+        }
+    }
+    if components.len() < root_components {
+        return None;
+    }
+    Some(format!("{}{}", prefix, components.join(r"\")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_length() {
+        assert_eq!(extended_length(r"C:\logs\2024\..\app.log").as_deref(), Some(r"\\?\C:\logs\app.log"));
+        assert_eq!(extended_length("d:/src/./main.rs").as_deref(), Some(r"\\?\d:\src\main.rs"));
+        assert_eq!(extended_length(r"\\server\share\deep\..\..\x.txt").as_deref(), Some(r"\\?\UNC\server\share\x.txt"));
+        assert_eq!(extended_length(r"\\?\C:\already"), None);
+        assert_eq!(extended_length(r"relative\path"), None);
+        assert_eq!(extended_length(r"\\server"), None);
+    }
+
+    #[test]
+    fn test_for_opening_keeps_paths_elsewhere() {
+        if cfg!(not(windows)) {
+            assert_eq!(for_opening(Path::new("a/b.txt")), Path::new("a/b.txt"));
+        }
+    }
+}
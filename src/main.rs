@@ -1,97 +1,1266 @@
-use clap::{Parser, ArgGroup};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ArgGroup};
+use clap::parser::ValueSource;
+use std::borrow::Cow;
 use std::fs;
-use std::io::{self, Read, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
 use colored::*;
-use encoding_rs::{Encoding, WINDOWS_1252};
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
 
-use ignore::WalkBuilder;
+use ignore::{WalkBuilder, WalkState};
+use ignore::overrides::OverrideBuilder;
 use regex::{Regex, RegexBuilder};
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
 
-#[derive(Parser, Debug)]
+mod archive;
+mod baseline;
+mod bench;
+mod budget;
+mod cache;
+mod chunks;
+mod capabilities;
+mod check;
+mod cluster;
+mod compare;
+mod completions;
+mod config;
+mod corpus;
+mod dedupe;
+mod decompress;
+mod engine;
+mod error;
+mod events;
+mod explain;
+mod extract;
+mod filter;
+mod filters;
+mod git;
+mod github;
+mod history;
+mod hook;
+mod hyperlink;
+mod ignores;
+mod index;
+mod json;
+mod lines;
+mod links;
+mod longpath;
+mod man;
+mod manifest;
+mod output;
+mod pager;
+mod patterns;
+mod pii;
+mod prefilter;
+mod preprocess;
+mod presets;
+mod progress;
+mod queue;
+mod report;
+mod resources;
+mod rules;
+mod sarif;
+mod secrets;
+mod signals;
+mod serve;
+mod similar;
+mod stacktrace;
+mod stats;
+mod template;
+mod theme;
+mod suggest;
+mod timestamp;
+mod todos;
+#[cfg(feature = "tui")]
+mod tui;
+mod types;
+mod unused;
+mod watch;
+mod window;
+mod word;
+mod xref;
+
+use bench::Stage;
+use engine::{CaseMode, Engine, Matcher};
+use error::FinderError;
+use filters::FileFilter;
+use github::AnnotationLevel;
+use lines::LineSelection;
+use hyperlink::HyperlinkFormat;
+use template::Template;
+use output::{GroupBy, OutputFormat, OutputOptions, Printer, Sink};
+use pager::PagerMode;
+use theme::Theme;
+use patterns::PatternSpec;
+use pii::ValidatedMatcher;
+use presets::Preset;
+use rules::{Ruleset, Severity};
+use stats::{RootKind, SearchStats, StatFormat};
+use timestamp::TimestampFormat;
+use word::{WordChars, WordMatcher};
+
+#[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about = None,
-    override_usage = "finder [OPTIONS] <PATHS>... -p <PATTERN>\n       finder [OPTIONS] <PATHS>... -f <FILE>")]
+    override_usage = "finder [OPTIONS] <PATHS>... -p <PATTERN>\n       finder [OPTIONS] <PATHS>... -f <FILE>\n       finder [OPTIONS] <PATHS>... --files\n       finder --from-manifest <FILE>\n       finder <COMMAND>",
+    args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 #[command(group(
     ArgGroup::new("pattern_source")
-        .required(true)
-        .args(["pattern", "input_file"]),
+        .args(["input_file", "preset", "rules"]),
 ))]
 struct Args {
-    /// The string to search for (mutually exclusive with -f)
-    #[arg(short = 'p', long)]
-    pattern: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// A file containing patterns to search for, one per line (mutually exclusive with -p)
+    /// The string to search for (can be repeated, and added to the patterns of -f)
+    #[arg(short = 'p', long, short_alias = 'e', alias = "regexp", conflicts_with_all = ["preset", "rules"], required_unless_present_any = ["input_file", "preset", "rules", "profile", "type_list", "files", "from_manifest", "capabilities", "help_long"])]
+    pattern: Vec<String>,
+
+    /// A file containing patterns to search for, one per line, optionally named as `name: regex`
     #[arg(short = 'f', long = "input-file")]
     input_file: Option<PathBuf>,
 
+    /// Search with a built-in ruleset instead of -p or -f: `licenses`, `pii` or `pii:<us|uk|fr|de>`
+    #[arg(long, value_name = "PRESET", value_parser = Preset::parse)]
+    preset: Option<Preset>,
+
+    /// Search with a versioned built-in ruleset with severities instead of -p or -f: `secrets`, or `secrets@<version>` to pin its version
+    #[arg(long, value_name = "RULESET", value_parser = Ruleset::parse)]
+    rules: Option<Ruleset>,
+
+    /// Take the pattern file, globs, file types and format of a profile of .finder.toml or of the user configuration; the command line adds to its globs and types and overrides the rest
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
     /// The path(s) to search in (files or directories)
-    #[arg(required = true)]
+    #[arg(required_unless_present_any = ["type_list", "from_manifest", "capabilities", "help_long"])]
     paths: Vec<PathBuf>,
 
     /// Show statistics about the search
     #[arg(short, long)]
     stat: bool,
 
+    /// Only print the paths of files with at least one match
+    #[arg(short = 'l', long)]
+    files_with_matches: bool,
+
+    /// Follow each path with a NUL byte instead of `:` (or of a newline with -l), for `xargs -0`
+    #[arg(short = '0', long, alias = "print0")]
+    null: bool,
+
+    /// List the files that would be searched, with the same ignore rules and filters, instead of searching them
+    #[arg(long, conflicts_with_all = ["pattern", "input_file", "preset", "rules", "match_path", "files_with_matches", "cluster", "format", "tui", "watch"])]
+    files: bool,
+
+    /// Match the patterns against the path of each file instead of its contents, and list the matching files
+    #[arg(long, conflicts_with_all = ["files_with_matches", "cluster", "format", "tui", "watch"])]
+    match_path: bool,
+
+    /// Group similar matched lines and print one representative per group with its count
+    #[arg(long, conflicts_with_all = ["files_with_matches", "tui", "watch"])]
+    cluster: bool,
+
+    /// Write the results as the begin, match, end and summary JSON events of `rg --json`, for the tools built to read them
+    #[arg(long, conflicts_with_all = ["files_with_matches", "cluster", "format", "template", "group_by", "tui", "watch"])]
+    json_lines: bool,
+
+    /// Group the results under a header per pattern, with its number of matches and files
+    #[arg(long, value_enum, value_name = "GROUP", conflicts_with_all = ["files_with_matches", "cluster", "format", "template", "tui", "watch"])]
+    group_by: Option<GroupBy>,
+
+    /// Print the line number of each match (the default)
+    #[arg(short = 'n', long, overrides_with = "no_line_number")]
+    line_number: bool,
+
+    /// Leave the line numbers out, printing `path:rule:text`
+    #[arg(short = 'N', long, overrides_with = "line_number")]
+    no_line_number: bool,
+
+    /// Print the byte offset in the file of each matching line instead of its line number
+    #[arg(short = 'b', long, conflicts_with = "no_line_number")]
+    byte_offset: bool,
+
+    /// Omit matched lines longer than N characters, e.g. minified files, printing the number of matches instead
+    #[arg(long, value_name = "N")]
+    max_columns: Option<usize>,
+
+    /// With --max-columns, print the first N characters of long lines and the number of matches past them
+    #[arg(long, requires = "max_columns")]
+    max_columns_preview: bool,
+
+    /// Change the style of the paths, line numbers, patterns or matches, as TYPE:fg:COLOR, TYPE:bg:COLOR, TYPE:style:STYLE or TYPE:none, e.g. `path:fg:blue` or `match:bg:yellow`
+    #[arg(long, value_name = "SPEC", value_parser = theme::parse_spec)]
+    colors: Vec<String>,
+
+    /// Highlight no more than N matches of each line, in a color per pattern; 0 highlights them all
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    max_matches_per_line: usize,
+
+    /// Format of the results: `path:line:pattern:line` text, JSON Lines, a SARIF 2.1.0 log for code scanning, GitHub Actions annotations, Vim quickfix and Emacs compilation-mode lines, or an HTML or XML report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, conflicts_with_all = ["files_with_matches", "cluster", "tui", "watch"])]
+    format: OutputFormat,
+
+    /// Write each result as TEMPLATE, e.g. '{path}\t{line_number}\t{text}', instead of a --format
+    #[arg(long, value_name = "TEMPLATE", value_parser = Template::parse, conflicts_with_all = ["format", "files_with_matches", "cluster", "tui", "watch"])]
+    template: Option<Template>,
+
+    /// Severity of the annotations of --format github
+    #[arg(long, value_enum, default_value_t = AnnotationLevel::Warning)]
+    annotation_level: AnnotationLevel,
+
+    /// Browse the results in an interactive terminal UI (requires the `tui` feature)
+    #[arg(long, conflicts_with = "output")]
+    tui: bool,
+
+    /// After the search, keep watching the paths and print new matches as files change
+    #[arg(long, conflicts_with_all = ["output", "tui"])]
+    watch: bool,
+
+    /// Print the matches of each file as soon as it is searched, flushing every line, rather than all of them once the search is over
+    #[arg(long, conflicts_with_all = ["output", "format", "template", "json_lines", "cluster", "group_by", "sort", "parse_timestamp", "baseline", "dedupe", "byte_offset", "tui", "dry_run"])]
+    line_buffered: bool,
+
+    /// Show the results through $PAGER, or `less -R` without it: `auto` when they do not fit in the terminal, `always` whenever the output is a terminal
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = PagerMode::Never, num_args = 0..=1, require_equals = true, default_missing_value = "auto", conflicts_with_all = ["line_buffered", "watch", "tui"])]
+    pager: PagerMode,
+
+    /// Format of the statistics: human-readable text, or a JSON object for scripts
+    #[arg(long, value_enum, default_value_t = StatFormat::Text, requires = "stat")]
+    stat_format: StatFormat,
+
+    /// Exit with status 1 if anything matched, e.g. to forbid patterns in CI
+    #[arg(long, conflicts_with_all = ["fail_if_missing", "watch"])]
+    fail_if_found: bool,
+
+    /// Exit with status 1 if a pattern of severity LEVEL or above matched; patterns without a severity are warn
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with_all = ["fail_if_found", "fail_if_missing", "watch"])]
+    fail_on: Option<Severity>,
+
+    /// Exit with status 1 if nothing matched
+    #[arg(long, conflicts_with = "watch")]
+    fail_if_missing: bool,
+
+    /// Follow symbolic links, skipping those that lead back to a directory already searched
+    #[arg(short = 'L', long)]
+    follow: bool,
+
+    /// Stop at the first file or directory that cannot be read instead of listing them after the results
+    #[arg(long)]
+    strict: bool,
+
+    /// Do not report missing or unreadable files and directories
+    #[arg(long)]
+    no_messages: bool,
+
+    /// Search the files under several of the given paths once for each, instead of once in all
+    #[arg(long)]
+    allow_duplicates: bool,
+
+    /// What to do with files that change while they are searched, such as live logs
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = ChangedFilePolicy::Keep)]
+    changed_file_policy: ChangedFilePolicy,
+
+    /// Rewrite FILE every second with the progress of the search as JSON (files done and total, throughput, ETA)
+    #[arg(long, value_name = "FILE")]
+    progress_file: Option<PathBuf>,
+
+    /// Print one final `matched=N files=M errors=K elapsed_ms=T` line to stderr, whatever the output format
+    #[arg(long)]
+    summary_line: bool,
+
+    /// Print statistics to stdout instead of stderr
+    #[arg(long, requires = "stat")]
+    stats_to_stdout: bool,
+
+    /// Break the statistics down by file extension
+    #[arg(long, requires = "stat")]
+    by_extension: bool,
+
+    /// Number of threads to walk and search with (default: one per CPU, up to 12)
+    #[arg(short = 'j', long, value_name = "N", default_value_t = 0, hide_default_value = true)]
+    threads: usize,
+
+    /// Number of threads that only list directories, handing the files over to the --threads search threads (default: the search threads walk as well)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    walk_threads: Option<u16>,
+
+    /// Search as usual but discard the results, and report the throughput and the time spent walking, reading, decoding, matching and printing
+    #[arg(long, alias = "stats-only", conflicts_with_all = ["output", "files", "match_path", "tui", "watch", "fail_if_found", "fail_if_missing", "fail_on"])]
+    dry_run: bool,
+
+    /// Record the command line, patterns and environment of this run in a TOML file, to replay it with --from-manifest
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Replay a run recorded with --manifest
+    #[arg(long, value_name = "FILE", exclusive = true)]
+    from_manifest: Option<PathBuf>,
+
+    /// The command line as given, for --manifest.
+    #[arg(skip)]
+    command_line: Vec<String>,
+
+    /// The hash of the patterns recorded in the replayed manifest.
+    #[arg(skip)]
+    expected_patterns_hash: Option<String>,
+
+    /// Leave out the matches recorded in FILE by `finder baseline update`, to only report new ones
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["files", "match_path", "watch"])]
+    baseline: Option<PathBuf>,
+
+    /// Write the matches into the --baseline file instead of leaving them out, for `finder baseline update`.
+    #[arg(skip)]
+    update_baseline: bool,
+
+    /// After the search, suggest excluding the directories and extensions that took long without matching
+    #[arg(long, conflicts_with = "watch")]
+    suggest: bool,
+
     /// Case-insensitive search
     #[arg(short, long)]
     ignore_case: bool,
 
-    /// Output results to a file instead of stdout
-    #[arg(short, long, value_name = "FILE")]
-    output: Option<PathBuf>,
+    /// Case-insensitive search unless a pattern contains an uppercase letter (decided per pattern; -i wins)
+    #[arg(short = 'S', long)]
+    smart_case: bool,
+
+    /// Make \w, \d, \s, \b and the case folding of -i and -S ASCII-only
+    #[arg(long, overrides_with = "unicode")]
+    no_unicode: bool,
+
+    /// Unicode-aware classes and case folding, the default (cancels an earlier --no-unicode)
+    #[arg(long, overrides_with = "no_unicode")]
+    unicode: bool,
+
+    /// Normalize the lines and the patterns to this Unicode form before matching, so that composed and decomposed accents match alike
+    #[arg(long, value_enum, value_name = "FORM", conflicts_with = "use_index")]
+    normalize: Option<Normalization>,
+
+    /// Output results to a file instead of stdout, in a format of its own as FORMAT=FILE, e.g. `json=results.json`, or in that of --format; repeat it to write several
+    #[arg(short, long, value_name = "[FORMAT=]FILE", value_parser = Sink::parse)]
+    output: Vec<Sink>,
+
+    /// With --output, print the results to stdout too, as text, colored on a terminal
+    #[arg(long, requires = "output")]
+    tee: bool,
+
+    /// Mask the matched text in the output, keeping only its punctuation (implied by --preset pii)
+    #[arg(long)]
+    redact: bool,
+
+    /// Make the printed paths clickable (OSC 8 links) when writing to a terminal
+    #[arg(long)]
+    hyperlink: bool,
+
+    /// What the links open, with {path}, {line} and {column} placeholders, or `vscode` or `cursor` (implies --hyperlink)
+    #[arg(long, value_name = "FMT", value_parser = HyperlinkFormat::parse)]
+    hyperlink_format: Option<HyperlinkFormat>,
+
+    /// Only match whole words: a match must not touch a word character on either side
+    #[arg(short = 'w', long)]
+    word_regexp: bool,
+
+    /// Regex character class of word characters for -w (default: Unicode letters and digits)
+    #[arg(long, value_name = "CLASS", requires = "word_regexp")]
+    word_chars: Option<String>,
+
+    /// Allow patterns to match across line boundaries
+    #[arg(short = 'U', long)]
+    multiline: bool,
+
+    /// Match across lines like -U, but through sliding windows of SIZE bytes (e.g. 4K), for PEM blocks and multi-line keys
+    #[arg(long, value_name = "SIZE", value_parser = corpus::parse_size, conflicts_with = "multiline")]
+    window: Option<u64>,
+
+    /// Regex engine to use
+    #[arg(long, value_enum, default_value_t = Engine::Default)]
+    engine: Engine,
+
+    /// Only search lines START-END of each file (e.g. 100-500, 100-, -500)
+    #[arg(long, value_name = "START-END", value_parser = LineSelection::parse_range, conflicts_with_all = ["head", "tail"])]
+    line_range: Option<LineSelection>,
+
+    /// Only search the first N lines of each file
+    #[arg(long, value_name = "N", conflicts_with = "tail")]
+    head: Option<usize>,
+
+    /// Only search the last N lines of each file
+    #[arg(long, value_name = "N")]
+    tail: Option<usize>,
+
+    /// Only report the first match of each file, and stop reading it there
+    #[arg(long, alias = "first-match", conflicts_with = "last_match_only")]
+    first_match_only: bool,
+
+    /// Only report the last match of each file (the whole file is still scanned)
+    #[arg(long)]
+    last_match_only: bool,
+
+    /// Only report lines that match every pattern, instead of any of them
+    #[arg(long, conflicts_with_all = ["multiline", "window"])]
+    all_of: bool,
+
+    /// Only search files that contain every pattern somewhere, not necessarily on the same line
+    #[arg(long)]
+    files_matching_all: bool,
+
+    /// Do not report matched lines that also match REGEX, e.g. known example keys in docs (can be repeated)
+    #[arg(long, value_name = "REGEX")]
+    ignore_match: Vec<String>,
+
+    /// Report the matches that `finder:ignore` and `finder:ignore-next-line` comments silence too
+    #[arg(long)]
+    no_inline_ignores: bool,
+
+    /// Collapse matches on consecutive lines into a single result with a line range
+    #[arg(long, conflicts_with_all = ["multiline", "window"])]
+    merge_ranges: bool,
+
+    /// Report a match inside a Java, Python or Rust stack trace as the whole trace
+    #[arg(long, conflicts_with_all = ["multiline", "window"])]
+    stacktrace: bool,
+
+    /// Sort the results by path or chronologically by timestamp
+    #[arg(long, value_enum)]
+    sort: Option<SortBy>,
+
+    /// Timestamp format used by --sort time: a strftime format like `%d/%m/%Y %H:%M:%S`, or `auto`
+    #[arg(long, value_name = "FORMAT|auto", value_parser = TimestampFormat::parse)]
+    parse_timestamp: Option<TimestampFormat>,
+
+    /// Search inside compressed files (.gz, .bz2, .xz, .zst)
+    #[arg(short = 'z', long)]
+    search_zip: bool,
+
+    /// Search inside .zip and .tar(.gz, .bz2, .xz, .zst) archives
+    #[arg(long)]
+    archives: bool,
+
+    /// Search what COMMAND writes when run on each file, with its path as argument and its content on stdin
+    #[arg(long, value_name = "COMMAND")]
+    pre: Option<PathBuf>,
+
+    /// Only run --pre on the files whose name matches GLOB (can be repeated)
+    #[arg(long, value_name = "GLOB", requires = "pre")]
+    pre_glob: Vec<String>,
+
+    /// Search the text of .docx and .xlsx documents, and of .pdf ones when built with the `pdf` feature
+    #[arg(long)]
+    documents: bool,
+
+    /// Only search files whose name matches GLOB (can be repeated)
+    #[arg(long = "include", value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Only search files of this type, e.g. `rust` (can be repeated; see --type-list)
+    #[arg(short = 't', long = "type", value_name = "TYPE")]
+    file_type: Vec<String>,
+
+    /// Do not search files of this type (can be repeated)
+    #[arg(short = 'T', long = "type-not", value_name = "TYPE")]
+    type_not: Vec<String>,
+
+    /// Define or extend a file type as `name:glob`, e.g. `proto:*.proto` (can be repeated)
+    #[arg(long = "type-add", value_name = "TYPE_SPEC")]
+    type_add: Vec<String>,
+
+    /// Skip files smaller than SIZE (e.g. 512, 10K, 1M)
+    #[arg(long, value_name = "SIZE", value_parser = corpus::parse_size)]
+    min_filesize: Option<u64>,
+
+    /// Skip files larger than SIZE (e.g. 10M, 1G)
+    #[arg(long, value_name = "SIZE", value_parser = corpus::parse_size)]
+    max_filesize: Option<u64>,
+
+    /// Only search files modified at or after DATE (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS, UTC)
+    #[arg(long, value_name = "DATE", value_parser = filters::parse_date)]
+    newer_than: Option<SystemTime>,
+
+    /// Only search files modified before DATE (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS, UTC)
+    #[arg(long, value_name = "DATE", value_parser = filters::parse_date)]
+    older_than: Option<SystemTime>,
+
+    /// Only search files modified within DURATION (e.g. 30m, 12h, 2d, 1w)
+    #[arg(long, value_name = "DURATION", value_parser = filters::parse_duration)]
+    changed_within: Option<Duration>,
+
+    /// Reuse the results of the files that did not change since the last search with the same patterns and options
+    #[arg(long, overrides_with = "no_cache")]
+    cache: bool,
+
+    /// Search every file again, even with --cache
+    #[arg(long, overrides_with = "cache")]
+    no_cache: bool,
+
+    /// Only search the files changed in the working tree or the index of their git repository, and the untracked ones
+    #[arg(long, conflicts_with_all = ["staged", "since", "watch"])]
+    changed: bool,
+
+    /// Only search the files staged in the index of their git repository, e.g. from a pre-commit hook
+    #[arg(long, conflicts_with_all = ["since", "watch"])]
+    staged: bool,
+
+    /// Only search the files changed since the common ancestor of REV (a commit or a branch) and HEAD, uncommitted changes included
+    #[arg(long, value_name = "REV", conflicts_with = "watch")]
+    since: Option<String>,
+
+    /// Skip the files that the `.finder-index` of a searched directory shows cannot match (see `finder index build`)
+    #[arg(long)]
+    use_index: bool,
+
+    /// Keep the files being read and the results held in memory under about SIZE (e.g. 512M), waiting for files to be done and writing results to a temporary file past it
+    #[arg(long, value_name = "SIZE", value_parser = corpus::parse_size)]
+    max_memory: Option<u64>,
+
+    /// Split the files larger than SIZE at line boundaries into chunks of about SIZE, searched in parallel (0 to search files whole)
+    #[arg(long, value_name = "SIZE", value_parser = corpus::parse_size, default_value = "64M")]
+    chunk_size: u64,
+
+    /// Search files with identical contents (e.g. vendored copies) once, and report their matches against every copy
+    #[arg(long)]
+    dedupe: bool,
+
+    /// Only search one of each group of files at least PERCENT similar (e.g. rotated logs), listing the others on stderr
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+    skip_similar: Option<u8>,
+
+    /// List the known file types and their globs, then exit
+    #[arg(long)]
+    type_list: bool,
+
+    /// Print the features, formats and format versions of this build as JSON, then exit
+    #[arg(long, exclusive = true)]
+    capabilities: bool,
+
+    /// Print the whole manual: the help of finder and of its commands, examples and exit statuses (see also `finder man`)
+    #[arg(long, exclusive = true)]
+    help_long: bool,
+
+    // grep compatibility: these flags describe finder's default behavior
+    // (recursive, extended regexes) and are accepted as no-ops.
+    #[arg(short = 'r', long = "recursive", hide = true)]
+    _recursive: bool,
+
+    #[arg(short = 'E', long = "extended-regexp", hide = true)]
+    _extended_regexp: bool,
 }
 
-#[derive(Debug)]
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a reproducible synthetic tree for benchmarks and rule validation
+    GenCorpus(corpus::GenCorpusArgs),
+    /// Run the same rules over two trees and report per-rule and per-path deltas
+    Compare(compare::CompareArgs),
+    /// Find TODO, FIXME, HACK and XXX markers and group them by owner
+    Todos(todos::TodosArgs),
+    /// Find definitions (feature flags, translation keys...) that are never used
+    Unused(unused::UnusedArgs),
+    /// Export where each defined identifier is defined and used, as JSON or CSV
+    Xref(xref::XrefArgs),
+    /// Narrow down results saved with `--format json` by pattern, path or rule
+    Filter(filter::FilterArgs),
+    /// Build a trigram index of a directory for --use-index
+    Index(index::IndexArgs),
+    /// Keep the file list warm and answer JSON search requests on a Unix socket
+    Serve(serve::ServeArgs),
+    /// Check that the patterns of a patterns file compile, and warn about risky ones
+    Check(check::CheckArgs),
+    /// Show how a pattern is compiled, and test it on sample lines given on stdin
+    Explain(explain::ExplainArgs),
+    /// Show how the findings of archived `--format json` runs evolved, per rule and per path
+    Report(report::ReportArgs),
+    /// Write the completion script of a shell: bash, zsh, fish, powershell or elvish
+    Completions(completions::CompletionsArgs),
+    /// Manage the results cached by --cache
+    Cache(cache::CacheArgs),
+    /// Search the lines that the commits of the git history added or removed
+    History(history::HistoryArgs),
+    /// Print the manual of finder as text, or as roff manual pages for `man`
+    Man(man::ManArgs),
+    /// Install or run a pre-commit hook that checks the staged changes against a rules file
+    Hook(hook::HookArgs),
+    /// Record the current matches of a search, for --baseline to only report new ones
+    Baseline(baseline::BaselineArgs),
+}
+
+#[derive(Debug, Clone)]
 struct SearchResult {
     path: PathBuf,
     line_number: usize,
     line: String,
     pattern: String,
+    /// Index of the matching pattern in the list given to the search
+    pattern_index: usize,
+    /// The indexes of the patterns after it that match the line too
+    other_patterns: Vec<usize>,
+    /// Last line of a range of adjacent matches merged by --merge-ranges
+    end_line_number: Option<usize>,
 }
 
-fn search_in_file_streaming(path: &Path, regexes: &[Regex]) -> io::Result<Vec<SearchResult>> {
-    let mut file = fs::File::open(path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+#[cfg(test)]
+impl SearchResult {
+    /// A result of the pattern at `pattern_index` on `line`, the line
+    /// `line_number` of `path`, for the tests to change what they look at.
+    fn new(path: &str, line_number: usize, line: &str, pattern_index: usize) -> SearchResult {
+        SearchResult {
+            path: PathBuf::from(path),
+            line_number,
+            line: line.to_string(),
+            pattern: String::new(),
+            pattern_index,
+            other_patterns: Vec::new(),
+            end_line_number: None,
+        }
+    }
+}
+
+impl SearchResult {
+    /// The indexes of the patterns that match the line, the one it is
+    /// reported against first.
+    fn pattern_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::once(self.pattern_index).chain(self.other_patterns.iter().copied())
+    }
+}
 
+/// The text of `bytes`, which have no BOM or a UTF-8 one: the bytes
+/// themselves when they are valid UTF-8, as most files are, without decoding
+/// nor copying them, and otherwise decoded from `encoding`, Windows-1252 for
+/// the files without a BOM.
+fn decode_utf8_or<'a>(bytes: &'a [u8], encoding: &'static Encoding) -> Cow<'a, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => encoding.decode_without_bom_handling(bytes).0,
+    }
+}
+
+fn decode_bytes(buffer: &[u8]) -> Cow<'_, str> {
     // Optimized encoding detection - only read first 4KB for BOM detection
     let bom_sample_size = std::cmp::min(4096, buffer.len());
     let (encoding, bom_len) = Encoding::for_bom(&buffer[..bom_sample_size]).unwrap_or((WINDOWS_1252, 0));
-    let (decoded_content, _, _) = encoding.decode(&buffer[bom_len..]);
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        return encoding.decode_without_bom_handling(&buffer[bom_len..]).0;
+    }
+    decode_utf8_or(&buffer[bom_len..], encoding)
+}
 
-    let mut results = Vec::new();
-    for (index, line) in decoded_content.lines().enumerate() {
-        for re in regexes {
-            if re.is_match(line) {
-                results.push(SearchResult {
-                    path: path.to_path_buf(),
-                    line_number: index + 1,
-                    line: line.to_string(),
-                    pattern: re.as_str().to_string(),
-                });
-                break;
-            }
+fn read_decoded(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(longpath::for_opening(path))?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    Ok(decode_bytes(&buffer).into_owned())
+}
+
+/// Like `read_decoded`, but a file that is not valid in its encoding is an
+/// error rather than decoded with replacement characters, for files whose
+/// content must be taken exactly, such as patterns.
+fn read_decoded_exactly(path: &Path) -> Result<String, FinderError> {
+    let buffer = fs::read(longpath::for_opening(path)).map_err(|e| FinderError::io(path, e))?;
+    let (encoding, bom_len) = Encoding::for_bom(&buffer).unwrap_or((WINDOWS_1252, 0));
+    let (content, had_errors) = encoding.decode_without_bom_handling(&buffer[bom_len..]);
+    if had_errors {
+        return Err(FinderError::Encoding { path: path.to_path_buf(), encoding: encoding.name() });
+    }
+    Ok(content.into_owned())
+}
+
+#[derive(Debug, Default, Clone)]
+struct SearchOptions {
+    multiline: bool,
+    search_zip: bool,
+    archives: bool,
+    /// Extract the text of documents before matching (`--documents`).
+    documents: bool,
+    /// Searches the output of this command instead of the files it applies
+    /// to (`--pre`).
+    preprocessor: Option<preprocess::Preprocessor>,
+    lines: Option<LineSelection>,
+    per_file: Option<PerFileMatch>,
+    merge_ranges: bool,
+    stacktrace: bool,
+    /// A line must match every pattern (`--all-of`).
+    all_of: bool,
+    /// Only files where every pattern matches somewhere have results
+    /// (`--files-matching-all`).
+    files_matching_all: bool,
+    /// Which patterns, by index, are exclusion patterns (`!regex` in a
+    /// patterns file); empty when there are none.
+    negated: Vec<bool>,
+    /// Matched lines these match are not reported (`--ignore-match`).
+    ignore_matches: Vec<Regex>,
+    /// Leaves out the matches of the rules that `finder:ignore` comments
+    /// name; `None` with `--no-inline-ignores`.
+    inline_ignores: Option<Arc<ignores::InlineIgnores>>,
+    /// Files at least this similar (0 to 1) to another one are not searched
+    /// (`--skip-similar`).
+    skip_similar: Option<f64>,
+    /// Files with the same contents are searched once (`--dedupe`).
+    dedupe: bool,
+    /// Multiline matches are looked for through windows of this many bytes
+    /// (`--window`), which implies `multiline`.
+    window: Option<usize>,
+    /// Rules out files with the trigram indexes of the searched
+    /// directories (`--use-index`).
+    index: Option<Arc<index::IndexFilter>>,
+    /// Stop the walk at the first file or directory that cannot be read
+    /// (`--strict`).
+    strict: bool,
+    /// Where the walker threads add up the time spent in each stage
+    /// (`--dry-run`).
+    timings: Option<Arc<bench::StageTimings>>,
+    /// What to do with the files that change while they are searched
+    /// (`--changed-file-policy`).
+    changed_file_policy: ChangedFilePolicy,
+    /// The lines are normalized to this form before matching (`--normalize`).
+    normalize: Option<Normalization>,
+    /// Where the results of the files that did not change are taken from
+    /// and those of the others are kept (`--cache`).
+    cache: Option<Arc<cache::ResultCache>>,
+    /// What the files being read and the results held in memory may take
+    /// (`--max-memory`).
+    memory: Option<Arc<budget::MemoryBudget>>,
+    /// With `--walk-threads`, how many threads search the files that the
+    /// walker threads find; without, the walker threads search them.
+    searchers: Option<usize>,
+    /// With `--follow`, the roots of the walk, so that the links to the
+    /// directories it goes through anyway are not followed.
+    follow_roots: Option<Arc<[PathBuf]>>,
+    /// How the files larger than a chunk are split and searched in
+    /// parallel (`--chunk-size`); `None` to search them whole.
+    chunking: Option<chunks::Chunking>,
+    /// Finds the lines that may match, so that the others are not run
+    /// through the patterns; `None` when the patterns have no literals to
+    /// look for.
+    prefilter: Option<Arc<prefilter::Prefilter>>,
+}
+
+impl SearchOptions {
+    fn is_negated(&self, pattern_index: usize) -> bool {
+        self.negated.get(pattern_index).copied().unwrap_or(false)
+    }
+
+    /// Whether every pattern that is not an exclusion pattern matches
+    /// somewhere in `content`, line by line unless in multiline mode.
+    fn contains_all<M: Matcher>(&self, regexes: &[M], content: &str) -> bool {
+        regexes.iter().enumerate()
+            .filter(|&(index, _)| !self.is_negated(index))
+            .all(|(_, re)| if self.multiline { re.is_match(content) } else { content.lines().any(|line| re.is_match(line)) })
+    }
+
+    /// The pattern `text` is reported against, if any: the first one that
+    /// matches it, or with --all-of the first one if they all do, unless an
+    /// exclusion pattern matches it too.
+    fn hit<'r, M: Matcher>(&self, regexes: &'r [M], text: &str) -> Option<(usize, &'r M)> {
+        self.hit_after(regexes, None, text)
+    }
+
+    /// Like `hit`, leaving out the patterns that the inline comments of
+    /// `text`, and those of `previous`, the line before it, silence.
+    fn hit_after<'r, M: Matcher>(&self, regexes: &'r [M], previous: Option<&str>, text: &str) -> Option<(usize, &'r M)> {
+        let hit = self.first_hit(regexes, text, |_| true)?;
+        // Most lines do not match, so only those that do look for comments.
+        let Some(inline_ignores) = &self.inline_ignores else { return Some(hit) };
+        match ignores::ignored(previous, text) {
+            ignores::Ignored::Nothing => Some(hit),
+            ignored => self.first_hit(regexes, text, |pattern_index| !inline_ignores.ignores(&ignored, pattern_index)),
         }
     }
+
+    /// The result of `line`, the line `line_number` of `path`, if a pattern
+    /// matches it, with all the patterns that do.
+    fn line_result<M: Matcher>(&self, regexes: &[M], path: &Path, line_number: usize, previous: Option<&str>, line: &str) -> Option<SearchResult> {
+        let (pattern_index, re) = self.hit_after(regexes, previous, line)?;
+        let ignored = self.inline_ignores.as_ref().map(|inline_ignores| (inline_ignores, ignores::ignored(previous, line)));
+        // The patterns before the first matching one do not match.
+        let other_patterns = (pattern_index + 1..regexes.len())
+            .filter(|&index| !self.is_negated(index))
+            .filter(|&index| !ignored.as_ref().is_some_and(|(inline_ignores, ignored)| inline_ignores.ignores(ignored, index)))
+            .filter(|&index| regexes[index].is_match(line))
+            .collect();
+        Some(SearchResult {
+            path: path.to_path_buf(),
+            line_number,
+            line: line.to_string(),
+            pattern: re.as_str().to_string(),
+            pattern_index,
+            other_patterns,
+            end_line_number: None,
+        })
+    }
+
+    /// Whether the inline comments of `text`, and those of `previous`,
+    /// silence the pattern at `pattern_index` on `text`.
+    fn is_ignored(&self, pattern_index: usize, previous: Option<&str>, text: &str) -> bool {
+        self.inline_ignores.as_ref().is_some_and(|inline_ignores| inline_ignores.ignores(&ignores::ignored(previous, text), pattern_index))
+    }
+
+    fn first_hit<'r, M: Matcher>(&self, regexes: &'r [M], text: &str, kept: impl Fn(usize) -> bool) -> Option<(usize, &'r M)> {
+        let mut positive = regexes.iter().enumerate().filter(|&(pattern_index, _)| !self.is_negated(pattern_index) && kept(pattern_index));
+        let hit = if self.all_of {
+            regexes.iter().enumerate()
+                .find(|&(pattern_index, _)| !self.is_negated(pattern_index) && kept(pattern_index))
+                .filter(|_| positive.all(|(_, re)| re.is_match(text)))
+        } else {
+            positive.find(|(_, re)| re.is_match(text))
+        };
+        hit.filter(|_| !self.is_excluded(regexes, text))
+    }
+
+    /// Whether the content searched in `path` is not its raw content, but
+    /// the output of the preprocessor, that of the archive entries, of the
+    /// decompressed stream or the text of the document.
+    fn reads_through(&self, path: &Path) -> bool {
+        self.preprocessor.as_ref().is_some_and(|preprocessor| preprocessor.applies_to(path))
+            || (self.documents && extract::DocumentKind::from_path(path).is_some())
+            || (self.archives && archive::ArchiveKind::from_path(path).is_some())
+            || (self.search_zip && decompress::Compression::from_path(path).is_some())
+    }
+
+    /// Whether files can be read only up to their first matching line:
+    /// nothing past it changes the result.
+    fn stops_at_first_match(&self) -> bool {
+        self.per_file == Some(PerFileMatch::First)
+            && !self.multiline
+            && !self.files_matching_all
+            && !self.stacktrace
+            && !matches!(self.lines, Some(LineSelection::Tail(_)))
+    }
+
+    /// Whether the results of a file can be found a chunk at a time: nothing
+    /// looks past the line being matched.
+    fn splits_files(&self) -> bool {
+        !self.multiline
+            && self.lines.is_none()
+            && !self.stacktrace
+            && !self.files_matching_all
+            && !self.stops_at_first_match()
+    }
+
+    /// Whether an exclusion pattern or an `--ignore-match` regex matches
+    /// `text`.
+    fn is_excluded<M: Matcher>(&self, regexes: &[M], text: &str) -> bool {
+        regexes.iter().enumerate().any(|(index, re)| self.is_negated(index) && re.is_match(text))
+            || self.ignore_matches.iter().any(|re| re.is_match(text))
+    }
+}
+
+fn search_in_file_streaming<M: Matcher>(path: &Path, regexes: &[M], options: &SearchOptions) -> io::Result<Vec<SearchResult>> {
+    if let Some(preprocessor) = &options.preprocessor
+        && preprocessor.applies_to(path)
+    {
+        let output = preprocessor.run(path)?;
+        return search_reader(path, &mut output.as_slice(), regexes, options);
+    }
+
+    if options.documents
+        && let Some(kind) = extract::DocumentKind::from_path(path)
+    {
+        let mut results = Vec::new();
+        for document in extract::extract(path, kind)? {
+            results.extend(search_extracted(&document, regexes, options));
+        }
+        return Ok(results);
+    }
+
+    if options.archives
+        && let Some(kind) = archive::ArchiveKind::from_path(path)
+    {
+        let mut results = Vec::new();
+        archive::for_each_entry(path, kind, |entry_path, reader| {
+            results.extend(search_reader(&entry_path, reader, regexes, options)?);
+            Ok(())
+        })?;
+        return Ok(results);
+    }
+
+    if let Some(chunking) = &options.chunking
+        && options.splits_files()
+        && !options.reads_through(path)
+    {
+        let len = fs::metadata(longpath::for_opening(path))?.len();
+        if len > chunking.size
+            && let Some(results) = chunks::search_chunked(path, len, regexes, options, chunking)?
+        {
+            return Ok(results);
+        }
+    }
+
+    let mut reader: Box<dyn Read> = if options.search_zip {
+        decompress::open_maybe_compressed(path)?
+    } else {
+        Box::new(fs::File::open(longpath::for_opening(path))?)
+    };
+    search_reader(path, &mut reader, regexes, options)
+}
+
+/// Searches the lines extracted from a document, each reported at the
+/// number of its page, paragraph or row.
+fn search_extracted<M: Matcher>(document: &extract::Extracted, regexes: &[M], options: &SearchOptions) -> Vec<SearchResult> {
+    let results = document.lines.iter().enumerate()
+        .filter_map(|(index, (number, line))| {
+            let previous = index.checked_sub(1).map(|previous| document.lines[previous].1.as_str());
+            options.line_result(regexes, &document.path, *number, previous, line)
+        })
+        .collect();
+    keep_per_file(results, options.per_file)
+}
+
+/// Searches the content of `reader`, reporting results against `path`, which
+/// may be a virtual path such as `archive.zip!inner/path.txt`.
+fn search_reader<M: Matcher>(path: &Path, reader: &mut dyn Read, regexes: &[M], options: &SearchOptions) -> io::Result<Vec<SearchResult>> {
+    let started = Instant::now();
+    if options.stops_at_first_match() {
+        let results = search_until_first_match(path, reader, regexes, options);
+        if let Some(timings) = &options.timings {
+            // Reading, decoding and matching are interleaved.
+            timings.add(Stage::Match, started.elapsed());
+        }
+        return results;
+    }
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+    let read = Instant::now();
+    let decoded_content = decode_bytes(&buffer);
+    let decoded_content = match options.normalize {
+        Some(normalization) => normalization.apply(&decoded_content),
+        None => Cow::Borrowed(&*decoded_content),
+    };
+    let decoded = Instant::now();
+    let results = search_decoded(path, &decoded_content, regexes, options);
+    if let Some(timings) = &options.timings {
+        timings.add(Stage::Read, read - started);
+        timings.add(Stage::Decode, decoded - read);
+        timings.add(Stage::Match, decoded.elapsed());
+    }
     Ok(results)
 }
 
-fn compile_regex_with_cache(patterns: &[String], ignore_case: bool) -> Result<Vec<Regex>, regex::Error> {
-    let mut cache: HashMap<(String, bool), Regex> = HashMap::new();
+/// Reads `reader` a chunk at a time, decoding it as `decode_bytes` does,
+/// until a line matches, so that a match at the top of a large file is
+/// found without reading the rest of it.
+fn search_until_first_match<M: Matcher>(path: &Path, reader: &mut dyn Read, regexes: &[M], options: &SearchOptions) -> io::Result<Vec<SearchResult>> {
+    let (first, last) = match options.lines {
+        Some(LineSelection::Range { first, last }) => (first, last),
+        _ => (1, None),
+    };
+    let mut chunk = vec![0; FIRST_MATCH_CHUNK];
+    // The bytes read and not searched yet or, for UTF-16 files, which are
+    // only split into lines once decoded, their text.
+    let mut pending = Vec::new();
+    let mut pending_text = String::new();
+    // Known once the BOM is sniffed.
+    let mut encoding = None;
+    let mut utf16_decoder: Option<Decoder> = None;
+    // Whether the lines so far were valid UTF-8. Once they are not, the
+    // rest is decoded as `decode_bytes` decodes a file that is not; only
+    // the lines before, already searched, are taken as UTF-8.
+    let mut utf8 = true;
+    let mut line_number = 0;
+    // The last line of the text searched so far, for `finder:ignore-next-line`.
+    let mut last_line = String::new();
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let at_end = read == 0;
+        pending.extend_from_slice(&chunk[..read]);
+        let encoding = match encoding {
+            Some(encoding) => encoding,
+            // The longest BOM is three bytes long.
+            None if pending.len() < 3 && !at_end => continue,
+            None => {
+                let (sniffed, bom_len) = Encoding::for_bom(&pending).unwrap_or((WINDOWS_1252, 0));
+                pending.drain(..bom_len);
+                if sniffed == UTF_16LE || sniffed == UTF_16BE {
+                    utf16_decoder = Some(sniffed.new_decoder_without_bom_handling());
+                }
+                *encoding.insert(sniffed)
+            }
+        };
+        // A line is only searched once complete, unless the file is over.
+        let (decoded, complete) = match &mut utf16_decoder {
+            Some(decoder) => {
+                pending_text.reserve(decoder.max_utf8_buffer_length(pending.len()).unwrap_or(pending.len() * 3));
+                let _ = decoder.decode_to_string(&pending, &mut pending_text, at_end);
+                pending.clear();
+                let complete = if at_end { pending_text.len() } else { pending_text.rfind('\n').map_or(0, |end| end + 1) };
+                (Cow::Borrowed(&pending_text[..complete]), complete)
+            }
+            None => {
+                let complete = if at_end { pending.len() } else { memchr::memrchr(b'\n', &pending).map_or(0, |end| end + 1) };
+                let lines = &pending[..complete];
+                let valid = if utf8 { std::str::from_utf8(lines).ok() } else { None };
+                let decoded = match valid {
+                    Some(text) => Cow::Borrowed(text),
+                    None => {
+                        utf8 = false;
+                        encoding.decode_without_bom_handling(lines).0
+                    }
+                };
+                (decoded, complete)
+            }
+        };
+        let text = match options.normalize {
+            Some(normalization) => normalization.apply(&decoded),
+            None => Cow::Borrowed(&*decoded),
+        };
+        let mut previous = (line_number > 0).then_some(last_line.as_str());
+        for line in text.lines() {
+            line_number += 1;
+            if last.is_some_and(|last| line_number > last) {
+                return Ok(Vec::new());
+            }
+            let hit = if line_number < first { None } else { options.line_result(regexes, path, line_number, previous, line) };
+            previous = Some(line);
+            if let Some(result) = hit {
+                return Ok(vec![result]);
+            }
+        }
+        if at_end {
+            return Ok(Vec::new());
+        }
+        last_line = previous.unwrap_or_default().to_string();
+        if utf16_decoder.is_some() {
+            pending_text.drain(..complete);
+        } else {
+            pending.drain(..complete);
+        }
+    }
+}
+
+fn search_decoded<M: Matcher>(path: &Path, decoded_content: &str, regexes: &[M], options: &SearchOptions) -> Vec<SearchResult> {
+    let (decoded_content, skipped_lines) = match options.lines {
+        Some(selection) => selection.select(decoded_content),
+        None => (decoded_content, 0),
+    };
+
+    if options.files_matching_all && !options.contains_all(regexes, decoded_content) {
+        return Vec::new();
+    }
+
+    if options.multiline {
+        let mut results = search_multiline(path, decoded_content, regexes, options);
+        results.retain(|result| !options.is_excluded(regexes, &result.line));
+        for result in &mut results {
+            result.line_number += skipped_lines;
+        }
+        return keep_per_file(results, options.per_file);
+    }
+
+    let lines: Box<dyn Iterator<Item = (usize, Option<&str>, &str)>> = match &options.prefilter {
+        Some(prefilter) => Box::new(prefilter.candidate_lines(decoded_content)),
+        None => Box::new(decoded_content.lines().enumerate().scan(None, |previous, (index, line)| {
+            Some((index, previous.replace(line), line))
+        })),
+    };
+    let mut results = Vec::new();
+    for (index, previous, line) in lines {
+        if options.per_file == Some(PerFileMatch::First) && !results.is_empty() {
+            break;
+        }
+        if let Some(result) = options.line_result(regexes, path, skipped_lines + index + 1, previous, line) {
+            results.push(result);
+        }
+    }
+    let mut results = keep_per_file(results, options.per_file);
+    if options.stacktrace {
+        let lines: Vec<&str> = decoded_content.lines().collect();
+        results = stacktrace::group_traces(results, &lines, skipped_lines + 1);
+    }
+    if options.merge_ranges { merge_ranges(results) } else { results }
+}
+
+/// A Unicode normalization form (`--normalize`).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Normalization {
+    /// Canonical composition: `e` followed by a combining acute accent becomes `é`
+    Nfc,
+}
+
+impl Normalization {
+    fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Normalization::Nfc if is_nfc_quick(text.chars()) == IsNormalized::Yes => Cow::Borrowed(text),
+            Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+        }
+    }
+}
+
+/// How much of a file `--first-match-only` reads at a time.
+const FIRST_MATCH_CHUNK: usize = 64 * 1024;
+
+/// How many times `--changed-file-policy retry` searches a file again.
+const CHANGED_FILE_RETRIES: usize = 3;
+
+/// What to do with a file that changed while it was searched.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ChangedFilePolicy {
+    /// Report its results, marked as possibly inconsistent
+    #[default]
+    Keep,
+    /// Search it again until it stops changing, a few times at most
+    Retry,
+    /// Leave it out and warn about it
+    Skip,
+}
+
+/// Whether `path` is no longer as `before` described it: its size or its
+/// modification time changed.
+fn changed_since(path: &Path, before: &fs::Metadata) -> bool {
+    fs::metadata(longpath::for_opening(path))
+        .is_ok_and(|now| now.len() != before.len() || now.modified().ok() != before.modified().ok())
+}
+
+/// Searches `path`, checking against `before`, its metadata from the walk,
+/// that it did not change in the meantime, and searching it again if
+/// `--changed-file-policy retry` says so. Also tells whether it changed
+/// while it was last searched.
+fn search_unchanged<M: Matcher>(
+    path: &Path,
+    mut before: Option<fs::Metadata>,
+    regexes: &[M],
+    options: &SearchOptions,
+) -> (io::Result<Vec<SearchResult>>, bool) {
+    let mut attempts = 0;
+    loop {
+        let searched = search_in_file_streaming(path, regexes, options);
+        // Archive entries and decompressed streams have nothing to compare.
+        let changed = searched.is_ok() && before.as_ref().is_some_and(|before| changed_since(path, before));
+        if changed && options.changed_file_policy == ChangedFilePolicy::Retry && attempts < CHANGED_FILE_RETRIES {
+            attempts += 1;
+            before = fs::metadata(longpath::for_opening(path)).ok();
+            continue;
+        }
+        return (searched, changed);
+    }
+}
+
+/// Output order of the results.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    /// By path, then line number
+    Path,
+    /// Chronologically, by the timestamp found in each line (see --parse-timestamp)
+    Time,
+}
+
+fn sort_results(mut results: Vec<SearchResult>, sort: Option<SortBy>, timestamp_format: Option<&TimestampFormat>) -> Vec<SearchResult> {
+    let Some(sort) = sort else { return results };
+    results.sort_by(|a, b| a.path.cmp(&b.path).then(a.line_number.cmp(&b.line_number)));
+    if sort == SortBy::Time {
+        // Lines without a timestamp go last, still in path order.
+        let format = timestamp_format.unwrap_or(&TimestampFormat::Auto);
+        results.sort_by_cached_key(|result| {
+            let timestamp = format.extract(&result.line);
+            (timestamp.is_none(), timestamp)
+        });
+    }
+    results
+}
+
+/// Which single match to keep per file, if not all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PerFileMatch {
+    First,
+    Last,
+}
+
+fn keep_per_file(mut results: Vec<SearchResult>, per_file: Option<PerFileMatch>) -> Vec<SearchResult> {
+    match per_file {
+        Some(PerFileMatch::First) => results.truncate(1),
+        Some(PerFileMatch::Last) => {
+            let last = results.pop();
+            results = last.into_iter().collect();
+        }
+        None => {}
+    }
+    results
+}
+
+/// Collapses runs of matches of the same pattern on consecutive lines into a
+/// single result spanning the range, with the lines joined as its snippet.
+fn merge_ranges(results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut merged: Vec<SearchResult> = Vec::with_capacity(results.len());
+    for result in results {
+        if let Some(previous) = merged.last_mut()
+            && previous.pattern_index == result.pattern_index
+            && previous.end_line_number.unwrap_or(previous.line_number) + 1 == result.line_number
+        {
+            previous.line.push('\n');
+            previous.line.push_str(&result.line);
+            previous.end_line_number = Some(result.line_number);
+            continue;
+        }
+        merged.push(result);
+    }
+    merged
+}
+
+/// Runs each regex over the whole decoded file so that matches may span
+/// several lines, then maps the byte offsets back to line numbers.
+fn search_multiline<M: Matcher>(path: &Path, decoded_content: &str, regexes: &[M], options: &SearchOptions) -> Vec<SearchResult> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(decoded_content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+    // (first line, last line, pattern index); the first pattern to hit a line wins
+    let mut hits: Vec<(usize, usize, usize)> = Vec::new();
+    for (pattern_index, re) in regexes.iter().enumerate().filter(|&(pattern_index, _)| !options.is_negated(pattern_index)) {
+        let spans = match options.window {
+            Some(window) => window::window_spans(re, decoded_content, window),
+            None => re.find_spans(decoded_content),
+        };
+        for span in spans {
+            let first = line_of(span.start);
+            let last = if span.end > span.start { line_of(span.end - 1) } else { first };
+            hits.push((first, last, pattern_index));
+        }
+    }
+    let line_at = |index: usize| {
+        let end = line_starts.get(index + 1).copied().unwrap_or(decoded_content.len());
+        decoded_content[line_starts[index]..end].trim_end_matches(['\n', '\r'])
+    };
+    hits.retain(|&(first, _, pattern_index)| !options.is_ignored(pattern_index, first.checked_sub(1).map(line_at), line_at(first)));
+    hits.sort_by_key(|&(first, _, pattern_index)| (first, pattern_index));
+    hits.dedup_by_key(|hit| hit.0);
+
+    hits.into_iter().map(|(first, last, pattern_index)| {
+        let start = line_starts[first];
+        let end = line_starts.get(last + 1).copied().unwrap_or(decoded_content.len());
+        let text = decoded_content[start..end].lines().collect::<Vec<_>>().join("\n");
+        SearchResult {
+            path: path.to_path_buf(),
+            line_number: first + 1,
+            line: text,
+            pattern: regexes[pattern_index].as_str().to_string(),
+            pattern_index,
+            other_patterns: Vec::new(),
+            end_line_number: None,
+        }
+    }).collect()
+}
+
+fn compile_regex_with_cache(patterns: &[String], case: CaseMode, multiline: bool) -> Result<Vec<Regex>, regex::Error> {
+    let mut cache: HashMap<(String, bool, bool), Regex> = HashMap::new();
     patterns.iter().map(|p| {
-        let cache_key = (p.clone(), ignore_case);
+        let ignore_case = case.ignore_case(p);
+        let cache_key = (p.clone(), ignore_case, multiline);
         if let Some(cached_regex) = cache.get(&cache_key) {
             Ok(cached_regex.clone())
         } else {
             let regex = RegexBuilder::new(p)
                 .case_insensitive(ignore_case)
+                .multi_line(multiline)
+                .crlf(multiline)
                 .build()?;
             cache.insert(cache_key, regex.clone());
             Ok(regex)
@@ -99,55 +1268,697 @@ fn compile_regex_with_cache(patterns: &[String], ignore_case: bool) -> Result<Ve
     }).collect()
 }
 
-fn partition_paths(paths: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<PathBuf>) {
-    paths.into_iter().partition(|p| p.exists())
+fn partition_paths(roots: &[(PathBuf, RootKind)]) -> (Vec<PathBuf>, Vec<(PathBuf, RootKind)>) {
+    let (valid, invalid): (Vec<_>, Vec<_>) = roots.iter().cloned().partition(|(_, kind)| kind.is_searchable());
+    (valid.into_iter().map(|(path, _)| path).collect(), invalid)
 }
 
-fn read_lines_from_file(path: &Path) -> io::Result<Vec<String>> {
-    let mut file = fs::File::open(path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+/// The roots to walk, leaving out those that are the same as an earlier
+/// one, and the paths that the walk of a root skips because they are
+/// nested roots of their own: their files are reported once, under the
+/// path they were given as.
+fn distinct_roots(paths: Vec<PathBuf>) -> (Vec<PathBuf>, HashSet<PathBuf>) {
+    let mut roots: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for path in paths {
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !roots.iter().any(|(_, other)| *other == canonical) {
+            roots.push((path, canonical));
+        }
+    }
+    let mut nested = HashSet::new();
+    for (_, canonical) in &roots {
+        for (other, other_canonical) in &roots {
+            if let Ok(relative) = canonical.strip_prefix(other_canonical)
+                && !relative.as_os_str().is_empty()
+            {
+                nested.insert(other.join(relative));
+            }
+        }
+    }
+    (roots.into_iter().map(|(path, _)| path).collect(), nested)
+}
 
-    // Optimized encoding detection - only read first 4KB for BOM detection
-    let bom_sample_size = std::cmp::min(4096, buffer.len());
-    let (encoding, bom_len) = Encoding::for_bom(&buffer[..bom_sample_size]).unwrap_or((WINDOWS_1252, 0));
-    let (decoded_content, _, _) = encoding.decode(&buffer[bom_len..]);
+fn load_patterns(args: &Args) -> Result<Vec<PatternSpec>, FinderError> {
+    if let Some(file_path) = &args.input_file {
+        // Ad-hoc -p patterns come after the rules of the file.
+        let mut pattern_specs = patterns::parse_patterns(&read_decoded_exactly(file_path)?);
+        pattern_specs.extend(args.pattern.iter().map(|pattern| PatternSpec::new(pattern)));
+        Ok(pattern_specs)
+    } else if !args.pattern.is_empty() {
+        Ok(args.pattern.iter().map(|pattern| PatternSpec::new(pattern)).collect())
+    } else if let Some(preset) = args.preset {
+        Ok(preset.patterns())
+    } else if let Some(ruleset) = args.rules {
+        Ok(ruleset.patterns())
+    } else if args.files {
+        Ok(Vec::new())
+    } else {
+        unreachable!("Either a pattern or an input file must be provided.");
+    }
+}
+
+/// What a walker thread found out about one file.
+enum FileOutcome {
+    /// `changed` when the file changed while it was searched, which makes
+    /// its results possibly inconsistent.
+    Searched { path: PathBuf, results: Vec<SearchResult>, bytes: u64, elapsed: Duration, changed: bool },
+    /// Not searched, as `--cache` had its results.
+    Cached { path: PathBuf, results: Vec<SearchResult>, bytes: u64 },
+    /// Left out because it changed while it was searched.
+    Changed(PathBuf),
+    Skipped,
+    /// Ruled out by `--use-index`.
+    Pruned,
+    Failed(FinderError),
+}
+
+/// Everything a walk produced: the searched files, their results, the
+/// counters for `--stat`, the files and directories that could not be read,
+/// the files that changed while they were searched, and the error that
+/// stopped it from printing its results as they came.
+struct WalkOutcome {
+    files: Vec<PathBuf>,
+    results: Vec<SearchResult>,
+    stats: SearchStats,
+    errors: Vec<FinderError>,
+    changed: Vec<PathBuf>,
+    output_error: Option<io::Error>,
+}
+
+/// Prints the results of a file as soon as it is searched
+/// (`--line-buffered`).
+type LiveOutput<'a> = &'a (dyn Fn(&[SearchResult]) -> io::Result<()> + Sync);
+
+/// Walks the tree configured in `walk_builder` and searches every file that
+/// passes `file_filter`.
+fn search_walk<M: Matcher>(
+    walk_builder: &WalkBuilder,
+    regexes: &[M],
+    search_options: &SearchOptions,
+    file_filter: &FileFilter,
+    pb: &ProgressBar,
+) -> WalkOutcome {
+    search_walk_printing(walk_builder, regexes, search_options, file_filter, pb, None)
+}
+
+/// Like `search_walk`, printing the results of each file with `live` as
+/// they come. A failure to print them, such as a closed pipe, stops the
+/// walk.
+fn search_walk_printing<M: Matcher>(
+    walk_builder: &WalkBuilder,
+    regexes: &[M],
+    search_options: &SearchOptions,
+    file_filter: &FileFilter,
+    pb: &ProgressBar,
+    live: Option<LiveOutput>,
+) -> WalkOutcome {
+    // Duplicates can only be told apart once every file is known, so
+    // --dedupe and --skip-similar need a pre-pass over the tree.
+    let mut duplicates = Vec::new();
+    let mut near_duplicates = Vec::new();
+    if search_options.dedupe || search_options.skip_similar.is_some() {
+        let mut files = list_files::<M>(walk_builder, &[], file_filter, search_options);
+        if search_options.dedupe {
+            duplicates = dedupe::find_duplicates(&files);
+            let copies: HashSet<&PathBuf> = duplicates.iter().flat_map(|group| &group.copies).collect();
+            files.retain(|path| !copies.contains(path));
+        }
+        if let Some(threshold) = search_options.skip_similar {
+            near_duplicates = similar::near_duplicates(files, threshold);
+        }
+    }
+    let copies: HashSet<&Path> = duplicates.iter().flat_map(|group| group.copies.iter().map(PathBuf::as_path)).collect();
+    let copies = &copies;
+    let skipped: HashSet<&Path> = near_duplicates.iter().map(|duplicate| duplicate.path.as_path()).collect();
+    let skipped = &skipped;
+
+    // Searches one file the walk found and sends its outcome back; tells
+    // whether reading it failed.
+    let search_file = |path: PathBuf, metadata: Option<fs::Metadata>, searched_at: &mut Instant, sender: &mpsc::Sender<FileOutcome>| {
+        if let Some(index) = &search_options.index
+            && !search_options.reads_through(&path)
+            && !index.may_match(&path, metadata.as_ref())
+        {
+            let _ = sender.send(FileOutcome::Pruned);
+            return false;
+        }
+        if let Some(cache) = &search_options.cache
+            && let Some(metadata) = &metadata
+            && let Some(results) = cache.get(&path, metadata)
+        {
+            pb.inc(1);
+            let _ = sender.send(FileOutcome::Cached { bytes: metadata.len(), path, results });
+            return false;
+        }
+        signals::pause_if_throttled();
+        let started = Instant::now();
+        if let Some(timings) = &search_options.timings {
+            timings.add(Stage::Walk, started - *searched_at);
+        }
+        let bytes = metadata.as_ref().map_or(0, |metadata| metadata.len());
+        let cached_metadata = search_options.cache.as_ref().and(metadata.clone());
+        let reservation = search_options.memory.as_ref().map(|memory| memory.reserve(bytes));
+        // The thread is not left for the chunks of a large file meanwhile.
+        let busy = search_options.chunking.as_ref().map(|chunking| chunking.threads.take(1));
+        let (searched, changed) = search_unchanged(&path, metadata, regexes, search_options);
+        *searched_at = Instant::now();
+        drop(busy);
+        drop(reservation);
+        // The results of a file that changed meanwhile may match
+        // neither its old nor its new contents.
+        if let (Some(cache), Some(metadata), Ok(results)) = (&search_options.cache, &cached_metadata, &searched)
+            && !changed
+        {
+            cache.insert(&path, metadata, results);
+        }
+        let outcome = match searched {
+            Ok(_) if changed && search_options.changed_file_policy == ChangedFilePolicy::Skip => FileOutcome::Changed(path),
+            Ok(results) => FileOutcome::Searched { results, bytes, elapsed: started.elapsed(), path, changed },
+            Err(e) => FileOutcome::Failed(FinderError::io(path, e)),
+        };
+        pb.inc(1);
+        let failed = matches!(outcome, FileOutcome::Failed(_));
+        let _ = sender.send(outcome);
+        failed
+    };
+    let search_file = &search_file;
+
+    // Without --walk-threads, files are searched by the walker threads as
+    // soon as they are found; with it, the walker threads only enumerate
+    // the directories and hand the files over to their own pool of
+    // searchers, so that a slow directory listing does not hold a search
+    // thread. Either way each file's outcome is sent back over a channel,
+    // so traversal and matching overlap. The walker threads queue the files
+    // for the searchers, and the next free searcher takes the next file.
+    let (sender, receiver) = mpsc::channel();
+    let work = search_options.searchers.map(|_| queue::WorkQueue::<(PathBuf, Option<fs::Metadata>)>::new());
+    let work = work.as_ref();
+    let quit = AtomicBool::new(false);
+    let quit = &quit;
+    let visited_links = search_options.follow_roots.as_deref().map(links::VisitedLinks::new);
+    let visited_links = visited_links.as_ref();
+    // The outcomes are gathered as the walk goes, so that SIGUSR1 can tell
+    // how far it got.
+    let mut outcome = thread::scope(|scope| {
+        let collector = scope.spawn(|| collect_outcomes(receiver, regexes.len(), search_options.memory.as_deref(), pb, live, quit));
+        for _ in 0..search_options.searchers.unwrap_or(0) {
+            let (sender, work) = (sender.clone(), work.unwrap());
+            scope.spawn(move || {
+                let mut searched_at = Instant::now();
+                while !quit.load(Ordering::Relaxed) {
+                    let Some((path, metadata)) = work.pop() else { break };
+                    if search_file(path, metadata, &mut searched_at, &sender) && search_options.strict {
+                        quit.store(true, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+        walk_builder.build_parallel().run(|| {
+            let sender = sender.clone();
+            // With --dry-run, the time between two searches of a thread is the
+            // time it spent walking.
+            let mut searched_at = Instant::now();
+            Box::new(move |entry| {
+                if quit.load(Ordering::Relaxed) {
+                    return WalkState::Quit;
+                }
+                let entry = match entry {
+                    Ok(entry) if entry.file_type().is_some_and(|ft| ft.is_file()) => entry,
+                    Ok(entry) => {
+                        let Some(target) = walked_link(visited_links, &entry) else { return WalkState::Continue };
+                        let _ = sender.send(FileOutcome::Failed(FinderError::SymlinkCycle { link: entry.into_path(), target }));
+                        return WalkState::Skip;
+                    }
+                    Err(e) => {
+                        let e = FinderError::from(e);
+                        // A cycle leaves nothing unsearched.
+                        let quit = search_options.strict && !e.is_symlink_cycle();
+                        let _ = sender.send(FileOutcome::Failed(e));
+                        return if quit { WalkState::Quit } else { WalkState::Continue };
+                    }
+                };
+                // Copies get the results of their original after the walk.
+                if copies.contains(entry.path()) {
+                    return WalkState::Continue;
+                }
+                let metadata = entry.metadata().ok();
+                if (!file_filter.is_empty() && !metadata.as_ref().is_some_and(|metadata| file_filter.accepts(metadata)))
+                    || skipped.contains(entry.path())
+                {
+                    let _ = sender.send(FileOutcome::Skipped);
+                    return WalkState::Continue;
+                }
+                if let Some(work) = work {
+                    work.push((entry.into_path(), metadata));
+                    return WalkState::Continue;
+                }
+                let failed = search_file(entry.into_path(), metadata, &mut searched_at, &sender);
+                if failed && search_options.strict { WalkState::Quit } else { WalkState::Continue }
+            })
+        });
+        // The searchers stop once they took every file the walk found.
+        if let Some(work) = work {
+            work.close();
+        }
+        drop(sender);
+        collector.join().unwrap()
+    });
+    if !duplicates.is_empty() {
+        let searched: HashSet<&PathBuf> = outcome.files.iter().collect();
+        let copies: Vec<PathBuf> = duplicates.iter()
+            .filter(|group| searched.contains(&group.original))
+            .flat_map(|group| group.copies.iter().cloned())
+            .collect();
+        outcome.stats.duplicates = copies.len();
+        outcome.files.extend(copies);
+        outcome.results = dedupe::fan_out(outcome.results, &duplicates);
+    }
+    outcome.stats.near_duplicates = near_duplicates;
+    // Walker threads report in no particular order.
+    outcome.errors.sort_by_key(|e| e.to_string());
+    outcome.changed.sort();
+    outcome
+}
+
+/// How often the outcomes collector checks whether SIGUSR1 or SIGUSR2
+/// came in while no file completes.
+const SIGNAL_POLL: Duration = Duration::from_millis(200);
+
+/// Gathers what the walker threads send until they are all done, writing
+/// the status of the search when SIGUSR1 asks for it. With `memory`, the
+/// results are spilled to a temporary file when they would exceed it, and
+/// read back at the end. With `live`, the results of each file are printed
+/// as they come, and the walk is told to `quit` if they cannot be.
+fn collect_outcomes(
+    receiver: mpsc::Receiver<FileOutcome>,
+    pattern_count: usize,
+    memory: Option<&budget::MemoryBudget>,
+    pb: &ProgressBar,
+    live: Option<LiveOutput>,
+    quit: &AtomicBool,
+) -> WalkOutcome {
+    let started = Instant::now();
+    let mut throttled = signals::is_throttled();
+    let mut outcome = WalkOutcome {
+        files: Vec::new(),
+        results: Vec::new(),
+        stats: SearchStats::new(pattern_count),
+        errors: Vec::new(),
+        changed: Vec::new(),
+        output_error: None,
+    };
+    let mut memory = memory;
+    let mut spill: Option<budget::Spill> = None;
+    let mut spilled = 0;
+    // The results at the start of `outcome.results` already counted in
+    // `memory`.
+    let mut counted = 0;
+    // Those already printed by `live`.
+    let mut printed = 0;
+    loop {
+        match receiver.recv_timeout(SIGNAL_POLL) {
+            Ok(FileOutcome::Searched { path, results, bytes, elapsed, changed }) => {
+                outcome.stats.record_file(&path, bytes, elapsed, &results);
+                if changed {
+                    outcome.changed.push(path.clone());
+                }
+                outcome.files.push(path);
+                outcome.results.extend(results);
+            }
+            Ok(FileOutcome::Cached { path, results, bytes }) => {
+                outcome.stats.record_file(&path, bytes, Duration::ZERO, &results);
+                outcome.stats.files_cached += 1;
+                outcome.files.push(path);
+                outcome.results.extend(results);
+            }
+            Ok(FileOutcome::Skipped) => outcome.stats.files_skipped += 1,
+            Ok(FileOutcome::Pruned) => outcome.stats.files_pruned += 1,
+            Ok(FileOutcome::Changed(path)) => {
+                outcome.stats.files_skipped += 1;
+                outcome.changed.push(path);
+            }
+            Ok(FileOutcome::Failed(e)) => {
+                outcome.stats.files_skipped += 1;
+                outcome.stats.read_errors += 1;
+                outcome.errors.push(e);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let Some(live) = live
+            && outcome.output_error.is_none()
+            && outcome.results.len() > printed
+        {
+            if let Err(e) = pb.suspend(|| live(&outcome.results[printed..])) {
+                outcome.output_error = Some(e);
+                quit.store(true, Ordering::Relaxed);
+            }
+            printed = outcome.results.len();
+        }
+        if let Some(budget) = memory
+            && outcome.results.len() > counted
+        {
+            let added = outcome.results[counted..].iter().map(budget::result_size).sum();
+            counted = outcome.results.len();
+            if budget.add_results(added) {
+                let written = match &mut spill {
+                    Some(spill) => spill.write(&outcome.results),
+                    None => budget::Spill::new().and_then(|new| spill.insert(new).write(&outcome.results)),
+                };
+                match written {
+                    Ok(()) => {
+                        spilled += outcome.results.len();
+                        outcome.results.clear();
+                        counted = 0;
+                        printed = 0;
+                    }
+                    Err(e) => {
+                        pb.suspend(|| eprintln!("{} cannot spill the results to disk, keeping them in memory: {}", "warning:".yellow().bold(), e));
+                        memory = None;
+                    }
+                }
+                budget.results_spilled();
+            }
+        }
+        if signals::take_status_request() {
+            let status = signals::status_line(&outcome.stats, spilled + outcome.results.len(), started.elapsed());
+            pb.suspend(|| eprintln!("{} {}", "note:".cyan().bold(), status));
+        }
+        if signals::is_throttled() != throttled {
+            throttled = !throttled;
+            let state = if throttled { "throttled until the next SIGUSR2" } else { "no longer throttled" };
+            pb.suspend(|| eprintln!("{} search {}", "note:".cyan().bold(), state));
+        }
+    }
+    if let Some(spill) = spill {
+        match spill.read_back() {
+            Ok(mut results) => {
+                results.append(&mut outcome.results);
+                outcome.results = results;
+            }
+            Err(e) => eprintln!("{} cannot read back the {} results spilled to disk: {}", "warning:".yellow().bold(), spilled, e),
+        }
+    }
+    outcome
+}
 
-    Ok(decoded_content.lines().map(String::from).collect())
+/// How many paths `warn_unreadable` lists for each reason.
+const UNREADABLE_PATHS_SHOWN: usize = 5;
+
+/// Lists the files and directories a walk could not read, grouped by
+/// reason.
+fn warn_unreadable(errors: &[FinderError]) {
+    let (cycles, errors): (Vec<&FinderError>, Vec<&FinderError>) = errors.iter().partition(|e| e.is_symlink_cycle());
+    if !cycles.is_empty() {
+        eprintln!("{} skipped {} symbolic links to directories already searched:", "note:".cyan().bold(), cycles.len());
+        for cycle in cycles.iter().take(UNREADABLE_PATHS_SHOWN) {
+            eprintln!("    {}", cycle);
+        }
+        if cycles.len() > UNREADABLE_PATHS_SHOWN {
+            eprintln!("    ... and {} more", cycles.len() - UNREADABLE_PATHS_SHOWN);
+        }
+    }
+    if errors.is_empty() {
+        return;
+    }
+    eprintln!("{} {} files or directories could not be read:", "warning:".yellow().bold(), errors.len());
+    for (reason, paths) in error::group_by_reason(errors) {
+        eprintln!("  {} ({}):", reason, paths.len());
+        for path in paths.iter().take(UNREADABLE_PATHS_SHOWN) {
+            eprintln!("    {}", path);
+        }
+        if paths.len() > UNREADABLE_PATHS_SHOWN {
+            eprintln!("    ... and {} more", paths.len() - UNREADABLE_PATHS_SHOWN);
+        }
+    }
 }
 
-fn load_patterns(args: &Args) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    if let Some(pattern) = &args.pattern {
-        Ok(vec![pattern.clone()])
-    } else if let Some(file_path) = &args.input_file {
-        Ok(read_lines_from_file(file_path)?)
+/// Lists the files that changed while they were searched, which were left
+/// out with `skipped`, or else whose results may be inconsistent.
+fn warn_changed(changed: &[PathBuf], skipped: bool) {
+    if changed.is_empty() {
+        return;
+    }
+    let consequence = if skipped { "they were skipped" } else { "their results may be inconsistent" };
+    eprintln!("{} {} files changed while they were searched, {}:", "warning:".yellow().bold(), changed.len(), consequence);
+    for path in changed.iter().take(UNREADABLE_PATHS_SHOWN) {
+        eprintln!("    {}", path.display());
+    }
+    if changed.len() > UNREADABLE_PATHS_SHOWN {
+        eprintln!("    ... and {} more", changed.len() - UNREADABLE_PATHS_SHOWN);
+    }
+}
+
+/// With `--follow`, where `entry` leads when it is a symbolic link to a
+/// directory that the walk goes through already.
+fn walked_link(visited_links: Option<&links::VisitedLinks>, entry: &ignore::DirEntry) -> Option<PathBuf> {
+    let visited_links = visited_links?;
+    if entry.depth() == 0 || !entry.path_is_symlink() || !entry.file_type().is_some_and(|ft| ft.is_dir()) {
+        return None;
+    }
+    visited_links.already_walked(entry.path())
+}
+
+/// The files a search would go through, in path order, for `--files`; with
+/// `--match-path`, only those whose path matches the patterns.
+fn list_files<M: Matcher>(
+    walk_builder: &WalkBuilder,
+    regexes: &[M],
+    file_filter: &FileFilter,
+    search_options: &SearchOptions,
+) -> Vec<PathBuf> {
+    let visited_links = search_options.follow_roots.as_deref().map(links::VisitedLinks::new);
+    let visited_links = visited_links.as_ref();
+    let (sender, receiver) = mpsc::channel();
+    walk_builder.build_parallel().run(|| {
+        let sender = sender.clone();
+        Box::new(move |entry| {
+            if let Ok(entry) = &entry
+                && walked_link(visited_links, entry).is_some()
+            {
+                return WalkState::Skip;
+            }
+            if let Ok(entry) = entry
+                && entry.file_type().is_some_and(|ft| ft.is_file())
+                && (file_filter.is_empty() || entry.metadata().is_ok_and(|metadata| file_filter.accepts(&metadata)))
+                && (regexes.is_empty() || search_options.hit(regexes, &entry.path().to_string_lossy()).is_some())
+            {
+                let _ = sender.send(entry.into_path());
+            }
+            WalkState::Continue
+        })
+    });
+    drop(sender);
+    let mut paths: Vec<PathBuf> = receiver.into_iter().collect();
+    paths.sort();
+    paths
+}
+
+/// How many threads the walk runs with: `--threads`, or by default what
+/// the walker picks.
+fn walk_threads(requested: usize) -> usize {
+    if requested > 0 {
+        requested
     } else {
-        unreachable!("Either a pattern or an input file must be provided.");
+        std::thread::available_parallelism().map_or(1, |n| n.get()).min(12)
+    }
+}
+
+/// The links to put on the printed paths: only asked for, and only on a
+/// terminal, as other readers would see the escape sequences.
+fn hyperlink_format(args: &Args) -> Option<HyperlinkFormat> {
+    if !(args.hyperlink || args.hyperlink_format.is_some()) || !prints_to_stdout(args) || !io::stdout().is_terminal() {
+        return None;
+    }
+    Some(args.hyperlink_format.clone().unwrap_or_default())
+}
+
+/// Whether the results are printed to stdout: without `--output`, or with
+/// `--tee`.
+fn prints_to_stdout(args: &Args) -> bool {
+    args.output.is_empty() || args.tee
+}
+
+/// Creates the file of an `--output` sink.
+fn create_sink(sink: &Sink) -> Result<io::BufWriter<fs::File>, FinderError> {
+    let file = fs::File::create(&sink.path).map_err(|e| FinderError::io(&sink.path, e))?;
+    Ok(io::BufWriter::new(file))
+}
+
+/// The colors of the printed results: those of the `[colors]` sections of
+/// the configuration files, changed by `--colors`. The files are only read
+/// when the results are colored.
+fn color_theme(args: &Args) -> Result<Theme, FinderError> {
+    let mut theme = Theme::default();
+    if !prints_to_stdout(args) || !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return Ok(theme);
     }
+    let dir = std::env::current_dir().map_err(|e| FinderError::io(".", e))?;
+    for spec in config::color_specs(&config::config_files(&dir))?.iter().chain(&args.colors) {
+        theme.apply(spec).map_err(FinderError::Usage)?;
+    }
+    Ok(theme)
 }
 
-fn run_app(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+fn run_app(args: Args) -> Result<(), FinderError> {
     let start_time = Instant::now();
 
-    let patterns = load_patterns(&args)?;
-    let regexes = compile_regex_with_cache(&patterns, args.ignore_case)?;
+    if args.tui && !cfg!(feature = "tui") {
+        return Err("finder was built without TUI support (rebuild with `--features tui`)".into());
+    }
+
+    if args.type_list {
+        write!(io::stdout().lock(), "{}", types::format_type_list(&args.type_add)?)?;
+        return Ok(());
+    }
+
+    if args.capabilities {
+        writeln!(io::stdout().lock(), "{}", capabilities::to_json(&Args::command()))?;
+        return Ok(());
+    }
+
+    if args.help_long {
+        man::write_text(&mut io::stdout().lock())?;
+        return Ok(());
+    }
+
+    let pattern_specs = load_patterns(&args)?;
+    if let Some(expected) = &args.expected_patterns_hash
+        && *expected != manifest::patterns_hash(&pattern_specs)
+    {
+        return Err("the patterns differ from those recorded in the manifest; has the patterns file changed?".into());
+    }
+    if let Some(path) = &args.manifest {
+        let manifest = manifest::Manifest::of_run(args.command_line.clone(), &pattern_specs);
+        fs::write(path, manifest.to_toml()).map_err(|e| FinderError::io(path, e))?;
+    }
+    if !args.files && pattern_specs.iter().all(|spec| spec.negated) {
+        return Err("the patterns file has only exclusion patterns (`!regex`)".into());
+    }
+    let patterns: Vec<String> = pattern_specs.iter()
+        .map(|spec| args.normalize.map_or(Cow::Borrowed(spec.pattern.as_str()), |normalization| normalization.apply(&spec.pattern)).into_owned())
+        .collect();
+    let case = CaseMode::from_flags(args.ignore_case, args.smart_case);
+    let unicode = args.unicode || !args.no_unicode;
+    let multiline = args.multiline || args.window.is_some();
+    let word_chars = match &args.word_chars {
+        Some(class) if args.word_regexp => Some(WordChars::from_class(class)?),
+        None if args.word_regexp => Some(WordChars::Alphanumeric),
+        _ => None,
+    };
+    let regexes: Vec<Box<dyn Matcher>> = match args.engine {
+        Engine::Default if !unicode => engine::compile_ascii(&patterns, case, multiline)?,
+        Engine::Default => compile_regex_with_cache(&patterns, case, multiline)?
+            .into_iter()
+            .map(|re| Box::new(re) as Box<dyn Matcher>)
+            .collect(),
+        // PCRE2 backtracks into the other alternatives by itself once the
+        // boundaries are part of the pattern. A --word-chars class it does
+        // not know leaves the patterns as they are.
+        Engine::Pcre2 => match &word_chars {
+            Some(word_chars) => {
+                let bounded: Vec<String> = patterns.iter().map(|p| word::look_around_pattern(p, word_chars)).collect();
+                engine::compile_pcre2(&bounded, case, multiline, unicode)
+                    .or_else(|_| engine::compile_pcre2(&patterns, case, multiline, unicode))?
+            }
+            None => engine::compile_pcre2(&patterns, case, multiline, unicode)?,
+        },
+    };
+    let regexes = match word_chars {
+        Some(word_chars) => regexes.into_iter().zip(&patterns)
+            .map(|(re, pattern)| {
+                let matcher = WordMatcher::new(re, word_chars.clone());
+                let bounded = (args.engine == Engine::Default)
+                    .then(|| word::compile_bounded(pattern, &word_chars, case.ignore_case(pattern), multiline, unicode))
+                    .flatten();
+                match bounded {
+                    Some(bounded) => Box::new(matcher.with_bounded(bounded)) as Box<dyn Matcher>,
+                    None => Box::new(matcher),
+                }
+            })
+            .collect(),
+        None => regexes,
+    };
+    let regexes: Vec<Box<dyn Matcher>> = match args.preset {
+        Some(preset) => regexes.into_iter().zip(&pattern_specs)
+            .map(|(re, spec)| match preset.validator(spec) {
+                Some(validate) => Box::new(ValidatedMatcher::new(re, validate)) as Box<dyn Matcher>,
+                None => re,
+            })
+            .collect(),
+        None => regexes,
+    };
+    let regexes: Vec<Box<dyn Matcher>> = match args.rules {
+        Some(ruleset) => regexes.into_iter().zip(&pattern_specs)
+            .map(|(re, spec)| match ruleset.rule(spec) {
+                Some(rule) => Box::new(ValidatedMatcher::new(re, |text: &str| rule.accepts(text))) as Box<dyn Matcher>,
+                None => re,
+            })
+            .collect(),
+        None => regexes,
+    };
 
-    let (valid_paths, invalid_paths) = partition_paths(args.paths);
+    let roots: Vec<(PathBuf, RootKind)> = args.paths.iter().map(|path| (path.clone(), RootKind::of(path))).collect();
+    let (valid_paths, invalid_paths) = partition_paths(&roots);
 
-    for path in &invalid_paths {
+    for (path, kind) in invalid_paths.iter().filter(|_| !args.no_messages) {
         eprintln!(
-            "{}: {}: No such file or directory",
+            "{}: {}: {}",
             "error".red().bold(),
-            path.display()
+            path.display(),
+            if *kind == RootKind::Missing { "No such file or directory" } else { "Permission denied" }
+        );
+    }
+    if !invalid_paths.is_empty() && !valid_paths.is_empty() && !args.no_messages {
+        eprintln!(
+            "{} {} of {} paths skipped, searching the others",
+            "warning:".yellow().bold(),
+            invalid_paths.len(),
+            roots.len()
         );
     }
 
     if valid_paths.is_empty() {
         eprintln!("{}", "No valid paths provided. Exiting.".yellow());
+        if args.summary_line {
+            eprintln!("{}", stats::summary_line(0, 0, invalid_paths.len(), start_time.elapsed()));
+        }
         if invalid_paths.is_empty() { return Ok(()); }
         std::process::exit(1);
     }
 
+    let git_scope = if args.changed {
+        Some(git::GitScope::Changed)
+    } else if args.staged {
+        Some(git::GitScope::Staged)
+    } else {
+        args.since.clone().map(git::GitScope::Since)
+    };
+    let scoped_files = git_scope.map(|scope| git::ScopedFiles::find(&valid_paths, &scope)).transpose().map_err(FinderError::Usage)?.map(Arc::new);
+    // The walker does not filter the paths it starts from.
+    let valid_paths: Vec<PathBuf> = valid_paths.into_iter()
+        .filter(|path| scoped_files.as_ref().is_none_or(|scoped| path.is_dir() || scoped.keeps(path, false)))
+        .collect();
+    if valid_paths.is_empty() {
+        eprintln!("No files to search in the provided paths.");
+        if args.fail_if_missing {
+            eprintln!("{} no match found (--fail-if-missing)", "error:".red().bold());
+        }
+        if args.summary_line {
+            eprintln!("{}", stats::summary_line(0, 0, invalid_paths.len(), start_time.elapsed()));
+        }
+        if args.fail_if_missing {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let (valid_paths, nested_roots) = if args.allow_duplicates {
+        (valid_paths, HashSet::new())
+    } else {
+        distinct_roots(valid_paths)
+    };
+
     let mut walk_builder = WalkBuilder::new(&valid_paths[0]);
     if valid_paths.len() > 1 {
         for path in &valid_paths[1..] {
@@ -155,104 +1966,586 @@ fn run_app(args: Args) -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    let files_to_search: Vec<PathBuf> = walk_builder.build()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()))
-        .map(|e| e.into_path())
-        .collect();
+    walk_builder.threads(args.walk_threads.map_or(args.threads, usize::from));
+    walk_builder.follow_links(args.follow);
+    // The walker takes a single filter, which does not apply to the roots.
+    if scoped_files.is_some() || !nested_roots.is_empty() {
+        walk_builder.filter_entry(move |entry| {
+            !nested_roots.contains(entry.path())
+                && scoped_files.as_ref().is_none_or(|scoped| scoped.keeps(entry.path(), entry.file_type().is_some_and(|ft| ft.is_dir())))
+        });
+    }
+
+    let overrides = if args.include.is_empty() {
+        None
+    } else {
+        let mut overrides = OverrideBuilder::new(".");
+        for glob in &args.include {
+            overrides.add(glob)?;
+        }
+        Some(overrides.build()?)
+    };
+    if let Some(overrides) = &overrides {
+        walk_builder.overrides(overrides.clone());
+    }
+
+    let file_types = if args.file_type.is_empty() && args.type_not.is_empty() {
+        None
+    } else {
+        Some(types::build_types(&args.type_add, &args.file_type, &args.type_not)?)
+    };
+    if let Some(file_types) = &file_types {
+        walk_builder.types(file_types.clone());
+    }
+
+    let file_filter = FileFilter {
+        min_size: args.min_filesize,
+        max_size: args.max_filesize,
+        newer_than: args.newer_than.max(args.changed_within.and_then(|within| SystemTime::now().checked_sub(within))),
+        older_than: args.older_than,
+    };
 
-    if files_to_search.is_empty() {
-        println!("No files to search in the provided paths.");
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::default_spinner()
+        .template("{spinner:.green} [{elapsed_precise}] {pos} files searched ({per_sec})").unwrap());
+
+    let index_filter = if args.use_index {
+        let queries = pattern_specs.iter()
+            .filter(|spec| !spec.negated)
+            .map(|spec| index::Query::of(&spec.pattern, case.ignore_case(&spec.pattern)))
+            .collect();
+        let index_filter = index::IndexFilter::load(&valid_paths, queries)?;
+        if index_filter.is_empty() {
+            eprintln!("{} no {} in the searched directories, run `finder index build <DIR>` first", "warning:".yellow().bold(), index::INDEX_FILE_NAME);
+        }
+        Some(Arc::new(index_filter))
+    } else {
+        None
+    };
+
+    let result_cache = if args.cache {
+        let dir = cache::cache_dir().ok_or("no cache directory for --cache: set FINDER_CACHE_DIR")?;
+        Some(Arc::new(cache::ResultCache::open(&dir, &cache_signature(&args, &pattern_specs))))
+    } else {
+        None
+    };
+
+    let search_options = SearchOptions {
+        multiline: args.multiline || args.window.is_some(),
+        search_zip: args.search_zip,
+        archives: args.archives,
+        documents: args.documents,
+        preprocessor: args.pre.clone().map(|command| preprocess::Preprocessor::new(command, &args.pre_glob)).transpose()?,
+        lines: args.line_range
+            .or(args.head.map(LineSelection::head))
+            .or(args.tail.map(LineSelection::Tail))
+            .or(args.preset.and_then(Preset::default_lines)),
+        per_file: if args.first_match_only {
+            Some(PerFileMatch::First)
+        } else if args.last_match_only {
+            Some(PerFileMatch::Last)
+        } else {
+            None
+        },
+        merge_ranges: args.merge_ranges,
+        stacktrace: args.stacktrace,
+        all_of: args.all_of,
+        files_matching_all: args.files_matching_all,
+        negated: pattern_specs.iter().map(|spec| spec.negated).collect(),
+        ignore_matches: compile_regex_with_cache(&args.ignore_match, case, false)?,
+        inline_ignores: (!args.no_inline_ignores).then(|| Arc::new(ignores::InlineIgnores::new(&pattern_specs))),
+        skip_similar: args.skip_similar.map(|percent| f64::from(percent) / 100.0),
+        dedupe: args.dedupe,
+        window: args.window.map(|size| size as usize),
+        index: index_filter.clone(),
+        strict: args.strict,
+        changed_file_policy: args.changed_file_policy,
+        normalize: args.normalize,
+        timings: args.dry_run.then(Arc::default),
+        cache: result_cache.clone(),
+        memory: args.max_memory.map(|limit| Arc::new(budget::MemoryBudget::new(limit))),
+        searchers: args.walk_threads.map(|_| walk_threads(args.threads)),
+        follow_roots: args.follow.then(|| valid_paths.clone().into()),
+        prefilter: (args.engine == Engine::Default && !multiline)
+            .then(|| prefilter::Prefilter::new(&patterns, &pattern_specs.iter().map(|spec| spec.negated).collect::<Vec<_>>(), case))
+            .flatten()
+            .map(Arc::new),
+        chunking: (args.chunk_size > 0).then(|| chunks::Chunking { size: args.chunk_size, threads: Arc::new(chunks::ThreadBudget::new(walk_threads(args.threads))) }),
+    };
+
+    if args.files || args.match_path {
+        let paths = list_files(&walk_builder, &regexes, &file_filter, &search_options);
+        let options = OutputOptions { null: args.null, ..Default::default() };
+        for sink in &args.output {
+            let printer = Printer { pattern_specs: &pattern_specs, matchers: &regexes, options: options.clone() };
+            let mut output_file = create_sink(sink)?;
+            printer.write_paths(&mut output_file, &paths)?;
+            output_file.flush()?;
+        }
+        if prints_to_stdout(&args) {
+            let options = OutputOptions { color: true, hyperlink: hyperlink_format(&args), theme: color_theme(&args)?, ..options };
+            let printer = Printer { pattern_specs: &pattern_specs, matchers: &regexes, options };
+            let mut stdout = io::BufWriter::new(io::stdout().lock());
+            printer.write_paths(&mut stdout, &paths)?;
+            stdout.flush()?;
+        }
         return Ok(());
     }
 
-    let pb = Arc::new(Mutex::new(ProgressBar::new(files_to_search.len() as u64)));
+    // What depends on every result, the changed files and the byte
+    // offsets, is only known once the search is over.
+    let mut output_options = OutputOptions {
+        color: true,
+        files_with_matches: args.files_with_matches,
+        null: args.null,
+        // Secrets are not to be copied into logs either.
+        redact: args.redact || args.preset.is_some_and(Preset::redacts) || args.rules.is_some(),
+        hyperlink: hyperlink_format(&args),
+        changed: HashSet::new(),
+        max_columns: args.max_columns,
+        max_columns_preview: args.max_columns_preview,
+        no_line_number: args.no_line_number,
+        byte_offsets: None,
+        max_matches_per_line: (args.max_matches_per_line > 0).then_some(args.max_matches_per_line),
+        theme: color_theme(&args)?,
+    };
+    let live_printer = Printer { pattern_specs: &pattern_specs, matchers: &regexes, options: output_options.clone() };
+    let print_live = |results: &[SearchResult]| -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        // The results come a file at a time: with -l, its path is printed once.
+        if args.files_with_matches {
+            live_printer.write_results(&mut stdout, results)?;
+            return stdout.flush();
+        }
+        for result in results {
+            live_printer.write_results(&mut stdout, std::slice::from_ref(result))?;
+            stdout.flush()?;
+        }
+        Ok(())
+    };
+    signals::install();
+    let progress_file = args.progress_file.clone()
+        .map(|path| progress::ProgressFile::start(path, &walk_builder, file_filter, pb.clone()));
+    let walk_started = Instant::now();
+    let live: Option<LiveOutput> = if args.line_buffered { Some(&print_live) } else { None };
+    let WalkOutcome { files: files_searched, results, mut stats, errors: mut read_errors, changed, output_error } =
+        search_walk_printing(&walk_builder, &regexes, &search_options, &file_filter, &pb, live);
+    let search_time = walk_started.elapsed();
+    pb.finish_with_message("Search complete");
+    if let Some(e) = output_error {
+        return Err(e.into());
+    }
+    if let Some(progress_file) = progress_file {
+        progress_file.finish()?;
+    }
+    if let Some(cache) = &result_cache
+        && let Err(e) = cache.save()
+    {
+        eprintln!("{} could not save the cache: {}", "warning:".yellow().bold(), e);
+    }
+    if args.strict
+        && let Some(position) = read_errors.iter().position(|e| !e.is_symlink_cycle())
+    {
+        return Err(read_errors.swap_remove(position));
+    }
+
+    stats.record_roots(&roots, &files_searched, &results);
+    for duplicate in &stats.near_duplicates {
+        eprintln!(
+            "{} skipped {}: {:.0}% similar to {}",
+            "note:".cyan().bold(),
+            duplicate.path.display(),
+            duplicate.similarity * 100.0,
+            duplicate.of.display()
+        );
+    }
+    if let Some(index_filter) = &index_filter
+        && !index_filter.is_empty()
+        && index_filter.stale() > 0
     {
-        let pb_guard = pb.lock().unwrap();
-        pb_guard.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%)").unwrap()
-            .progress_chars("#>-"));
+        eprintln!(
+            "{} {} files are not in the index or changed since it was built; run `finder index build` again to speed them up",
+            "note:".cyan().bold(),
+            index_filter.stale()
+        );
+    }
+    let errors = invalid_paths.len() + stats.read_errors;
+    // An empty search still writes its empty reports and goes through
+    // --fail-if-missing.
+    // Files that could not be read are listed after the results instead.
+    let unreadable = read_errors.iter().filter(|e| !e.is_symlink_cycle()).count();
+    if files_searched.is_empty() && unreadable == 0 && !args.watch {
+        eprintln!("No files to search in the provided paths.");
     }
 
-    let regexes = Arc::new(regexes);
-    let output_results = Arc::new(Mutex::new(Vec::new()));
+    // A timestamp format on its own means the user wants a chronological stream.
+    let sort = args.sort.or(args.parse_timestamp.as_ref().map(|_| SortBy::Time));
+    let results = sort_results(results, sort, args.parse_timestamp.as_ref());
 
-    files_to_search.par_iter().for_each(|path| {
-        {
-            let pb_guard = pb.lock().unwrap();
-            pb_guard.inc(1);
-        }
-        
-        match search_in_file_streaming(path, &regexes) {
-            Ok(search_results) => {
-                if !search_results.is_empty() {
-                    let mut output_guard = output_results.lock().unwrap();
-                    output_guard.extend(search_results);
-                }
-            },
-            Err(e) => {
-                eprintln!("{} Failed to read file {}: {}", "error:".red().bold(), path.display(), e);
+    let results = match &args.baseline {
+        Some(path) if args.update_baseline => {
+            let baseline = baseline::Baseline::of(&pattern_specs, &results);
+            fs::write(path, baseline.to_json()).map_err(|e| FinderError::io(path, e))?;
+            eprintln!("Recorded {} matches in {}", baseline.findings.len(), path.display());
+            return Ok(());
+        }
+        Some(path) => {
+            if !path.exists() {
+                return Err(FinderError::Usage(format!("no baseline {}: write it with `finder baseline update {} <PATHS>...`", path.display(), path.display())));
+            }
+            let baseline = baseline::Baseline::parse(&read_decoded_exactly(path)?)
+                .map_err(|e| FinderError::Usage(format!("{}: {}", path.display(), e)))?;
+            let (results, stale) = baseline.suppress(&pattern_specs, results);
+            if stale > 0 && !args.no_messages {
+                eprintln!(
+                    "{} {} matches of {} are gone; run `finder baseline update` to drop them",
+                    "note:".cyan().bold(),
+                    stale,
+                    path.display()
+                );
             }
+            results
         }
-    });
+        None => results,
+    };
 
-    {
-        let pb_guard = pb.lock().unwrap();
-        pb_guard.finish_with_message("Search complete");
+    #[cfg(feature = "tui")]
+    if args.tui {
+        tui::browse(&results, &pattern_specs)?;
+        return Ok(());
     }
 
-    let results = Arc::try_unwrap(output_results).unwrap().into_inner().unwrap();
-
-    if let Some(output_path) = &args.output {
-        let mut output_file = fs::File::create(output_path)?;
-        for result in &results {
-            // In file output, we don't colorize, just output the raw data.
-            writeln!(
-                output_file,
-                "{}:{}:{}:{}",
-                result.path.display(),
-                result.line_number,
-                result.pattern,
-                result.line.trim()
-            )?;
+    // The lines of decompressed, extracted or preprocessed contents have no
+    // offset in the file.
+    let line_offsets = if args.byte_offset || args.json_lines {
+        output::line_offsets(results.iter().filter(|result| !search_options.reads_through(&result.path)))
+    } else {
+        HashMap::new()
+    };
+    if args.changed_file_policy != ChangedFilePolicy::Skip {
+        output_options.changed = changed.iter().cloned().collect();
+    }
+    output_options.byte_offsets = args.byte_offset.then(|| line_offsets.clone());
+    // In file output, we don't colorize, just output the raw data.
+    let file_printer = Printer {
+        pattern_specs: &pattern_specs,
+        matchers: &regexes,
+        options: OutputOptions { color: false, hyperlink: None, theme: Theme::default(), ..output_options.clone() },
+    };
+    let printer = Printer { pattern_specs: &pattern_specs, matchers: &regexes, options: output_options };
+    let clusters = args.cluster.then(|| cluster::cluster_lines(&results));
+    // A sink in a format of its own leaves --template and --json-lines to
+    // the others.
+    let write_report = |out: &mut dyn Write, printer: &Printer<_>, format: Option<OutputFormat>| {
+        if format.is_none() {
+            if let Some(template) = &args.template {
+                return template.write_results(out, printer, &results);
+            }
+            if args.json_lines {
+                return events::write_events(out, printer, &results, &stats, &line_offsets, start_time.elapsed());
+            }
         }
+        match format.unwrap_or(args.format) {
+            OutputFormat::Sarif => return sarif::write_sarif(out, &pattern_specs, &regexes, &results, args.rules),
+            OutputFormat::Github => return github::write_annotations(out, &pattern_specs, &regexes, &results, args.annotation_level),
+            OutputFormat::Json => return printer.write_json(out, &results),
+            OutputFormat::Quickfix => return printer.write_quickfix(out, &results),
+            OutputFormat::Emacs => return printer.write_emacs(out, &results),
+            OutputFormat::Html => return report::write_html(out, printer, &results, files_searched.len()),
+            OutputFormat::Xml => return report::write_xml(out, printer, &results, files_searched.len()),
+            OutputFormat::Text => {}
+        }
+        match (&clusters, args.group_by) {
+            (Some(clusters), _) => printer.write_clusters(out, &results, clusters)?,
+            (None, Some(GroupBy::Pattern)) => printer.write_pattern_groups(out, &results)?,
+            // Printed as they were found.
+            (None, None) if args.line_buffered => {}
+            (None, None) => printer.write_results(out, &results)?,
+        }
+        if args.preset == Some(Preset::Licenses) {
+            presets::write_license_summary(out, &pattern_specs, &results, files_searched.len())?;
+        }
+        Ok::<_, io::Error>(())
+    };
+    if let Some(timings) = &search_options.timings {
+        let printed = Instant::now();
+        write_report(&mut io::sink(), &printer, None)?;
+        let dry_run = bench::DryRun {
+            files: stats.files_searched,
+            bytes: stats.bytes_scanned,
+            matches: results.len(),
+            threads: walk_threads(args.threads),
+            search_time,
+            print_time: printed.elapsed(),
+            stages: timings,
+        };
+        dry_run.write(&mut io::stdout().lock())?;
     } else {
-        for result in &results {
-            let re = RegexBuilder::new(&result.pattern)
-                .case_insensitive(args.ignore_case)
-                .build()?;
-            let highlighted_line = re.replace_all(&result.line, |caps: &regex::Captures| {
-                caps[0].red().bold().to_string()
-            });
-            println!(
-                "{}:{}:{}:{}",
-                result.path.display().to_string().green(),
-                result.line_number.to_string().yellow(),
-                result.pattern.magenta(),
-                highlighted_line.trim()
-            );
+        for sink in &args.output {
+            let mut output_file = create_sink(sink)?;
+            write_report(&mut output_file, &file_printer, sink.format)?;
+            output_file.flush()?;
+        }
+        if prints_to_stdout(&args) {
+            // Next to files, the terminal gets the results as text.
+            let format = args.tee.then_some(OutputFormat::Text);
+            if args.pager != PagerMode::Never {
+                let mut output = Vec::new();
+                write_report(&mut output, &printer, format)?;
+                pager::show(args.pager, &output)?;
+            } else {
+                let mut stdout = io::BufWriter::new(io::stdout().lock());
+                write_report(&mut stdout, &printer, format)?;
+                stdout.flush()?;
+            }
         }
     }
 
+    let elapsed = start_time.elapsed();
+    let total_matches = results.len();
+    let files_with_matches: HashSet<_> = results.iter().map(|r| r.path.clone()).collect();
     if args.stat {
-        let elapsed = start_time.elapsed();
-        let total_matches = results.len();
-        let files_with_matches: HashSet<_> = results.iter().map(|r| r.path.clone()).collect();
+        stats.resources = resources::ResourceUsage::current(start_time.elapsed());
+        stats.by_extension = args.by_extension;
+        // Statistics are diagnostics: keep them off stdout unless asked, so
+        // piped result streams stay clean.
+        let mut stats_out: Box<dyn Write> = if args.stats_to_stdout {
+            Box::new(io::stdout())
+        } else {
+            Box::new(io::stderr())
+        };
+        match args.stat_format {
+            StatFormat::Text => {
+                writeln!(stats_out, "\n--- Statistics ---")?;
+                writeln!(stats_out, "Total matches found: {}", total_matches)?;
+                writeln!(stats_out, "Files with matches: {}", files_with_matches.len())?;
+                writeln!(stats_out, "Time elapsed: {:?}", elapsed)?;
+                stats.write_details(&mut stats_out, &pattern_specs)?;
+            }
+            StatFormat::Json => stats.write_json(&mut stats_out, &pattern_specs, total_matches, files_with_matches.len(), elapsed)?,
+        }
+    }
 
-        println!("
---- Statistics ---");
-        println!("Total matches found: {}", total_matches);
-        println!("Files with matches: {}", files_with_matches.len());
-        println!("Time elapsed: {:?}", elapsed);
+    let failure = if files_searched.is_empty() && unreadable > 0 {
+        Some(format!("none of the {} files or directories could be read", unreadable))
+    } else if args.fail_if_found && !results.is_empty() {
+        Some(format!("{} matches found (--fail-if-found)", results.len()))
+    } else if args.fail_if_missing && results.is_empty() {
+        Some("no match found (--fail-if-missing)".to_string())
+    } else if let Some(level) = args.fail_on {
+        let failing = results.iter()
+            .filter(|result| result.pattern_indexes().any(|pattern_index| pattern_specs[pattern_index].level() >= level))
+            .count();
+        (failing > 0).then(|| format!("{} matches of severity {} or above (--fail-on {})", failing, level.as_str(), level.as_str()))
+    } else {
+        None
+    };
+    if args.suggest {
+        let suggestions = suggest::suggest_exclusions(stats.timings(), &valid_paths);
+        if suggestions.is_empty() {
+            eprintln!("{} no exclusion to suggest", "note:".cyan().bold());
+        }
+        for suggestion in suggestions {
+            eprintln!(
+                "{} {} took {:.0}% of the search time and produced no match; consider `--include '{}'`",
+                "note:".cyan().bold(),
+                suggestion.subject,
+                suggestion.share * 100.0,
+                suggestion.glob
+            );
+        }
+    }
+    // Listed after the results rather than in the middle of them.
+    if !args.no_messages {
+        warn_unreadable(&read_errors);
+        warn_changed(&changed, args.changed_file_policy == ChangedFilePolicy::Skip);
+    }
+    if let Some(failure) = &failure {
+        eprintln!("{} {}", "error:".red().bold(), failure);
+    }
+    // Printed last, so that scripts can read it with `tail -n 1`.
+    if args.summary_line {
+        eprintln!("{}", stats::summary_line(total_matches, files_with_matches.len(), errors, elapsed));
+    }
+    // process::exit skips destructors: make sure the results are out first.
+    io::stdout().flush()?;
+    if failure.is_some() {
+        std::process::exit(1);
+    }
+
+    if args.watch {
+        let mut seen = watch::SeenMatches::default();
+        seen.record(&files_searched, &results);
+        let filter = watch::WatchFilter::new(&valid_paths, overrides, file_types);
+        let mut indexes = index::IndexUpdater::load(&valid_paths)?;
+        eprintln!("{}", "Watching for changes (Ctrl-C to stop)...".yellow());
+        watch::watch_files(&valid_paths, |paths| {
+            for path in paths.iter().filter(|path| filter.accepts(path)) {
+                indexes.update(path);
+                if !path.is_file() {
+                    continue;
+                }
+                match search_in_file_streaming(path, &regexes, &search_options) {
+                    Ok(file_results) => {
+                        let fresh = seen.fresh(path, file_results);
+                        let mut stdout = io::stdout().lock();
+                        if let Err(e) = printer.write_results(&mut stdout, &fresh).and_then(|_| stdout.flush()) {
+                            eprintln!("{} Failed to write results: {}", "error:".red().bold(), e);
+                        }
+                    }
+                    Err(_) if args.no_messages => {}
+                    Err(e) => eprintln!("{} {}", "error:".red().bold(), FinderError::io(path, e)),
+                }
+            }
+            if let Err(e) = indexes.checkpoint() {
+                eprintln!("{} Failed to update the index: {}", "error:".red().bold(), e);
+            }
+        })?;
     }
 
     Ok(())
 }
 
+/// The signature of the `--cache` of a search: everything besides the
+/// contents of a file that its results depend on. The validation of the
+/// matches of `--preset` and `--rules` comes with finder itself, and may
+/// change with its version.
+fn cache_signature(args: &Args, pattern_specs: &[PatternSpec]) -> String {
+    let options = format!("{:?}", (
+        (args.ignore_case, args.smart_case, args.no_unicode, args.unicode, args.normalize, args.engine, args.word_regexp, &args.word_chars),
+        (args.multiline, args.window, args.line_range, args.head, args.tail, args.first_match_only, args.last_match_only),
+        (args.all_of, args.files_matching_all, &args.ignore_match, args.no_inline_ignores, args.merge_ranges, args.stacktrace),
+        (args.search_zip, args.archives, args.documents, &args.pre, &args.pre_glob),
+        (args.preset, args.rules),
+    ));
+    cache::signature(&[env!("CARGO_PKG_VERSION"), &manifest::patterns_hash(pattern_specs), &options])
+}
+
+/// Runs the search recorded in the manifest at `path` again, from the
+/// directory it ran in.
+fn replay(path: &Path) -> Result<(), FinderError> {
+    let manifest = manifest::Manifest::parse(&read_decoded_exactly(path)?)
+        .map_err(|e| FinderError::Usage(format!("{}: {}", path.display(), e)))?;
+    if manifest.version != env!("CARGO_PKG_VERSION") {
+        eprintln!("{} recorded with finder {}, replaying with {}", "warning:".yellow().bold(), manifest.version, env!("CARGO_PKG_VERSION"));
+    }
+    if manifest.os != std::env::consts::OS || manifest.arch != std::env::consts::ARCH {
+        eprintln!("{} recorded on {}/{}, replaying on {}/{}", "warning:".yellow().bold(), manifest.os, manifest.arch, std::env::consts::OS, std::env::consts::ARCH);
+    }
+    if std::env::current_dir().ok().as_ref() != Some(&manifest.cwd) {
+        std::env::set_current_dir(&manifest.cwd).map_err(|e| FinderError::io(&manifest.cwd, e))?;
+        eprintln!("{} replaying in {}", "note:".cyan().bold(), manifest.cwd.display());
+    }
+    let mut args = Args::try_parse_from(std::iter::once("finder".to_string()).chain(manifest.args.iter().cloned()))
+        .map_err(|e| FinderError::Usage(format!("cannot replay {}: {}", path.display(), e)))?;
+    args.command_line = manifest.args;
+    args.expected_patterns_hash = Some(manifest.patterns_hash);
+    run_app(args)
+}
+
+/// `argv` with the options of `profile` before those of the command line,
+/// and without `--profile`. The globs and types of the profile add to those
+/// of the command line; its other options are left out when the command line
+/// gives them or an option they cannot be used with.
+fn with_profile(argv: Vec<OsString>, profile: &config::Profile) -> Vec<OsString> {
+    let mut command = Args::command();
+    command.build();
+    let matches = command.clone().get_matches_from(&argv);
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let kept = |id: &str| {
+        let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else { return false };
+        if given(id) && !matches!(arg.get_action(), ArgAction::Append) {
+            return false;
+        }
+        // The arguments of a group that allows a single one of them conflict too.
+        let exclusive_group = |other: &clap::Id| command.get_groups().any(|group| {
+            !group.clone().is_multiple() && group.get_args().any(|member| member == id) && group.get_args().any(|member| member == other)
+        });
+        !command.get_arguments().filter(|other| given(other.get_id().as_str())).any(|other| {
+            command.get_arg_conflicts_with(arg).iter().any(|conflict| conflict.get_id() == other.get_id())
+                || command.get_arg_conflicts_with(other).iter().any(|conflict| conflict.get_id() == id)
+                || exclusive_group(other.get_id())
+        })
+    };
+    let mut argv = argv.into_iter();
+    let mut expanded: Vec<OsString> = argv.next().into_iter().collect();
+    expanded.extend(profile.options().into_iter().filter(|(id, _)| kept(id)).map(|(_, option)| OsString::from(option)));
+    expanded.extend(config::without_profile(argv));
+    expanded
+}
+
+/// Runs with the options of the profile `name`.
+fn run_profile(name: &str) -> Result<(), FinderError> {
+    let dir = std::env::current_dir().map_err(|e| FinderError::io(".", e))?;
+    let profile = config::find_profile(name, &config::config_files(&dir))?;
+    let argv = with_profile(std::env::args_os().collect(), &profile);
+    let mut args = Args::try_parse_from(&argv)
+        .map_err(|e| FinderError::Usage(format!("cannot apply profile `{}`: {}", name, e)))?;
+    // A manifest records what the profile expanded to, so that replaying it
+    // does not depend on the configuration.
+    args.command_line = manifest::replayable_args(argv.into_iter().skip(1));
+    run_app(args)
+}
+
+/// Runs the search of `finder baseline update`, writing its matches into
+/// the baseline file rather than printing them.
+fn update_baseline(baseline_args: baseline::BaselineArgs) -> Result<(), FinderError> {
+    let baseline::BaselineCommand::Update { file, search } = baseline_args.command;
+    // Given as --baseline, the file is checked against the options of the
+    // search like any other.
+    let argv: Vec<OsString> = std::iter::once(OsString::from("finder"))
+        .chain(search)
+        .chain([OsString::from("--baseline"), file.into_os_string()])
+        .collect();
+    let mut args = Args::try_parse_from(&argv)
+        .map_err(|e| FinderError::Usage(format!("invalid search for the baseline: {}", e)))?;
+    args.command_line = manifest::replayable_args(argv.into_iter().skip(1));
+    args.update_baseline = true;
+    run_app(args)
+}
+
+/// Whether `e` comes from writing to a pipe that was closed.
+fn is_broken_pipe(e: &(dyn std::error::Error + 'static)) -> bool {
+    std::iter::successors(Some(e), |e| e.source())
+        .any(|e| e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe))
+}
+
 fn main() {
-    let args = Args::parse();
-    if let Err(e) = run_app(args) {
-        eprintln!("{} Application error: {}", "error:".red().bold(), e);
+    let mut args = Args::parse();
+    args.command_line = manifest::replayable_args(std::env::args_os().skip(1));
+    let outcome = match args.command.take() {
+        Some(Command::GenCorpus(corpus_args)) => corpus::run(corpus_args),
+        Some(Command::Compare(compare_args)) => compare::run(compare_args),
+        Some(Command::Todos(todos_args)) => todos::run(todos_args),
+        Some(Command::Unused(unused_args)) => unused::run(unused_args),
+        Some(Command::Xref(xref_args)) => xref::run(xref_args),
+        Some(Command::Filter(filter_args)) => filter::run(filter_args),
+        Some(Command::Index(index_args)) => index::run(index_args),
+        Some(Command::Serve(serve_args)) => serve::run(serve_args),
+        Some(Command::Check(check_args)) => check::run(check_args),
+        Some(Command::Explain(explain_args)) => explain::run(explain_args),
+        Some(Command::Report(report_args)) => report::run(report_args),
+        Some(Command::Cache(cache_args)) => cache::run(cache_args),
+        Some(Command::Completions(completions_args)) => completions::run(completions_args),
+        Some(Command::History(history_args)) => history::run(history_args),
+        Some(Command::Hook(hook_args)) => hook::run(hook_args),
+        Some(Command::Man(man_args)) => man::run(man_args),
+        Some(Command::Baseline(baseline_args)) => update_baseline(baseline_args),
+        None => match args.from_manifest.take() {
+            Some(path) => replay(&path),
+            None => match args.profile.take() {
+                Some(name) => run_profile(&name),
+                None => run_app(args),
+            },
+        },
+    };
+    if let Err(e) = outcome {
+        // The reader of the output went away, as `head` does once it has
+        // enough: there is nothing left to do.
+        if is_broken_pipe(&e) {
+            std::process::exit(0);
+        }
+        eprintln!("{} {}", "error:".red().bold(), e);
         std::process::exit(1);
     }
 }
@@ -261,6 +2554,7 @@ fn main() {
 mod tests {
     use super::*;
     use std::io::Write;
+    use std::sync::Mutex;
     use encoding_rs::WINDOWS_1252;
     use tempfile::tempdir;
 
@@ -275,7 +2569,7 @@ mod tests {
         let test_file_path = test_dir.path().join("test_found.txt");
         create_test_file(&test_file_path, "hello world\nfind me here\nanother line");
         let re = vec![Regex::new("find me").unwrap()];
-        let results = search_in_file_streaming(&test_file_path, &re).unwrap();
+        let results = search_in_file_streaming(&test_file_path, &re, &SearchOptions::default()).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].line_number, 2);
         assert_eq!(results[0].line, "find me here");
@@ -289,7 +2583,7 @@ mod tests {
         let test_file_path = test_dir.path().join("test_not_found.txt");
         create_test_file(&test_file_path, "hello world\nanother line");
         let re = vec![Regex::new("missing").unwrap()];
-        let results = search_in_file_streaming(&test_file_path, &re).unwrap();
+        let results = search_in_file_streaming(&test_file_path, &re, &SearchOptions::default()).unwrap();
         assert!(results.is_empty());
         test_dir.close().unwrap();
     }
@@ -300,13 +2594,64 @@ mod tests {
         let test_file_path = test_dir.path().join("test_multiple.txt");
         create_test_file(&test_file_path, "match one\nsome line\nmatch two");
         let re = vec![Regex::new("match").unwrap()];
-        let results = search_in_file_streaming(&test_file_path, &re).unwrap();
+        let results = search_in_file_streaming(&test_file_path, &re, &SearchOptions::default()).unwrap();
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].line_number, 1);
         assert_eq!(results[1].line_number, 3);
         test_dir.close().unwrap();
     }
 
+    #[test]
+    fn test_with_profile() {
+        let profile = config::Profile {
+            input_file: Some(PathBuf::from("rules.txt")),
+            exclude: vec!["vendor/".to_string()],
+            types: vec!["py".to_string()],
+            format: Some("json".to_string()),
+            ..config::Profile::default()
+        };
+        let expand = |argv: &[&str]| with_profile(argv.iter().map(OsString::from).collect(), &profile);
+        let expanded = expand(&["finder", "--profile", "secrets", "src"]);
+        assert_eq!(expanded, ["finder", "--input-file=rules.txt", "--include=!vendor/", "--type=py", "--format=json", "src"]);
+        let args = Args::try_parse_from(&expanded).unwrap();
+        assert!(args.profile.is_none() && args.format == OutputFormat::Json);
+        // The command line overrides the format, and adds to the types.
+        assert_eq!(
+            expand(&["finder", "src", "--profile=secrets", "--format", "sarif", "-t", "rust"]),
+            ["finder", "--input-file=rules.txt", "--include=!vendor/", "--type=py", "src", "--format", "sarif", "-t", "rust"]
+        );
+        // Neither goes with --preset and -l.
+        let expanded = expand(&["finder", "src", "--profile", "secrets", "--preset", "licenses", "-l"]);
+        assert_eq!(expanded, ["finder", "--include=!vendor/", "--type=py", "src", "--preset", "licenses", "-l"]);
+        assert!(Args::try_parse_from(&expanded).is_ok());
+    }
+
+    #[test]
+    fn test_distinct_roots() {
+        let test_dir = tempdir().unwrap();
+        let repo = test_dir.path().join("repo");
+        fs::create_dir_all(repo.join("src/.hidden")).unwrap();
+        create_test_file(&repo.join("src/lib.rs"), "match");
+        create_test_file(&repo.join("src/.hidden/notes.txt"), "match");
+        create_test_file(&repo.join("README.md"), "match");
+
+        let paths = vec![repo.clone(), repo.join("src/../src/.hidden"), repo.join("src/lib.rs"), repo.join("./")];
+        let (roots, nested) = distinct_roots(paths.clone());
+        assert_eq!(roots, paths[..3]);
+        assert_eq!(nested, HashSet::from([repo.join("src/.hidden"), repo.join("src/lib.rs")]));
+
+        // The hidden directory is searched, as it was asked for, and the
+        // file once, under the path it was given as.
+        let mut walk_builder = WalkBuilder::new(&roots[0]);
+        for root in &roots[1..] {
+            walk_builder.add(root);
+        }
+        walk_builder.filter_entry(move |entry| !nested.contains(entry.path()));
+        let files = list_files::<Regex>(&walk_builder, &[], &FileFilter::default(), &SearchOptions::default());
+        assert_eq!(files, [repo.join("README.md"), repo.join("src/../src/.hidden/notes.txt"), repo.join("src/lib.rs")]);
+        test_dir.close().unwrap();
+    }
+
     #[test]
     fn test_partition_paths() {
         let test_dir = tempdir().unwrap();
@@ -314,13 +2659,15 @@ mod tests {
         create_test_file(&valid_file_path, "content");
         let invalid_path = test_dir.path().join("non_existent_file.txt");
 
-        let paths = vec![valid_file_path.clone(), invalid_path.clone()];
-        let (valid, invalid) = partition_paths(paths);
+        let roots: Vec<(PathBuf, RootKind)> = [&valid_file_path, &invalid_path].iter()
+            .map(|path| (path.to_path_buf(), RootKind::of(path)))
+            .collect();
+        let (valid, invalid) = partition_paths(&roots);
 
         assert_eq!(valid.len(), 1);
         assert_eq!(valid[0], valid_file_path);
         assert_eq!(invalid.len(), 1);
-        assert_eq!(invalid[0], invalid_path);
+        assert_eq!(invalid[0], (invalid_path, RootKind::Missing));
 
         test_dir.close().unwrap();
     }
@@ -334,20 +2681,48 @@ mod tests {
         file.write_all(&encoded_content).unwrap();
 
         let re = vec![Regex::new("Héllö").unwrap()];
-        let results = search_in_file_streaming(&test_file_path, &re).unwrap();
+        let results = search_in_file_streaming(&test_file_path, &re, &SearchOptions::default()).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].line, "Héllö Wörld");
 
         test_dir.close().unwrap();
     }
 
+    #[test]
+    fn test_search_in_file_utf8_encoding() {
+        let test_dir = tempdir().unwrap();
+        let utf8_path = test_dir.path().join("utf8.txt");
+        create_test_file(&utf8_path, "Héllö\nWörld");
+        let mixed_path = test_dir.path().join("mixed.txt");
+        let mut mixed = "Wörld\n".as_bytes().to_vec();
+        mixed.extend_from_slice(&WINDOWS_1252.encode("Héllö").0);
+        fs::write(&mixed_path, mixed).unwrap();
+        let with_bom_path = test_dir.path().join("bom.txt");
+        fs::write(&with_bom_path, [&b"\xEF\xBB\xBF"[..], "x\nWörld".as_bytes()].concat()).unwrap();
+
+        let re = vec![Regex::new("Wörld|Héllö").unwrap()];
+        let found = |path: &Path, options: &SearchOptions| -> Vec<String> {
+            search_in_file_streaming(path, &re, options).unwrap().into_iter().map(|r| r.line).collect()
+        };
+        let first = SearchOptions { per_file: Some(PerFileMatch::First), ..Default::default() };
+        assert_eq!(found(&utf8_path, &SearchOptions::default()), ["Héllö", "Wörld"]);
+        assert_eq!(found(&utf8_path, &first), ["Héllö"]);
+        // A file that is not valid UTF-8 throughout is Windows-1252.
+        assert_eq!(found(&mixed_path, &SearchOptions::default()), ["Héllö"]);
+        assert_eq!(found(&with_bom_path, &SearchOptions::default()), ["Wörld"]);
+        assert_eq!(found(&with_bom_path, &first), ["Wörld"]);
+        assert!(matches!(decode_bytes(b"plain"), Cow::Borrowed("plain")));
+
+        test_dir.close().unwrap();
+    }
+
     #[test]
     fn test_search_in_file_case_insensitive() {
         let test_dir = tempdir().unwrap();
         let test_file_path = test_dir.path().join("test_case_insensitive.txt");
         create_test_file(&test_file_path, "Hello hello HeLLo");
         let re = vec![RegexBuilder::new("hello").case_insensitive(true).build().unwrap()];
-        let results = search_in_file_streaming(&test_file_path, &re).unwrap();
+        let results = search_in_file_streaming(&test_file_path, &re, &SearchOptions::default()).unwrap();
         assert_eq!(results.len(), 1);
         test_dir.close().unwrap();
     }
@@ -361,12 +2736,11 @@ mod tests {
         create_test_file(&input_file_path, "Line 1 with pattern\nLine 2\nAnother line with pattern");
 
         let args = Args {
-            pattern: Some("pattern".to_string()),
+            pattern: vec!["pattern".to_string()],
             input_file: None,
             paths: vec![input_file_path.clone()],
-            stat: false,
-            ignore_case: false,
-            output: Some(output_file_path.clone()),
+            output: vec![Sink { format: None, path: output_file_path.clone() }],
+            ..Default::default()
         };
 
         run_app(args).unwrap();
@@ -392,21 +2766,18 @@ mod tests {
         create_test_file(&patterns_file_path, "one\nthird");
 
         let args = Args {
-            pattern: None,
             input_file: Some(patterns_file_path),
             paths: vec![target_file_path.clone()],
-            stat: false,
-            ignore_case: false,
-            output: None,
+            ..Default::default()
         };
 
         // We can't directly test run_app and capture stdout easily without a more complex setup.
         // So we'll test the core logic parts.
         let patterns = load_patterns(&args).unwrap();
-        assert_eq!(patterns, vec!["one", "third"]);
+        assert_eq!(patterns, vec![PatternSpec::new("one"), PatternSpec::new("third")]);
 
-        let regexes: Vec<Regex> = patterns.iter().map(|p| Regex::new(p).unwrap()).collect();
-        let results = search_in_file_streaming(&target_file_path, &regexes).unwrap();
+        let regexes: Vec<Regex> = patterns.iter().map(|p| Regex::new(&p.pattern).unwrap()).collect();
+        let results = search_in_file_streaming(&target_file_path, &regexes, &SearchOptions::default()).unwrap();
 
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].line_number, 1);
@@ -426,10 +2797,715 @@ mod tests {
         let test_file_path = test_dir.path().join("test_crlf.txt");
         create_test_file(&test_file_path, "line one\r\nline two\r\nline three");
         let re = vec![Regex::new("two").unwrap()];
-        let results = search_in_file_streaming(&test_file_path, &re).unwrap();
+        let results = search_in_file_streaming(&test_file_path, &re, &SearchOptions::default()).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].line_number, 2);
         assert_eq!(results[0].line, "line two");
         test_dir.close().unwrap();
     }
+
+    #[test]
+    fn test_search_in_file_multiline() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("test_multiline.rs");
+        create_test_file(&test_file_path, "fn bar() {}\nfn foo() {\n    unimplemented!()\n}\n");
+        let re = vec![RegexBuilder::new(r"fn foo\(\)\s*\{\n\s*unimplemented!").multi_line(true).build().unwrap()];
+        let multiline = SearchOptions { multiline: true, ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &multiline).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].line, "fn foo() {\n    unimplemented!()");
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_in_file_multiline_with_crlf() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("test_multiline_crlf.txt");
+        create_test_file(&test_file_path, "one\r\nstart\r\nend\r\nstart again");
+        let re = vec![RegexBuilder::new(r"start\s+end").multi_line(true).build().unwrap()];
+        let multiline = SearchOptions { multiline: true, ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &multiline).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].line, "start\nend");
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_in_file_with_exclusion_patterns() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("app.log");
+        create_test_file(&test_file_path, "ERROR disk full\nERROR healthcheck timeout\nWARN healthcheck slow\nWARN retrying\n");
+        let re = vec![Regex::new("healthcheck").unwrap(), Regex::new("ERROR").unwrap(), Regex::new("WARN").unwrap()];
+        let options = SearchOptions { negated: vec![true, false, false], ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &options).unwrap();
+        let lines: Vec<(usize, usize)> = results.iter().map(|r| (r.line_number, r.pattern_index)).collect();
+        assert_eq!(lines, vec![(1, 1), (4, 2)]);
+
+        let multiline = SearchOptions { multiline: true, ..options };
+        let results = search_in_file_streaming(&test_file_path, &re, &multiline).unwrap();
+        assert_eq!(results.iter().map(|r| r.line_number).collect::<Vec<_>>(), vec![1, 4]);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_in_file_all_of_patterns() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("app.log");
+        create_test_file(&test_file_path, "user=42 login\nuser=42 timeout\nuser=7 timeout\n");
+        let re = vec![Regex::new("user=42").unwrap(), Regex::new("timeout").unwrap()];
+
+        let all_of = SearchOptions { all_of: true, ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &all_of).unwrap();
+        assert_eq!(results.iter().map(|r| r.line_number).collect::<Vec<_>>(), vec![2]);
+
+        let files_matching_all = SearchOptions { files_matching_all: true, ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &files_matching_all).unwrap();
+        assert_eq!(results.len(), 3);
+        let re = vec![Regex::new("user=42").unwrap(), Regex::new("crash").unwrap()];
+        assert!(search_in_file_streaming(&test_file_path, &re, &files_matching_all).unwrap().is_empty());
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_repeated_pattern_flags() {
+        let args = Args::try_parse_from(["finder", "logs", "-p", "ERROR", "-p", "user=42", "--all-of"]).unwrap();
+        assert_eq!(load_patterns(&args).unwrap(), vec![PatternSpec::new("ERROR"), PatternSpec::new("user=42")]);
+        assert!(Args::try_parse_from(["finder", "logs", "-p", "ERROR", "--preset", "licenses"]).is_err());
+
+        let test_dir = tempdir().unwrap();
+        let rules_path = test_dir.path().join("rules.txt");
+        create_test_file(&rules_path, "errors: ERROR\n");
+        let args = Args::try_parse_from(["finder".as_ref(), "logs".as_ref(), "-f".as_ref(), rules_path.as_os_str(), "-p".as_ref(), "timeout".as_ref()]).unwrap();
+        assert_eq!(load_patterns(&args).unwrap(), vec![PatternSpec { name: Some("errors".to_string()), ..PatternSpec::new("ERROR") }, PatternSpec::new("timeout")]);
+    }
+
+    #[test]
+    fn test_include_glob_restricts_files() {
+        let test_dir = tempdir().unwrap();
+        let rust_file_path = test_dir.path().join("lib.rs");
+        let text_file_path = test_dir.path().join("notes.txt");
+        let output_file_path = test_dir.path().join("output.out");
+
+        create_test_file(&rust_file_path, "needle in rust");
+        create_test_file(&text_file_path, "needle in text");
+
+        let args = Args::parse_from([
+            "finder", "-r", "-n", "-E", "-e", "needle", "--include", "*.rs",
+            test_dir.path().to_str().unwrap(),
+            "-o", output_file_path.to_str().unwrap(),
+        ]);
+        run_app(args).unwrap();
+
+        let output_content = fs::read_to_string(&output_file_path).unwrap();
+        assert_eq!(output_content, format!("{}:1:needle:needle in rust\n", rust_file_path.display()));
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_in_file_gzip_with_search_zip() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("app.log.3.gz");
+        let mut encoder = flate2::write::GzEncoder::new(fs::File::create(&test_file_path).unwrap(), flate2::Compression::default());
+        encoder.write_all(b"starting\nERROR disk full\nstopping").unwrap();
+        encoder.finish().unwrap();
+
+        let re = vec![Regex::new("ERROR").unwrap()];
+        let search_zip = SearchOptions { search_zip: true, ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &search_zip).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].line, "ERROR disk full");
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_strict_stops_at_unreadable_file() {
+        let test_dir = tempdir().unwrap();
+        create_test_file(&test_dir.path().join("app.log"), "ERROR disk full");
+        create_test_file(&test_dir.path().join("broken.log.gz"), "not gzip at all");
+        let output_file_path = test_dir.path().join("output.out");
+        let args = |strict: &[&str]| Args::parse_from(
+            ["finder", "-p", "ERROR", "--search-zip", test_dir.path().to_str().unwrap(), "-o", output_file_path.to_str().unwrap()]
+                .iter()
+                .chain(strict),
+        );
+
+        // The unreadable file is reported after the results of the others.
+        run_app(args(&[])).unwrap();
+        assert!(fs::read_to_string(&output_file_path).unwrap().contains("ERROR disk full"));
+
+        let error = run_app(args(&["--strict"])).unwrap_err();
+        assert!(matches!(error, FinderError::Io { path: Some(ref path), .. } if path.ends_with("broken.log.gz")), "{}", error);
+    }
+
+    #[test]
+    fn test_cache_signature() {
+        let signature = |argv: &[&str]| {
+            let args = Args::try_parse_from(std::iter::once("finder").chain(argv.iter().copied())).unwrap();
+            cache_signature(&args, &[PatternSpec::new("x")])
+        };
+        let plain = signature(&["-p", "x", "."]);
+        assert_eq!(plain, signature(&["-p", "x", ".", "--format", "json"]));
+        for argv in [&["--preset", "pii", "."][..], &["--rules", "secrets", "."], &["-p", "x", "--ignore-match", "y", "."], &["-p", "x", "-i", "."]] {
+            assert_ne!(plain, signature(argv), "{:?}", argv);
+        }
+    }
+
+    #[test]
+    fn test_manifest_replay() {
+        let test_dir = tempdir().unwrap();
+        let rules_path = test_dir.path().join("rules.txt");
+        let manifest_path = test_dir.path().join("run.toml");
+        let output_file_path = test_dir.path().join("output.out");
+        create_test_file(&rules_path, "TODO\n");
+        create_test_file(&test_dir.path().join("a.txt"), "TODO: replay me");
+        let command_line = [
+            test_dir.path().to_str().unwrap(), "-f", rules_path.to_str().unwrap(), "-i",
+            "--manifest", manifest_path.to_str().unwrap(), "-o", output_file_path.to_str().unwrap(),
+        ];
+        let mut args = Args::parse_from(std::iter::once("finder").chain(command_line));
+        args.command_line = manifest::replayable_args(command_line.map(std::ffi::OsString::from));
+        run_app(args).unwrap();
+        let first_run = fs::read_to_string(&output_file_path).unwrap();
+        assert!(first_run.contains("TODO: replay me"));
+
+        let manifest = manifest::Manifest::parse(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert!(!manifest.args.iter().any(|arg| arg == "--manifest"));
+        fs::remove_file(&output_file_path).unwrap();
+        replay(&manifest_path).unwrap();
+        assert_eq!(fs::read_to_string(&output_file_path).unwrap(), first_run);
+
+        create_test_file(&rules_path, "FIXME\n");
+        assert!(replay(&manifest_path).unwrap_err().to_string().contains("patterns differ"));
+    }
+
+    #[test]
+    fn test_search_in_archive_reports_inner_path() {
+        let test_dir = tempdir().unwrap();
+        let archive_path = test_dir.path().join("bundle.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(fs::File::create(&archive_path).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let content = b"alpha\nneedle here\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, "inner/path.txt", &content[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let re = vec![Regex::new("needle").unwrap()];
+        let archives = SearchOptions { archives: true, ..Default::default() };
+        let results = search_in_file_streaming(&archive_path, &re, &archives).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, PathBuf::from(format!("{}!inner/path.txt", archive_path.display())));
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].line, "needle here");
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_output_to_several_sinks() {
+        let test_dir = tempdir().unwrap();
+        let input_file_path = test_dir.path().join("input.txt");
+        let json_path = test_dir.path().join("results.json");
+        let quickfix_path = test_dir.path().join("results.txt");
+
+        create_test_file(&input_file_path, "Line 1\nLine 2 with pattern");
+
+        let json_sink = format!("json={}", json_path.display());
+        let args = Args::try_parse_from([
+            "finder",
+            "-p",
+            "pattern",
+            input_file_path.to_str().unwrap(),
+            "--format",
+            "quickfix",
+            "--output",
+            &json_sink,
+            "--output",
+            quickfix_path.to_str().unwrap(),
+        ])
+        .unwrap();
+        run_app(args).unwrap();
+
+        // Each sink in its own format, or in that of --format.
+        let json = fs::read_to_string(&json_path).unwrap();
+        assert!(json.starts_with('{') && json.contains("\"line\":2"));
+        let quickfix = fs::read_to_string(&quickfix_path).unwrap();
+        assert_eq!(quickfix, format!("{}:2:13:pattern: Line 2 with pattern\n", input_file_path.display()));
+
+        // --tee needs a file to write next to stdout.
+        assert!(Args::try_parse_from(["finder", "-p", "x", ".", "--tee"]).is_err());
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_empty_search_writes_empty_report() {
+        let test_dir = tempdir().unwrap();
+        let sarif_path = test_dir.path().join("empty.sarif");
+        fs::create_dir(test_dir.path().join("empty")).unwrap();
+
+        let args = Args {
+            pattern: vec!["needle".to_string()],
+            paths: vec![test_dir.path().join("empty")],
+            format: OutputFormat::Sarif,
+            output: vec![Sink { format: None, path: sarif_path.clone() }],
+            ..Default::default()
+        };
+        run_app(args).unwrap();
+
+        // Code scanning uploads expect a log, even without results.
+        assert!(fs::read_to_string(&sarif_path).unwrap().contains("\"results\":[]"));
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_output_reports_pattern_name() {
+        let test_dir = tempdir().unwrap();
+        let target_file_path = test_dir.path().join("config.ini");
+        let patterns_file_path = test_dir.path().join("rules.txt");
+        let output_file_path = test_dir.path().join("output.txt");
+
+        create_test_file(&target_file_path, "user = admin\napi_key = 1234");
+        create_test_file(&patterns_file_path, "# secrets audit\nsecrets: (?i)api[_-]?key\\s*=\n");
+
+        let args = Args {
+            input_file: Some(patterns_file_path),
+            paths: vec![target_file_path.clone()],
+            output: vec![Sink { format: None, path: output_file_path.clone() }],
+            ..Default::default()
+        };
+        run_app(args).unwrap();
+
+        let output_content = fs::read_to_string(&output_file_path).unwrap();
+        assert_eq!(output_content, format!("{}:2:secrets:api_key = 1234\n", target_file_path.display()));
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_files_and_match_path() {
+        let test_dir = tempdir().unwrap();
+        fs::create_dir(test_dir.path().join("tests")).unwrap();
+        for name in ["main.rs", "notes.md", "tests/search.rs"] {
+            create_test_file(&test_dir.path().join(name), "needle");
+        }
+        let output_file_path = test_dir.path().join("output.out");
+        let list = |extra: &[&str]| {
+            let mut argv = vec!["finder", test_dir.path().to_str().unwrap(), "-o", output_file_path.to_str().unwrap(), "--include", "!*.out"];
+            argv.extend(extra);
+            run_app(Args::parse_from(argv)).unwrap();
+            fs::read_to_string(&output_file_path).unwrap()
+        };
+        let path = |name: &str| test_dir.path().join(name).display().to_string();
+
+        assert_eq!(list(&["--files"]), format!("{}\n{}\n{}\n", path("main.rs"), path("notes.md"), path("tests/search.rs")));
+        assert_eq!(list(&["--match-path", "-p", r"\.rs$"]), format!("{}\n{}\n", path("main.rs"), path("tests/search.rs")));
+        assert_eq!(list(&["--files", "--max-filesize", "0"]), "");
+        assert!(Args::try_parse_from(["finder", "src", "--files", "-p", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_type_filters() {
+        let test_dir = tempdir().unwrap();
+        let rust_file_path = test_dir.path().join("lib.rs");
+        let proto_file_path = test_dir.path().join("api.proto");
+        let js_file_path = test_dir.path().join("app.js");
+        let output_file_path = test_dir.path().join("output.out");
+
+        create_test_file(&rust_file_path, "needle");
+        create_test_file(&proto_file_path, "needle");
+        create_test_file(&js_file_path, "needle");
+
+        let args = Args::parse_from([
+            "finder", "-p", "needle", "--type-add", "proto:*.proto", "-t", "rust", "--type", "proto",
+            test_dir.path().to_str().unwrap(),
+            "-o", output_file_path.to_str().unwrap(),
+        ]);
+        run_app(args).unwrap();
+
+        let mut lines: Vec<String> = fs::read_to_string(&output_file_path).unwrap().lines().map(String::from).collect();
+        lines.sort();
+        assert_eq!(lines, vec![
+            format!("{}:1:needle:needle", proto_file_path.display()),
+            format!("{}:1:needle:needle", rust_file_path.display()),
+        ]);
+
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_type_list_needs_no_pattern_or_path() {
+        let args = Args::try_parse_from(["finder", "--type-list"]).unwrap();
+        assert!(args.type_list);
+        assert!(Args::try_parse_from(["finder", "some/path"]).is_err());
+        assert!(Args::try_parse_from(["finder", "-p", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_capabilities_stands_alone() {
+        assert!(Args::try_parse_from(["finder", "--capabilities"]).unwrap().capabilities);
+        assert!(Args::try_parse_from(["finder", "--help-long"]).unwrap().help_long);
+        assert!(Args::try_parse_from(["finder", "--capabilities", "src", "-p", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_search_in_file_line_selection() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("test_lines.log");
+        create_test_file(&test_file_path, "match 1\nmatch 2\nmatch 3\nmatch 4\nmatch 5");
+        let re = vec![Regex::new("match").unwrap()];
+        let line_numbers = |lines| {
+            let options = SearchOptions { lines: Some(lines), ..Default::default() };
+            search_in_file_streaming(&test_file_path, &re, &options).unwrap()
+                .iter().map(|r| r.line_number).collect::<Vec<_>>()
+        };
+        assert_eq!(line_numbers(LineSelection::parse_range("2-3").unwrap()), vec![2, 3]);
+        assert_eq!(line_numbers(LineSelection::head(1)), vec![1]);
+        assert_eq!(line_numbers(LineSelection::Tail(2)), vec![4, 5]);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_times_the_stages() {
+        let test_dir = tempdir().unwrap();
+        create_test_file(&test_dir.path().join("a.log"), &"a match\n".repeat(10_000));
+        let timings = Arc::new(bench::StageTimings::default());
+        let options = SearchOptions { timings: Some(timings.clone()), ..Default::default() };
+        let re = vec![Regex::new("match").unwrap()];
+        let outcome = search_walk(&WalkBuilder::new(test_dir.path()), &re, &options, &FileFilter::default(), &ProgressBar::hidden());
+        assert_eq!(outcome.results.len(), 10_000);
+        assert!(timings.get(Stage::Match) > Duration::ZERO);
+        assert!(timings.get(Stage::Read) + timings.get(Stage::Decode) > Duration::ZERO);
+        assert!(Args::try_parse_from(["finder", ".", "-p", "x", "--stats-only", "-j", "2"]).unwrap().dry_run);
+        assert!(Args::try_parse_from(["finder", ".", "-p", "x", "--dry-run", "-o", "out.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_line_buffered_output() {
+        let test_dir = tempdir().unwrap();
+        for name in ["a.log", "b.log", "c.log"] {
+            create_test_file(&test_dir.path().join(name), "a match\nnothing\nmatch again");
+        }
+        let re = vec![Regex::new("match").unwrap()];
+        let printed = Mutex::new(Vec::new());
+        let print = |results: &[SearchResult]| {
+            printed.lock().unwrap().push(results.len());
+            Ok(())
+        };
+        let walk_builder = WalkBuilder::new(test_dir.path());
+        let outcome = search_walk_printing(&walk_builder, &re, &SearchOptions::default(), &FileFilter::default(), &ProgressBar::hidden(), Some(&print));
+        assert_eq!(outcome.results.len(), 6);
+        assert!(outcome.output_error.is_none());
+        // A file at a time.
+        assert_eq!(printed.into_inner().unwrap(), [2, 2, 2]);
+
+        let closed = |_: &[SearchResult]| Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        let options = SearchOptions { searchers: Some(1), ..Default::default() };
+        let outcome = search_walk_printing(&walk_builder, &re, &options, &FileFilter::default(), &ProgressBar::hidden(), Some(&closed));
+        let e = FinderError::from(outcome.output_error.unwrap());
+        assert!(is_broken_pipe(&e));
+        assert!(!is_broken_pipe(&FinderError::from(io::Error::from(io::ErrorKind::NotFound))));
+
+        assert!(Args::try_parse_from(["finder", ".", "-p", "x", "--line-buffered", "-l"]).is_ok());
+        assert!(Args::try_parse_from(["finder", ".", "-p", "x", "--line-buffered", "--sort", "path"]).is_err());
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_walk_threads() {
+        let test_dir = tempdir().unwrap();
+        for dir in 0..8 {
+            fs::create_dir(test_dir.path().join(dir.to_string())).unwrap();
+            for file in 0..20 {
+                create_test_file(&test_dir.path().join(format!("{}/{}.txt", dir, file)), "no\nmatch here\n");
+            }
+        }
+        let re = vec![Regex::new("match").unwrap()];
+        let mut walk_builder = WalkBuilder::new(test_dir.path());
+        walk_builder.threads(3);
+        let options = SearchOptions { searchers: Some(2), ..Default::default() };
+        let outcome = search_walk(&walk_builder, &re, &options, &FileFilter::default(), &ProgressBar::hidden());
+        assert_eq!((outcome.files.len(), outcome.results.len()), (160, 160));
+        assert!(outcome.results.iter().all(|result| result.line_number == 2));
+        let files = list_files::<Regex>(&walk_builder, &[], &FileFilter::default(), &options);
+        assert_eq!(files.len(), 160);
+        assert!(files.is_sorted());
+        assert!(Args::try_parse_from(["finder", ".", "-p", "x", "--walk-threads", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_ignore_match() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("README.md");
+        create_test_file(&test_file_path, "api_key = EXAMPLE123\napi_key = s3cr3t\nAPI_KEY = Example456\n");
+        let re = vec![Regex::new("(?i)api_key").unwrap()];
+        let options = SearchOptions {
+            ignore_matches: compile_regex_with_cache(&["example".to_string()], CaseMode::Insensitive, false).unwrap(),
+            ..Default::default()
+        };
+        let results = search_in_file_streaming(&test_file_path, &re, &options).unwrap();
+        assert_eq!(results.iter().map(|r| r.line_number).collect::<Vec<_>>(), vec![2]);
+
+        let multiline = SearchOptions { multiline: true, ..options };
+        let results = search_in_file_streaming(&test_file_path, &re, &multiline).unwrap();
+        assert_eq!(results.iter().map(|r| r.line_number).collect::<Vec<_>>(), vec![2]);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_inline_ignores() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("config.py");
+        create_test_file(&test_file_path, concat!(
+            "token = 'a' # finder:ignore\n",
+            "# finder:ignore-next-line[keys]\n",
+            "token = 'b'\n",
+            "token = 'c' # finder:ignore[other]\n",
+            "token = 'd' # TODO finder:ignore[keys]\n",
+        ));
+        let pattern_specs = [
+            PatternSpec { name: Some("keys".to_string()), ..PatternSpec::new("token") },
+            PatternSpec::new("TODO"),
+        ];
+        let re = vec![Regex::new("token").unwrap(), Regex::new("TODO").unwrap()];
+        let options = SearchOptions { inline_ignores: Some(Arc::new(ignores::InlineIgnores::new(&pattern_specs))), ..Default::default() };
+        let found = |options: &SearchOptions| -> Vec<(usize, usize)> {
+            let results = search_in_file_streaming(&test_file_path, &re, options).unwrap();
+            results.iter().map(|r| (r.line_number, r.pattern_index)).collect()
+        };
+        // The line that a comment silences for one rule is reported for the others.
+        assert_eq!(found(&options), [(4, 0), (5, 1)]);
+        assert_eq!(found(&SearchOptions { multiline: true, ..options.clone() }), [(4, 0), (5, 1)]);
+        let first_match = SearchOptions { per_file: Some(PerFileMatch::First), ..options };
+        assert_eq!(found(&first_match), [(4, 0)]);
+        assert_eq!(found(&SearchOptions::default()).len(), 4);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_chunked_search() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("big.log");
+        let mut content = String::from("\u{feff}");
+        for index in 0..200 {
+            let line = if index % 7 == 0 {
+                format!("ERROR {}", index)
+            } else if index % 14 == 13 {
+                "# finder:ignore-next-line".to_string()
+            } else {
+                format!("info {}", index)
+            };
+            content.push_str(&line);
+            content.push_str(if index % 2 == 0 { "\r\n" } else { "\n" });
+        }
+        content.push_str("ERROR without a newline");
+        create_test_file(&test_file_path, &content);
+        let pattern_specs = [PatternSpec::new("ERROR"), PatternSpec::new("info 4")];
+        let re = vec![Regex::new("ERROR").unwrap(), Regex::new("info 4").unwrap()];
+        let whole = SearchOptions { inline_ignores: Some(Arc::new(ignores::InlineIgnores::new(&pattern_specs))), ..Default::default() };
+        let found = |options: &SearchOptions| -> Vec<(usize, usize, String)> {
+            let results = search_in_file_streaming(&test_file_path, &re, options).unwrap();
+            results.into_iter().map(|r| (r.line_number, r.pattern_index, r.line)).collect()
+        };
+        let expected = found(&whole);
+        // Half of the 29 ERROR lines are silenced, then "info 4" and "info 4x".
+        assert_eq!(expected.len(), 15 + 8 + 1);
+        // Chunk sizes that split the file at lines that matter and others.
+        for size in [1, 7, 64, 100, 1000] {
+            let chunked = SearchOptions { chunking: Some(chunks::Chunking { size, threads: Arc::new(chunks::ThreadBudget::new(3)) }), ..whole.clone() };
+            assert_eq!(found(&chunked), expected, "chunks of {} bytes", size);
+            let merged = |options: &SearchOptions| SearchOptions { merge_ranges: true, per_file: Some(PerFileMatch::Last), ..options.clone() };
+            assert_eq!(found(&merged(&chunked)), found(&merged(&whole)));
+        }
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_chunked_search_decodes_like_whole_file() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("mostly-utf8.log");
+        // Valid UTF-8 but for a Windows-1252 `é` on the last line, so the
+        // whole file is decoded from Windows-1252.
+        let mut content = "café ERROR\n".repeat(50).into_bytes();
+        content.extend_from_slice(b"caf\xe9 ERROR\n");
+        fs::write(&test_file_path, content).unwrap();
+        let re = vec![Regex::new("ERROR").unwrap()];
+        let found = |options: &SearchOptions| -> Vec<(usize, String)> {
+            let results = search_in_file_streaming(&test_file_path, &re, options).unwrap();
+            results.into_iter().map(|r| (r.line_number, r.line)).collect()
+        };
+        let expected = found(&SearchOptions::default());
+        assert_eq!(expected[0].1, "cafÃ© ERROR");
+        assert_eq!(expected[50].1, "café ERROR");
+        let chunked = SearchOptions { chunking: Some(chunks::Chunking { size: 40, threads: Arc::new(chunks::ThreadBudget::new(3)) }), ..Default::default() };
+        assert_eq!(found(&chunked), expected);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_every_matching_pattern() {
+        let patterns = ["ERROR".to_string(), "two".to_string(), "three".to_string(), "ERROR two".to_string()];
+        let re = compile_regex_with_cache(&patterns, CaseMode::Sensitive, false).unwrap();
+        let pattern_specs: Vec<PatternSpec> = patterns.iter().map(|pattern| PatternSpec::new(pattern)).collect();
+        let options = SearchOptions { inline_ignores: Some(Arc::new(ignores::InlineIgnores::new(&pattern_specs))), ..Default::default() };
+        let content = "ERROR two ERROR\nonly two\nERROR two # finder:ignore[two]\n";
+        let found: Vec<(usize, Vec<usize>)> = search_decoded(Path::new("a.log"), content, &re, &options)
+            .iter()
+            .map(|result| (result.line_number, result.pattern_indexes().collect()))
+            .collect();
+        // The patterns silenced on a line are left out.
+        assert_eq!(found, [(1, vec![0, 1, 3]), (2, vec![1]), (3, vec![0, 3])]);
+    }
+
+    #[test]
+    fn test_prefiltered_search() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("app.log");
+        create_test_file(&test_file_path, "start\r\nerror: disk full\r\n# finder:ignore-next-line\nERROR: timeout\nwarning: retry ERROR\nok\nError: done");
+        let patterns = ["(?i)error: \\w+".to_string(), "warning".to_string(), "retry".to_string()];
+        let re = compile_regex_with_cache(&patterns, CaseMode::Sensitive, false).unwrap();
+        let pattern_specs: Vec<PatternSpec> = patterns.iter().map(|pattern| PatternSpec::new(pattern)).collect();
+        let options = SearchOptions {
+            negated: vec![false, false, true],
+            inline_ignores: Some(Arc::new(ignores::InlineIgnores::new(&pattern_specs))),
+            ..Default::default()
+        };
+        let prefilter = prefilter::Prefilter::new(&patterns, &options.negated, CaseMode::Sensitive).map(Arc::new);
+        assert!(prefilter.is_some());
+        let prefiltered = SearchOptions { prefilter, ..options.clone() };
+        let found = |options: &SearchOptions| -> Vec<(usize, String)> {
+            let results = search_in_file_streaming(&test_file_path, &re, options).unwrap();
+            results.into_iter().map(|r| (r.line_number, r.line)).collect()
+        };
+        assert_eq!(found(&prefiltered), [(2, "error: disk full".to_string()), (7, "Error: done".to_string())]);
+        assert_eq!(found(&prefiltered), found(&options));
+        let last = |options: &SearchOptions| SearchOptions { per_file: Some(PerFileMatch::Last), merge_ranges: true, ..options.clone() };
+        assert_eq!(found(&last(&prefiltered)), found(&last(&options)));
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_unchanged() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("live.log");
+        create_test_file(&test_file_path, "ERROR one\n");
+        // As if the file grew between the walk and the search.
+        let before = fs::metadata(&test_file_path).unwrap();
+        create_test_file(&test_file_path, "ERROR one\nERROR two\n");
+        let re = vec![Regex::new("ERROR").unwrap()];
+        let search = |policy| {
+            let options = SearchOptions { changed_file_policy: policy, ..Default::default() };
+            let (results, changed) = search_unchanged(&test_file_path, Some(before.clone()), &re, &options);
+            (results.unwrap().len(), changed)
+        };
+        assert_eq!(search(ChangedFilePolicy::Keep), (2, true));
+        assert_eq!(search(ChangedFilePolicy::Retry), (2, false));
+        let current = fs::metadata(&test_file_path).unwrap();
+        assert!(!search_unchanged(&test_file_path, Some(current), &re, &SearchOptions::default()).1);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_in_file_first_and_last_match_only() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("app.log.1");
+        create_test_file(&test_file_path, "ERROR one\nok\nERROR two\nERROR three\nok");
+        let re = vec![Regex::new("ERROR").unwrap()];
+        for (per_file, expected) in [(PerFileMatch::First, "ERROR one"), (PerFileMatch::Last, "ERROR three")] {
+            for multiline in [false, true] {
+                let options = SearchOptions { per_file: Some(per_file), multiline, ..Default::default() };
+                let results = search_in_file_streaming(&test_file_path, &re, &options).unwrap();
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].line, expected);
+            }
+        }
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_until_first_match() {
+        /// Fails the search if it reads past the first match.
+        struct Unreadable;
+        impl Read for Unreadable {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("read past the first match"))
+            }
+        }
+        let re = vec![Regex::new("ERROR").unwrap()];
+        let options = SearchOptions { per_file: Some(PerFileMatch::First), ..Default::default() };
+        let mut reader = io::Cursor::new(b"ok\r\nERROR one\n".to_vec()).chain(Unreadable);
+        let results = search_reader(Path::new("app.log"), &mut reader, &re, &options).unwrap();
+        assert_eq!((results[0].line_number, results[0].line.as_str()), (2, "ERROR one"));
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("ERROR one\nERROR two\n".encode_utf16().flat_map(u16::to_le_bytes));
+        let from_line_two = SearchOptions { lines: Some(LineSelection::Range { first: 2, last: None }), ..options.clone() };
+        let results = search_reader(Path::new("app.log"), &mut utf16.as_slice(), &re, &from_line_two).unwrap();
+        assert_eq!((results[0].line_number, results[0].line.as_str()), (2, "ERROR two"));
+        let only_line_one = SearchOptions { lines: Some(LineSelection::head(1)), ..options };
+        assert!(search_reader(Path::new("app.log"), &mut &b"ok\nERROR\n"[..], &re, &only_line_one).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_nfc() {
+        let re = vec![Regex::new("café").unwrap()];
+        let decomposed = "\u{feff}un cafe\u{301} noir\n";
+        assert!(search_reader(Path::new("menu.txt"), &mut decomposed.as_bytes(), &re, &SearchOptions::default()).unwrap().is_empty());
+        for per_file in [None, Some(PerFileMatch::First)] {
+            let options = SearchOptions { normalize: Some(Normalization::Nfc), per_file, ..Default::default() };
+            let results = search_reader(Path::new("menu.txt"), &mut decomposed.as_bytes(), &re, &options).unwrap();
+            assert_eq!(results[0].line, "un café noir");
+        }
+        assert!(matches!(Normalization::Nfc.apply("café"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let test_dir = tempdir().unwrap();
+        let test_file_path = test_dir.path().join("header.rs");
+        create_test_file(&test_file_path, "// Copyright\n// License: MIT\nfn main() {}\n// TODO\nlet x = 1; // note\n");
+        let re = vec![Regex::new("^//").unwrap(), Regex::new("//").unwrap()];
+        let options = SearchOptions { merge_ranges: true, ..Default::default() };
+        let results = search_in_file_streaming(&test_file_path, &re, &options).unwrap();
+        let ranges: Vec<(usize, Option<usize>, &str)> = results.iter()
+            .map(|r| (r.line_number, r.end_line_number, r.line.as_str()))
+            .collect();
+        assert_eq!(ranges, vec![
+            (1, Some(2), "// Copyright\n// License: MIT"),
+            (4, None, "// TODO"),
+            (5, None, "let x = 1; // note"),
+        ]);
+        test_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_sort_results_by_time() {
+        let result = |path: &str, line_number: usize, line: &str| SearchResult { pattern: "ERROR".to_string(), ..SearchResult::new(path, line_number, line, 0) };
+        let results = vec![
+            result("b.log", 1, "2024-01-01 10:00:05 ERROR third"),
+            result("a.log", 9, "ERROR without timestamp"),
+            result("a.log", 2, "2024-01-01 10:00:01 ERROR first"),
+            result("b.log.1", 4, "2024-01-01T10:00:03+00:00 ERROR second"),
+        ];
+        let sorted = sort_results(results, Some(SortBy::Time), None);
+        let lines: Vec<&str> = sorted.iter().map(|r| r.line.as_str()).collect();
+        assert_eq!(lines, vec![
+            "2024-01-01 10:00:01 ERROR first",
+            "2024-01-01T10:00:03+00:00 ERROR second",
+            "2024-01-01 10:00:05 ERROR third",
+            "ERROR without timestamp",
+        ]);
+
+        let by_path = sort_results(sorted, Some(SortBy::Path), None);
+        let positions: Vec<(String, usize)> = by_path.iter().map(|r| (r.path.display().to_string(), r.line_number)).collect();
+        assert_eq!(positions[0], ("a.log".to_string(), 2));
+        assert_eq!(positions[1], ("a.log".to_string(), 9));
+    }
 }
@@ -0,0 +1,160 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{Args as ClapArgs, Command, CommandFactory};
+use roff::{Roff, bold, roman};
+
+use crate::error::FinderError;
+
+/// Examples of the manual: what each does, and the command line.
+const EXAMPLES: &[(&str, &str)] = &[
+    ("Search a directory for a string, case-insensitively:", "finder src/ -i -p 'connection refused'"),
+    ("Search for the patterns of a rules file, one per line, as name: regex", "finder . -f rules.txt"),
+    ("Fail a CI job when a forbidden pattern is found, reporting it as GitHub annotations:", "finder . -f forbidden.txt --fail-if-found --format github"),
+    ("Only search Rust files, leaving out the tests:", "finder . -t rust --include '!*_test.rs' -p 'unwrap\\(\\)'"),
+    ("Search compressed logs and archives, newest matches last:", "finder /var/log -z --archives -p ERROR --sort time"),
+    ("Only search the files changed since the branch left main:", "finder . --since main -f rules.txt"),
+    ("Look for personal data, masking it in the output:", "finder exports/ --preset pii:fr --redact"),
+    ("Debug why a rule does not match a line:", "echo 'myapi_key=1' | finder explain -w -p 'api_key=\\d'"),
+    ("Record the current findings, then only fail on new ones:", "finder baseline update baseline.json . -f rules.txt && finder . -f rules.txt --baseline baseline.json --fail-if-found"),
+    ("Install a pre-commit hook that checks the staged changes against .finder-rules:", "finder hook install"),
+];
+
+/// What the exit statuses of finder mean.
+const EXIT_STATUS: &[(&str, &str)] = &[
+    ("0", "The search ran, whether or not anything matched."),
+    ("1", "An error stopped finder, a path could not be searched, or --fail-if-found, --fail-if-missing or --fail-on failed."),
+    ("2", "The command line is invalid."),
+];
+
+/// Arguments of `finder man`.
+#[derive(ClapArgs, Debug)]
+pub struct ManArgs {
+    /// Write the manual as roff, for `man`, rather than as text
+    #[arg(long)]
+    pub roff: bool,
+
+    /// Write the roff pages of finder and of each of its subcommands into DIR, as packages install them
+    #[arg(long, value_name = "DIR", requires = "roff")]
+    pub dir: Option<PathBuf>,
+}
+
+/// The command finder parses, with the names of its subcommands as they are
+/// typed.
+fn command() -> Command {
+    let mut command = crate::Args::command().disable_help_subcommand(true);
+    command.build();
+    command
+}
+
+/// Indents every line of `text` by four spaces.
+fn indent(text: &str) -> String {
+    text.lines().map(|line| if line.is_empty() { String::new() } else { format!("    {}", line) }).collect::<Vec<_>>().join("\n")
+}
+
+/// Writes the whole manual as text: the help of finder and of each of its
+/// subcommands, then the examples and the exit statuses. This is what
+/// `--help-long` prints.
+pub fn write_text(out: &mut dyn Write) -> io::Result<()> {
+    let command = command();
+    writeln!(out, "NAME\n    finder - {}\n", command.get_about().map(ToString::to_string).unwrap_or_default())?;
+    // The help starts with the description the name already gives.
+    let mut command = command.about(None::<&str>);
+    writeln!(out, "{}\n", command.render_long_help().to_string().trim_end())?;
+    writeln!(out, "COMMANDS")?;
+    for subcommand in command.get_subcommands().filter(|subcommand| !subcommand.is_hide_set()) {
+        writeln!(out, "\n  finder {}\n{}", subcommand.get_name(), indent(subcommand.clone().render_long_help().to_string().trim_end()))?;
+    }
+    writeln!(out, "\nEXAMPLES")?;
+    for (description, example) in EXAMPLES {
+        writeln!(out, "    {}\n        {}\n", description, example)?;
+    }
+    writeln!(out, "EXIT STATUS")?;
+    for (status, meaning) in EXIT_STATUS {
+        writeln!(out, "    {}  {}", status, meaning)?;
+    }
+    Ok(())
+}
+
+/// The EXAMPLES and EXIT STATUS sections in roff.
+fn extra_sections() -> String {
+    let mut roff = Roff::new();
+    roff.control("SH", ["EXAMPLES"]);
+    for (description, example) in EXAMPLES {
+        roff.control("PP", []).text([roman(*description)]).control("RS", ["4"]).text([bold(*example)]).control("RE", []);
+    }
+    roff.control("SH", ["EXIT STATUS"]);
+    for (status, meaning) in EXIT_STATUS {
+        roff.control("TP", []).text([bold(*status)]).text([roman(*meaning)]);
+    }
+    let rendered = roff.render();
+    // The page these sections go in already starts with the preamble
+    // `render` adds.
+    rendered[rendered.find(".SH").unwrap_or(0)..].to_string()
+}
+
+/// Writes the manual page of finder, finder(1), in roff.
+pub fn write_roff(out: &mut dyn Write) -> io::Result<()> {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(command()).render(&mut page)?;
+    let page = String::from_utf8_lossy(&page);
+    // The examples go before the version, as in most manual pages.
+    let (head, tail) = page.split_at(page.find(".SH VERSION").unwrap_or(page.len()));
+    write!(out, "{}{}{}", head, extra_sections(), tail)
+}
+
+/// Writes finder.1 and a page for each subcommand, such as finder-check.1,
+/// into `dir`.
+fn write_pages(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    // The page of finder itself is then replaced by the one with examples.
+    clap_mangen::generate_to(command(), dir)?;
+    let mut page = io::BufWriter::new(fs::File::create(dir.join("finder.1"))?);
+    write_roff(&mut page)?;
+    page.flush()
+}
+
+pub fn run(args: ManArgs) -> Result<(), FinderError> {
+    if let Some(dir) = &args.dir {
+        write_pages(dir)?;
+        eprintln!("Wrote the manual pages into {}", dir.display());
+        return Ok(());
+    }
+    let mut out = io::stdout().lock();
+    if args.roff { write_roff(&mut out)? } else { write_text(&mut out)? }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_manual() {
+        let mut text = Vec::new();
+        write_text(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("NAME\n    finder - "));
+        assert!(text.contains("--walk-threads <N>"));
+        assert!(text.contains("\n  finder check\n    Check that the patterns"));
+        assert!(text.contains("\nEXAMPLES\n    Search a directory for a string, case-insensitively:\n        finder src/ -i -p 'connection refused'\n"));
+
+        let mut roff = Vec::new();
+        write_roff(&mut roff).unwrap();
+        let roff = String::from_utf8(roff).unwrap();
+        assert!(roff.starts_with(".ie \\n(.g .ds Aq \\(aq"));
+        assert_eq!(roff.matches(".ds Aq").count(), 2);
+        let examples = roff.find(".SH EXAMPLES").unwrap();
+        assert!(roff.find(".SH OPTIONS").unwrap() < examples && examples < roff.find(".SH \"EXIT STATUS\"").unwrap());
+        assert!(roff[examples..].contains("\\fBfinder . \\-f rules.txt\\fR"));
+
+        let dir = tempdir().unwrap();
+        write_pages(dir.path()).unwrap();
+        assert!(fs::read_to_string(dir.path().join("finder.1")).unwrap().contains(".SH EXAMPLES"));
+        assert!(fs::read_to_string(dir.path().join("finder-check.1")).unwrap().contains("finder\\-check"));
+        assert!(dir.path().join("finder-hook-install.1").exists());
+    }
+}
@@ -0,0 +1,178 @@
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::json::{json_string, parse_string_literal};
+use crate::patterns::PatternSpec;
+
+/// What `--manifest` records of a run so that `--from-manifest` can replay
+/// it: the command line, what the patterns were and where it ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub version: String,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    pub os: String,
+    pub arch: String,
+    pub cwd: PathBuf,
+    pub threads: usize,
+    /// The command line, `--manifest` left out.
+    pub args: Vec<String>,
+    pub pattern_count: usize,
+    pub patterns_hash: String,
+}
+
+/// A hash of the effective patterns, wherever they came from, to tell
+/// whether a patterns file changed since the manifest was written.
+pub fn patterns_hash(pattern_specs: &[PatternSpec]) -> String {
+    let mut hasher = Xxh3::new();
+    for spec in pattern_specs {
+        hasher.update(spec.name.as_deref().unwrap_or_default().as_bytes());
+        hasher.update(&[0, u8::from(spec.negated)]);
+        hasher.update(spec.pattern.as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("xxh3:{:032x}", hasher.digest128())
+}
+
+/// `args` (the program name excluded) without `--manifest` and its value.
+pub fn replayable_args(args: impl IntoIterator<Item = OsString>) -> Vec<String> {
+    let mut replayable = Vec::new();
+    let mut args = args.into_iter().map(|arg| arg.to_string_lossy().into_owned());
+    while let Some(arg) = args.next() {
+        if arg == "--manifest" {
+            args.next();
+        } else if !arg.starts_with("--manifest=") {
+            replayable.push(arg);
+        }
+    }
+    replayable
+}
+
+impl Manifest {
+    /// The manifest of a run started now, in the current directory.
+    pub fn of_run(args: Vec<String>, pattern_specs: &[PatternSpec]) -> Manifest {
+        Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cwd: std::env::current_dir().unwrap_or_default(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            args,
+            pattern_count: pattern_specs.len(),
+            patterns_hash: patterns_hash(pattern_specs),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::from("# Replay with `finder --from-manifest <this file>`.\n");
+        let _ = writeln!(toml, "\n[finder]\nversion = {}\ncreated = {}", json_string(&self.version), self.created);
+        let _ = writeln!(
+            toml,
+            "\n[environment]\nos = {}\narch = {}\ncwd = {}\nthreads = {}",
+            json_string(&self.os),
+            json_string(&self.arch),
+            json_string(&self.cwd.to_string_lossy()),
+            self.threads
+        );
+        toml.push_str("\n[run]\nargs = [\n");
+        for arg in &self.args {
+            let _ = writeln!(toml, "    {},", json_string(arg));
+        }
+        toml.push_str("]\n");
+        let _ = writeln!(toml, "\n[patterns]\ncount = {}\nhash = {}", self.pattern_count, json_string(&self.patterns_hash));
+        toml
+    }
+
+    /// Reads a manifest written by `to_toml`; this is not a general TOML
+    /// parser.
+    pub fn parse(toml: &str) -> Result<Manifest, String> {
+        let mut values: Vec<(String, String)> = Vec::new();
+        let mut args: Option<Vec<String>> = None;
+        let mut section = String::new();
+        let mut lines = toml.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let line = line.trim();
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| error(format!("expected `key = value`, found `{}`", line)))?;
+            let key = format!("{}.{}", section, key.trim());
+            if key == "run.args" {
+                if value.trim() != "[" {
+                    return Err(error("expected the arguments one per line".to_string()));
+                }
+                let mut list = Vec::new();
+                loop {
+                    let (index, line) = lines.next().ok_or("unterminated `args` list")?;
+                    let line = line.trim();
+                    if line == "]" {
+                        break;
+                    }
+                    let arg = line.strip_suffix(',').unwrap_or(line);
+                    list.push(parse_string_literal(arg).map_err(|e| format!("line {}: {}", index + 1, e))?);
+                }
+                args = Some(list);
+            } else {
+                values.push((key, value.trim().to_string()));
+            }
+        }
+
+        let value = |key: &str| {
+            values.iter()
+                .find(|(known, _)| known == key)
+                .map(|(_, value)| value.as_str())
+                .ok_or_else(|| format!("missing `{}`", key))
+        };
+        let string = |key: &str| value(key).and_then(|value| parse_string_literal(value).map_err(|e| format!("`{}`: {}", key, e)));
+        let number = |key: &str| value(key).and_then(|value| value.parse::<u64>().map_err(|_| format!("`{}`: expected a number", key)));
+        Ok(Manifest {
+            version: string("finder.version")?,
+            created: number("finder.created")?,
+            os: string("environment.os")?,
+            arch: string("environment.arch")?,
+            cwd: PathBuf::from(string("environment.cwd")?),
+            threads: number("environment.threads")? as usize,
+            args: args.ok_or("missing `run.args`")?,
+            pattern_count: number("patterns.count")? as usize,
+            patterns_hash: string("patterns.hash")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let pattern_specs = [PatternSpec::new("TODO"), PatternSpec { name: Some("keys".to_string()), ..PatternSpec::new("api_key") }];
+        let args = replayable_args(["src", "-f", "rules.txt", "--manifest", "run.toml", "-p", "say \"hi\"", "--manifest=x.toml"].map(OsString::from));
+        assert_eq!(args, vec!["src", "-f", "rules.txt", "-p", "say \"hi\""]);
+        let manifest = Manifest::of_run(args, &pattern_specs);
+        let toml = manifest.to_toml();
+        assert!(toml.contains("[run]\nargs = [\n    \"src\",\n"), "{}", toml);
+        assert_eq!(Manifest::parse(&toml).unwrap(), manifest);
+
+        assert!(Manifest::parse("[finder]\nversion = \"1\"\n").unwrap_err().contains("missing"));
+        assert!(Manifest::parse("[run]\nargs = [\n    \"a\",\n").is_err());
+    }
+
+    #[test]
+    fn test_patterns_hash() {
+        let hash = patterns_hash(&[PatternSpec::new("TODO")]);
+        assert!(hash.starts_with("xxh3:"));
+        assert_eq!(hash, patterns_hash(&[PatternSpec::new("TODO")]));
+        assert_ne!(hash, patterns_hash(&[PatternSpec::new("FIXME")]));
+        assert_ne!(hash, patterns_hash(&[PatternSpec { negated: true, ..PatternSpec::new("TODO") }]));
+    }
+}
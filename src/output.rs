@@ -0,0 +1,757 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use colored::*;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+use crate::SearchResult;
+use crate::cluster::Cluster;
+use crate::engine::Matcher;
+use crate::hyperlink::{self, HyperlinkFormat};
+use crate::json::json_string;
+use crate::longpath;
+use crate::patterns::PatternSpec;
+use crate::theme::Theme;
+
+/// The format of the results (`--format`).
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `path:line:pattern:line` lines
+    #[default]
+    Text,
+    /// A SARIF 2.1.0 log, for code scanning tools
+    Sarif,
+    /// GitHub Actions workflow commands, shown as annotations on pull requests
+    Github,
+    /// One JSON object per result and per line, which `finder filter` reads back
+    Json,
+    /// `path:line:column:message` lines for Vim's quickfix list (`:cfile`)
+    Quickfix,
+    /// GNU `path:line.column-column: message` lines for Emacs compilation-mode
+    Emacs,
+    /// A standalone HTML page with a summary and a collapsible section per file
+    Html,
+    /// An XML document with one element per file and per result
+    Xml,
+}
+
+/// How text results are grouped (`--group-by`).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// Under one header per pattern, in the order of the patterns
+    Pattern,
+}
+
+/// A file the results are written to (`--output`), in a format of its own,
+/// or in that of `--format` without one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sink {
+    pub format: Option<OutputFormat>,
+    pub path: PathBuf,
+}
+
+impl Sink {
+    /// Parses `FILE` or `FORMAT=FILE`, such as `json=results.json`. A file
+    /// whose name has a `=` after something other than a format is taken
+    /// whole.
+    pub fn parse(value: &str) -> Result<Sink, String> {
+        if value.is_empty() {
+            return Err("expected FILE or FORMAT=FILE".to_string());
+        }
+        let format = value.split_once('=').and_then(|(format, path)| {
+            let format = <OutputFormat as clap::ValueEnum>::from_str(format, true).ok()?;
+            Some((format, path))
+        });
+        match format {
+            Some((_, "")) => Err(format!("`{}`: expected a file after the format", value)),
+            Some((format, path)) => Ok(Sink { format: Some(format), path: PathBuf::from(path) }),
+            None => Ok(Sink { format: None, path: PathBuf::from(value) }),
+        }
+    }
+}
+
+/// How results are written.
+#[derive(Debug, Default, Clone)]
+pub struct OutputOptions {
+    /// Colorize paths, line numbers, patterns and matches (terminal output).
+    pub color: bool,
+    /// Print only the paths of files with at least one match (`-l`).
+    pub files_with_matches: bool,
+    /// Follow every path with a NUL byte instead of `:` (or newline with `-l`).
+    pub null: bool,
+    /// Mask the matched text (`--redact`).
+    pub redact: bool,
+    /// Make the paths links to this target (`--hyperlink`), in color output.
+    pub hyperlink: Option<HyperlinkFormat>,
+    /// Files that changed while they were searched, whose results are
+    /// marked in structured output.
+    pub changed: HashSet<PathBuf>,
+    /// Lines longer than this many characters are not printed whole
+    /// (`--max-columns`).
+    pub max_columns: Option<usize>,
+    /// Print the start of such lines rather than omit them
+    /// (`--max-columns-preview`).
+    pub max_columns_preview: bool,
+    /// Leave the line numbers out (`--no-line-number`).
+    pub no_line_number: bool,
+    /// The byte offsets of the matching lines, by path and line number,
+    /// printed in place of the line numbers (`--byte-offset`).
+    pub byte_offsets: Option<HashMap<PathBuf, HashMap<usize, u64>>>,
+    /// Highlight no more than this many matches of a line
+    /// (`--max-matches-per-line`).
+    pub max_matches_per_line: Option<usize>,
+    /// The colors of the parts of a result (`--colors`).
+    pub theme: Theme,
+}
+
+/// The byte offsets of the starts of the lines of `content` but the first:
+/// after each `\n` byte, or each `\n` code unit of a file with a UTF-16 BOM.
+fn line_starts(content: &[u8]) -> Vec<usize> {
+    let newline = match Encoding::for_bom(content) {
+        Some((encoding, _)) if encoding == UTF_16LE => [b'\n', 0],
+        Some((encoding, _)) if encoding == UTF_16BE => [0, b'\n'],
+        _ => return content.iter().enumerate().filter(|&(_, &b)| b == b'\n').map(|(index, _)| index + 1).collect(),
+    };
+    // The BOM is two bytes long, so the code units start at even offsets.
+    content.chunks_exact(2).enumerate().filter(|(_, unit)| *unit == newline).map(|(index, _)| 2 * index + 2).collect()
+}
+
+/// The byte offset in its file of the first line of each result, for
+/// `--byte-offset`. Files that cannot be read are left out.
+pub fn line_offsets<'r>(results: impl IntoIterator<Item = &'r SearchResult>) -> HashMap<PathBuf, HashMap<usize, u64>> {
+    let mut wanted: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for result in results {
+        wanted.entry(&result.path).or_default().push(result.line_number);
+    }
+    let mut offsets = HashMap::new();
+    for (path, mut line_numbers) in wanted {
+        let Ok(content) = fs::read(longpath::for_opening(path)) else { continue };
+        line_numbers.sort_unstable();
+        line_numbers.dedup();
+        let starts = std::iter::once(0).chain(line_starts(&content));
+        let mut file_offsets = HashMap::new();
+        let mut line_numbers = line_numbers.into_iter().peekable();
+        for (index, start) in starts.enumerate() {
+            let Some(&line_number) = line_numbers.peek() else { break };
+            if index + 1 == line_number {
+                file_offsets.insert(line_number, start as u64);
+                line_numbers.next();
+            }
+        }
+        offsets.insert(path.to_path_buf(), file_offsets);
+    }
+    offsets
+}
+
+/// Replaces each of the `matches` of `line`, in the order of the line, with
+/// `rewrite(ordinal, pattern_index, text)`.
+fn rewrite_matches(line: &str, matches: &[(usize, Range<usize>)], rewrite: impl Fn(usize, usize, &str) -> String) -> String {
+    let mut rewritten = String::with_capacity(line.len());
+    let mut last_end = 0;
+    for (ordinal, (pattern_index, span)) in matches.iter().enumerate() {
+        rewritten.push_str(&line[last_end..span.start]);
+        rewritten.push_str(&rewrite(ordinal, *pattern_index, &line[span.clone()]));
+        last_end = span.end;
+    }
+    rewritten.push_str(&line[last_end..]);
+    rewritten
+}
+
+/// Masks the letters and digits of a match, so that `jane@example.com`
+/// shows as `****@*******.***`.
+pub fn mask(text: &str) -> String {
+    text.chars().map(|c| if c.is_alphanumeric() { '*' } else { c }).collect()
+}
+
+/// What to show for the matching pattern: its name when it has one, the raw regex otherwise.
+pub fn pattern_label<'a>(pattern_specs: &'a [PatternSpec], result: &'a SearchResult) -> &'a str {
+    pattern_specs[result.pattern_index].name.as_deref().unwrap_or(&result.pattern)
+}
+
+/// The label of the pattern at `pattern_index`, one of those that match the
+/// line of `result`.
+pub fn label_of<'a>(pattern_specs: &'a [PatternSpec], result: &'a SearchResult, pattern_index: usize) -> &'a str {
+    match &pattern_specs[pattern_index] {
+        _ if pattern_index == result.pattern_index => pattern_label(pattern_specs, result),
+        spec => spec.name.as_deref().unwrap_or(&spec.pattern),
+    }
+}
+
+/// The pattern label of the text and editor formats, followed by the
+/// severity of the rule when it has one, as in `keys[error]`.
+pub fn rule_label<'a>(pattern_specs: &'a [PatternSpec], result: &'a SearchResult) -> Cow<'a, str> {
+    let label = pattern_label(pattern_specs, result);
+    match pattern_specs[result.pattern_index].severity {
+        Some(severity) => Cow::Owned(format!("{}[{}]", label, severity.as_str())),
+        None => Cow::Borrowed(label),
+    }
+}
+
+/// The line number of a result, or its `first-last` range when adjacent
+/// matches were merged.
+pub fn line_label(result: &SearchResult) -> String {
+    match result.end_line_number {
+        Some(end) => format!("{}-{}", result.line_number, end),
+        None => result.line_number.to_string(),
+    }
+}
+
+/// The 1-based start and end (exclusive) columns, in characters, of the
+/// first match in a result. Merged ranges and multiline matches span several
+/// lines and get none.
+pub fn match_columns<M: Matcher>(matcher: &M, result: &SearchResult) -> Option<(usize, usize)> {
+    if result.end_line_number.is_some() {
+        return None;
+    }
+    let span = matcher.find_at(&result.line, 0)?;
+    if result.line[..span.end].contains('\n') {
+        return None;
+    }
+    let start_column = result.line[..span.start].chars().count() + 1;
+    Some((start_column, start_column + result.line[span].chars().count()))
+}
+
+/// The path exactly as the OS knows it, so that `--null` consumers get the
+/// original file name back even when it is not valid UTF-8.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.to_string_lossy().into_owned().into_bytes())
+}
+
+pub struct Printer<'a, M> {
+    pub pattern_specs: &'a [PatternSpec],
+    pub matchers: &'a [M],
+    pub options: OutputOptions,
+}
+
+impl<M: Matcher> Printer<'_, M> {
+    /// Writes `path`, linked to `line` and `column` with `--hyperlink`.
+    fn write_path(&self, out: &mut dyn Write, path: &Path, line: usize, column: usize) -> io::Result<()> {
+        if self.options.color {
+            let colored = self.options.theme.path.paint(&path.display().to_string()).to_string();
+            match &self.options.hyperlink {
+                Some(format) => write!(out, "{}", hyperlink::wrap(&format.url(path, line, column), &colored)),
+                None => write!(out, "{}", colored),
+            }
+        } else {
+            out.write_all(&path_bytes(path))
+        }
+    }
+
+    /// Writes one path per line (or NUL-terminated with `-0`), for `--files`
+    /// and `--match-path`.
+    pub fn write_paths(&self, out: &mut dyn Write, paths: &[PathBuf]) -> io::Result<()> {
+        for path in paths {
+            self.write_path(out, path, 1, 1)?;
+            out.write_all(if self.options.null { b"\0" } else { b"\n" })?;
+        }
+        Ok(())
+    }
+
+    pub fn write_results(&self, out: &mut dyn Write, results: &[SearchResult]) -> io::Result<()> {
+        if self.options.files_with_matches {
+            let mut seen = HashSet::new();
+            for result in results {
+                if seen.insert(&result.path) {
+                    self.write_path(out, &result.path, result.line_number, 1)?;
+                    out.write_all(if self.options.null { b"\0" } else { b"\n" })?;
+                }
+            }
+            return Ok(());
+        }
+
+        for result in results {
+            self.write_result(out, result)?;
+        }
+        Ok(())
+    }
+
+    /// Writes one representative line per cluster, prefixed with the number
+    /// of results in the cluster.
+    pub fn write_clusters(&self, out: &mut dyn Write, results: &[SearchResult], clusters: &[Cluster]) -> io::Result<()> {
+        let width = clusters.iter().map(|cluster| cluster.count.to_string().len()).max().unwrap_or(0);
+        for cluster in clusters {
+            let count = format!("{:>width$}", cluster.count);
+            if self.options.color {
+                write!(out, "{} ", count.cyan().bold())?;
+            } else {
+                write!(out, "{} ", count)?;
+            }
+            self.write_result(out, &results[cluster.representative])?;
+        }
+        Ok(())
+    }
+
+    /// Writes the results under a header per pattern that matched, with its
+    /// number of matches and files, in the order of the patterns.
+    pub fn write_pattern_groups(&self, out: &mut dyn Write, results: &[SearchResult]) -> io::Result<()> {
+        let mut groups: Vec<Vec<&SearchResult>> = vec![Vec::new(); self.pattern_specs.len()];
+        for result in results {
+            groups[result.pattern_index].push(result);
+        }
+        for (index, group) in groups.iter().filter(|group| !group.is_empty()).enumerate() {
+            if index > 0 {
+                writeln!(out)?;
+            }
+            let files: HashSet<&PathBuf> = group.iter().map(|result| &result.path).collect();
+            let header = format!(
+                "--- {}: {} matches in {} files ---",
+                pattern_label(self.pattern_specs, group[0]),
+                group.len(),
+                files.len()
+            );
+            if self.options.color {
+                writeln!(out, "{}", self.options.theme.pattern.paint(&header).bold())?;
+            } else {
+                writeln!(out, "{}", header)?;
+            }
+            for result in group {
+                self.write_result(out, result)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The matches in `line` of every pattern but the exclusion patterns, in
+    /// the order of the line, each with the index of its pattern. Where the
+    /// matches of two patterns overlap, the one that starts first, or else
+    /// the longer one, is kept.
+    pub fn line_matches(&self, line: &str) -> Vec<(usize, Range<usize>)> {
+        let mut matches: Vec<(usize, Range<usize>)> = self.matchers.iter().enumerate()
+            .filter(|(pattern_index, _)| !self.pattern_specs.get(*pattern_index).is_some_and(|spec| spec.negated))
+            .flat_map(|(pattern_index, matcher)| matcher.find_spans(line).into_iter().map(move |span| (pattern_index, span)))
+            .collect();
+        matches.sort_by_key(|(pattern_index, span)| (span.start, Reverse(span.end), *pattern_index));
+        let mut end = 0;
+        matches.retain(|(_, span)| {
+            let kept = span.start >= end;
+            if kept {
+                end = span.end;
+            }
+            kept
+        });
+        matches
+    }
+
+    /// `line` with every match masked, for `--redact`.
+    pub fn masked(&self, line: &str) -> String {
+        rewrite_matches(line, &self.line_matches(line), |_, _, text| mask(text))
+    }
+
+    /// The text of a result as it should be shown, masked with `--redact`.
+    fn result_text<'r>(&self, result: &'r SearchResult) -> Cow<'r, str> {
+        if self.options.redact {
+            Cow::Owned(self.masked(&result.line))
+        } else {
+            Cow::Borrowed(result.line.as_str())
+        }
+    }
+
+    /// The `rule: text` message of an editor error line, kept on one line:
+    /// the lines of a multiline match are joined with a literal `\n`.
+    fn editor_message(&self, result: &SearchResult) -> String {
+        let text = self.result_text(result);
+        let text: Vec<&str> = text.lines().map(str::trim).collect();
+        format!("{}: {}", rule_label(self.pattern_specs, result), text.join("\\n"))
+    }
+
+    /// Writes one `path:line:column:message` line per result, the format
+    /// Vim's default 'errorformat' reads into the quickfix list. The column
+    /// is a byte column, as Vim expects; results spanning several lines
+    /// have none.
+    pub fn write_quickfix(&self, out: &mut dyn Write, results: &[SearchResult]) -> io::Result<()> {
+        for result in results {
+            out.write_all(&path_bytes(&result.path))?;
+            write!(out, ":{}:", result.line_number)?;
+            if match_columns(&self.matchers[result.pattern_index], result).is_some()
+                && let Some(span) = self.matchers[result.pattern_index].find_at(&result.line, 0)
+            {
+                write!(out, "{}:", span.start + 1)?;
+            }
+            writeln!(out, "{}", self.editor_message(result))?;
+        }
+        Ok(())
+    }
+
+    /// Writes one GNU-style `path:line.column-column: message` line per
+    /// result, which Emacs compilation-mode (and `M-x grep`) turns into
+    /// links; merged ranges are written `path:line-line: message`.
+    pub fn write_emacs(&self, out: &mut dyn Write, results: &[SearchResult]) -> io::Result<()> {
+        for result in results {
+            out.write_all(&path_bytes(&result.path))?;
+            match (result.end_line_number, match_columns(&self.matchers[result.pattern_index], result)) {
+                (Some(end), _) => write!(out, ":{}-{}", result.line_number, end)?,
+                (None, Some((start_column, end_column))) => write!(out, ":{}.{}-{}", result.line_number, start_column, end_column)?,
+                (None, None) => write!(out, ":{}", result.line_number)?,
+            }
+            writeln!(out, ": {}", self.editor_message(result))?;
+        }
+        Ok(())
+    }
+
+    /// Writes one JSON object per result (JSON Lines): `path`, `line`,
+    /// `end_line` for merged ranges, `rule`, `severity` for the rules that
+    /// have one, `pattern`, `text`, and `changed_during_scan` for the results
+    /// of a file that changed.
+    pub fn write_json(&self, out: &mut dyn Write, results: &[SearchResult]) -> io::Result<()> {
+        for result in results {
+            let text = self.result_text(result);
+            write!(out, "{{\"path\":{},\"line\":{}", json_string(&result.path.to_string_lossy()), result.line_number)?;
+            if let Some(end) = result.end_line_number {
+                write!(out, ",\"end_line\":{}", end)?;
+            }
+            write!(out, ",\"rule\":{}", json_string(pattern_label(self.pattern_specs, result)))?;
+            let spec = &self.pattern_specs[result.pattern_index];
+            if let Some(severity) = spec.severity {
+                write!(out, ",\"severity\":\"{}\"", severity.as_str())?;
+            }
+            if let Some(description) = &spec.description {
+                write!(out, ",\"description\":{}", json_string(description))?;
+            }
+            if let Some(help_uri) = &spec.help_uri {
+                write!(out, ",\"help_uri\":{}", json_string(help_uri))?;
+            }
+            if !spec.tags.is_empty() {
+                let tags: Vec<String> = spec.tags.iter().map(|tag| json_string(tag)).collect();
+                write!(out, ",\"tags\":[{}]", tags.join(","))?;
+            }
+            if !result.other_patterns.is_empty() {
+                let others: Vec<String> = result.other_patterns.iter()
+                    .map(|&pattern_index| json_string(label_of(self.pattern_specs, result, pattern_index)))
+                    .collect();
+                write!(out, ",\"other_rules\":[{}]", others.join(","))?;
+            }
+            write!(
+                out,
+                ",\"pattern\":{},\"text\":{}",
+                json_string(&result.pattern),
+                json_string(&text),
+            )?;
+            if self.options.changed.contains(&result.path) {
+                write!(out, ",\"changed_during_scan\":true")?;
+            }
+            writeln!(out, "}}")?;
+        }
+        Ok(())
+    }
+
+    /// `text` with its matches highlighted in color output, and masked with
+    /// `--redact`.
+    fn styled<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        if !self.options.color && !self.options.redact {
+            return Cow::Borrowed(text);
+        }
+        let highlighted = self.options.max_matches_per_line.unwrap_or(usize::MAX);
+        Cow::Owned(rewrite_matches(text, &self.line_matches(text), |ordinal, pattern_index, text| {
+            let text = if self.options.redact { mask(text) } else { text.to_string() };
+            if self.options.color && ordinal < highlighted {
+                self.options.theme.match_style(pattern_index).paint(&text).to_string()
+            } else {
+                text
+            }
+        }))
+    }
+
+    /// With `--max-columns`, what to print of a `line` longer than the
+    /// limit: nothing, or its start with `--max-columns-preview`, followed by
+    /// a note on the matches left out.
+    fn long_line<'l>(&self, line: &'l str) -> Option<(&'l str, String)> {
+        let (cut, _) = line.char_indices().nth(self.options.max_columns?)?;
+        let spans: Vec<Range<usize>> = self.line_matches(line).into_iter().map(|(_, span)| span).collect();
+        if !self.options.max_columns_preview {
+            return Some(("", format!("[Omitted long line with {} matches]", spans.len())));
+        }
+        // Never cut through a match, which --redact would then only partly mask.
+        let cut = spans.iter().find(|span: &&Range<usize>| span.contains(&cut)).map_or(cut, |span| span.start);
+        let more = spans.iter().filter(|span| span.start >= cut).count();
+        Some((line[..cut].trim_end(), format!("[... {} more matches]", more)))
+    }
+
+    fn write_result(&self, out: &mut dyn Write, result: &SearchResult) -> io::Result<()> {
+        let separator = if self.options.null { "\0" } else { ":" };
+        let label = rule_label(self.pattern_specs, result);
+        let matcher = &self.matchers[result.pattern_index];
+        let column = match_columns(matcher, result).map_or(1, |(start_column, _)| start_column);
+        self.write_path(out, &result.path, result.line_number, column)?;
+        let text = match self.long_line(result.line.trim()) {
+            Some(("", note)) if self.options.color => note.dimmed().to_string(),
+            Some(("", note)) => note,
+            Some((start, note)) if self.options.color => format!("{} {}", self.styled(start), note.dimmed()),
+            Some((start, note)) => format!("{} {}", self.styled(start), note),
+            None => self.styled(&result.line).trim().to_string(),
+        };
+        let position = match &self.options.byte_offsets {
+            Some(offsets) => offsets.get(&result.path)
+                .and_then(|offsets| offsets.get(&result.line_number))
+                .map_or_else(|| line_label(result), u64::to_string),
+            None if self.options.no_line_number => {
+                return if self.options.color {
+                    writeln!(out, "{}{}:{}", separator, self.options.theme.pattern.paint(&label), text)
+                } else {
+                    writeln!(out, "{}{}:{}", separator, label, text)
+                };
+            }
+            None => line_label(result),
+        };
+        if self.options.color {
+            let theme = &self.options.theme;
+            writeln!(out, "{}{}:{}:{}", separator, theme.line.paint(&position), theme.pattern.paint(&label), text)
+        } else {
+            writeln!(out, "{}{}:{}:{}", separator, position, label, text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Severity;
+    use regex::Regex;
+    use std::path::PathBuf;
+
+    fn result(path: &str, line_number: usize) -> SearchResult {
+        SearchResult { pattern: "match".to_string(), ..SearchResult::new(path, line_number, "  a match  ", 0) }
+    }
+
+    fn render(options: OutputOptions, results: &[SearchResult]) -> Vec<u8> {
+        let pattern_specs = [PatternSpec::new("match")];
+        let matchers = [Regex::new("match").unwrap()];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options };
+        let mut out = Vec::new();
+        printer.write_results(&mut out, results).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_sink() {
+        assert_eq!(Sink::parse("results.txt").unwrap(), Sink { format: None, path: PathBuf::from("results.txt") });
+        assert_eq!(Sink::parse("json=out/results.json").unwrap(), Sink { format: Some(OutputFormat::Json), path: PathBuf::from("out/results.json") });
+        assert_eq!(Sink::parse("SARIF=a=b.sarif").unwrap().format, Some(OutputFormat::Sarif));
+        // Not a format: the whole file name.
+        assert_eq!(Sink::parse("a=b.txt").unwrap(), Sink { format: None, path: PathBuf::from("a=b.txt") });
+        assert!(Sink::parse("html=").is_err());
+        assert!(Sink::parse("").is_err());
+    }
+
+    #[test]
+    fn test_hyperlinked_paths() {
+        let results = [result("/src/a.rs", 3)];
+        let linked = OutputOptions { color: true, hyperlink: Some(HyperlinkFormat::parse("vscode").unwrap()), ..Default::default() };
+        let out = String::from_utf8(render(linked, &results)).unwrap();
+        assert!(out.starts_with("\x1b]8;;vscode://file/src/a.rs:3:5\x1b\\"), "{:?}", out);
+        // Never in plain output, which is meant for files and pipes.
+        let plain = OutputOptions { hyperlink: Some(HyperlinkFormat::default()), ..Default::default() };
+        assert_eq!(render(plain, &results), b"/src/a.rs:3:match:a match\n");
+    }
+
+    #[test]
+    fn test_plain_and_null_separated_lines() {
+        let results = [result("odd:name\n.txt", 3)];
+        assert_eq!(render(OutputOptions::default(), &results), b"odd:name\n.txt:3:match:a match\n");
+        let null = OutputOptions { null: true, ..Default::default() };
+        assert_eq!(render(null, &results), b"odd:name\n.txt\x003:match:a match\n");
+    }
+
+    #[test]
+    fn test_redacted_line() {
+        let redact = OutputOptions { redact: true, ..Default::default() };
+        assert_eq!(render(redact, &[result("a.txt", 3)]), b"a.txt:3:match:a *****\n");
+    }
+
+    #[test]
+    fn test_line_matches() {
+        let negated = PatternSpec { negated: true, ..PatternSpec::new("debug") };
+        let pattern_specs = [PatternSpec::new("key"), PatternSpec::new("api_key=\\w+"), PatternSpec::new("é+"), negated];
+        let matchers = ["key", r"api_key=\w+", "é+", "debug"].map(|pattern| Regex::new(pattern).unwrap());
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions::default() };
+        let line = "debug api_key=abc key ééé";
+        // The longer match of the second pattern hides the one of the first in it.
+        assert_eq!(printer.line_matches(line), [(1, 6..17), (0, 18..21), (2, 22..28)]);
+        assert_eq!(printer.masked(line), "debug ***_***=*** *** ***");
+
+        let redact = OutputOptions { redact: true, ..Default::default() };
+        let printer = Printer { options: redact, ..printer };
+        let result = SearchResult { line: line.to_string(), pattern: "key".to_string(), ..result("a.txt", 1) };
+        let mut out = Vec::new();
+        printer.write_results(&mut out, &[result]).unwrap();
+        assert_eq!(out, "a.txt:1:key:debug ***_***=*** *** ***\n".as_bytes());
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let pattern_specs = [PatternSpec::new("match")];
+        let matchers = [Regex::new("match").unwrap()];
+        let changed = HashSet::from([PathBuf::from("a.txt")]);
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions { redact: true, changed, ..Default::default() } };
+        let mut merged = result("dir/a \"b\".txt", 1);
+        merged.end_line_number = Some(2);
+        let mut out = Vec::new();
+        printer.write_json(&mut out, &[result("a.txt", 3), merged]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"{"path":"a.txt","line":3,"rule":"match","pattern":"match","text":"  a *****  ","changed_during_scan":true}"#, "\n",
+            r#"{"path":"dir/a \"b\".txt","line":1,"end_line":2,"rule":"match","pattern":"match","text":"  a *****  "}"#, "\n",
+        ));
+    }
+
+    #[test]
+    fn test_severity() {
+        let pattern_specs = [PatternSpec { name: Some("keys".to_string()), severity: Some(Severity::Error), ..PatternSpec::new("match") }];
+        let matchers = [Regex::new("match").unwrap()];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions::default() };
+        let results = [result("a.txt", 3)];
+        let mut out = Vec::new();
+        printer.write_results(&mut out, &results).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a.txt:3:keys[error]:a match\n");
+        let mut out = Vec::new();
+        printer.write_json(&mut out, &results).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"path\":\"a.txt\",\"line\":3,\"rule\":\"keys\",\"severity\":\"error\",\"pattern\":\"match\",\"text\":\"  a match  \"}\n");
+        let mut out = Vec::new();
+        printer.write_quickfix(&mut out, &results).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a.txt:3:5:keys[error]: a match\n");
+
+        let pattern_specs = [PatternSpec {
+            description: Some("API keys".to_string()),
+            help_uri: Some("https://example.com/keys".to_string()),
+            tags: vec!["security".to_string()],
+            ..pattern_specs[0].clone()
+        }];
+        let printer = Printer { pattern_specs: &pattern_specs, ..printer };
+        let mut out = Vec::new();
+        printer.write_json(&mut out, &results).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with(concat!(
+            r#"{"path":"a.txt","line":3,"rule":"keys","severity":"error","description":"API keys","#,
+            r#""help_uri":"https://example.com/keys","tags":["security"],"pattern":"match""#,
+        )));
+    }
+
+    #[test]
+    fn test_editor_formats() {
+        let pattern_specs = [PatternSpec::new("match")];
+        let matchers = [Regex::new("match").unwrap()];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions::default() };
+        let mut accented = result("src/é.rs", 4);
+        accented.line = "é match".to_string();
+        let mut multiline = result("a.txt", 1);
+        multiline.line = "first match\n  second".to_string();
+        let mut merged = result("a.txt", 7);
+        merged.end_line_number = Some(9);
+        let results = [accented, multiline, merged];
+
+        let mut out = Vec::new();
+        printer.write_quickfix(&mut out, &results).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+src/é.rs:4:4:match: é match
+a.txt:1:7:match: first match\\nsecond
+a.txt:7:match: a match
+");
+
+        let mut out = Vec::new();
+        printer.write_emacs(&mut out, &results).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+src/é.rs:4.3-8: match: é match
+a.txt:1.7-12: match: first match\\nsecond
+a.txt:7-9: match: a match
+");
+    }
+
+    #[test]
+    fn test_merged_range() {
+        let mut merged = result("LICENSE", 1);
+        merged.end_line_number = Some(3);
+        assert_eq!(render(OutputOptions::default(), &[merged]), b"LICENSE:1-3:match:a match\n");
+    }
+
+    #[test]
+    fn test_clusters() {
+        let results = [result("a.log", 1), result("a.log", 2), result("b.log", 7)];
+        let clusters = [Cluster { representative: 2, count: 12 }, Cluster { representative: 0, count: 1 }];
+        let pattern_specs = [PatternSpec::new("match")];
+        let matchers = [Regex::new("match").unwrap()];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions::default() };
+        let mut out = Vec::new();
+        printer.write_clusters(&mut out, &results, &clusters).unwrap();
+        assert_eq!(out, b"12 b.log:7:match:a match\n 1 a.log:1:match:a match\n");
+    }
+
+    #[test]
+    fn test_pattern_groups() {
+        let pattern_specs = [PatternSpec::new("match"), PatternSpec { name: Some("keys".to_string()), ..PatternSpec::new("KEY") }, PatternSpec::new("a")];
+        let matchers = [Regex::new("match").unwrap(), Regex::new("KEY").unwrap(), Regex::new("a").unwrap()];
+        let key = SearchResult { line: "KEY=1".to_string(), pattern: "KEY".to_string(), pattern_index: 1, ..result("b.env", 3) };
+        let results = [key.clone(), result("a.log", 1), SearchResult { line_number: 9, ..key }, result("c.log", 2)];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions::default() };
+        let mut out = Vec::new();
+        printer.write_pattern_groups(&mut out, &results).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "--- match: 2 matches in 2 files ---\na.log:1:match:a match\nc.log:2:match:a match\n\n--- keys: 2 matches in 1 files ---\nb.env:3:keys:KEY=1\nb.env:9:keys:KEY=1\n");
+    }
+
+    #[test]
+    fn test_max_columns() {
+        let long = SearchResult { line: format!("  {} key=abc123 {}", "x".repeat(20), "key=def456 ".repeat(3)), ..result("app.min.js", 1) };
+        let pattern_specs = [PatternSpec::new("key=\\w+")];
+        let matchers = [Regex::new("key=\\w+").unwrap()];
+        let render = |options: OutputOptions| {
+            let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options };
+            let mut out = Vec::new();
+            printer.write_results(&mut out, &[result("short.js", 2), long.clone()]).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let omitted = OutputOptions { max_columns: Some(25), ..Default::default() };
+        assert_eq!(render(omitted.clone()), "short.js:2:match:a match\napp.min.js:1:match:[Omitted long line with 4 matches]\n");
+        let preview = OutputOptions { max_columns_preview: true, ..omitted };
+        assert!(render(preview.clone()).ends_with("app.min.js:1:match:xxxxxxxxxxxxxxxxxxxx [... 4 more matches]\n"));
+        // The cut moves back to the start of the match it falls in.
+        let redacted = OutputOptions { max_columns: Some(34), redact: true, ..preview };
+        assert!(render(redacted).ends_with(":xxxxxxxxxxxxxxxxxxxx ***=****** [... 3 more matches]\n"));
+    }
+
+    #[test]
+    fn test_line_positions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "ok\r\nmatch one\nok\nmatch two\n").unwrap();
+        let results = [
+            SearchResult { path: path.clone(), ..result("", 2) },
+            SearchResult { path: path.clone(), ..result("", 4) },
+            result("missing.log", 3),
+        ];
+        let shown = |options: OutputOptions| -> Vec<String> {
+            String::from_utf8(render(options, &results)).unwrap().lines()
+                .map(|line| line.rsplit_once(&*path.to_string_lossy()).map_or(line, |(_, rest)| rest).to_string())
+                .collect()
+        };
+        assert_eq!(shown(OutputOptions { no_line_number: true, ..Default::default() }), [":match:a match", ":match:a match", "missing.log:match:a match"]);
+        let byte_offsets = Some(line_offsets(&results));
+        assert_eq!(shown(OutputOptions { byte_offsets, ..Default::default() }), [":4:match:a match", ":17:match:a match", "missing.log:3:match:a match"]);
+    }
+
+    #[test]
+    fn test_line_starts() {
+        assert_eq!(line_starts(b"ok\r\nmatch\n"), [4, 10]);
+        // U+0A0A holds two newline bytes, but no newline code unit.
+        let utf16le: Vec<u8> = [0xFF, 0xFE].into_iter().chain("a\n\u{a0a}\nb".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_eq!(line_starts(&utf16le), [6, 10]);
+        let utf16be: Vec<u8> = [0xFE, 0xFF].into_iter().chain("a\nb".encode_utf16().flat_map(u16::to_be_bytes)).collect();
+        assert_eq!(line_starts(&utf16be), [6]);
+    }
+
+    #[test]
+    fn test_files_with_matches() {
+        let results = [result("a.txt", 1), result("a.txt", 2), result("b.txt", 1)];
+        let files = OutputOptions { files_with_matches: true, ..Default::default() };
+        assert_eq!(render(files, &results), b"a.txt\nb.txt\n");
+        let print0 = OutputOptions { files_with_matches: true, null: true, ..Default::default() };
+        assert_eq!(render(print0, &results), b"a.txt\0b.txt\0");
+    }
+}
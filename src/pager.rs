@@ -0,0 +1,89 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use colored::Colorize;
+
+/// When the results are shown through a pager (`--pager`).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PagerMode {
+    /// When they do not fit in the terminal
+    Auto,
+    /// Whenever the output is a terminal
+    Always,
+    /// Never
+    #[default]
+    Never,
+}
+
+/// The pager used when `$PAGER` is not set; `-R` keeps the colors.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// How many rows of a terminal `columns` wide `text` takes, its long lines
+/// wrapped.
+fn rows(text: &str, columns: usize) -> usize {
+    text.lines()
+        .map(|line| console::measure_text_width(line).div_ceil(columns.max(1)).max(1))
+        .sum()
+}
+
+impl PagerMode {
+    /// Whether `output`, about to be written to stdout, goes through the
+    /// pager.
+    fn pages(self, output: &[u8]) -> bool {
+        if self == PagerMode::Never || !io::stdout().is_terminal() {
+            return false;
+        }
+        match console::Term::stdout().size_checked() {
+            Some((height, width)) if self == PagerMode::Auto => {
+                rows(&String::from_utf8_lossy(output), usize::from(width)) >= usize::from(height)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Writes `output` to stdout, through `$PAGER`, or `less -R` without it,
+/// when `mode` says so. Without a pager to run, it is written as it is.
+pub fn show(mode: PagerMode, output: &[u8]) -> io::Result<()> {
+    if mode.pages(output) {
+        let pager = env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or_else(|| DEFAULT_PAGER.to_string());
+        let mut words = pager.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        let mut command = Command::new(program);
+        command.args(words).stdin(Stdio::piped());
+        // A `$PAGER` of `less` keeps the colors too.
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", "R");
+        }
+        match command.spawn() {
+            Ok(mut child) => {
+                let written = child.stdin.take().map_or(Ok(()), |mut stdin| stdin.write_all(output));
+                child.wait()?;
+                // Leaving the pager before the end closes the pipe.
+                return match written {
+                    Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                    _ => Ok(()),
+                };
+            }
+            Err(e) => eprintln!("{} cannot run the pager `{}`: {}", "warning:".yellow().bold(), pager, e),
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(output)?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows() {
+        assert_eq!(rows("a\nb\n", 80), 2);
+        assert_eq!(rows(&format!("{}\n\n", "x".repeat(100)), 40), 4);
+        // Colors take no room.
+        assert_eq!(rows("\x1b[31mabcd\x1b[0m\n", 4), 1);
+        assert!(!PagerMode::Never.pages(b"a\n"));
+    }
+}
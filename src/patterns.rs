@@ -0,0 +1,179 @@
+use crate::rules::Severity;
+
+/// A pattern read from `-p` or from a patterns file, with its optional name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternSpec {
+    pub name: Option<String>,
+    pub pattern: String,
+    /// An exclusion pattern (`!regex`): lines it matches are never reported.
+    pub negated: bool,
+    /// Given as `name[error]: regex`.
+    pub severity: Option<Severity>,
+    /// What the rule finds, from a `# description:` comment above it.
+    pub description: Option<String>,
+    /// Where to read more about the rule, from a `# help:` comment.
+    pub help_uri: Option<String>,
+    /// From a `# tags: a, b` comment.
+    pub tags: Vec<String>,
+}
+
+impl PatternSpec {
+    pub fn new(pattern: &str) -> PatternSpec {
+        PatternSpec { name: None, pattern: pattern.to_string(), negated: false, severity: None, description: None, help_uri: None, tags: Vec::new() }
+    }
+
+    /// The severity of the matches, `warn` when the rule has none.
+    pub fn level(&self) -> Severity {
+        self.severity.unwrap_or(Severity::Warn)
+    }
+}
+
+/// Parses a patterns file.
+///
+/// Each non-empty line is a pattern. Lines starting with `#` are comments.
+/// A line of the form `name: regex`, where `name` is made of letters,
+/// digits, `_`, `-` or `.` and is followed by a colon and whitespace, gives
+/// the pattern a name that is reported in place of the regex. The name may
+/// be followed by a severity in brackets, `info`, `warn` or `error`, as in
+/// `keys[error]: regex`. Write `\:` for a regex that looks like a name
+/// followed by a literal colon, as in `ERROR\: disk full`: the regex reads
+/// it as a colon. A line starting with `!` is an exclusion pattern: a line
+/// of the searched files matches only if it matches a pattern and no
+/// exclusion pattern. Write `\!` for a regex that starts with a literal `!`.
+///
+/// The comments right above a pattern may describe it for the reports:
+/// `# description: text`, `# help: URL` and `# tags: a, b`.
+pub fn parse_patterns(content: &str) -> Vec<PatternSpec> {
+    parse_patterns_located(content).into_iter().map(|(_, _, spec)| spec).collect()
+}
+
+/// Like `parse_patterns`, with the line number of each pattern and the
+/// column its regex starts at, both counted from 1.
+pub fn parse_patterns_located(content: &str) -> Vec<(usize, usize, PatternSpec)> {
+    let mut located = Vec::new();
+    // The metadata comments seen since the last pattern or blank line.
+    let mut metadata = PatternSpec::new("");
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            metadata = PatternSpec::new("");
+        } else if let Some(comment) = line.trim_start().strip_prefix('#') {
+            let Some((key, value)) = comment.split_once(':') else { continue };
+            let value = value.trim();
+            match key.trim() {
+                "description" => metadata.description = Some(value.to_string()),
+                "help" => metadata.help_uri = Some(value.to_string()),
+                "tags" => metadata.tags = value.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect(),
+                _ => {}
+            }
+        } else {
+            let metadata = std::mem::replace(&mut metadata, PatternSpec::new(""));
+            let spec = PatternSpec { description: metadata.description, help_uri: metadata.help_uri, tags: metadata.tags, ..parse_line(line) };
+            // The regex is what remains of the line after the name and `!`.
+            let column = line.chars().count() - spec.pattern.chars().count() + 1;
+            located.push((index + 1, column, spec));
+        }
+    }
+    located
+}
+
+fn parse_line(line: &str) -> PatternSpec {
+    match line.strip_prefix('!') {
+        Some(rest) => PatternSpec { negated: true, ..parse_named(rest) },
+        None => parse_named(line),
+    }
+}
+
+fn parse_named(line: &str) -> PatternSpec {
+    if let Some((label, rest)) = line.split_once(':') {
+        let (name, severity) = match label.strip_suffix(']').and_then(|label| label.split_once('[')) {
+            Some((name, level)) => match Severity::parse(level) {
+                Some(severity) => (name, Some(severity)),
+                None => return PatternSpec::new(line),
+            },
+            None => (label, None),
+        };
+        let is_name = !name.is_empty()
+            && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if is_name && rest.starts_with(char::is_whitespace) {
+            return PatternSpec { name: Some(name.to_string()), severity, ..PatternSpec::new(rest.trim_start()) };
+        }
+    }
+    PatternSpec::new(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_patterns_named_and_comments() {
+        let content = "# audit rules\n\nsecrets: (?i)api[_-]?key\\s*=\nTODO\n  # indented comment\nhost-name:\tprod\\.example\\.com\n";
+        let patterns = parse_patterns(content);
+        assert_eq!(patterns, vec![
+            PatternSpec { name: Some("secrets".to_string()), ..PatternSpec::new("(?i)api[_-]?key\\s*=") },
+            PatternSpec::new("TODO"),
+            PatternSpec { name: Some("host-name".to_string()), ..PatternSpec::new("prod\\.example\\.com") },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_patterns_with_severity() {
+        let patterns = parse_patterns("keys[error]: api_key\ntodo[info]: TODO\n[abc]: x\nlog[fatal]: x\n!noise[warn]: y\n");
+        assert_eq!(patterns, vec![
+            PatternSpec { name: Some("keys".to_string()), severity: Some(Severity::Error), ..PatternSpec::new("api_key") },
+            PatternSpec { name: Some("todo".to_string()), severity: Some(Severity::Info), ..PatternSpec::new("TODO") },
+            PatternSpec::new("[abc]: x"),
+            PatternSpec::new("log[fatal]: x"),
+            PatternSpec { name: Some("noise".to_string()), negated: true, severity: Some(Severity::Warn), ..PatternSpec::new("y") },
+        ]);
+        assert_eq!(patterns[1].level(), Severity::Info);
+        assert_eq!(PatternSpec::new("x").level(), Severity::Warn);
+    }
+
+    #[test]
+    fn test_parse_patterns_with_metadata() {
+        let content = "# description: AWS access key id\n# help: https://example.com/aws\n# tags: security, aws\naws[error]: AKIA\n# TODO: more rules\nTODO\n# description: dropped\n\nFIXME\n";
+        let patterns = parse_patterns(content);
+        assert_eq!(patterns[0], PatternSpec {
+            name: Some("aws".to_string()),
+            severity: Some(Severity::Error),
+            description: Some("AWS access key id".to_string()),
+            help_uri: Some("https://example.com/aws".to_string()),
+            tags: vec!["security".to_string(), "aws".to_string()],
+            ..PatternSpec::new("AKIA")
+        });
+        assert_eq!(patterns[1..], [PatternSpec::new("TODO"), PatternSpec::new("FIXME")]);
+    }
+
+    #[test]
+    fn test_parse_patterns_colon_inside_regex() {
+        let patterns = parse_patterns("https?://\n(?:foo|bar)\nkey:value\nERROR\\: disk full\n");
+        assert_eq!(patterns, vec![
+            PatternSpec::new("https?://"),
+            PatternSpec::new("(?:foo|bar)"),
+            PatternSpec::new("key:value"),
+            PatternSpec::new("ERROR\\: disk full"),
+        ]);
+        assert!(regex::Regex::new(&patterns[3].pattern).unwrap().is_match("ERROR: disk full"));
+    }
+
+    #[test]
+    fn test_parse_patterns_located() {
+        let located = parse_patterns_located("# rules\nTODO\n\n!noise: x+\n");
+        assert_eq!(located, vec![
+            (2, 1, PatternSpec::new("TODO")),
+            (4, 9, PatternSpec { name: Some("noise".to_string()), negated: true, ..PatternSpec::new("x+") }),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_negated_patterns() {
+        let patterns = parse_patterns("ERROR\n!generated: \\bretrying\\b\n!healthcheck\n\\!important\n");
+        assert_eq!(patterns, vec![
+            PatternSpec::new("ERROR"),
+            PatternSpec { name: Some("generated".to_string()), negated: true, ..PatternSpec::new("\\bretrying\\b") },
+            PatternSpec { negated: true, ..PatternSpec::new("healthcheck") },
+            PatternSpec::new("\\!important"),
+        ]);
+    }
+}
@@ -0,0 +1,258 @@
+use std::ops::Range;
+
+use crate::engine::{Matcher, next_char_boundary};
+
+/// Rules of `--preset pii` that apply whatever the region. Identifiers that
+/// give access to money or to an identity are errors, contact details
+/// warnings.
+const COMMON: &str = r#"
+email[warn]: \b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b
+iban[error]: \b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b
+credit-card[error]: \b\d(?:[ -]?\d){12,18}\b
+"#;
+
+const US: &str = r#"
+phone-us[warn]: (?:\+1[ .-]?)?\(?\b[2-9]\d{2}\)?[ .-]?\d{3}[ .-]\d{4}\b
+ssn-us[error]: \b\d{3}-\d{2}-\d{4}\b
+"#;
+
+const UK: &str = r#"
+phone-uk[warn]: (?:\+44 ?7\d{3}|\b07\d{3}) ?\d{3} ?\d{3}\b
+nino-uk[error]: \b[A-CEGHJ-PR-TW-Z]{2} ?\d{2} ?\d{2} ?\d{2} ?[A-D]\b
+"#;
+
+const FR: &str = r#"
+phone-fr[warn]: (?:\+33 ?|\b0)[1-9](?:[ .-]?\d{2}){4}\b
+nir-fr[error]: \b[12] ?\d{2} ?(?:0[1-9]|1[0-2]|[2-9]\d) ?(?:\d{2}|2[AB]) ?\d{3} ?\d{3} ?\d{2}\b
+"#;
+
+const DE: &str = r#"
+phone-de[warn]: (?:\+49 ?|\b0)1[5-7]\d ?\d{7,8}\b
+steuer-id-de[error]: \b[1-9]\d{10}\b
+"#;
+
+/// Region whose phone formats and national identifiers `--preset pii:<region>` looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Us,
+    Uk,
+    Fr,
+    De,
+}
+
+impl Region {
+    pub const ALL: [Region; 4] = [Region::Us, Region::Uk, Region::Fr, Region::De];
+
+    pub fn parse(value: &str) -> Option<Region> {
+        match value.to_ascii_lowercase().as_str() {
+            "us" => Some(Region::Us),
+            "uk" | "gb" => Some(Region::Uk),
+            "fr" => Some(Region::Fr),
+            "de" => Some(Region::De),
+            _ => None,
+        }
+    }
+
+    fn rules(self) -> &'static str {
+        match self {
+            Region::Us => US,
+            Region::Uk => UK,
+            Region::Fr => FR,
+            Region::De => DE,
+        }
+    }
+}
+
+/// The rules, in the patterns file format, for `region` or for all regions.
+pub fn rules(region: Option<Region>) -> String {
+    let regions = match region {
+        Some(region) => vec![region],
+        None => Region::ALL.to_vec(),
+    };
+    regions.into_iter().fold(COMMON.to_string(), |rules, region| rules + region.rules())
+}
+
+/// The checksum or plausibility check of a rule, if it has one.
+pub fn validator(rule: &str) -> Option<fn(&str) -> bool> {
+    match rule {
+        "iban" => Some(is_valid_iban),
+        "credit-card" => Some(is_valid_card_number),
+        "ssn-us" => Some(is_valid_ssn),
+        "nir-fr" => Some(is_valid_nir),
+        "steuer-id-de" => Some(is_valid_steuer_id),
+        _ => None,
+    }
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// ISO 13616: the rearranged IBAN, letters as numbers, is 1 modulo 97.
+fn is_valid_iban(text: &str) -> bool {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let (head, tail) = compact.split_at(4);
+    let mut remainder = 0u32;
+    for c in tail.chars().chain(head.chars()) {
+        let Some(value) = c.to_digit(36) else { return false };
+        remainder = if value < 10 { (remainder * 10 + value) % 97 } else { (remainder * 100 + value) % 97 };
+    }
+    remainder == 1
+}
+
+/// The Luhn check digit of payment card numbers.
+fn is_valid_card_number(text: &str) -> bool {
+    let digits = digits(text);
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    // Every second digit from the right is doubled, summing the digits of the product.
+    let sum: u32 = digits.iter().rev().enumerate()
+        .map(|(index, &digit)| if index % 2 == 1 { (digit * 2) % 10 + digit * 2 / 10 } else { digit })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Social Security numbers never use area 000, 666 or 900-999, group 00 or serial 0000.
+fn is_valid_ssn(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('-').collect();
+    let [area, group, serial] = parts[..] else { return false };
+    area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+}
+
+/// French social security number: the last two digits are 97 minus the
+/// first thirteen modulo 97, with Corsica's 2A and 2B counted as 19 and 18.
+fn is_valid_nir(text: &str) -> bool {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.len() != 15 {
+        return false;
+    }
+    let (number, key) = compact.split_at(13);
+    let number = number.replace("2A", "19").replace("2B", "18");
+    match (number.parse::<u64>(), key.parse::<u64>()) {
+        (Ok(number), Ok(key)) => 97 - number % 97 == key,
+        _ => false,
+    }
+}
+
+/// German tax ID: ISO 7064 MOD 11,10 check digit, and in the first ten
+/// digits one digit appears two or three times while others appear at most once.
+fn is_valid_steuer_id(text: &str) -> bool {
+    let digits = digits(text);
+    if digits.len() != 11 {
+        return false;
+    }
+    let mut counts = [0; 10];
+    for &digit in &digits[..10] {
+        counts[digit as usize] += 1;
+    }
+    let repeated = counts.iter().filter(|&&count| count > 1).count();
+    if repeated != 1 || counts.iter().any(|&count| count > 3) {
+        return false;
+    }
+    let mut product = 10;
+    for &digit in &digits[..10] {
+        let mut sum = (digit + product) % 10;
+        if sum == 0 {
+            sum = 10;
+        }
+        product = (sum * 2) % 11;
+    }
+    let check = (11 - product) % 10;
+    check == digits[10]
+}
+
+/// Restricts another matcher to the matches that pass a validation, such as
+/// a checksum.
+pub struct ValidatedMatcher<M, F = fn(&str) -> bool> {
+    inner: M,
+    validate: F,
+}
+
+impl<M: Matcher, F: Fn(&str) -> bool + Send + Sync> ValidatedMatcher<M, F> {
+    pub fn new(inner: M, validate: F) -> ValidatedMatcher<M, F> {
+        ValidatedMatcher { inner, validate }
+    }
+}
+
+impl<M: Matcher, F: Fn(&str) -> bool + Send + Sync> Matcher for ValidatedMatcher<M, F> {
+    fn as_str(&self) -> &str {
+        self.inner.as_str()
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while start <= haystack.len() {
+            let Some(span) = self.find_at(haystack, start) else { break };
+            start = if span.is_empty() { next_char_boundary(haystack, span.end) } else { span.end };
+            spans.push(span);
+        }
+        spans
+    }
+
+    fn find_at(&self, haystack: &str, mut start: usize) -> Option<Range<usize>> {
+        // A candidate that fails the validation may hide a valid one that
+        // overlaps it, such as a card number inside a longer run of digits:
+        // the search resumes just after its start.
+        while start <= haystack.len() {
+            let span = self.inner.find_at(haystack, start)?;
+            if (self.validate)(&haystack[span.clone()]) {
+                return Some(span);
+            }
+            start = next_char_boundary(haystack, span.start);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::parse_patterns;
+    use regex::Regex;
+
+    #[test]
+    fn test_validators() {
+        assert!(is_valid_iban("DE89 3704 0044 0532 0130 00"));
+        assert!(is_valid_iban("GB82WEST12345698765432"));
+        assert!(!is_valid_iban("GB82WEST12345698765433"));
+        assert!(is_valid_card_number("4111 1111 1111 1111"));
+        assert!(!is_valid_card_number("4111 1111 1111 1112"));
+        assert!(is_valid_ssn("123-45-6789"));
+        assert!(!is_valid_ssn("666-45-6789"));
+        assert!(is_valid_nir("2 69 05 49 588 157 80"));
+        assert!(!is_valid_nir("2 69 05 49 588 157 81"));
+        assert!(is_valid_steuer_id("86095742719"));
+        assert!(!is_valid_steuer_id("86095742718"));
+    }
+
+    #[test]
+    fn test_pii_rules() {
+        let specs = parse_patterns(&rules(Some(Region::Fr)));
+        let names: Vec<&str> = specs.iter().filter_map(|spec| spec.name.as_deref()).collect();
+        assert_eq!(names, vec!["email", "iban", "credit-card", "phone-fr", "nir-fr"]);
+
+        let matchers: Vec<ValidatedMatcher<Regex>> = specs.iter()
+            .map(|spec| ValidatedMatcher::new(Regex::new(&spec.pattern).unwrap(), validator(spec.name.as_deref().unwrap()).unwrap_or(|_| true)))
+            .collect();
+        let hits = |line: &str| -> Vec<&str> {
+            specs.iter().zip(&matchers).filter(|(_, m)| m.is_match(line)).filter_map(|(spec, _)| spec.name.as_deref()).collect()
+        };
+        assert_eq!(hits("contact: jane.doe@example.com, 06 12 34 56 78"), vec!["email", "phone-fr"]);
+        assert_eq!(hits("virement FR76 3000 6000 0112 3456 7890 189"), vec!["iban"]);
+        assert_eq!(hits("faux IBAN FR76 3000 6000 0112 3456 7890 188"), Vec::<&str>::new());
+        assert_eq!(hits("NIR 2 69 05 49 588 157 80"), vec!["nir-fr"]);
+        // The longest candidate fails the checksum; the card inside it does not.
+        let card = &matchers[2];
+        assert!(!is_valid_card_number("1234 4111 1111 1111"));
+        assert_eq!(card.find_spans("carte 1234 4111 1111 1111 1111"), vec![11..30]);
+    }
+}
@@ -0,0 +1,103 @@
+use aho_corasick::AhoCorasick;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+
+use crate::engine::CaseMode;
+
+/// Literals shorter than this are in too many lines for looking for them
+/// first to pay off.
+const MIN_LITERAL_LEN: usize = 3;
+
+/// Finds the lines that may match before the patterns are run on them: the
+/// lines that contain one of the literals that every match of a pattern
+/// starts with. The lines without any are skipped at the speed of a
+/// substring search, instead of going through each pattern.
+#[derive(Debug)]
+pub struct Prefilter {
+    literals: AhoCorasick,
+}
+
+/// The literals that every match of `pattern` starts with, or `None` when
+/// there are none to look for, such as for `\w+` or `a|\d`.
+fn required_literals(pattern: &str, ignore_case: bool) -> Option<Vec<Vec<u8>>> {
+    let hir = regex_syntax::ParserBuilder::new().case_insensitive(ignore_case).build().parse(pattern).ok()?;
+    let mut seq = Extractor::new().kind(ExtractKind::Prefix).extract(&hir);
+    seq.optimize_for_prefix_by_preference();
+    let literals = seq.literals()?;
+    if literals.is_empty() || literals.iter().any(|literal| literal.len() < MIN_LITERAL_LEN) {
+        return None;
+    }
+    Some(literals.iter().map(|literal| literal.as_bytes().to_vec()).collect())
+}
+
+impl Prefilter {
+    /// The prefilter of the patterns that are not exclusion patterns, or
+    /// `None` if one of them has no literals to look for.
+    pub fn new(patterns: &[String], negated: &[bool], case: CaseMode) -> Option<Prefilter> {
+        let mut literals = Vec::new();
+        for (index, pattern) in patterns.iter().enumerate() {
+            if !negated.get(index).copied().unwrap_or(false) {
+                literals.extend(required_literals(pattern, case.ignore_case(pattern))?);
+            }
+        }
+        if literals.is_empty() {
+            return None;
+        }
+        AhoCorasick::new(&literals).ok().map(|literals| Prefilter { literals })
+    }
+
+    /// The lines of `content` that contain one of the literals, as
+    /// `str::lines` gives them, each with its index and the line before it.
+    pub fn candidate_lines<'a>(&'a self, content: &'a str) -> impl Iterator<Item = (usize, Option<&'a str>, &'a str)> + 'a {
+        let bytes = content.as_bytes();
+        let mut from = 0;
+        let mut counted = 0;
+        let mut index = 0;
+        std::iter::from_fn(move || {
+            let found = self.literals.find(&bytes[from..])?;
+            let at = from + found.start();
+            let start = memchr::memrchr(b'\n', &bytes[..at]).map_or(0, |newline| newline + 1);
+            let end = memchr::memchr(b'\n', &bytes[at..]).map_or(bytes.len(), |newline| at + newline);
+            index += memchr::memchr_iter(b'\n', &bytes[counted..start]).count();
+            counted = start;
+            from = (end + 1).min(bytes.len());
+            let previous = start.checked_sub(1).map(|previous_end| {
+                let previous_start = memchr::memrchr(b'\n', &bytes[..previous_end]).map_or(0, |newline| newline + 1);
+                trim_cr(&content[previous_start..previous_end])
+            });
+            Some((index, previous, trim_cr(&content[start..end])))
+        })
+    }
+}
+
+/// `line` without the `\r` of a CRLF line ending, as `str::lines` gives it.
+fn trim_cr(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_literals() {
+        assert_eq!(required_literals("ERROR: .*", false), Some(vec![b"ERROR: ".to_vec()]));
+        assert_eq!(required_literals("foo|bar", false), Some(vec![b"foo".to_vec(), b"bar".to_vec()]));
+        let literals = required_literals("key", true).unwrap();
+        assert!(literals.contains(&b"KEY".to_vec()) && literals.contains(&b"kEy".to_vec()));
+        assert_eq!(required_literals(r"\w+@example", false), None);
+        assert_eq!(required_literals("ab|cde", false), None);
+        assert_eq!(required_literals("(", false), None);
+
+        assert!(Prefilter::new(&["ERROR".to_string(), r"\d+".to_string()], &[false, true], CaseMode::Sensitive).is_some());
+        assert!(Prefilter::new(&["ERROR".to_string(), r"\d+".to_string()], &[false, false], CaseMode::Sensitive).is_none());
+    }
+
+    #[test]
+    fn test_candidate_lines() {
+        let prefilter = Prefilter::new(&["ERROR".to_string(), "WARN".to_string()], &[], CaseMode::Sensitive).unwrap();
+        let content = "INFO a\r\nERROR b ERROR\r\nWARN c\n\nINFO d\nlast ERROR";
+        let lines: Vec<(usize, Option<&str>, &str)> = prefilter.candidate_lines(content).collect();
+        assert_eq!(lines, [(1, Some("INFO a"), "ERROR b ERROR"), (2, Some("ERROR b ERROR"), "WARN c"), (5, Some("INFO d"), "last ERROR")]);
+        assert_eq!(prefilter.candidate_lines("ERROR\n").collect::<Vec<_>>(), [(0, None, "ERROR")]);
+    }
+}
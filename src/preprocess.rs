@@ -0,0 +1,92 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ignore::overrides::{Override, OverrideBuilder};
+
+use crate::longpath;
+
+/// A command files are piped through before they are searched (`--pre`).
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    command: PathBuf,
+    /// The files it applies to (`--pre-glob`), all of them if `None`.
+    globs: Option<Override>,
+}
+
+impl Preprocessor {
+    pub fn new(command: PathBuf, globs: &[String]) -> Result<Preprocessor, ignore::Error> {
+        let globs = if globs.is_empty() {
+            None
+        } else {
+            let mut overrides = OverrideBuilder::new(".");
+            for glob in globs {
+                overrides.add(glob)?;
+            }
+            Some(overrides.build()?)
+        };
+        Ok(Preprocessor { command, globs })
+    }
+
+    pub fn applies_to(&self, path: &Path) -> bool {
+        self.globs.as_ref().is_none_or(|globs| globs.matched(path, false).is_whitelist())
+    }
+
+    /// Runs the command with `path` as its argument and the file on its
+    /// standard input, and returns what it writes on its standard output.
+    /// The command failing makes the file unreadable, with the first line
+    /// of its standard error as the reason.
+    pub fn run(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let output = Command::new(&self.command)
+            .arg(path)
+            .stdin(fs::File::open(longpath::for_opening(path))?)
+            .output()
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot run `{}`: {}", self.command.display(), e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().find(|line| !line.trim().is_empty()).map(|line| format!(": {}", line.trim())).unwrap_or_default();
+            return Err(io::Error::other(format!("`{}` failed with {}{}", self.command.display(), output.status, reason)));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("notes.enc");
+        fs::write(&file, "secret\n").unwrap();
+        let upper = Preprocessor::new(script(dir.path(), "upper", "tr a-z A-Z"), &[]).unwrap();
+        assert_eq!(upper.run(&file).unwrap(), b"SECRET\n");
+        let named = Preprocessor::new(script(dir.path(), "named", r#"echo "$1""#), &[]).unwrap();
+        assert_eq!(named.run(&file).unwrap(), format!("{}\n", file.display()).into_bytes());
+
+        let failing = Preprocessor::new(script(dir.path(), "failing", "echo 'bad key' >&2; exit 3"), &[]).unwrap();
+        let error = failing.run(&file).unwrap_err().to_string();
+        assert!(error.ends_with("failed with exit status: 3: bad key"), "{}", error);
+        assert!(Preprocessor::new(dir.path().join("missing"), &[]).unwrap().run(&file).unwrap_err().to_string().starts_with("cannot run"));
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let pre = Preprocessor::new(PathBuf::from("pdftotext"), &["*.pdf".to_string(), "!private/**".to_string()]).unwrap();
+        assert!(pre.applies_to(Path::new("docs/report.pdf")));
+        assert!(!pre.applies_to(Path::new("docs/report.txt")));
+        assert!(!pre.applies_to(Path::new("private/report.pdf")));
+        assert!(Preprocessor::new(PathBuf::from("cat"), &[]).unwrap().applies_to(Path::new("any.txt")));
+    }
+}
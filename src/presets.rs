@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::SearchResult;
+use crate::lines::LineSelection;
+use crate::patterns::{self, PatternSpec};
+use crate::pii::{self, Region};
+
+/// Lines searched by `--preset licenses` unless a line selection is given:
+/// license notices live in the header of each file.
+const LICENSE_HEADER_LINES: usize = 30;
+
+/// Rules of `--preset licenses`, in the patterns file format. Each rule is
+/// named after the SPDX identifier of the license it detects, from either
+/// an `SPDX-License-Identifier` tag or the usual wording of the notice.
+const LICENSES: &str = r#"
+AGPL-3.0: SPDX-License-Identifier:\s*AGPL-3\.0|GNU Affero General Public License
+LGPL: SPDX-License-Identifier:\s*LGPL-|GNU (Lesser|Library) General Public License
+GPL-3.0: SPDX-License-Identifier:\s*GPL-3\.0|GNU General Public License.*version 3|either version 3 of the License
+GPL-2.0: SPDX-License-Identifier:\s*GPL-2\.0|GNU General Public License.*version 2|either version 2 of the License
+Apache-2.0: SPDX-License-Identifier:\s*Apache-2\.0|Licensed under the Apache License, Version 2\.0
+MPL-2.0: SPDX-License-Identifier:\s*MPL-2\.0|Mozilla Public License,? v(ersion|\.) ?2\.0
+BSD-3-Clause: SPDX-License-Identifier:\s*BSD-3-Clause|Neither the name of .* nor the names of its contributors
+BSD-2-Clause: SPDX-License-Identifier:\s*BSD-2-Clause
+MIT: SPDX-License-Identifier:\s*MIT\b|Permission is hereby granted, free of charge, to any person
+ISC: SPDX-License-Identifier:\s*ISC\b|Permission to use, copy, modify, and/or distribute this software for any
+Unlicense: SPDX-License-Identifier:\s*Unlicense|This is free and unencumbered software released into the public domain
+"#;
+
+/// A built-in ruleset (`--preset`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// License notices and SPDX identifiers, searched in the first lines of each file.
+    Licenses,
+    /// Personal data: emails, IBANs, card numbers, and the phone numbers and
+    /// national identifiers of one region, or of all of them.
+    Pii(Option<Region>),
+}
+
+impl Preset {
+    /// Parses `licenses`, `pii` or `pii:<region>` (`us`, `uk`, `fr`, `de`).
+    pub fn parse(value: &str) -> Result<Preset, String> {
+        let (name, region) = match value.split_once(':') {
+            Some((name, region)) => (name, Some(region)),
+            None => (value, None),
+        };
+        match (name, region) {
+            ("licenses", None) => Ok(Preset::Licenses),
+            ("pii", None) => Ok(Preset::Pii(None)),
+            ("pii", Some(region)) => Region::parse(region)
+                .map(|region| Preset::Pii(Some(region)))
+                .ok_or_else(|| format!("unknown region `{}`, expected one of us, uk, fr, de", region)),
+            _ => Err(format!("unknown preset `{}`, expected licenses or pii[:region]", value)),
+        }
+    }
+
+    pub fn patterns(self) -> Vec<PatternSpec> {
+        match self {
+            Preset::Licenses => patterns::parse_patterns(LICENSES),
+            Preset::Pii(region) => patterns::parse_patterns(&pii::rules(region)),
+        }
+    }
+
+    /// The part of each file searched when the user did not choose one.
+    pub fn default_lines(self) -> Option<LineSelection> {
+        match self {
+            Preset::Licenses => Some(LineSelection::head(LICENSE_HEADER_LINES)),
+            Preset::Pii(_) => None,
+        }
+    }
+
+    /// The validation a match of `spec` must pass on top of its regex.
+    pub fn validator(self, spec: &PatternSpec) -> Option<fn(&str) -> bool> {
+        match self {
+            Preset::Licenses => None,
+            Preset::Pii(_) => pii::validator(spec.name.as_deref()?),
+        }
+    }
+
+    /// Whether matches are masked in the output, as personal data should be.
+    pub fn redacts(self) -> bool {
+        matches!(self, Preset::Pii(_))
+    }
+}
+
+/// Writes how many files carry each license, most common first, and how many
+/// searched files have none.
+pub fn write_license_summary(
+    out: &mut dyn Write,
+    pattern_specs: &[PatternSpec],
+    results: &[SearchResult],
+    files_searched: usize,
+) -> io::Result<()> {
+    let mut files_per_license: HashMap<usize, HashSet<_>> = HashMap::new();
+    for result in results {
+        files_per_license.entry(result.pattern_index).or_default().insert(&result.path);
+    }
+    let licensed: HashSet<_> = results.iter().map(|result| &result.path).collect();
+
+    let mut counts: Vec<(&str, usize)> = files_per_license.iter()
+        .map(|(&index, files)| (pattern_specs[index].name.as_deref().unwrap_or(&pattern_specs[index].pattern), files.len()))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    writeln!(out, "\n--- Licenses ---")?;
+    for (license, files) in counts {
+        writeln!(out, "{}: {} files", license, files)?;
+    }
+    writeln!(out, "No license found: {} files", files_searched.saturating_sub(licensed.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use crate::rules::Severity;
+
+    #[test]
+    fn test_parse_preset() {
+        assert_eq!(Preset::parse("licenses"), Ok(Preset::Licenses));
+        assert_eq!(Preset::parse("pii"), Ok(Preset::Pii(None)));
+        assert_eq!(Preset::parse("pii:fr"), Ok(Preset::Pii(Some(Region::Fr))));
+        assert!(Preset::parse("pii:xx").is_err());
+        assert!(Preset::parse("licenses:fr").is_err());
+    }
+
+    #[test]
+    fn test_pii_severities() {
+        let pattern_specs = Preset::Pii(None).patterns();
+        let level = |name: &str| pattern_specs.iter().find(|spec| spec.name.as_deref() == Some(name)).and_then(|spec| spec.severity);
+        assert_eq!(level("iban"), Some(Severity::Error));
+        assert_eq!(level("nir-fr"), Some(Severity::Error));
+        assert_eq!(level("email"), Some(Severity::Warn));
+        assert!(pattern_specs.iter().all(|spec| spec.severity.is_some()));
+    }
+
+    #[test]
+    fn test_license_rules() {
+        let rules = Preset::Licenses.patterns();
+        let regexes: Vec<Regex> = rules.iter().map(|rule| Regex::new(&rule.pattern).unwrap()).collect();
+        let detect = |line: &str| {
+            regexes.iter().position(|re| re.is_match(line)).and_then(|index| rules[index].name.as_deref())
+        };
+        assert_eq!(detect("// SPDX-License-Identifier: MIT OR Apache-2.0"), Some("MIT"));
+        assert_eq!(detect("# SPDX-License-Identifier: GPL-2.0-or-later"), Some("GPL-2.0"));
+        assert_eq!(detect(" * Licensed under the Apache License, Version 2.0 (the \"License\");"), Some("Apache-2.0"));
+        assert_eq!(detect(" * GNU Lesser General Public License for more details."), Some("LGPL"));
+        assert_eq!(detect("Permission is hereby granted, free of charge, to any person obtaining a copy"), Some("MIT"));
+        assert_eq!(detect("fn main() {}"), None);
+    }
+
+    #[test]
+    fn test_license_summary() {
+        let pattern_specs = Preset::Licenses.patterns();
+        let mit = pattern_specs.iter().position(|spec| spec.name.as_deref() == Some("MIT")).unwrap();
+        let apache = pattern_specs.iter().position(|spec| spec.name.as_deref() == Some("Apache-2.0")).unwrap();
+        let result = |path: &str, pattern_index: usize| SearchResult::new(path, 1, "", pattern_index);
+        let results = [result("a.rs", mit), result("a.rs", mit), result("b.rs", mit), result("b.rs", apache)];
+        let mut out = Vec::new();
+        write_license_summary(&mut out, &pattern_specs, &results, 5).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\n--- Licenses ---\nMIT: 2 files\nApache-2.0: 1 files\nNo license found: 3 files\n");
+    }
+}
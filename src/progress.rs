@@ -0,0 +1,190 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use ignore::{WalkBuilder, WalkState};
+use indicatif::ProgressBar;
+
+use crate::filters::FileFilter;
+
+/// How often `--progress-file` is rewritten.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// The state of a search at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub files_done: u64,
+    /// Unknown until the files to search have all been counted.
+    pub files_total: Option<u64>,
+    pub elapsed: Duration,
+    pub finished: bool,
+}
+
+impl Snapshot {
+    pub fn files_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 { self.files_done as f64 / seconds } else { 0.0 }
+    }
+
+    /// The time left at the current throughput, once the total is known.
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.files_total?.saturating_sub(self.files_done);
+        if remaining == 0 {
+            return Some(Duration::ZERO);
+        }
+        let rate = self.files_per_second();
+        (rate > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    pub fn json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        format!(
+            "{{\"files_done\":{},\"files_total\":{},\"elapsed_seconds\":{},\"files_per_second\":{},\"eta_seconds\":{},\"finished\":{}}}\n",
+            self.files_done,
+            optional(self.files_total.map(|total| total.to_string())),
+            self.elapsed.as_secs_f64(),
+            self.files_per_second(),
+            optional(self.eta().map(|eta| eta.as_secs_f64().to_string())),
+            self.finished,
+        )
+    }
+}
+
+/// Replaces `path` with `contents` through a rename, so that a reader, or a
+/// scan interrupted with Ctrl-C, never leaves a half-written file behind.
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
+
+/// Writes a [`Snapshot`] of the search to a file every second, for
+/// orchestration systems to monitor long scans (`--progress-file`).
+pub struct ProgressFile {
+    path: PathBuf,
+    stop: mpsc::Sender<()>,
+    stop_count: Arc<AtomicBool>,
+    writer: JoinHandle<()>,
+    state: Arc<State>,
+}
+
+struct State {
+    started: Instant,
+    pb: ProgressBar,
+    /// Files counted so far, and whether the count is complete.
+    files_total: AtomicU64,
+    counted: AtomicBool,
+}
+
+impl State {
+    fn snapshot(&self, finished: bool) -> Snapshot {
+        let files_done = self.pb.position();
+        Snapshot {
+            files_done,
+            files_total: if finished {
+                Some(files_done)
+            } else {
+                self.counted.load(Ordering::Relaxed).then(|| self.files_total.load(Ordering::Relaxed))
+            },
+            elapsed: self.started.elapsed(),
+            finished,
+        }
+    }
+}
+
+impl ProgressFile {
+    /// Starts counting the files the walk will search, in the background,
+    /// and writing snapshots of `pb`, which counts the files searched.
+    pub fn start(path: PathBuf, walk_builder: &WalkBuilder, file_filter: FileFilter, pb: ProgressBar) -> ProgressFile {
+        let state = Arc::new(State {
+            started: Instant::now(),
+            pb,
+            files_total: AtomicU64::new(0),
+            counted: AtomicBool::new(false),
+        });
+        let stop_count = Arc::new(AtomicBool::new(false));
+
+        let walk = walk_builder.build_parallel();
+        let counter_state = Arc::clone(&state);
+        let counter_stop = Arc::clone(&stop_count);
+        thread::spawn(move || {
+            walk.run(|| {
+                let (state, stop, file_filter) = (&counter_state, &counter_stop, &file_filter);
+                Box::new(move |entry| {
+                    if stop.load(Ordering::Relaxed) {
+                        return WalkState::Quit;
+                    }
+                    if let Ok(entry) = entry
+                        && entry.file_type().is_some_and(|ft| ft.is_file())
+                        && (file_filter.is_empty() || entry.metadata().is_ok_and(|metadata| file_filter.accepts(&metadata)))
+                    {
+                        state.files_total.fetch_add(1, Ordering::Relaxed);
+                    }
+                    WalkState::Continue
+                })
+            });
+            if !counter_stop.load(Ordering::Relaxed) {
+                counter_state.counted.store(true, Ordering::Relaxed);
+            }
+        });
+
+        let (stop, stopped) = mpsc::channel();
+        let writer_state = Arc::clone(&state);
+        let writer_path = path.clone();
+        let writer = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(INTERVAL) {
+                if let Err(e) = write_atomically(&writer_path, writer_state.snapshot(false).json()) {
+                    eprintln!("Failed to write progress file {}: {}", writer_path.display(), e);
+                }
+            }
+        });
+
+        ProgressFile { path, stop, stop_count, writer, state }
+    }
+
+    /// Stops the background threads and writes the final snapshot.
+    pub fn finish(self) -> io::Result<()> {
+        self.stop_count.store(true, Ordering::Relaxed);
+        let _ = self.stop.send(());
+        let _ = self.writer.join();
+        write_atomically(&self.path, self.state.snapshot(true).json())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_json() {
+        let snapshot = Snapshot { files_done: 50, files_total: Some(200), elapsed: Duration::from_secs(10), finished: false };
+        assert_eq!(snapshot.eta(), Some(Duration::from_secs(30)));
+        assert_eq!(
+            snapshot.json(),
+            "{\"files_done\":50,\"files_total\":200,\"elapsed_seconds\":10,\"files_per_second\":5,\"eta_seconds\":30,\"finished\":false}\n"
+        );
+        let counting = Snapshot { files_total: None, ..snapshot };
+        assert_eq!(counting.eta(), None);
+        assert!(counting.json().contains("\"files_total\":null,"));
+    }
+
+    #[test]
+    fn test_progress_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let path = dir.path().join("progress.json");
+        let pb = ProgressBar::hidden();
+        let progress = ProgressFile::start(path.clone(), &WalkBuilder::new(dir.path()), FileFilter::default(), pb.clone());
+        pb.inc(1);
+        progress.finish().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("{\"files_done\":1,\"files_total\":1,"));
+        assert!(contents.ends_with("\"finished\":true}\n"));
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use crossbeam_deque::{Injector, Steal};
+
+/// The files the walker threads found and no searcher took yet
+/// (`--walk-threads`). Whichever searcher is free takes the next one, so
+/// that a slow file only holds the searcher that is on it.
+pub struct WorkQueue<T> {
+    injector: Injector<T>,
+    /// How many searchers wait for work, so that a push only takes the lock
+    /// when one of them has to be woken.
+    waiting: AtomicUsize,
+    closed: AtomicBool,
+    lock: Mutex<()>,
+    pushed: Condvar,
+}
+
+impl<T> WorkQueue<T> {
+    pub fn new() -> WorkQueue<T> {
+        WorkQueue {
+            injector: Injector::new(),
+            waiting: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            pushed: Condvar::new(),
+        }
+    }
+
+    pub fn push(&self, item: T) {
+        self.injector.push(item);
+        if self.waiting.load(Ordering::SeqCst) > 0 {
+            let _lock = self.lock.lock().unwrap();
+            self.pushed.notify_one();
+        }
+    }
+
+    /// Tells the searchers that nothing more is coming: they stop once the
+    /// queue is empty.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _lock = self.lock.lock().unwrap();
+        self.pushed.notify_all();
+    }
+
+    /// The next item, waiting for one if need be, or `None` once the queue
+    /// is closed and empty.
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            match self.injector.steal() {
+                Steal::Success(item) => return Some(item),
+                Steal::Retry => continue,
+                Steal::Empty => {}
+            }
+            let lock = self.lock.lock().unwrap();
+            self.waiting.fetch_add(1, Ordering::SeqCst);
+            // Looked at again once counted as waiting: an item pushed since
+            // is either seen here or wakes this thread up.
+            let closed = self.closed.load(Ordering::SeqCst);
+            if self.injector.is_empty() && !closed {
+                drop(self.pushed.wait(lock).unwrap());
+            }
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+            if closed && self.injector.is_empty() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_work_queue() {
+        let queue = WorkQueue::new();
+        let taken = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while let Some(item) = queue.pop() {
+                        taken.lock().unwrap().push(item);
+                    }
+                });
+            }
+            for item in 0..1000 {
+                queue.push(item);
+            }
+            queue.close();
+        });
+        let mut taken = taken.into_inner().unwrap();
+        taken.sort();
+        assert_eq!(taken, (0..1000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args as ClapArgs;
+
+use crate::SearchResult;
+use crate::engine::Matcher;
+use crate::error::FinderError;
+use crate::filter::Record;
+use crate::json::json_string;
+use crate::output::{self, Printer, line_label, match_columns, pattern_label};
+use crate::patterns::PatternSpec;
+
+/// Styles of `--format html`, inlined so that the page stands alone.
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; }
+table { border-collapse: collapse; }
+th, td { padding: 0.2em 0.8em; text-align: left; vertical-align: top; }
+.rules th, .rules td { border-bottom: 1px solid #ddd; }
+details { margin: 0.4em 0; }
+summary { cursor: pointer; font-family: monospace; font-size: 1.05em; }
+summary .count { color: #777; font-family: system-ui, sans-serif; font-size: 0.9em; }
+.matches td { font-family: monospace; }
+.line { color: #777; text-align: right; }
+.rule { color: #8a2be2; }
+.text { white-space: pre-wrap; }
+mark { background: #ffe066; }
+";
+
+/// `text` with the characters that are markup in XML and HTML escaped, and
+/// the control characters XML 1.0 cannot hold replaced.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The results grouped by file, the files in the order the results come in.
+fn by_file(results: &[SearchResult]) -> Vec<(&Path, Vec<&SearchResult>)> {
+    let mut files: Vec<(&Path, Vec<&SearchResult>)> = Vec::new();
+    let mut positions: HashMap<&Path, usize> = HashMap::new();
+    for result in results {
+        let position = *positions.entry(&result.path).or_insert_with(|| {
+            files.push((&result.path, Vec::new()));
+            files.len() - 1
+        });
+        files[position].1.push(result);
+    }
+    files
+}
+
+/// The escaped line of a result with its matches in `<mark>`, masked with
+/// `--redact`.
+fn highlighted<M: Matcher>(printer: &Printer<M>, result: &SearchResult) -> String {
+    let line = &result.line;
+    let mut html = String::with_capacity(line.len());
+    let mut last_end = 0;
+    for (_, span) in printer.line_matches(line) {
+        let text = &line[span.clone()];
+        let text = if printer.options.redact { output::mask(text) } else { text.to_string() };
+        html.push_str(&escape(&line[last_end..span.start]));
+        html.push_str(&format!("<mark>{}</mark>", escape(&text)));
+        last_end = span.end;
+    }
+    html.push_str(&escape(&line[last_end..]));
+    html
+}
+
+/// Writes a standalone HTML page: a summary of the search with the number
+/// of matches and files per rule, then one collapsible section per file
+/// with its matched lines.
+pub fn write_html<M: Matcher>(out: &mut dyn Write, printer: &Printer<M>, results: &[SearchResult], files_searched: usize) -> io::Result<()> {
+    let files = by_file(results);
+    writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>finder report</title>")?;
+    writeln!(out, "<style>\n{}</style>\n</head>\n<body>\n<h1>finder report</h1>", STYLE)?;
+    writeln!(
+        out,
+        "<p class=\"summary\">{} matches in {} files, of {} files searched, by finder {}.</p>",
+        results.len(),
+        files.len(),
+        files_searched,
+        env!("CARGO_PKG_VERSION")
+    )?;
+
+    // Rules in the order they first matched, with their match and file
+    // counts, described when the rules file describes them.
+    let mut rules: Vec<(&str, &PatternSpec, usize, HashSet<&Path>)> = Vec::new();
+    for result in results {
+        let label = pattern_label(printer.pattern_specs, result);
+        let position = match rules.iter().position(|(known, _, _, _)| *known == label) {
+            Some(position) => position,
+            None => {
+                rules.push((label, &printer.pattern_specs[result.pattern_index], 0, HashSet::new()));
+                rules.len() - 1
+            }
+        };
+        rules[position].2 += 1;
+        rules[position].3.insert(&result.path);
+    }
+    if !rules.is_empty() {
+        writeln!(out, "<table class=\"rules\">\n<tr><th>Rule</th><th>Description</th><th>Matches</th><th>Files</th></tr>")?;
+        for (label, spec, matches, paths) in rules {
+            let rule = match &spec.help_uri {
+                Some(help_uri) => format!("<a href=\"{}\">{}</a>", escape(help_uri), escape(label)),
+                None => escape(label),
+            };
+            let mut description = escape(spec.description.as_deref().unwrap_or(""));
+            if !spec.tags.is_empty() {
+                description.push_str(&format!(" <span class=\"tags\">{}</span>", escape(&spec.tags.join(", "))));
+            }
+            writeln!(out, "<tr><td class=\"rule\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", rule, description, matches, paths.len())?;
+        }
+        writeln!(out, "</table>")?;
+    }
+
+    for (path, file_results) in files {
+        writeln!(
+            out,
+            "<details open>\n<summary>{} <span class=\"count\">({} matches)</span></summary>\n<table class=\"matches\">",
+            escape(&path.to_string_lossy()),
+            file_results.len()
+        )?;
+        for result in file_results {
+            writeln!(
+                out,
+                "<tr><td class=\"line\">{}</td><td class=\"rule\">{}</td><td class=\"text\">{}</td></tr>",
+                line_label(result),
+                escape(pattern_label(printer.pattern_specs, result)),
+                highlighted(printer, result)
+            )?;
+        }
+        writeln!(out, "</table>\n</details>")?;
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+/// Writes the results as an XML document: one `<file>` element per file
+/// holding one `<match>` element per result, the line as its text.
+pub fn write_xml<M: Matcher>(out: &mut dyn Write, printer: &Printer<M>, results: &[SearchResult], files_searched: usize) -> io::Result<()> {
+    let files = by_file(results);
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<finder version=\"{}\">", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "  <summary matches=\"{}\" files=\"{}\" searched=\"{}\"/>", results.len(), files.len(), files_searched)?;
+    for (path, file_results) in files {
+        writeln!(out, "  <file path=\"{}\">", escape(&path.to_string_lossy()))?;
+        for result in file_results {
+            write!(out, "    <match line=\"{}\"", result.line_number)?;
+            if let Some(end) = result.end_line_number {
+                write!(out, " end-line=\"{}\"", end)?;
+            }
+            if let Some((start_column, end_column)) = match_columns(&printer.matchers[result.pattern_index], result) {
+                write!(out, " column=\"{}\" end-column=\"{}\"", start_column, end_column)?;
+            }
+            if printer.options.changed.contains(&result.path) {
+                write!(out, " changed-during-scan=\"true\"")?;
+            }
+            let text = if printer.options.redact {
+                printer.masked(&result.line)
+            } else {
+                result.line.clone()
+            };
+            writeln!(
+                out,
+                " rule=\"{}\" pattern=\"{}\">{}</match>",
+                escape(pattern_label(printer.pattern_specs, result)),
+                escape(&result.pattern),
+                escape(&text)
+            )?;
+        }
+        writeln!(out, "  </file>")?;
+    }
+    writeln!(out, "</finder>")
+}
+
+/// Arguments of `finder report`.
+#[derive(ClapArgs, Debug)]
+pub struct ReportArgs {
+    /// Results files written by `--format json`, one per run, oldest first
+    #[arg(long, required = true, num_args = 1.., value_name = "FILE")]
+    pub runs: Vec<PathBuf>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = TrendFormat::Text)]
+    pub format: TrendFormat,
+
+    /// How many paths to list, those with the most findings in the last run first
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub top: usize,
+}
+
+/// The format of `finder report`.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TrendFormat {
+    #[default]
+    Text,
+    /// A standalone HTML page
+    Html,
+    Json,
+}
+
+/// The findings of a series of runs, counted per run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trend {
+    /// The name of each run: its file name without extension.
+    pub runs: Vec<String>,
+    pub totals: Vec<usize>,
+    /// Sorted by count in the last run, the largest first.
+    pub rules: Vec<(String, Vec<usize>)>,
+    pub paths: Vec<(String, Vec<usize>)>,
+    /// Findings of the last run that the one before did not have, and the
+    /// other way round, told apart by path, rule and text rather than line
+    /// number, which changes as files are edited.
+    pub new: usize,
+    pub resolved: usize,
+}
+
+/// Counts of `key` in each run, sorted by count in the last run.
+fn counts_per_run(runs: &[Vec<Record>], key: impl Fn(&Record) -> &str) -> Vec<(String, Vec<usize>)> {
+    let mut counts: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, records) in runs.iter().enumerate() {
+        for record in records {
+            counts.entry(key(record)).or_insert_with(|| vec![0; runs.len()])[index] += 1;
+        }
+    }
+    let mut counts: Vec<(String, Vec<usize>)> = counts.into_iter().map(|(key, counts)| (key.to_string(), counts)).collect();
+    counts.sort_by(|a, b| b.1.last().cmp(&a.1.last()).then_with(|| b.1.cmp(&a.1)).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+impl Trend {
+    pub fn of(runs: Vec<String>, records: &[Vec<Record>]) -> Trend {
+        let (mut new, mut resolved) = (0, 0);
+        if let [.., previous, last] = records {
+            let mut previous_findings: HashMap<(&str, &str, &str), usize> = HashMap::new();
+            for record in previous {
+                *previous_findings.entry((&record.path, &record.rule, record.text.trim())).or_default() += 1;
+            }
+            for record in last {
+                match previous_findings.get_mut(&(&record.path, &record.rule, record.text.trim())) {
+                    Some(count) if *count > 0 => *count -= 1,
+                    _ => new += 1,
+                }
+            }
+            resolved = previous_findings.values().sum();
+        }
+        Trend {
+            runs,
+            totals: records.iter().map(Vec::len).collect(),
+            rules: counts_per_run(records, |record| &record.rule),
+            paths: counts_per_run(records, |record| &record.path),
+            new,
+            resolved,
+        }
+    }
+
+    fn delta(counts: &[usize]) -> String {
+        match counts {
+            [first, .., last] => format!("{:+}", *last as i64 - *first as i64),
+            _ => String::new(),
+        }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write, top: usize) -> io::Result<()> {
+        let width = self.runs.iter().map(|run| run.chars().count()).max().unwrap_or(0).max(6);
+        let name_width = self.rules.iter().chain(self.paths.iter().take(top))
+            .map(|(name, _)| name.chars().count() + 2)
+            .max()
+            .unwrap_or(0)
+            .max("Findings".len());
+        let row = |out: &mut dyn Write, name: &str, counts: &[usize]| -> io::Result<()> {
+            write!(out, "{:<name_width$}", name)?;
+            for count in counts {
+                write!(out, " {:>width$}", count)?;
+            }
+            writeln!(out, " {:>7}", Trend::delta(counts))
+        };
+        write!(out, "{:<name_width$}", "")?;
+        for run in &self.runs {
+            write!(out, " {:>width$}", run)?;
+        }
+        writeln!(out, " {:>7}", "change")?;
+        row(out, "Findings", &self.totals)?;
+        writeln!(out, "By rule:")?;
+        for (rule, counts) in &self.rules {
+            row(out, &format!("  {}", rule), counts)?;
+        }
+        writeln!(out, "By path:")?;
+        for (path, counts) in self.paths.iter().take(top) {
+            row(out, &format!("  {}", path), counts)?;
+        }
+        if self.paths.len() > top {
+            writeln!(out, "  ... and {} more paths", self.paths.len() - top)?;
+        }
+        if let [.., previous, _] = self.runs.as_slice() {
+            writeln!(out, "Since {}: {} new, {} resolved", previous, self.new, self.resolved)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_html(&self, out: &mut dyn Write, top: usize) -> io::Result<()> {
+        let table = |out: &mut dyn Write, heading: &str, rows: &[(String, Vec<usize>)]| -> io::Result<()> {
+            write!(out, "<h2>{}</h2>\n<table class=\"rules\">\n<tr><th></th>", escape(heading))?;
+            for run in &self.runs {
+                write!(out, "<th>{}</th>", escape(run))?;
+            }
+            writeln!(out, "<th>Change</th></tr>")?;
+            for (name, counts) in rows {
+                write!(out, "<tr><td class=\"rule\">{}</td>", escape(name))?;
+                for count in counts {
+                    write!(out, "<td>{}</td>", count)?;
+                }
+                let delta = Trend::delta(counts);
+                let class = if delta.starts_with('-') { "better" } else if delta.starts_with('+') && delta != "+0" { "worse" } else { "same" };
+                writeln!(out, "<td class=\"{}\">{}</td></tr>", class, delta)?;
+            }
+            writeln!(out, "</table>")
+        };
+        writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>finder trend report</title>")?;
+        writeln!(out, "<style>\n{}.better {{ color: #2b8a3e; }}\n.worse {{ color: #c92a2a; }}\n</style>\n</head>\n<body>", STYLE)?;
+        writeln!(out, "<h1>finder trend report</h1>")?;
+        if let [.., previous, _] = self.runs.as_slice() {
+            writeln!(out, "<p class=\"summary\">Since {}: {} new, {} resolved.</p>", escape(previous), self.new, self.resolved)?;
+        }
+        table(out, "Findings", &[("All rules".to_string(), self.totals.clone())])?;
+        table(out, "By rule", &self.rules)?;
+        table(out, "By path", &self.paths[..top.min(self.paths.len())])?;
+        if self.paths.len() > top {
+            writeln!(out, "<p>... and {} more paths.</p>", self.paths.len() - top)?;
+        }
+        writeln!(out, "</body>\n</html>")
+    }
+
+    pub fn write_json(&self, out: &mut dyn Write, top: usize) -> io::Result<()> {
+        let counts = |counts: &[usize]| counts.iter().map(usize::to_string).collect::<Vec<_>>().join(",");
+        let rows = |rows: &[(String, Vec<usize>)], key: &str| {
+            rows.iter()
+                .map(|(name, row)| format!("{{\"{}\":{},\"counts\":[{}]}}", key, json_string(name), counts(row)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        writeln!(
+            out,
+            "{{\"runs\":[{}],\"totals\":[{}],\"rules\":[{}],\"paths\":[{}],\"new\":{},\"resolved\":{}}}",
+            self.runs.iter().map(|run| json_string(run)).collect::<Vec<_>>().join(","),
+            counts(&self.totals),
+            rows(&self.rules, "rule"),
+            rows(&self.paths[..top.min(self.paths.len())], "path"),
+            self.new,
+            self.resolved
+        )
+    }
+}
+
+pub fn run(args: ReportArgs) -> Result<(), FinderError> {
+    let mut runs = Vec::new();
+    let mut records = Vec::new();
+    for path in &args.runs {
+        let content = fs::read_to_string(path).map_err(|e| FinderError::io(path, e))?;
+        let run = content.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                Record::parse(line).map_err(|e| FinderError::Usage(format!("{}:{}: not a `--format json` result: {}", path.display(), index + 1, e)))
+            })
+            .collect::<Result<Vec<Record>, FinderError>>()?;
+        runs.push(path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned());
+        records.push(run);
+    }
+    let trend = Trend::of(runs, &records);
+    let mut out = io::stdout().lock();
+    match args.format {
+        TrendFormat::Text => trend.write_text(&mut out, args.top)?,
+        TrendFormat::Html => trend.write_html(&mut out, args.top)?,
+        TrendFormat::Json => trend.write_json(&mut out, args.top)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputOptions;
+    use regex::Regex;
+
+    fn result(path: &str, line_number: usize, line: &str) -> SearchResult {
+        SearchResult { pattern: "<key>".to_string(), ..SearchResult::new(path, line_number, line, 0) }
+    }
+
+    type Report = fn(&mut dyn Write, &Printer<Regex>, &[SearchResult], usize) -> io::Result<()>;
+
+    fn render(write: Report, redact: bool) -> String {
+        let pattern_specs = [PatternSpec {
+            name: Some("keys".to_string()),
+            description: Some("Keys & tokens".to_string()),
+            help_uri: Some("https://example.com/keys?a&b".to_string()),
+            tags: vec!["security".to_string()],
+            ..PatternSpec::new("<key>")
+        }];
+        let matchers = [Regex::new("<key>").unwrap()];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions { redact, ..Default::default() } };
+        let results = [result("a & b.txt", 3, "x = <key> & y"), result("c.txt", 1, "<key>"), result("a & b.txt", 9, "\u{1}<key>")];
+        let mut out = Vec::new();
+        write(&mut out, &printer, &results, 10).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_html() {
+        let html = render(write_html, false);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("3 matches in 2 files, of 10 files searched"));
+        assert!(html.contains(concat!(
+            "<tr><td class=\"rule\"><a href=\"https://example.com/keys?a&amp;b\">keys</a></td>",
+            "<td>Keys &amp; tokens <span class=\"tags\">security</span></td><td>3</td><td>2</td></tr>",
+        )), "{}", html);
+        assert!(html.contains("<summary>a &amp; b.txt <span class=\"count\">(2 matches)</span></summary>"), "{}", html);
+        assert!(html.contains("<td class=\"text\">x = <mark>&lt;key&gt;</mark> &amp; y</td>"));
+        assert!(html.find("line\">9<").unwrap() < html.find("c.txt").unwrap());
+        assert!(render(write_html, true).contains("<mark>&lt;***&gt;</mark>"));
+    }
+
+    #[test]
+    fn test_write_xml() {
+        let xml = render(write_xml, false);
+        assert!(xml.contains("  <summary matches=\"3\" files=\"2\" searched=\"10\"/>\n  <file path=\"a &amp; b.txt\">\n"), "{}", xml);
+        assert!(xml.contains("<match line=\"3\" column=\"5\" end-column=\"10\" rule=\"keys\" pattern=\"&lt;key&gt;\">x = &lt;key&gt; &amp; y</match>"));
+        assert!(xml.contains(">\u{FFFD}&lt;key&gt;</match>"));
+        assert!(xml.ends_with("  </file>\n</finder>\n"));
+        assert!(render(write_xml, true).contains(">&lt;***&gt;</match>"));
+    }
+
+    fn record(path: &str, line: usize, rule: &str, text: &str) -> Record {
+        Record { path: path.to_string(), line, end_line: None, rule: rule.to_string(), other_rules: Vec::new(), severity: None, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_trend() {
+        let records = vec![
+            vec![record("a.rs", 1, "keys", "k1"), record("a.rs", 5, "keys", "k2"), record("b.rs", 2, "todo", "TODO x")],
+            vec![record("a.rs", 3, "keys", "k2"), record("b.rs", 2, "todo", "TODO x"), record("c.rs", 7, "todo", "TODO y")],
+        ];
+        let trend = Trend::of(vec!["week1".to_string(), "week2".to_string()], &records);
+        assert_eq!(trend.totals, vec![3, 3]);
+        assert_eq!(trend.rules, vec![("todo".to_string(), vec![1, 2]), ("keys".to_string(), vec![2, 1])]);
+        assert_eq!(trend.paths[0], ("a.rs".to_string(), vec![2, 1]));
+        // k2 moved from line 5 to 3 but is the same finding.
+        assert_eq!((trend.new, trend.resolved), (1, 1));
+
+        let mut out = Vec::new();
+        trend.write_text(&mut out, 2).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("\n  keys        2      1      -1\n"), "{}", text);
+        assert!(text.contains("  ... and 1 more paths\nSince week1: 1 new, 1 resolved\n"), "{}", text);
+
+        let mut out = Vec::new();
+        trend.write_json(&mut out, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"runs\":[\"week1\",\"week2\"],\"totals\":[3,3],\"rules\":[{\"rule\":\"todo\",\"counts\":[1,2]},{\"rule\":\"keys\",\"counts\":[2,1]}],\"paths\":[{\"path\":\"a.rs\",\"counts\":[2,1]}],\"new\":1,\"resolved\":1}\n"
+        );
+
+        let mut out = Vec::new();
+        trend.write_html(&mut out, 5).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("<tr><td class=\"rule\">keys</td><td>2</td><td>1</td><td class=\"better\">-1</td></tr>"), "{}", html);
+    }
+}
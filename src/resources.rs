@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+/// What the process has used so far, for `--stat`. Fields a platform does
+/// not report are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceUsage {
+    /// The time the usage is measured over.
+    pub wall_time: Duration,
+    pub peak_rss_bytes: Option<u64>,
+    pub user_time: Duration,
+    pub system_time: Duration,
+    /// Everything read through system calls, decompressed input excluded.
+    pub bytes_read: Option<u64>,
+}
+
+impl ResourceUsage {
+    #[cfg(unix)]
+    pub fn current(wall_time: Duration) -> Option<ResourceUsage> {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: getrusage only writes to the struct it is given.
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: initialized by the successful call above.
+        let usage = unsafe { usage.assume_init() };
+        let time = |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+        // Linux counts the peak resident set size in kilobytes, macOS in bytes.
+        let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+        Some(ResourceUsage {
+            wall_time,
+            peak_rss_bytes: Some(usage.ru_maxrss as u64 * rss_unit),
+            user_time: time(usage.ru_utime),
+            system_time: time(usage.ru_stime),
+            bytes_read: bytes_read(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn current(_wall_time: Duration) -> Option<ResourceUsage> {
+        None
+    }
+
+    /// The CPU time spent per second of wall time, i.e. how many threads
+    /// were busy on average.
+    pub fn parallelism(&self) -> f64 {
+        if self.wall_time.is_zero() {
+            return 0.0;
+        }
+        (self.user_time + self.system_time).as_secs_f64() / self.wall_time.as_secs_f64()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn bytes_read() -> Option<u64> {
+    parse_rchar(&std::fs::read_to_string("/proc/self/io").ok()?)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn bytes_read() -> Option<u64> {
+    None
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn parse_rchar(io: &str) -> Option<u64> {
+    io.lines().find_map(|line| line.strip_prefix("rchar:")).and_then(|value| value.trim().parse().ok())
+}
+
+/// Like `1.5 MiB`, for the statistics block.
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rchar() {
+        assert_eq!(parse_rchar("rchar: 123456\nwchar: 42\nsyscr: 7\n"), Some(123456));
+        assert_eq!(parse_rchar("wchar: 42\n"), None);
+    }
+
+    #[test]
+    fn test_parallelism_and_sizes() {
+        let mut usage = ResourceUsage {
+            wall_time: Duration::from_secs(2),
+            peak_rss_bytes: None,
+            user_time: Duration::from_millis(2500),
+            system_time: Duration::from_millis(500),
+            bytes_read: None,
+        };
+        assert_eq!(usage.parallelism(), 1.5);
+        usage.wall_time = Duration::ZERO;
+        assert_eq!(usage.parallelism(), 0.0);
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1536 * 1024), "1.5 MiB");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_current() {
+        let usage = ResourceUsage::current(Duration::from_secs(1)).unwrap();
+        assert!(usage.peak_rss_bytes.unwrap() > 0);
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+use crate::github::AnnotationLevel;
+use crate::patterns::PatternSpec;
+use crate::secrets;
+
+/// How serious a finding of a rule is, from a built-in ruleset or from a
+/// rules file (`name[error]: regex`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Severity {
+    Info,
+    #[value(alias = "warning")]
+    Warn,
+    Error,
+}
+
+impl Severity {
+    /// The severities, the most serious first.
+    pub const ALL: [Severity; 3] = [Severity::Error, Severity::Warn, Severity::Info];
+
+    /// Parses `info`, `warn` (or `warning`) or `error`.
+    pub fn parse(value: &str) -> Option<Severity> {
+        Severity::from_str(value, true).ok()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        }
+    }
+
+    /// The SARIF level of the findings.
+    pub fn sarif_level(self) -> &'static str {
+        match self {
+            Severity::Info => "note",
+            Severity::Warn => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// The score, out of 10, that GitHub code scanning ranks security alerts
+    /// by (`security-severity`).
+    pub fn security_severity(self) -> &'static str {
+        match self {
+            Severity::Info => "3.0",
+            Severity::Warn => "5.5",
+            Severity::Error => "8.5",
+        }
+    }
+
+    pub fn annotation_level(self) -> AnnotationLevel {
+        match self {
+            Severity::Info => AnnotationLevel::Notice,
+            Severity::Warn => AnnotationLevel::Warning,
+            Severity::Error => AnnotationLevel::Error,
+        }
+    }
+}
+
+/// A rule of a built-in ruleset.
+#[derive(Debug)]
+pub struct Rule {
+    /// Also the name of its pattern, reported in the results.
+    pub id: &'static str,
+    pub description: &'static str,
+    pub severity: Severity,
+    pub pattern: &'static str,
+    /// The least entropy, in bits per character, of the secret a match ends
+    /// with: below it, the match is taken for a word or a placeholder rather
+    /// than a generated key.
+    pub min_entropy: Option<f64>,
+}
+
+/// Text that marks a match as an example or a placeholder rather than a
+/// real secret, in lowercase.
+const ALLOWLIST: &[&str] = &["example", "dummy", "placeholder", "changeme", "redacted", "xxxxxxxx", "00000000", "your_", "your-"];
+
+/// The comment that keeps the line it is on out of the findings of a
+/// ruleset, for the known false positives.
+pub const ALLOW_COMMENT: &str = "finder:allow";
+
+impl Rule {
+    /// Whether a match of the rule is a finding: it is not allowlisted, and
+    /// its secret is random enough.
+    pub fn accepts(&self, text: &str) -> bool {
+        let lowercase = text.to_lowercase();
+        !ALLOWLIST.iter().any(|allowed| lowercase.contains(allowed))
+            && self.min_entropy.is_none_or(|min_entropy| entropy(trailing_token(text)) >= min_entropy)
+    }
+}
+
+/// The last run of the characters keys are made of in `text`: the value of
+/// a `name = value` match.
+fn trailing_token(text: &str) -> &str {
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '_' | '-');
+    let end = text.rfind(is_key_char).map_or(0, |index| index + 1);
+    let start = text[..end].rfind(|c: char| !is_key_char(c)).map_or(0, |index| index + 1);
+    &text[start..end]
+}
+
+/// The Shannon entropy of `text`, in bits per character.
+pub fn entropy(text: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in text.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let length = counts.values().sum::<usize>() as f64;
+    counts.values().map(|&count| count as f64 / length).map(|p| -p * p.log2()).sum()
+}
+
+/// A versioned set of built-in rules (`--rules`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ruleset {
+    /// Credentials: cloud and service keys, tokens, private keys and
+    /// hard-coded passwords.
+    Secrets,
+}
+
+impl Ruleset {
+    /// Parses `secrets`, or `secrets@<version>` to fail rather than run with
+    /// other rules than expected.
+    pub fn parse(value: &str) -> Result<Ruleset, String> {
+        let (name, version) = match value.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (value, None),
+        };
+        let ruleset = match name {
+            "secrets" => Ruleset::Secrets,
+            _ => return Err(format!("unknown ruleset `{}`, expected secrets", name)),
+        };
+        match version {
+            Some(version) if version != ruleset.version() => {
+                Err(format!("this finder has version {} of the {} rules, not {}", ruleset.version(), name, version))
+            }
+            _ => Ok(ruleset),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Ruleset::Secrets => "secrets",
+        }
+    }
+
+    /// Raised whenever a rule is added or removed, or changes what it finds.
+    pub fn version(self) -> &'static str {
+        match self {
+            Ruleset::Secrets => secrets::VERSION,
+        }
+    }
+
+    pub fn rules(self) -> &'static [Rule] {
+        match self {
+            Ruleset::Secrets => secrets::RULES,
+        }
+    }
+
+    /// The patterns of the rules, named after their ids, described and
+    /// tagged with the name of the ruleset, then the exclusion pattern of the
+    /// allow comment.
+    pub fn patterns(self) -> Vec<PatternSpec> {
+        let mut pattern_specs: Vec<PatternSpec> = self.rules().iter()
+            .map(|rule| PatternSpec {
+                name: Some(rule.id.to_string()),
+                severity: Some(rule.severity),
+                description: Some(rule.description.to_string()),
+                tags: vec![self.name().to_string()],
+                ..PatternSpec::new(rule.pattern)
+            })
+            .collect();
+        pattern_specs.push(PatternSpec { name: Some("allowed".to_string()), negated: true, ..PatternSpec::new(&regex::escape(ALLOW_COMMENT)) });
+        pattern_specs
+    }
+
+    /// The rule whose pattern is `spec`.
+    pub fn rule(self, spec: &PatternSpec) -> Option<&'static Rule> {
+        self.rules().iter().find(|rule| !spec.negated && spec.name.as_deref() == Some(rule.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ruleset() {
+        assert_eq!(Ruleset::parse("secrets"), Ok(Ruleset::Secrets));
+        assert_eq!(Ruleset::parse(&format!("secrets@{}", secrets::VERSION)), Ok(Ruleset::Secrets));
+        assert!(Ruleset::parse("secrets@0").unwrap_err().starts_with("this finder has version"));
+        assert!(Ruleset::parse("licenses").is_err());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(""), 0.0);
+        assert_eq!(entropy("aaaa"), 0.0);
+        assert_eq!(entropy("abab"), 1.0);
+        assert!(entropy("wJalrXUtnFEMI/K7MDENG/bPxRfiCY") > 4.0);
+        assert_eq!(trailing_token("api_key = \"Zm9vYmFy\";"), "Zm9vYmFy");
+        assert_eq!(trailing_token("token: abc-def"), "abc-def");
+    }
+
+    #[test]
+    fn test_accepts() {
+        let rule = Rule { id: "generic", description: "", severity: Severity::Info, pattern: "", min_entropy: Some(3.5) };
+        assert!(rule.accepts("api_key = \"q8Fz2LkW9rTb4XvN7pHs\""));
+        assert!(!rule.accepts("api_key = \"aaaaaaaabbbbbbbbbbbb\""));
+        assert!(!rule.accepts("api_key = \"EXAMPLEq8Fz2LkW9rTb4XvN7pHs\""));
+        assert!(!rule.accepts("api_key = \"your_api_key_goes_here\""));
+
+        let specs = Ruleset::Secrets.patterns();
+        let last = specs.last().unwrap();
+        assert!(last.negated && last.pattern == "finder:allow");
+        assert_eq!(Ruleset::Secrets.rule(&specs[0]).map(|rule| rule.id), specs[0].name.as_deref());
+        assert!(Ruleset::Secrets.rule(last).is_none());
+        assert_eq!(specs[0].severity, Some(Severity::Error));
+
+        assert_eq!(Severity::parse("Warning"), Some(Severity::Warn));
+        assert_eq!(Severity::parse("fatal"), None);
+        assert!(Severity::Error > Severity::Warn && Severity::Warn > Severity::Info);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, mpsc};
+use std::thread;
+use std::time::Instant;
+
+use clap::Args as ClapArgs;
+use ignore::{WalkBuilder, WalkState};
+
+use crate::engine::CaseMode;
+use crate::error::FinderError;
+use crate::index::{self, Index, IndexFilter, Query};
+use crate::json::{self, Value, json_string};
+use crate::longpath;
+use crate::output::{OutputOptions, Printer};
+use crate::patterns::PatternSpec;
+use crate::{SearchOptions, SearchResult};
+
+/// Arguments of `finder serve`.
+#[derive(ClapArgs, Debug)]
+pub struct ServeArgs {
+    /// The path(s) to serve searches of (files or directories)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// The Unix socket to listen on
+    #[arg(long, value_name = "PATH")]
+    pub socket: PathBuf,
+
+    /// Keep the `.finder-index` of the served directories in memory and use it to skip files
+    #[arg(long)]
+    pub use_index: bool,
+}
+
+/// The indexes of the served directories, shared with the running searches.
+type Indexes = Arc<Vec<(PathBuf, Index)>>;
+
+/// What the server keeps warm between queries.
+pub struct Server {
+    roots: Vec<PathBuf>,
+    files: RwLock<Vec<PathBuf>>,
+    indexes: Option<RwLock<Indexes>>,
+}
+
+fn walk(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut walk_builder = WalkBuilder::new(&roots[0]);
+    for root in &roots[1..] {
+        walk_builder.add(root);
+    }
+    let (sender, receiver) = mpsc::channel();
+    walk_builder.build_parallel().run(|| {
+        let sender = sender.clone();
+        Box::new(move |entry| {
+            if let Ok(entry) = entry
+                && entry.file_type().is_some_and(|ft| ft.is_file())
+            {
+                let _ = sender.send(entry.into_path());
+            }
+            WalkState::Continue
+        })
+    });
+    drop(sender);
+    // The walker threads report in no particular order.
+    let mut files: Vec<PathBuf> = receiver.into_iter().collect();
+    files.sort();
+    files
+}
+
+impl Server {
+    pub fn new(roots: Vec<PathBuf>, use_index: bool) -> io::Result<Server> {
+        let indexes = if use_index { Some(RwLock::new(Arc::new(index::load_indexes(&roots)?))) } else { None };
+        let files = RwLock::new(walk(&roots));
+        Ok(Server { roots, files, indexes })
+    }
+
+    /// Answers one request line with JSON lines ending with a `done` (or an
+    /// `error`) object.
+    pub fn handle(&self, request: &str, out: &mut dyn Write) -> io::Result<()> {
+        let started = Instant::now();
+        let request = match json::parse_object(request) {
+            Ok(request) => request,
+            Err(e) => return writeln!(out, "{{\"error\":{}}}", json_string(&format!("invalid request: {}", e))),
+        };
+        match request.get("command").and_then(Value::as_str).unwrap_or("search") {
+            "search" => self.search(&request, out, started),
+            "refresh" => {
+                // `finder --watch --use-index` keeps the indexes up to date
+                // on disk.
+                if let Some(indexes) = &self.indexes {
+                    match index::load_indexes(&self.roots) {
+                        Ok(loaded) => *indexes.write().unwrap() = Arc::new(loaded),
+                        Err(e) => return writeln!(out, "{{\"error\":{}}}", json_string(&format!("failed to load the indexes: {}", e))),
+                    }
+                }
+                let files = walk(&self.roots);
+                let count = files.len();
+                *self.files.write().unwrap() = files;
+                writeln!(out, "{{\"done\":true,\"files\":{},\"elapsed_ms\":{}}}", count, started.elapsed().as_millis())
+            }
+            command => writeln!(out, "{{\"error\":{}}}", json_string(&format!("unknown command `{}`", command))),
+        }
+    }
+
+    fn search(&self, request: &HashMap<String, Value>, out: &mut dyn Write, started: Instant) -> io::Result<()> {
+        let Some(pattern) = request.get("pattern").and_then(Value::as_str) else {
+            return writeln!(out, "{{\"error\":\"missing `pattern`\"}}");
+        };
+        let flag = |key: &str| request.get(key) == Some(&Value::Bool(true));
+        let max_results = request.get("max_results").and_then(Value::as_usize).unwrap_or(usize::MAX);
+        let case = CaseMode::from_flags(flag("ignore_case"), flag("smart_case"));
+        let regexes = match crate::compile_regex_with_cache(&[pattern.to_string()], case, false) {
+            Ok(regexes) => regexes,
+            Err(e) => return writeln!(out, "{{\"error\":{}}}", json_string(&e.to_string())),
+        };
+        let index_filter = self.indexes.as_ref()
+            .map(|indexes| IndexFilter::new(indexes.read().unwrap().clone(), vec![Query::of(pattern, case.ignore_case(pattern))]));
+        let pattern_specs = [PatternSpec::new(pattern)];
+        let printer = Printer { pattern_specs: &pattern_specs, matchers: &regexes, options: OutputOptions::default() };
+        let options = SearchOptions::default();
+
+        let files = self.files.read().unwrap();
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let mut matches = 0;
+        let (sender, receiver) = mpsc::channel::<Vec<SearchResult>>();
+        let written = thread::scope(|scope| {
+            for _ in 0..thread::available_parallelism().map_or(1, |n| n.get()) {
+                let sender = sender.clone();
+                let (files, next, stop, regexes, options, index_filter) = (&files, &next, &stop, &regexes, &options, &index_filter);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let Some(path) = files.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+                        if let Some(index_filter) = index_filter
+                            && !index_filter.may_match(path, std::fs::metadata(longpath::for_opening(path)).ok().as_ref())
+                        {
+                            continue;
+                        }
+                        if let Ok(results) = crate::search_in_file_streaming(path, regexes, options)
+                            && !results.is_empty()
+                            && sender.send(results).is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+            // Results are streamed as files are searched.
+            for mut results in receiver {
+                results.truncate(max_results - matches);
+                matches += results.len();
+                if let Err(e) = printer.write_json(out, &results).and_then(|_| out.flush()) {
+                    stop.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+                if matches == max_results {
+                    stop.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+            Ok(())
+        });
+        written?;
+        writeln!(
+            out,
+            "{{\"done\":true,\"matches\":{},\"files\":{},\"elapsed_ms\":{}}}",
+            matches,
+            files.len(),
+            started.elapsed().as_millis()
+        )
+    }
+}
+
+#[cfg(unix)]
+pub fn run(args: ServeArgs) -> Result<(), FinderError> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by a previous server would make bind fail.
+    if std::fs::symlink_metadata(&args.socket).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(&args.socket)?;
+    }
+    let server = Arc::new(Server::new(args.paths, args.use_index)?);
+    let listener = UnixListener::bind(&args.socket)?;
+    eprintln!("Serving {} files on {}", server.files.read().unwrap().len(), args.socket.display());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let server = server.clone();
+        thread::spawn(move || {
+            let mut writer = io::BufWriter::new(&stream);
+            for line in BufReader::new(&stream).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if server.handle(&line, &mut writer).and_then(|_| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run(_args: ServeArgs) -> Result<(), FinderError> {
+    Err("finder serve needs Unix sockets, which this platform does not have".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn answer(server: &Server, request: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        server.handle(request, &mut out).unwrap();
+        String::from_utf8(out).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_requests() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "Needle\nhay\nneedle\n").unwrap();
+        let server = Server::new(vec![dir.path().to_path_buf()], false).unwrap();
+
+        let lines = answer(&server, r#"{"pattern":"needle","ignore_case":true}"#);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains(r#""line":1,"rule":"needle""#), "{}", lines[0]);
+        assert!(lines[2].starts_with(r#"{"done":true,"matches":2,"files":1,"#));
+
+        let lines = answer(&server, r#"{"pattern":"needle","ignore_case":true,"max_results":1}"#);
+        assert!(lines[1].starts_with(r#"{"done":true,"matches":1,"#));
+
+        fs::write(dir.path().join("b.txt"), "needle").unwrap();
+        assert!(answer(&server, r#"{"pattern":"needle"}"#).last().unwrap().contains(r#""matches":1,"files":1"#));
+        assert!(answer(&server, r#"{"command":"refresh"}"#)[0].starts_with(r#"{"done":true,"files":2,"#));
+        assert!(answer(&server, r#"{"pattern":"needle"}"#).last().unwrap().contains(r#""matches":2,"files":2"#));
+
+        assert_eq!(answer(&server, r#"{"pattern":"("}"#).len(), 1);
+        assert!(answer(&server, "not json")[0].starts_with(r#"{"error":"invalid request"#));
+        assert_eq!(answer(&server, "{}"), vec![r#"{"error":"missing `pattern`"}"#]);
+    }
+
+    #[test]
+    fn test_refresh_reloads_indexes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "needle").unwrap();
+        fs::write(dir.path().join(index::INDEX_FILE_NAME), Index::build(dir.path()).to_bytes()).unwrap();
+        let server = Server::new(vec![dir.path().to_path_buf()], true).unwrap();
+        let indexed = || server.indexes.as_ref().unwrap().read().unwrap()[0].1.files.len();
+        assert_eq!(indexed(), 1);
+
+        fs::write(dir.path().join("b.txt"), "needle").unwrap();
+        fs::write(dir.path().join(index::INDEX_FILE_NAME), Index::build(dir.path()).to_bytes()).unwrap();
+        assert_eq!(indexed(), 1);
+        assert!(answer(&server, r#"{"command":"refresh"}"#)[0].starts_with(r#"{"done":true,"files":"#));
+        assert_eq!(indexed(), 2);
+        assert!(answer(&server, r#"{"pattern":"needle"}"#).last().unwrap().contains(r#""matches":2,"#));
+    }
+}
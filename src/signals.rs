@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::resources::human_bytes;
+use crate::stats::SearchStats;
+
+/// How long each walker thread waits before every file while throttled.
+const THROTTLE_PAUSE: Duration = Duration::from_millis(100);
+
+/// Set by SIGUSR1 until the status is written.
+static STATUS_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Flipped by SIGUSR2.
+static THROTTLED: AtomicBool = AtomicBool::new(false);
+
+/// Makes SIGUSR1 ask for the status of the search and SIGUSR2 turn
+/// throttling on and off, instead of terminating the process.
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn handle(signal: libc::c_int) {
+        // Only atomics here: anything else is not async-signal-safe.
+        if signal == libc::SIGUSR1 {
+            STATUS_REQUESTED.store(true, Ordering::Relaxed);
+        } else if signal == libc::SIGUSR2 {
+            THROTTLED.fetch_xor(true, Ordering::Relaxed);
+        }
+    }
+    for signal in [libc::SIGUSR1, libc::SIGUSR2] {
+        // SAFETY: the action is fully initialized before it is installed,
+        // and the handler is async-signal-safe.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Whether the status was asked for since the last call.
+pub fn take_status_request() -> bool {
+    STATUS_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+pub fn is_throttled() -> bool {
+    THROTTLED.load(Ordering::Relaxed)
+}
+
+/// Slows the calling walker thread down while the search is throttled.
+pub fn pause_if_throttled() {
+    if is_throttled() {
+        thread::sleep(THROTTLE_PAUSE);
+    }
+}
+
+/// The one-line status SIGUSR1 prints: what the search went through so far.
+pub fn status_line(stats: &SearchStats, matches: usize, elapsed: Duration) -> String {
+    format!(
+        "status: {} files searched ({}), {} skipped, {} unreadable, {} matches after {:.1}s{}",
+        stats.files_searched,
+        human_bytes(stats.bytes_scanned),
+        stats.files_skipped,
+        stats.read_errors,
+        matches,
+        elapsed.as_secs_f64(),
+        if is_throttled() { ", throttled" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_status_line() {
+        let mut stats = SearchStats::new(1);
+        stats.record_file(Path::new("a.log"), 2048, Duration::from_millis(3), &[]);
+        stats.files_skipped = 2;
+        assert_eq!(
+            status_line(&stats, 5, Duration::from_millis(12_340)),
+            "status: 1 files searched (2.0 KiB), 2 skipped, 0 unreadable, 5 matches after 12.3s"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::longpath;
+
+/// Chunks are cut where the top bits of the rolling hash are all zero,
+/// which happens every 64 bytes on average.
+const BOUNDARY_MASK: u64 = 0xFC00_0000_0000_0000;
+const MIN_CHUNK: usize = 16;
+const MAX_CHUNK: usize = 1024;
+
+/// Random values for the gear rolling hash, one per byte value.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut index = 0;
+    while index < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[index] = z ^ (z >> 31);
+        index += 1;
+    }
+    table
+};
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+/// The set of the hashes of a content's chunks, cut where the content
+/// itself says so rather than at fixed offsets: a line inserted at the top
+/// of a rotated log only changes the chunks around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature(Vec<u64>);
+
+impl Signature {
+    pub fn of(content: &[u8]) -> Signature {
+        let mut hashes = Vec::new();
+        let mut start = 0;
+        let mut rolling: u64 = 0;
+        for (index, &byte) in content.iter().enumerate() {
+            rolling = (rolling << 1).wrapping_add(GEAR[byte as usize]);
+            let length = index + 1 - start;
+            if (length >= MIN_CHUNK && rolling & BOUNDARY_MASK == 0) || length >= MAX_CHUNK {
+                hashes.push(fnv1a(&content[start..=index]));
+                start = index + 1;
+                rolling = 0;
+            }
+        }
+        if start < content.len() {
+            hashes.push(fnv1a(&content[start..]));
+        }
+        hashes.sort_unstable();
+        hashes.dedup();
+        Signature(hashes)
+    }
+
+    /// The share of chunks the two contents have in common (Jaccard index),
+    /// from 0 to 1.
+    pub fn similarity(&self, other: &Signature) -> f64 {
+        let (a, b) = (&self.0, &other.0);
+        if a.is_empty() && b.is_empty() {
+            return 1.0;
+        }
+        let (mut i, mut j, mut common) = (0, 0, 0);
+        while i < a.len() && j < b.len() {
+            match a[i].cmp(&b[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    common += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        common as f64 / (a.len() + b.len() - common) as f64
+    }
+
+    /// The best similarity this signature could have with one of `len`
+    /// chunks, to avoid comparing files of very different sizes.
+    fn max_similarity(&self, len: usize) -> f64 {
+        let (small, large) = if self.0.len() < len { (self.0.len(), len) } else { (len, self.0.len()) };
+        if large == 0 { 1.0 } else { small as f64 / large as f64 }
+    }
+}
+
+/// A file left out by `--skip-similar`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearDuplicate {
+    pub path: PathBuf,
+    /// The searched file it resembles.
+    pub of: PathBuf,
+    pub similarity: f64,
+}
+
+/// Picks the files that are at least `threshold` (0 to 1) similar to a file
+/// that comes before them in path order, which is searched in their stead.
+/// Files that cannot be read are never left out.
+pub fn near_duplicates(mut files: Vec<PathBuf>, threshold: f64) -> Vec<NearDuplicate> {
+    files.sort();
+    let mut kept: Vec<(PathBuf, Signature)> = Vec::new();
+    let mut skipped = Vec::new();
+    for path in files {
+        let Ok(content) = fs::read(longpath::for_opening(&path)) else { continue };
+        let signature = Signature::of(&content);
+        let closest = kept.iter()
+            .filter(|(_, other)| signature.max_similarity(other.0.len()) >= threshold)
+            .map(|(other_path, other)| (other_path, signature.similarity(other)))
+            .find(|&(_, similarity)| similarity >= threshold);
+        match closest {
+            Some((of, similarity)) => skipped.push(NearDuplicate { path, of: of.clone(), similarity }),
+            None => kept.push((path, signature)),
+        }
+    }
+    skipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn log(first: usize, count: usize) -> String {
+        (first..first + count)
+            .map(|n| format!("2024-05-01T10:{:02}:{:02} INFO request {} served in {}ms\n", n / 60 % 60, n % 60, n * 7919 % 10007, n % 97))
+            .collect()
+    }
+
+    #[test]
+    fn test_similarity() {
+        let base = Signature::of(log(0, 500).as_bytes());
+        assert_eq!(base.similarity(&base), 1.0);
+        // A rotated log: a few lines gone at the top, a few new at the bottom.
+        let rotated = Signature::of(log(20, 500).as_bytes());
+        assert!(base.similarity(&rotated) > 0.8, "{}", base.similarity(&rotated));
+        let other = Signature::of(log(5000, 500).as_bytes());
+        assert!(base.similarity(&other) < 0.1, "{}", base.similarity(&other));
+        assert_eq!(Signature::of(b"").similarity(&Signature::of(b"")), 1.0);
+    }
+
+    #[test]
+    fn test_near_duplicates() {
+        let dir = tempdir().unwrap();
+        let write = |name: &str, content: String| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let files = vec![
+            write("app.log.2", log(40, 500)),
+            write("app.log", log(0, 500)),
+            write("app.log.1", log(20, 500)),
+            write("other.log", log(5000, 500)),
+        ];
+        let skipped = near_duplicates(files, 0.8);
+        let pairs: Vec<(String, String)> = skipped.iter()
+            .map(|duplicate| (
+                duplicate.path.file_name().unwrap().to_string_lossy().into_owned(),
+                duplicate.of.file_name().unwrap().to_string_lossy().into_owned(),
+            ))
+            .collect();
+        assert_eq!(pairs, vec![
+            ("app.log.1".to_string(), "app.log".to_string()),
+            ("app.log.2".to_string(), "app.log".to_string()),
+        ]);
+    }
+}
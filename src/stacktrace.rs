@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+use crate::SearchResult;
+
+/// Finds the stack traces in `lines`, as ranges of line indices. Recognized
+/// formats are Java exceptions (with `Caused by:` chains), Python tracebacks
+/// and Rust panics (with an optional `stack backtrace:`).
+pub fn find_traces(lines: &[&str]) -> Vec<Range<usize>> {
+    let mut traces = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        match trace_end(lines, index) {
+            Some(end) => {
+                traces.push(index..end);
+                index = end;
+            }
+            None => index += 1,
+        }
+    }
+    traces
+}
+
+/// The end of the trace starting at `start`, if a trace starts there.
+fn trace_end(lines: &[&str], start: usize) -> Option<usize> {
+    let header = lines[start];
+    let continues = |end: usize, frame: fn(&str) -> bool| {
+        (end..lines.len()).find(|&index| !frame(lines[index])).unwrap_or(lines.len())
+    };
+
+    if header.trim_end() == "Traceback (most recent call last):" {
+        let end = continues(start + 1, |line| line.starts_with(' '));
+        if end == start + 1 {
+            return None;
+        }
+        // The exception itself closes the traceback.
+        return Some(if lines.get(end).is_some_and(|line| !line.trim().is_empty()) { end + 1 } else { end });
+    }
+
+    if header.starts_with("thread '") && header.contains("' panicked at ") {
+        let mut end = start + 1;
+        // Since Rust 1.73 the panic message is on its own line.
+        if header.trim_end().ends_with(':') && end < lines.len() {
+            end += 1;
+        }
+        if lines.get(end).is_some_and(|line| line.trim_end() == "stack backtrace:") {
+            end = continues(end + 1, is_rust_frame);
+        }
+        if lines.get(end).is_some_and(|line| line.starts_with("note: ")) {
+            end += 1;
+        }
+        return Some(end);
+    }
+
+    if !header.trim().is_empty() && !is_java_frame(header) && lines.get(start + 1).is_some_and(|line| is_java_frame(line)) {
+        return Some(continues(start + 1, is_java_frame));
+    }
+    None
+}
+
+fn is_java_frame(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    (trimmed.len() < line.len() && (trimmed.starts_with("at ") || (trimmed.starts_with("... ") && trimmed.ends_with(" more"))))
+        || trimmed.starts_with("Caused by: ")
+        || trimmed.starts_with("Suppressed: ")
+}
+
+fn is_rust_frame(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("at ")
+        || trimmed.split_once(": ").is_some_and(|(number, _)| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Replaces the results that fall inside a stack trace with a single result
+/// per trace covering the whole block, header and frames. `lines` are the
+/// searched lines and `first_line_number` is the line number of `lines[0]`.
+pub fn group_traces(results: Vec<SearchResult>, lines: &[&str], first_line_number: usize) -> Vec<SearchResult> {
+    let traces = find_traces(lines);
+    let mut grouped: Vec<SearchResult> = Vec::with_capacity(results.len());
+    let mut last_trace = None;
+    for mut result in results {
+        let index = result.line_number - first_line_number;
+        let position = traces.partition_point(|trace| trace.end <= index);
+        let Some(trace) = traces.get(position).filter(|trace| trace.contains(&index)) else {
+            grouped.push(result);
+            continue;
+        };
+        if last_trace == Some(position) {
+            continue;
+        }
+        last_trace = Some(position);
+        result.line_number = first_line_number + trace.start;
+        result.end_line_number = Some(first_line_number + trace.end - 1);
+        result.line = lines[trace.clone()].join("\n");
+        grouped.push(result);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+INFO starting
+Exception in thread \"main\" java.lang.IllegalStateException: boom
+\tat com.example.App.run(App.java:42)
+\tat com.example.App.main(App.java:10)
+Caused by: java.io.IOException: disk full
+\tat com.example.Store.write(Store.java:7)
+\t... 2 more
+INFO retrying
+Traceback (most recent call last):
+  File \"app.py\", line 3, in <module>
+    main()
+ValueError: bad input
+thread 'main' panicked at src/main.rs:2:5:
+index out of bounds
+stack backtrace:
+   0: rust_begin_unwind
+             at /rustc/library/std/src/panicking.rs:645:5
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+INFO done";
+
+    #[test]
+    fn test_find_traces() {
+        let lines: Vec<&str> = LOG.lines().collect();
+        assert_eq!(find_traces(&lines), vec![1..7, 8..12, 12..18]);
+    }
+
+    #[test]
+    fn test_group_traces() {
+        let lines: Vec<&str> = LOG.lines().collect();
+        let result = |line_number: usize| SearchResult {
+            pattern: "Exception|Error|INFO done".to_string(),
+            ..SearchResult::new("app.log", line_number, lines[line_number - 1], 0)
+        };
+        let grouped = group_traces(vec![result(2), result(5), result(12), result(19)], &lines, 1);
+        let ranges: Vec<(usize, Option<usize>)> = grouped.iter().map(|r| (r.line_number, r.end_line_number)).collect();
+        assert_eq!(ranges, vec![(2, Some(7)), (9, Some(12)), (19, None)]);
+        assert!(grouped[1].line.starts_with("Traceback (most recent call last):\n  File \"app.py\""));
+        assert!(grouped[1].line.ends_with("ValueError: bad input"));
+    }
+}
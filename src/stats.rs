@@ -0,0 +1,522 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::SearchResult;
+use crate::json::json_string;
+use crate::longpath;
+use crate::patterns::PatternSpec;
+use crate::resources::{ResourceUsage, human_bytes};
+use crate::rules::Severity;
+use crate::similar::NearDuplicate;
+
+/// How many of the slowest files `--stat` lists.
+const SLOWEST_FILES: usize = 5;
+
+/// How `--stat` prints the statistics.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StatFormat {
+    /// A human-readable block
+    #[default]
+    Text,
+    /// A single-line JSON object
+    Json,
+}
+
+/// What a path given on the command line turned out to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootKind {
+    File,
+    Directory,
+    Missing,
+    /// It exists but cannot be opened, e.g. for lack of permissions.
+    Unreadable,
+}
+
+impl RootKind {
+    pub fn of(path: &Path) -> RootKind {
+        let path = &*longpath::for_opening(path);
+        match fs::metadata(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => RootKind::Missing,
+            Err(_) => RootKind::Unreadable,
+            Ok(metadata) if metadata.is_dir() => {
+                if fs::read_dir(path).is_ok() { RootKind::Directory } else { RootKind::Unreadable }
+            }
+            Ok(_) => {
+                if fs::File::open(path).is_ok() { RootKind::File } else { RootKind::Unreadable }
+            }
+        }
+    }
+
+    pub fn is_searchable(self) -> bool {
+        matches!(self, RootKind::File | RootKind::Directory)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RootKind::File => "file",
+            RootKind::Directory => "directory",
+            RootKind::Missing => "missing",
+            RootKind::Unreadable => "unreadable",
+        }
+    }
+}
+
+/// Counters of a path given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootStats {
+    pub path: PathBuf,
+    pub kind: RootKind,
+    pub files_searched: usize,
+    pub matches: usize,
+}
+
+/// Whether `path`, a searched file or an `archive.zip!inner/path.txt`
+/// entry, comes from `root`.
+fn is_under(path: &Path, root: &Path) -> bool {
+    path.starts_with(root)
+        || path.to_string_lossy().strip_prefix(&*root.to_string_lossy()).is_some_and(|rest| rest.starts_with('!'))
+}
+
+/// Counters of a single pattern.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PatternStats {
+    pub matches: usize,
+    pub files: usize,
+}
+
+/// Counters of the files with a given extension (`--by-extension`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExtensionStats {
+    pub files: usize,
+    pub bytes: u64,
+    /// Summed over the files, searched in parallel: it can exceed the
+    /// elapsed time.
+    pub time: Duration,
+    pub matches: usize,
+}
+
+/// The extension `--by-extension` files `path` under, lowercased.
+fn extension_of(path: &Path) -> String {
+    path.extension().map_or_else(|| "(none)".to_string(), |extension| format!(".{}", extension.to_string_lossy().to_lowercase()))
+}
+
+/// Counters recorded while searching, file by file, for `--stat`.
+#[derive(Debug, Default)]
+pub struct SearchStats {
+    pub files_searched: usize,
+    /// Files left out by the size and date filters or by `--skip-similar`, or
+    /// that could not be read.
+    pub files_skipped: usize,
+    /// Files that `--use-index` showed could not match.
+    pub files_pruned: usize,
+    /// Files given their cached results (`--cache`), also counted in
+    /// `files_searched`.
+    pub files_cached: usize,
+    /// Files that could not be read, also counted in `files_skipped`.
+    pub read_errors: usize,
+    pub bytes_scanned: u64,
+    /// Indexed like the patterns given to the search.
+    pub patterns: Vec<PatternStats>,
+    /// In command-line order.
+    pub roots: Vec<RootStats>,
+    /// Copies of another file, given its results instead of being searched
+    /// (`--dedupe`).
+    pub duplicates: usize,
+    /// The files left out by `--skip-similar`, in path order.
+    pub near_duplicates: Vec<NearDuplicate>,
+    /// Measured once the search is over, where the platform allows.
+    pub resources: Option<ResourceUsage>,
+    /// Whether the extensions are reported (`--by-extension`).
+    pub by_extension: bool,
+    extensions: HashMap<String, ExtensionStats>,
+    slowest: BinaryHeap<Reverse<(Duration, PathBuf)>>,
+    timings: Vec<(PathBuf, Duration, usize)>,
+}
+
+impl SearchStats {
+    pub fn new(pattern_count: usize) -> SearchStats {
+        SearchStats { patterns: vec![PatternStats::default(); pattern_count], ..Default::default() }
+    }
+
+    pub fn record_file(&mut self, path: &Path, bytes: u64, elapsed: Duration, results: &[SearchResult]) {
+        self.files_searched += 1;
+        self.bytes_scanned += bytes;
+        let extension = self.extensions.entry(extension_of(path)).or_default();
+        extension.files += 1;
+        extension.bytes += bytes;
+        extension.time += elapsed;
+        extension.matches += results.len();
+        self.timings.push((path.to_path_buf(), elapsed, results.len()));
+        let mut patterns_in_file = HashSet::new();
+        for pattern_index in results.iter().flat_map(SearchResult::pattern_indexes) {
+            self.patterns[pattern_index].matches += 1;
+            if patterns_in_file.insert(pattern_index) {
+                self.patterns[pattern_index].files += 1;
+            }
+        }
+        // A min-heap of the slowest files seen so far: the fastest one is evicted first.
+        self.slowest.push(Reverse((elapsed, path.to_path_buf())));
+        if self.slowest.len() > SLOWEST_FILES {
+            self.slowest.pop();
+        }
+    }
+
+    /// Attributes the searched files and the results to the paths given on
+    /// the command line, the first one that contains them when they overlap.
+    pub fn record_roots(&mut self, roots: &[(PathBuf, RootKind)], files: &[PathBuf], results: &[SearchResult]) {
+        self.roots = roots.iter()
+            .map(|(path, kind)| RootStats { path: path.clone(), kind: *kind, files_searched: 0, matches: 0 })
+            .collect();
+        let root_of = |path: &Path| roots.iter().position(|(root, kind)| kind.is_searchable() && is_under(path, root));
+        for file in files {
+            if let Some(index) = root_of(file) {
+                self.roots[index].files_searched += 1;
+            }
+        }
+        for result in results {
+            if let Some(index) = root_of(&result.path) {
+                self.roots[index].matches += 1;
+            }
+        }
+    }
+
+    /// Every searched file with its search time and number of matches.
+    pub fn timings(&self) -> &[(PathBuf, Duration, usize)] {
+        &self.timings
+    }
+
+    /// The slowest files to search, slowest first.
+    pub fn slowest_files(&self) -> Vec<(Duration, &Path)> {
+        let mut slowest: Vec<(Duration, &Path)> = self.slowest.iter()
+            .map(|Reverse((elapsed, path))| (*elapsed, path.as_path()))
+            .collect();
+        slowest.sort_by(|a, b| b.cmp(a));
+        slowest
+    }
+
+    /// The extensions of the searched files, the slowest to search first.
+    pub fn extensions(&self) -> Vec<(&str, &ExtensionStats)> {
+        let mut extensions: Vec<(&str, &ExtensionStats)> = self.extensions.iter()
+            .map(|(extension, stats)| (extension.as_str(), stats))
+            .collect();
+        extensions.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+        extensions
+    }
+
+    /// The number of matches of each severity, the most serious first, when
+    /// some of the patterns have a severity; the others count as `warn`.
+    pub fn severities(&self, pattern_specs: &[PatternSpec]) -> Option<Vec<(Severity, usize)>> {
+        if pattern_specs.iter().all(|spec| spec.severity.is_none()) {
+            return None;
+        }
+        Some(Severity::ALL.into_iter()
+            .map(|severity| {
+                let matches = pattern_specs.iter().zip(&self.patterns)
+                    .filter(|(spec, _)| spec.level() == severity)
+                    .map(|(_, stats)| stats.matches)
+                    .sum();
+                (severity, matches)
+            })
+            .collect())
+    }
+
+    /// Writes the lines that follow the totals of the statistics block.
+    pub fn write_details(&self, out: &mut dyn Write, pattern_specs: &[PatternSpec]) -> io::Result<()> {
+        writeln!(out, "Files searched: {}", self.files_searched)?;
+        writeln!(out, "Files skipped: {}", self.files_skipped)?;
+        if self.files_pruned > 0 {
+            writeln!(out, "Files ruled out by the index: {}", self.files_pruned)?;
+        }
+        if self.files_cached > 0 {
+            writeln!(out, "Files answered from the cache: {}", self.files_cached)?;
+        }
+        if self.duplicates > 0 {
+            writeln!(out, "Duplicate files (not searched again): {}", self.duplicates)?;
+        }
+        writeln!(out, "Bytes scanned: {}", self.bytes_scanned)?;
+        writeln!(out, "Per pattern:")?;
+        for (spec, stats) in pattern_specs.iter().zip(&self.patterns) {
+            let label = spec.name.as_deref().unwrap_or(&spec.pattern);
+            writeln!(out, "  {}: {} matches in {} files", label, stats.matches, stats.files)?;
+        }
+        if let Some(severities) = self.severities(pattern_specs) {
+            writeln!(out, "Per severity:")?;
+            for (severity, matches) in severities {
+                writeln!(out, "  {}: {} matches", severity.as_str(), matches)?;
+            }
+        }
+        if !self.slowest.is_empty() {
+            writeln!(out, "Slowest files:")?;
+            for (elapsed, path) in self.slowest_files() {
+                writeln!(out, "  {:?} {}", elapsed, path.display())?;
+            }
+        }
+        if !self.roots.is_empty() {
+            writeln!(out, "Per path:")?;
+            for root in &self.roots {
+                write!(out, "  {} ({})", root.path.display(), root.kind.label())?;
+                if root.kind.is_searchable() {
+                    write!(out, ": {} files searched, {} matches", root.files_searched, root.matches)?;
+                }
+                writeln!(out)?;
+            }
+        }
+        if !self.near_duplicates.is_empty() {
+            writeln!(out, "Near duplicates skipped:")?;
+            for duplicate in &self.near_duplicates {
+                writeln!(out, "  {} ({:.0}% similar to {})", duplicate.path.display(), duplicate.similarity * 100.0, duplicate.of.display())?;
+            }
+        }
+        if self.by_extension {
+            writeln!(out, "Per extension:")?;
+            for (extension, stats) in self.extensions() {
+                writeln!(
+                    out,
+                    "  {}: {} files, {}, {:?}, {} matches",
+                    extension,
+                    stats.files,
+                    human_bytes(stats.bytes),
+                    stats.time,
+                    stats.matches
+                )?;
+            }
+        }
+        if let Some(resources) = &self.resources {
+            writeln!(out, "Resources:")?;
+            if let Some(peak_rss) = resources.peak_rss_bytes {
+                writeln!(out, "  Peak memory: {}", human_bytes(peak_rss))?;
+            }
+            writeln!(out, "  CPU time: {:?} user, {:?} system", resources.user_time, resources.system_time)?;
+            if let Some(bytes_read) = resources.bytes_read {
+                writeln!(out, "  Bytes read: {}", human_bytes(bytes_read))?;
+            }
+            writeln!(out, "  Average parallelism: {:.2}", resources.parallelism())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole statistics block as one JSON object, durations in
+    /// seconds.
+    pub fn write_json(
+        &self,
+        out: &mut dyn Write,
+        pattern_specs: &[PatternSpec],
+        total_matches: usize,
+        files_with_matches: usize,
+        elapsed: Duration,
+    ) -> io::Result<()> {
+        let patterns: Vec<String> = pattern_specs.iter().zip(&self.patterns)
+            .map(|(spec, stats)| format!(
+                "{{\"pattern\":{},\"name\":{},\"matches\":{},\"files\":{}}}",
+                json_string(&spec.pattern),
+                spec.name.as_deref().map_or("null".to_string(), json_string),
+                stats.matches,
+                stats.files,
+            ))
+            .collect();
+        let slowest: Vec<String> = self.slowest_files().into_iter()
+            .map(|(elapsed, path)| format!(
+                "{{\"path\":{},\"seconds\":{}}}",
+                json_string(&path.to_string_lossy()),
+                elapsed.as_secs_f64(),
+            ))
+            .collect();
+        let roots: Vec<String> = self.roots.iter()
+            .map(|root| format!(
+                "{{\"path\":{},\"kind\":\"{}\",\"files_searched\":{},\"matches\":{}}}",
+                json_string(&root.path.to_string_lossy()),
+                root.kind.label(),
+                root.files_searched,
+                root.matches,
+            ))
+            .collect();
+        let near_duplicates: Vec<String> = self.near_duplicates.iter()
+            .map(|duplicate| format!(
+                "{{\"path\":{},\"of\":{},\"similarity\":{}}}",
+                json_string(&duplicate.path.to_string_lossy()),
+                json_string(&duplicate.of.to_string_lossy()),
+                duplicate.similarity,
+            ))
+            .collect();
+        let extensions = if self.by_extension {
+            let extensions: Vec<String> = self.extensions().into_iter()
+                .map(|(extension, stats)| format!(
+                    "{{\"extension\":{},\"files\":{},\"bytes\":{},\"seconds\":{},\"matches\":{}}}",
+                    json_string(extension),
+                    stats.files,
+                    stats.bytes,
+                    stats.time.as_secs_f64(),
+                    stats.matches,
+                ))
+                .collect();
+            format!(",\"extensions\":[{}]", extensions.join(","))
+        } else {
+            String::new()
+        };
+        let severities = self.severities(pattern_specs).map_or(String::new(), |severities| {
+            let counts: Vec<String> = severities.iter().map(|(severity, matches)| format!("\"{}\":{}", severity.as_str(), matches)).collect();
+            format!(",\"severities\":{{{}}}", counts.join(","))
+        });
+        let option = |value: Option<u64>| value.map_or("null".to_string(), |value| value.to_string());
+        let resources = self.resources.as_ref().map_or("null".to_string(), |resources| format!(
+            "{{\"peak_rss_bytes\":{},\"user_seconds\":{},\"system_seconds\":{},\"bytes_read\":{},\"parallelism\":{}}}",
+            option(resources.peak_rss_bytes),
+            resources.user_time.as_secs_f64(),
+            resources.system_time.as_secs_f64(),
+            option(resources.bytes_read),
+            resources.parallelism(),
+        ));
+        writeln!(
+            out,
+            "{{\"total_matches\":{},\"files_with_matches\":{},\"files_searched\":{},\"files_skipped\":{},\"files_pruned\":{},\"files_cached\":{},\"duplicates\":{},\"bytes_scanned\":{},\"elapsed_seconds\":{},\"patterns\":[{}],\"slowest_files\":[{}],\"roots\":[{}],\"near_duplicates\":[{}],\"resources\":{}{}{}}}",
+            total_matches,
+            files_with_matches,
+            self.files_searched,
+            self.files_skipped,
+            self.files_pruned,
+            self.files_cached,
+            self.duplicates,
+            self.bytes_scanned,
+            elapsed.as_secs_f64(),
+            patterns.join(","),
+            slowest.join(","),
+            roots.join(","),
+            near_duplicates.join(","),
+            resources,
+            extensions,
+            severities,
+        )
+    }
+}
+
+/// The single line of `--summary-line`, for wrapper scripts.
+pub fn summary_line(matched: usize, files: usize, errors: usize, elapsed: Duration) -> String {
+    format!("matched={} files={} errors={} elapsed_ms={}", matched, files, errors, elapsed.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, pattern_index: usize) -> SearchResult {
+        SearchResult::new(path, 1, "", pattern_index)
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let mut stats = SearchStats::new(2);
+        for index in 0..7 {
+            let path = format!("file{}.log", index);
+            let results = if index == 0 {
+                // A line that both patterns match counts for both.
+                vec![result(&path, 0), SearchResult { other_patterns: vec![1], ..result(&path, 0) }, result(&path, 1)]
+            } else {
+                vec![result(&path, 1)]
+            };
+            stats.record_file(Path::new(&path), 100, Duration::from_millis(index), &results);
+        }
+        stats.files_skipped += 1;
+
+        assert_eq!(stats.patterns, vec![PatternStats { matches: 2, files: 1 }, PatternStats { matches: 8, files: 7 }]);
+        let slowest: Vec<String> = stats.slowest_files().iter().map(|(_, path)| path.display().to_string()).collect();
+        assert_eq!(slowest, vec!["file6.log", "file5.log", "file4.log", "file3.log", "file2.log"]);
+
+        let pattern_specs = [PatternSpec::new("ERROR"), PatternSpec { name: Some("warnings".to_string()), ..PatternSpec::new("WARN") }];
+        let mut out = Vec::new();
+        stats.write_details(&mut out, &pattern_specs).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with("Files searched: 7\nFiles skipped: 1\nBytes scanned: 700\nPer pattern:\n  ERROR: 2 matches in 1 files\n  warnings: 8 matches in 7 files\nSlowest files:\n  6ms file6.log\n"));
+    }
+
+    #[test]
+    fn test_severity_stats() {
+        let mut stats = SearchStats::new(3);
+        stats.record_file(Path::new("a.env"), 10, Duration::ZERO, &[result("a.env", 0), result("a.env", 1), result("a.env", 2), result("a.env", 2)]);
+        let spec = |name: &str, severity| PatternSpec { name: Some(name.to_string()), severity, ..PatternSpec::new(name) };
+        let pattern_specs = [spec("key", Some(Severity::Error)), spec("todo", None), spec("note", Some(Severity::Info))];
+        assert_eq!(stats.severities(&pattern_specs), Some(vec![(Severity::Error, 1), (Severity::Warn, 1), (Severity::Info, 2)]));
+        assert_eq!(stats.severities(&[PatternSpec::new("a"), PatternSpec::new("b"), PatternSpec::new("c")]), None);
+
+        let mut out = Vec::new();
+        stats.write_details(&mut out, &pattern_specs).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("Per severity:\n  error: 1 matches\n  warn: 1 matches\n  info: 2 matches\n"));
+        let mut out = Vec::new();
+        stats.write_json(&mut out, &pattern_specs, 4, 1, Duration::ZERO).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(r#""severities":{"error":1,"warn":1,"info":2}}
+"#));
+    }
+
+    #[test]
+    fn test_extension_stats() {
+        let mut stats = SearchStats::new(1);
+        stats.record_file(Path::new("a.LOG"), 100, Duration::from_millis(30), &[result("a.LOG", 0), result("a.LOG", 0)]);
+        stats.record_file(Path::new("b.log"), 50, Duration::from_millis(20), &[]);
+        stats.record_file(Path::new("Makefile"), 10, Duration::from_millis(1), &[result("Makefile", 0)]);
+        stats.record_file(Path::new("c.rs"), 2048, Duration::from_millis(5), &[]);
+        stats.by_extension = true;
+
+        let mut out = Vec::new();
+        stats.write_details(&mut out, &[PatternSpec::new("x")]).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(
+            "Per extension:\n  .log: 2 files, 150 B, 50ms, 2 matches\n  .rs: 1 files, 2.0 KiB, 5ms, 0 matches\n  (none): 1 files, 10 B, 1ms, 1 matches\n"
+        ));
+        let mut out = Vec::new();
+        stats.write_json(&mut out, &[PatternSpec::new("x")], 3, 2, Duration::from_millis(60)).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(r#""extensions":[{"extension":".log","files":2,"bytes":150,"seconds":0.05,"matches":2},"#));
+    }
+
+    #[test]
+    fn test_root_stats() {
+        let roots = [
+            (PathBuf::from("src"), RootKind::Directory),
+            (PathBuf::from("gone"), RootKind::Missing),
+            (PathBuf::from("logs/app.zip"), RootKind::File),
+        ];
+        let files = [PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs"), PathBuf::from("logs/app.zip")];
+        let results = [result("src/a.rs", 0), result("logs/app.zip!inner/x.log", 0), result("logs/app.zip!inner/y.log", 0)];
+        let mut stats = SearchStats::new(1);
+        stats.record_roots(&roots, &files, &results);
+        let counts: Vec<(usize, usize)> = stats.roots.iter().map(|root| (root.files_searched, root.matches)).collect();
+        assert_eq!(counts, vec![(2, 1), (0, 0), (1, 2)]);
+
+        let mut out = Vec::new();
+        stats.write_details(&mut out, &[PatternSpec::new("x")]).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(
+            "Per path:\n  src (directory): 2 files searched, 1 matches\n  gone (missing)\n  logs/app.zip (file): 1 files searched, 2 matches\n"
+        ));
+    }
+
+    #[test]
+    fn test_root_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "a").unwrap();
+        assert_eq!(RootKind::of(dir.path()), RootKind::Directory);
+        assert_eq!(RootKind::of(&file), RootKind::File);
+        assert_eq!(RootKind::of(&dir.path().join("missing")), RootKind::Missing);
+    }
+
+    #[test]
+    fn test_summary_line() {
+        assert_eq!(summary_line(12, 3, 1, Duration::from_micros(45_600)), "matched=12 files=3 errors=1 elapsed_ms=45");
+    }
+
+    #[test]
+    fn test_json_stats() {
+        let mut stats = SearchStats::new(1);
+        stats.record_file(Path::new("dir/\"quoted\".log"), 42, Duration::from_millis(250), &[result("dir/\"quoted\".log", 0)]);
+        let pattern_specs = [PatternSpec::new(r"\bERROR\b")];
+        let mut out = Vec::new();
+        stats.write_json(&mut out, &pattern_specs, 1, 1, Duration::from_millis(1500)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"{"total_matches":1,"files_with_matches":1,"files_searched":1,"files_skipped":0,"files_pruned":0,"files_cached":0,"duplicates":0,"bytes_scanned":42,"elapsed_seconds":1.5,"#,
+            r#""patterns":[{"pattern":"\\bERROR\\b","name":null,"matches":1,"files":1}],"#,
+            r#""slowest_files":[{"path":"dir/\"quoted\".log","seconds":0.25}],"roots":[],"near_duplicates":[],"resources":null}"#,
+            "\n",
+        ));
+    }
+}
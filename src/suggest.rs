@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The share of the search time a directory or extension without matches
+/// must take before `--suggest` proposes to exclude it.
+const MIN_SHARE: f64 = 0.1;
+
+/// An exclusion `--suggest` proposes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// What to exclude, as the user would call it: `node_modules`, `*.min.js`.
+    pub subject: String,
+    /// The `--include` glob that excludes it.
+    pub glob: String,
+    /// Its share (0 to 1) of the time spent searching files.
+    pub share: f64,
+}
+
+#[derive(Default)]
+struct Tally {
+    time: Duration,
+    matches: usize,
+}
+
+/// The directories and extensions that took a large share of the search
+/// time without producing a single match, the costliest first. `files`
+/// holds each searched file with its search time and number of matches.
+pub fn suggest_exclusions(files: &[(PathBuf, Duration, usize)], roots: &[PathBuf]) -> Vec<Suggestion> {
+    let total: Duration = files.iter().map(|(_, time, _)| *time).sum();
+    if total.is_zero() {
+        return Vec::new();
+    }
+    let relative: Vec<&Path> = files.iter()
+        .map(|(path, _, _)| roots.iter().find_map(|root| path.strip_prefix(root).ok()).unwrap_or(path))
+        .collect();
+    let mut directories: HashMap<&Path, Tally> = HashMap::new();
+    for ((_, time, matches), path) in files.iter().zip(&relative) {
+        for directory in path.ancestors().skip(1).filter(|directory| !directory.as_os_str().is_empty()) {
+            let tally = directories.entry(directory).or_default();
+            tally.time += *time;
+            tally.matches += matches;
+        }
+    }
+    let share = |tally: &Tally| tally.time.as_secs_f64() / total.as_secs_f64();
+
+    let mut idle: Vec<(&Path, f64)> = directories.iter()
+        .filter(|(_, tally)| tally.matches == 0 && share(tally) >= MIN_SHARE)
+        .map(|(directory, tally)| (*directory, share(tally)))
+        .collect();
+    // Shortest paths first, so that only the outermost idle directory is kept.
+    idle.sort_by_key(|(directory, _)| (directory.components().count(), *directory));
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    let mut excluded: Vec<&Path> = Vec::new();
+    for (directory, share) in idle {
+        if excluded.iter().any(|outer| directory.starts_with(outer)) {
+            continue;
+        }
+        excluded.push(directory);
+        let subject = directory.to_string_lossy().replace('\\', "/");
+        // Globs with a slash are anchored to the current directory: `**/`
+        // keeps this one working whichever path the search was given.
+        let glob = format!("!**/{}/**", subject);
+        suggestions.push(Suggestion { subject, glob, share });
+    }
+    // Extensions are only suggested for what the directories leave.
+    let mut extensions: HashMap<String, Tally> = HashMap::new();
+    for ((_, time, matches), path) in files.iter().zip(&relative) {
+        if excluded.iter().any(|directory| path.starts_with(directory)) {
+            continue;
+        }
+        if let Some(extension) = path.extension() {
+            let tally = extensions.entry(extension.to_string_lossy().to_lowercase()).or_default();
+            tally.time += *time;
+            tally.matches += matches;
+        }
+    }
+    for (extension, tally) in &extensions {
+        if tally.matches == 0 && share(tally) >= MIN_SHARE {
+            suggestions.push(Suggestion { subject: format!("*.{}", extension), glob: format!("!*.{}", extension), share: share(tally) });
+        }
+    }
+    suggestions.sort_by(|a, b| b.share.total_cmp(&a.share).then_with(|| a.subject.cmp(&b.subject)));
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, millis: u64, matches: usize) -> (PathBuf, Duration, usize) {
+        (PathBuf::from(path), Duration::from_millis(millis), matches)
+    }
+
+    #[test]
+    fn test_suggest_exclusions() {
+        let files = [
+            file("repo/node_modules/a/index.js", 300, 0),
+            file("repo/node_modules/b/index.js", 320, 0),
+            file("repo/src/main.rs", 100, 3),
+            file("repo/src/vendor/big/lib.rs", 150, 0),
+            file("repo/src/app.rs", 30, 1),
+            file("repo/docs/guide.txt", 130, 0),
+            file("repo/README.md", 70, 0),
+            file("repo/CHANGELOG.md", 70, 0),
+        ];
+        let suggestions = suggest_exclusions(&files, &[PathBuf::from("repo")]);
+        let globs: Vec<(&str, &str)> = suggestions.iter().map(|s| (s.subject.as_str(), s.glob.as_str())).collect();
+        assert_eq!(globs, vec![
+            ("node_modules", "!**/node_modules/**"),
+            ("src/vendor", "!**/src/vendor/**"),
+            ("*.md", "!*.md"),
+            ("docs", "!**/docs/**"),
+        ]);
+        assert_eq!(suggestions[0].share, 620.0 / 1170.0);
+
+        assert!(suggest_exclusions(&[file("a.rs", 0, 0)], &[]).is_empty());
+    }
+}
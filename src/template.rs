@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::SearchResult;
+use crate::engine::Matcher;
+use crate::longpath;
+use crate::output::{self, Printer, match_columns, pattern_label};
+
+/// A value `--template` can put in its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Path,
+    FileName,
+    Line,
+    EndLine,
+    Column,
+    Pattern,
+    PatternName,
+    Rule,
+    Text,
+    Match,
+    Size,
+    Modified,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("path", Field::Path),
+    ("filename", Field::FileName),
+    ("line_number", Field::Line),
+    ("line", Field::Line),
+    ("end_line", Field::EndLine),
+    ("column", Field::Column),
+    ("pattern", Field::Pattern),
+    ("pattern_name", Field::PatternName),
+    ("rule", Field::Rule),
+    ("text", Field::Text),
+    ("match", Field::Match),
+    ("size", Field::Size),
+    ("modified", Field::Modified),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// The format of each result with `--template`, such as
+/// `{path}\t{line_number}\t{text}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Segment>);
+
+/// What `--template` needs to know of a file.
+struct FileInfo {
+    size: Option<u64>,
+    modified: Option<String>,
+}
+
+impl Template {
+    /// Parses a template: `{name}` placeholders, `{{` and `}}` for literal
+    /// braces, and `\t`, `\n` and `\\` escapes, as shells do not expand them
+    /// in quotes.
+    pub fn parse(value: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let Some(&(_, field)) = FIELDS.iter().find(|(known, _)| *known == name) else {
+                        let known: Vec<&str> = FIELDS.iter().map(|(known, _)| *known).collect();
+                        return Err(format!("unknown placeholder `{{{}}}`, expected one of {}", name, known.join(", ")));
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '}' => return Err("unmatched `}`, write `}}` for a literal brace".to_string()),
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('0') => literal.push('\0'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template(segments))
+    }
+
+    fn uses(&self, fields: &[Field]) -> bool {
+        self.0.iter().any(|segment| matches!(segment, Segment::Field(field) if fields.contains(field)))
+    }
+
+    /// Writes each result as the template says, followed by a newline.
+    /// Matched text is masked with `--redact`.
+    pub fn write_results<M: Matcher>(&self, out: &mut dyn Write, printer: &Printer<M>, results: &[SearchResult]) -> io::Result<()> {
+        let mut files: HashMap<&Path, FileInfo> = HashMap::new();
+        let needs_metadata = self.uses(&[Field::Size, Field::Modified]);
+        let unknown = FileInfo { size: None, modified: None };
+        for result in results {
+            let matcher = &printer.matchers[result.pattern_index];
+            let file = if needs_metadata {
+                files.entry(&result.path).or_insert_with(|| {
+                    let metadata = fs::metadata(longpath::for_opening(&result.path)).ok();
+                    FileInfo {
+                        size: metadata.as_ref().map(|metadata| metadata.len()),
+                        modified: metadata.and_then(|metadata| metadata.modified().ok())
+                            .map(|modified| DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Secs, true)),
+                    }
+                })
+            } else {
+                &unknown
+            };
+            for segment in &self.0 {
+                match segment {
+                    Segment::Literal(text) => out.write_all(text.as_bytes())?,
+                    Segment::Field(Field::Path) => write!(out, "{}", result.path.display())?,
+                    Segment::Field(Field::FileName) => {
+                        write!(out, "{}", result.path.file_name().unwrap_or_default().to_string_lossy())?
+                    }
+                    Segment::Field(Field::Line) => write!(out, "{}", result.line_number)?,
+                    Segment::Field(Field::EndLine) => write!(out, "{}", result.end_line_number.unwrap_or(result.line_number))?,
+                    Segment::Field(Field::Column) => {
+                        write!(out, "{}", match_columns(matcher, result).map_or(1, |(start_column, _)| start_column))?
+                    }
+                    Segment::Field(Field::Pattern) => write!(out, "{}", result.pattern)?,
+                    Segment::Field(Field::PatternName) => {
+                        write!(out, "{}", printer.pattern_specs[result.pattern_index].name.as_deref().unwrap_or_default())?
+                    }
+                    Segment::Field(Field::Rule) => write!(out, "{}", pattern_label(printer.pattern_specs, result))?,
+                    Segment::Field(Field::Text) => {
+                        let text = if printer.options.redact {
+                            printer.masked(&result.line)
+                        } else {
+                            result.line.clone()
+                        };
+                        write!(out, "{}", text.trim())?
+                    }
+                    Segment::Field(Field::Match) => {
+                        let matched = matcher.find_at(&result.line, 0).map_or("", |span| &result.line[span]);
+                        if printer.options.redact {
+                            write!(out, "{}", output::mask(matched))?
+                        } else {
+                            write!(out, "{}", matched)?
+                        }
+                    }
+                    Segment::Field(Field::Size) => {
+                        if let Some(size) = file.size {
+                            write!(out, "{}", size)?
+                        }
+                    }
+                    Segment::Field(Field::Modified) => {
+                        if let Some(modified) = &file.modified {
+                            write!(out, "{}", modified)?
+                        }
+                    }
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputOptions;
+    use crate::patterns::PatternSpec;
+    use regex::Regex;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Template::parse("{path}\\t{line}").unwrap(), Template(vec![
+            Segment::Field(Field::Path),
+            Segment::Literal("\t".to_string()),
+            Segment::Field(Field::Line),
+        ]));
+        assert_eq!(Template::parse("{{{rule}}}").unwrap().0[0], Segment::Literal("{".to_string()));
+        assert!(Template::parse("{nope}").unwrap_err().contains("unknown placeholder `{nope}`"));
+        assert!(Template::parse("a}b").is_err());
+    }
+
+    #[test]
+    fn test_write_results() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.env");
+        fs::write(&path, "  API_KEY=abc123\n").unwrap();
+        let pattern_specs = [PatternSpec { name: Some("keys".to_string()), ..PatternSpec::new("KEY=\\w+") }];
+        let matchers = [Regex::new("KEY=\\w+").unwrap()];
+        let result = SearchResult { path: path.clone(), pattern: "KEY=\\w+".to_string(), ..SearchResult::new("", 1, "  API_KEY=abc123", 0) };
+        let render = |template: &str, redact: bool| {
+            let printer = Printer { pattern_specs: &pattern_specs, matchers: &matchers, options: OutputOptions { redact, ..Default::default() } };
+            let mut out = Vec::new();
+            Template::parse(template).unwrap().write_results(&mut out, &printer, std::slice::from_ref(&result)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(render("{filename}:{line_number}:{column}\\t{rule}/{pattern_name}: {match} in {text}", false), "app.env:1:7\tkeys/keys: KEY=abc123 in API_KEY=abc123\n");
+        assert_eq!(render("{match} {text}", true), "***=****** API_***=******\n");
+        assert_eq!(render("{size}", false), "17\n");
+        assert!(render("{modified}", false).ends_with("Z\n"));
+    }
+}
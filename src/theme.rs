@@ -0,0 +1,162 @@
+use colored::{Color, ColoredString, Colorize};
+
+/// How a part of a result is styled in terminal output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub underline: bool,
+    pub italic: bool,
+}
+
+impl Style {
+    fn fg(color: Color) -> Style {
+        Style { fg: Some(color), ..Style::default() }
+    }
+
+    pub fn paint(&self, text: &str) -> ColoredString {
+        let mut painted = ColoredString::from(text);
+        if let Some(fg) = self.fg {
+            painted = painted.color(fg);
+        }
+        if let Some(bg) = self.bg {
+            painted = painted.on_color(bg);
+        }
+        if self.bold {
+            painted = painted.bold();
+        }
+        if self.underline {
+            painted = painted.underline();
+        }
+        if self.italic {
+            painted = painted.italic();
+        }
+        painted
+    }
+}
+
+/// The colors of the matches, by pattern, in turn, unless `match:fg` says
+/// otherwise.
+const PATTERN_COLORS: [Color; 5] = [Color::Red, Color::Blue, Color::Green, Color::Cyan, Color::Yellow];
+
+/// The styles of the parts of a result in terminal output, changed with
+/// `--colors` and the `[colors]` section of the configuration files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub path: Style,
+    pub line: Style,
+    pub pattern: Style,
+    /// The style of the matches, which take the color of their pattern
+    /// when it has no `fg`, unless `pattern_colors` is off.
+    pub matched: Style,
+    pub pattern_colors: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            path: Style::fg(Color::Green),
+            line: Style::fg(Color::Yellow),
+            pattern: Style::fg(Color::Magenta),
+            matched: Style { bold: true, ..Style::default() },
+            pattern_colors: true,
+        }
+    }
+}
+
+/// A color name, such as `blue` or `bright-red`, or a `#rrggbb` color.
+fn parse_color(value: &str) -> Result<Color, String> {
+    if let Some(hex) = value.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())) {
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).unwrap_or_default();
+        return Ok(Color::TrueColor { r: channel(0), g: channel(2), b: channel(4) });
+    }
+    value.replace(['-', '_'], " ").parse()
+        .map_err(|_| format!("unknown color `{}`, expected black, red, green, yellow, blue, magenta, cyan, white, bright-NAME or #rrggbb", value))
+}
+
+impl Theme {
+    /// The style of the matches of the pattern at `pattern_index`.
+    pub fn match_style(&self, pattern_index: usize) -> Style {
+        match self.matched.fg {
+            None if self.pattern_colors => Style { fg: Some(PATTERN_COLORS[pattern_index % PATTERN_COLORS.len()]), ..self.matched },
+            _ => self.matched,
+        }
+    }
+
+    /// Applies a `--colors` spec: `TYPE:fg:COLOR`, `TYPE:bg:COLOR`,
+    /// `TYPE:style:STYLE` or `TYPE:none`, which leaves TYPE unstyled, where
+    /// TYPE is `path`, `line`, `pattern` or `match`.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let mut parts = spec.splitn(3, ':');
+        let kind = parts.next().unwrap_or_default();
+        let style = match kind {
+            "path" => &mut self.path,
+            "line" => &mut self.line,
+            "pattern" => &mut self.pattern,
+            "match" => &mut self.matched,
+            _ => return Err(format!("`{}`: unknown type `{}`, expected path, line, pattern or match", spec, kind)),
+        };
+        match (parts.next(), parts.next()) {
+            (Some("none"), None) => {
+                *style = Style::default();
+                if kind == "match" {
+                    self.pattern_colors = false;
+                }
+            }
+            (Some("fg"), Some(value)) => {
+                style.fg = Some(parse_color(value).map_err(|e| format!("`{}`: {}", spec, e))?);
+            }
+            (Some("bg"), Some(value)) => {
+                style.bg = Some(parse_color(value).map_err(|e| format!("`{}`: {}", spec, e))?);
+            }
+            (Some("style"), Some(value)) => match value {
+                "bold" => style.bold = true,
+                "nobold" => style.bold = false,
+                "underline" => style.underline = true,
+                "nounderline" => style.underline = false,
+                "italic" => style.italic = true,
+                "noitalic" => style.italic = false,
+                _ => return Err(format!("`{}`: unknown style `{}`, expected bold, underline, italic or nobold, nounderline, noitalic", spec, value)),
+            },
+            _ => return Err(format!("`{}`: expected TYPE:fg:COLOR, TYPE:bg:COLOR, TYPE:style:STYLE or TYPE:none", spec)),
+        }
+        Ok(())
+    }
+}
+
+/// Checks a `--colors` spec on the command line.
+pub fn parse_spec(spec: &str) -> Result<String, String> {
+    Theme::default().apply(spec)?;
+    Ok(spec.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut theme = Theme::default();
+        theme.apply("path:fg:blue").unwrap();
+        theme.apply("line:fg:bright-black").unwrap();
+        theme.apply("match:bg:yellow").unwrap();
+        theme.apply("pattern:style:underline").unwrap();
+        assert_eq!(theme.path, Style::fg(Color::Blue));
+        assert_eq!(theme.line, Style::fg(Color::BrightBlack));
+        assert!(theme.pattern.underline && theme.pattern.fg == Some(Color::Magenta));
+        // The matches keep the color of their pattern.
+        assert_eq!(theme.match_style(1), Style { fg: Some(Color::Blue), bg: Some(Color::Yellow), bold: true, ..Style::default() });
+
+        theme.apply("match:fg:#ff8000").unwrap();
+        assert_eq!(theme.match_style(1).fg, Some(Color::TrueColor { r: 255, g: 128, b: 0 }));
+        theme.apply("match:none").unwrap();
+        assert_eq!(theme.match_style(0), Style::default());
+
+        assert!(theme.apply("file:fg:blue").unwrap_err().contains("unknown type `file`"));
+        assert!(theme.apply("path:fg:grey").unwrap_err().contains("unknown color `grey`"));
+        assert!(theme.apply("path:style:blink").is_err());
+        assert!(theme.apply("path:fg").is_err());
+        assert!(parse_spec("match:style:nobold").is_ok());
+    }
+}
@@ -0,0 +1,99 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+/// Formats tried, in order, by `--parse-timestamp auto`. Formats with an
+/// offset come before their naive counterparts so the offset is not lost.
+const AUTO_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%d/%b/%Y:%H:%M:%S %z",
+    "%Y/%m/%d %H:%M:%S",
+    "%a %b %e %H:%M:%S %Y",
+    "%Y-%m-%d",
+];
+
+/// How to find the timestamp of a line (`--parse-timestamp`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Try a list of common log formats (ISO 8601, Apache, nginx, ctime).
+    Auto,
+    /// A strftime-style format such as `%d/%m/%Y %H:%M`.
+    Custom(String),
+}
+
+impl TimestampFormat {
+    pub fn parse(value: &str) -> Result<TimestampFormat, String> {
+        match value {
+            "auto" => Ok(TimestampFormat::Auto),
+            "" => Err("the timestamp format cannot be empty".to_string()),
+            format => Ok(TimestampFormat::Custom(format.to_string())),
+        }
+    }
+
+    /// Finds the first timestamp in `line`. Times without an offset are taken as UTC.
+    pub fn extract(&self, line: &str) -> Option<DateTime<Utc>> {
+        let mut previous: Option<char> = None;
+        for (offset, c) in line.char_indices() {
+            // Timestamps start a token: skip positions in the middle of a word or number.
+            let starts_token = c.is_ascii_alphanumeric() && !previous.is_some_and(|p| p.is_ascii_alphanumeric());
+            previous = Some(c);
+            if !starts_token {
+                continue;
+            }
+            let candidate = &line[offset..];
+            let found = match self {
+                TimestampFormat::Auto => AUTO_FORMATS.iter().find_map(|format| parse_prefix(candidate, format)),
+                TimestampFormat::Custom(format) => parse_prefix(candidate, format),
+            };
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+fn parse_prefix(text: &str, format: &str) -> Option<DateTime<Utc>> {
+    if let Ok((datetime, _)) = DateTime::parse_and_remainder(text, format) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    if let Ok((datetime, _)) = NaiveDateTime::parse_and_remainder(text, format) {
+        return Some(datetime.and_utc());
+    }
+    NaiveDate::parse_and_remainder(text, format)
+        .ok()
+        .and_then(|(date, _)| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_auto_formats() {
+        let auto = TimestampFormat::Auto;
+        assert_eq!(auto.extract("[2024-03-01T10:15:30+02:00] ERROR boom"), Some(utc("2024-03-01T08:15:30Z")));
+        assert_eq!(auto.extract("2024-03-01 10:15:30.250 WARN slow"), Some(utc("2024-03-01T10:15:30.250Z")));
+        assert_eq!(
+            auto.extract(r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 500"#),
+            Some(utc("2000-10-10T20:55:36Z"))
+        );
+        assert_eq!(auto.extract("release 2023-12-24"), Some(utc("2023-12-24T00:00:00Z")));
+        assert_eq!(auto.extract("no time here 12345"), None);
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let format = TimestampFormat::parse("%d.%m.%Y %H:%M").unwrap();
+        assert_eq!(format.extract("job 7 finished 24.12.2023 18:30 ok"), Some(utc("2023-12-24T18:30:00Z")));
+        assert_eq!(format.extract("2023-12-24 18:30"), None);
+        assert!(TimestampFormat::parse("").is_err());
+        assert_eq!(TimestampFormat::parse("auto"), Ok(TimestampFormat::Auto));
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Args as ClapArgs;
+use ignore::WalkBuilder;
+use indicatif::ProgressBar;
+use regex::Regex;
+
+use crate::SearchOptions;
+use crate::error::FinderError;
+use crate::filters::FileFilter;
+
+/// Markers looked for, optionally followed by an owner in parentheses:
+/// `TODO(alice): ...`.
+const MARKER: &str = r"\b(TODO|FIXME|HACK|XXX)\b(?:\(([^)]*)\))?:?\s*(.*)";
+
+/// Arguments of `finder todos`.
+#[derive(ClapArgs, Debug)]
+pub struct TodosArgs {
+    /// The path(s) to search in (files or directories)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Use `git blame` to find the author of markers without an owner, and the age of every marker
+    #[arg(long)]
+    pub blame: bool,
+}
+
+/// A TODO-like marker found in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Todo {
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub kind: String,
+    pub owner: Option<String>,
+    pub text: String,
+    /// Time since the line was last changed, from `git blame`.
+    pub age: Option<Duration>,
+}
+
+/// Extracts the marker of `line`, with its owner from `TODO(name)` or from
+/// an `@name` mention in the text.
+pub fn parse_marker(marker: &Regex, line: &str) -> Option<(String, Option<String>, String)> {
+    let captures = marker.captures(line)?;
+    let text = captures[3].trim().trim_end_matches("*/").trim_end().to_string();
+    let owner = captures.get(2)
+        .map(|owner| owner.as_str().trim().trim_start_matches('@').to_string())
+        .filter(|owner| !owner.is_empty())
+        .or_else(|| {
+            text.split_whitespace()
+                .find_map(|word| word.strip_prefix('@'))
+                .map(|name| name.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '_').to_string())
+                .filter(|name| !name.is_empty())
+        });
+    Some((captures[1].to_string(), owner, text))
+}
+
+/// Author and author time (seconds since the epoch) of each line number.
+type Blame = HashMap<usize, (String, u64)>;
+
+/// Parses `git blame --porcelain` output.
+pub fn parse_blame(porcelain: &str) -> Blame {
+    let mut commits: HashMap<&str, (String, u64)> = HashMap::new();
+    let mut lines = HashMap::new();
+    let mut current: Option<(&str, usize)> = None;
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            if let Some((sha, line_number)) = current.take()
+                && let Some(commit) = commits.get(sha)
+            {
+                lines.insert(line_number, commit.clone());
+            }
+            continue;
+        }
+        let fields: Vec<&str> = line.split(' ').collect();
+        if fields.len() >= 3 && fields[0].len() == 40 && fields[0].bytes().all(|b| b.is_ascii_hexdigit()) {
+            if let Ok(line_number) = fields[2].parse() {
+                current = Some((fields[0], line_number));
+                commits.entry(fields[0]).or_insert_with(|| (String::new(), 0));
+            }
+        } else if let Some((sha, _)) = current {
+            let commit = commits.entry(sha).or_insert_with(|| (String::new(), 0));
+            if let Some(author) = line.strip_prefix("author ") {
+                commit.0 = author.to_string();
+            } else if let Some(time) = line.strip_prefix("author-time ") {
+                commit.1 = time.parse().unwrap_or(0);
+            }
+        }
+    }
+    lines
+}
+
+fn blame(path: &Path) -> Option<Blame> {
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let output = Command::new("git")
+        .arg("-C").arg(directory)
+        .args(["blame", "--porcelain", "--"])
+        .arg(path.file_name()?)
+        .output()
+        .ok()?;
+    output.status.success().then(|| parse_blame(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Fills in the owner and age of `todos` from `git blame`, one call per file.
+fn add_blame(todos: &mut [Todo]) {
+    let now = SystemTime::now();
+    let mut blames: HashMap<PathBuf, Option<Blame>> = HashMap::new();
+    for todo in todos {
+        let blame = blames.entry(todo.path.clone()).or_insert_with(|| blame(&todo.path));
+        let Some((author, time)) = blame.as_ref().and_then(|blame| blame.get(&todo.line_number)) else { continue };
+        if todo.owner.is_none() && author != "Not Committed Yet" {
+            todo.owner = Some(author.clone());
+        }
+        todo.age = now.duration_since(UNIX_EPOCH + Duration::from_secs(*time)).ok();
+    }
+}
+
+/// Writes the markers grouped by owner, the busiest owner first and the
+/// unassigned ones last.
+pub fn write_report(out: &mut dyn Write, todos: &[Todo]) -> io::Result<()> {
+    let mut by_owner: BTreeMap<Option<&str>, Vec<&Todo>> = BTreeMap::new();
+    for todo in todos {
+        by_owner.entry(todo.owner.as_deref()).or_default().push(todo);
+    }
+    let mut groups: Vec<(Option<&str>, Vec<&Todo>)> = by_owner.into_iter().collect();
+    groups.sort_by_key(|(owner, todos)| (owner.is_none(), std::cmp::Reverse(todos.len())));
+
+    for (index, (owner, todos)) in groups.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "{} ({})", owner.unwrap_or("unassigned"), todos.len())?;
+        for todo in todos {
+            write!(out, "  {}:{}: {} {}", todo.path.display(), todo.line_number, todo.kind, todo.text)?;
+            match todo.age {
+                Some(age) => writeln!(out, " ({} days)", age.as_secs() / 86_400)?,
+                None => writeln!(out)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: TodosArgs) -> Result<(), FinderError> {
+    let marker = Regex::new(MARKER)?;
+    let mut walk_builder = WalkBuilder::new(&args.paths[0]);
+    for path in &args.paths[1..] {
+        walk_builder.add(path);
+    }
+    let outcome = crate::search_walk(
+        &walk_builder,
+        std::slice::from_ref(&marker),
+        &SearchOptions::default(),
+        &FileFilter::default(),
+        &ProgressBar::hidden(),
+    );
+    crate::warn_unreadable(&outcome.errors);
+
+    let mut todos: Vec<Todo> = outcome.results.into_iter()
+        .filter_map(|result| {
+            let (kind, owner, text) = parse_marker(&marker, &result.line)?;
+            Some(Todo { path: result.path, line_number: result.line_number, kind, owner, text, age: None })
+        })
+        .collect();
+    todos.sort_by(|a, b| a.path.cmp(&b.path).then(a.line_number.cmp(&b.line_number)));
+    if args.blame {
+        add_blame(&mut todos);
+    }
+    write_report(&mut io::stdout().lock(), &todos)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_marker() {
+        let marker = Regex::new(MARKER).unwrap();
+        assert_eq!(
+            parse_marker(&marker, "// TODO(alice): handle retries"),
+            Some(("TODO".to_string(), Some("alice".to_string()), "handle retries".to_string()))
+        );
+        assert_eq!(
+            parse_marker(&marker, "/* FIXME leaks on error, ask @bob. */"),
+            Some(("FIXME".to_string(), Some("bob".to_string()), "leaks on error, ask @bob.".to_string()))
+        );
+        assert_eq!(
+            parse_marker(&marker, "# HACK: until upstream is fixed"),
+            Some(("HACK".to_string(), None, "until upstream is fixed".to_string()))
+        );
+        assert_eq!(parse_marker(&marker, "let todos = Vec::new();"), None);
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let porcelain = "\
+1111111111111111111111111111111111111111 1 1 2
+author Alice
+author-time 1700000000
+summary first
+filename src/lib.rs
+\tline one
+1111111111111111111111111111111111111111 2 2
+\tline two
+2222222222222222222222222222222222222222 5 3 1
+author Bob
+author-time 1710000000
+filename src/lib.rs
+\tline three
+";
+        let blame = parse_blame(porcelain);
+        assert_eq!(blame.get(&2), Some(&("Alice".to_string(), 1_700_000_000)));
+        assert_eq!(blame.get(&3), Some(&("Bob".to_string(), 1_710_000_000)));
+    }
+
+    #[test]
+    fn test_write_report() {
+        let todo = |path: &str, line_number: usize, owner: Option<&str>, age_days: Option<u64>| Todo {
+            path: PathBuf::from(path),
+            line_number,
+            kind: "TODO".to_string(),
+            owner: owner.map(str::to_string),
+            text: "x".to_string(),
+            age: age_days.map(|days| Duration::from_secs(days * 86_400)),
+        };
+        let todos = [
+            todo("a.rs", 1, None, None),
+            todo("a.rs", 2, Some("bob"), Some(3)),
+            todo("b.rs", 7, Some("alice"), None),
+            todo("c.rs", 4, Some("bob"), None),
+        ];
+        let mut out = Vec::new();
+        write_report(&mut out, &todos).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+bob (2)
+  a.rs:2: TODO x (3 days)
+  c.rs:4: TODO x
+
+alice (1)
+  b.rs:7: TODO x
+
+unassigned (1)
+  a.rs:1: TODO x
+");
+    }
+}
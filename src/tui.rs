@@ -0,0 +1,364 @@
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use ignore::overrides::OverrideBuilder;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use regex::RegexBuilder;
+
+use crate::SearchResult;
+use crate::engine::CaseMode;
+use crate::output::{line_label, pattern_label};
+use crate::patterns::PatternSpec;
+
+/// Navigation and narrowing state of the result browser, kept apart from
+/// the terminal so that it can be tested.
+pub struct BrowserState {
+    /// `path:line:pattern:text` of every result, lowercased, used for narrowing.
+    haystacks: Vec<String>,
+    filter: String,
+    /// Filters stacked with Tab, each with the results it kept.
+    stages: Vec<(String, Vec<usize>)>,
+    visible: Vec<usize>,
+    selected: usize,
+}
+
+impl BrowserState {
+    pub fn new(haystacks: Vec<String>) -> BrowserState {
+        let haystacks: Vec<String> = haystacks.into_iter().map(|h| h.to_lowercase()).collect();
+        let visible = (0..haystacks.len()).collect();
+        BrowserState { haystacks, filter: String::new(), stages: Vec::new(), visible, selected: 0 }
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Labels of the stacked filters, the first applied first.
+    pub fn stages(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().map(|(label, _)| label.as_str())
+    }
+
+    pub fn visible(&self) -> &[usize] {
+        &self.visible
+    }
+
+    /// Index (into the full result list) of the highlighted result.
+    pub fn selected(&self) -> Option<usize> {
+        self.visible.get(self.selected).copied()
+    }
+
+    pub fn selected_position(&self) -> usize {
+        self.selected
+    }
+
+    pub fn push_filter_char(&mut self, c: char) {
+        self.filter.push(c);
+        self.refilter();
+    }
+
+    pub fn pop_filter_char(&mut self) {
+        self.filter.pop();
+        self.refilter();
+    }
+
+    /// Narrows the results kept by the previous stages down to those that
+    /// `keep` accepts, and clears the typed filter.
+    pub fn push_stage(&mut self, label: String, keep: impl Fn(usize) -> bool) {
+        let kept = self.candidates().filter(|&index| keep(index)).collect();
+        self.stages.push((label, kept));
+        self.filter.clear();
+        self.refilter();
+    }
+
+    /// Undoes the last stacked filter; false when there was none.
+    pub fn pop_stage(&mut self) -> bool {
+        let popped = self.stages.pop().is_some();
+        self.refilter();
+        popped
+    }
+
+    fn candidates(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        match self.stages.last() {
+            Some((_, kept)) => Box::new(kept.iter().copied()),
+            None => Box::new(0..self.haystacks.len()),
+        }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let last = self.visible.len() - 1;
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn refilter(&mut self) {
+        // Every whitespace-separated term must appear somewhere in the result.
+        let terms: Vec<String> = self.filter.to_lowercase().split_whitespace().map(String::from).collect();
+        let previous = self.selected();
+        self.visible = self.candidates()
+            .filter(|&index| terms.iter().all(|term| self.haystacks[index].contains(term.as_str())))
+            .collect();
+        self.selected = previous
+            .and_then(|index| self.visible.iter().position(|&visible| visible == index))
+            .unwrap_or(0);
+    }
+}
+
+struct Browser<'a> {
+    results: &'a [SearchResult],
+    pattern_specs: &'a [PatternSpec],
+    state: BrowserState,
+    /// Result index and lines of the file shown in the preview pane.
+    preview: Option<(usize, Vec<String>)>,
+    status: String,
+}
+
+const HELP: &str = "type to narrow · Tab stack as pattern (or path:GLOB) · Shift-Tab undo · ↑/↓ PgUp/PgDn move · Enter open in $EDITOR · Esc quit";
+
+impl Browser<'_> {
+    fn preview_lines(&mut self, index: usize) -> &[String] {
+        if self.preview.as_ref().is_none_or(|(cached, _)| *cached != index) {
+            // Virtual paths (`archive.zip!inner`) cannot be opened directly.
+            let lines = crate::read_decoded(&self.results[index].path)
+                .map(|content| content.lines().map(String::from).collect())
+                .unwrap_or_else(|e| vec![format!("(preview unavailable: {})", e)]);
+            self.preview = Some((index, lines));
+        }
+        &self.preview.as_ref().unwrap().1
+    }
+
+    /// Stacks the typed text as a filter: `path:GLOB` keeps the results in
+    /// matching paths, anything else is a smart-case regex over the matched
+    /// text.
+    fn stack_filter(&mut self) {
+        let text = self.state.filter().trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        let results = self.results;
+        let outcome = match text.strip_prefix("path:") {
+            Some(glob) => OverrideBuilder::new(".").add(glob).and_then(|builder| builder.build())
+                .map(|paths| self.state.push_stage(text.clone(), |index| paths.matched(&results[index].path, false).is_whitelist()))
+                .map_err(|e| e.to_string()),
+            None => RegexBuilder::new(&text).case_insensitive(CaseMode::Smart.ignore_case(&text)).build()
+                .map(|re| self.state.push_stage(text.clone(), |index| re.is_match(&results[index].line)))
+                .map_err(|e| e.to_string()),
+        };
+        self.status = match outcome {
+            Ok(()) => String::new(),
+            Err(e) => format!("invalid filter: {}", e.lines().last().unwrap_or_default()),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, body_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+        let [list_area, preview_area] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(body_area);
+
+        let mut title = format!(" Filter ({}/{}) ", self.state.visible().len(), self.results.len());
+        for stage in self.state.stages() {
+            title.push_str(&format!("› {} ", stage));
+        }
+        frame.render_widget(
+            Paragraph::new(self.state.filter().to_string()).block(Block::default().borders(Borders::ALL).title(title)),
+            filter_area,
+        );
+
+        let items: Vec<ListItem> = self.state.visible().iter().map(|&index| {
+            let result = &self.results[index];
+            ListItem::new(Line::from(vec![
+                Span::styled(result.path.display().to_string(), Style::default().fg(Color::Green)),
+                Span::raw(":"),
+                Span::styled(line_label(result), Style::default().fg(Color::Yellow)),
+                Span::raw(":"),
+                Span::styled(pattern_label(self.pattern_specs, result).to_string(), Style::default().fg(Color::Magenta)),
+                Span::raw(":"),
+                Span::raw(result.line.trim().to_string()),
+            ]))
+        }).collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Results "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut list_state = ListState::default().with_selected(self.state.selected().map(|_| self.state.selected_position()));
+        frame.render_stateful_widget(list, list_area, &mut list_state);
+
+        let preview_height = preview_area.height.saturating_sub(2) as usize;
+        let preview = match self.state.selected() {
+            Some(index) => {
+                let line_number = self.results[index].line_number;
+                let title = format!(" {} ", self.results[index].path.display());
+                let first = line_number.saturating_sub(preview_height / 2).max(1);
+                let lines: Vec<Line> = self.preview_lines(index).iter().enumerate()
+                    .skip(first - 1)
+                    .take(preview_height)
+                    .map(|(offset, text)| {
+                        let number = offset + 1;
+                        let style = if number == line_number { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+                        Line::from(vec![
+                            Span::styled(format!("{:>5} ", number), Style::default().fg(Color::Yellow)),
+                            Span::styled(text.clone(), style),
+                        ])
+                    })
+                    .collect();
+                Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title))
+            }
+            None => Paragraph::new("").block(Block::default().borders(Borders::ALL).title(" Preview ")),
+        };
+        frame.render_widget(preview, preview_area);
+
+        let status = if self.status.is_empty() { HELP } else { &self.status };
+        frame.render_widget(Paragraph::new(status.to_string()), status_area);
+    }
+
+    fn open_in_editor(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let Some(index) = self.state.selected() else { return Ok(()) };
+        let result = &self.results[index];
+        if !Path::new(&result.path).is_file() {
+            self.status = format!("{} cannot be opened in an editor", result.path.display());
+            return Ok(());
+        }
+        let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+
+        ratatui::restore();
+        let status = process::Command::new(program)
+            .args(words)
+            .arg(format!("+{}", result.line_number))
+            .arg(&result.path)
+            .status();
+        *terminal = ratatui::init();
+        terminal.clear()?;
+        self.status = match status {
+            Ok(status) if status.success() => String::new(),
+            Ok(status) => format!("{} exited with {}", program, status),
+            Err(e) => format!("failed to run {}: {}", program, e),
+        };
+        Ok(())
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let page = terminal.size()?.height.saturating_sub(6).max(1) as isize;
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Up => self.state.move_selection(-1),
+                KeyCode::Down => self.state.move_selection(1),
+                KeyCode::PageUp => self.state.move_selection(-page),
+                KeyCode::PageDown => self.state.move_selection(page),
+                KeyCode::Home => self.state.move_selection(isize::MIN),
+                KeyCode::End => self.state.move_selection(isize::MAX),
+                KeyCode::Enter => self.open_in_editor(terminal)?,
+                KeyCode::Tab => self.stack_filter(),
+                KeyCode::BackTab => {
+                    self.state.pop_stage();
+                }
+                KeyCode::Backspace => self.state.pop_filter_char(),
+                KeyCode::Char(c) => self.state.push_filter_char(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Shows `results` in an interactive, filterable list with a preview pane.
+pub fn browse(results: &[SearchResult], pattern_specs: &[PatternSpec]) -> io::Result<()> {
+    let haystacks = results.iter().map(|result| {
+        format!("{}:{}:{}:{}", result.path.display(), result.line_number, pattern_label(pattern_specs, result), result.line)
+    }).collect();
+    let mut browser = Browser {
+        results,
+        pattern_specs,
+        state: BrowserState::new(haystacks),
+        preview: None,
+        status: String::new(),
+    };
+    let mut terminal = ratatui::init();
+    let outcome = browser.run(&mut terminal);
+    ratatui::restore();
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> BrowserState {
+        BrowserState::new(vec![
+            "src/main.rs:10:todo:TODO refactor".to_string(),
+            "src/lib.rs:3:todo:TODO tests".to_string(),
+            "docs/guide.md:7:todo:todo later".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_narrowing_by_typing() {
+        let mut state = state();
+        for c in "SRC tes".chars() {
+            state.push_filter_char(c);
+        }
+        assert_eq!(state.visible(), &[1]);
+        assert_eq!(state.selected(), Some(1));
+        for _ in 0..4 {
+            state.pop_filter_char();
+        }
+        assert_eq!(state.filter(), "SRC");
+        assert_eq!(state.visible(), &[0, 1]);
+        // The highlighted result stays selected while it remains visible.
+        assert_eq!(state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_stacked_filters() {
+        let mut state = state();
+        state.push_stage("src".to_string(), |index| index < 2);
+        assert_eq!(state.visible(), &[0, 1]);
+        state.push_filter_char('t');
+        state.push_stage("tests".to_string(), |index| index != 0);
+        assert_eq!(state.filter(), "");
+        assert_eq!(state.stages().collect::<Vec<_>>(), vec!["src", "tests"]);
+        assert_eq!(state.visible(), &[1]);
+        // The typed filter only narrows what the stages kept.
+        state.push_filter_char('z');
+        assert!(state.visible().is_empty());
+        state.pop_filter_char();
+        assert!(state.pop_stage());
+        assert_eq!(state.visible(), &[0, 1]);
+        assert!(state.pop_stage());
+        assert!(!state.pop_stage());
+        assert_eq!(state.visible(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn test_selection_is_clamped() {
+        let mut state = state();
+        state.move_selection(10);
+        assert_eq!(state.selected(), Some(2));
+        state.move_selection(isize::MIN);
+        assert_eq!(state.selected(), Some(0));
+        state.push_filter_char('#');
+        assert_eq!(state.selected(), None);
+        state.move_selection(1);
+        assert_eq!(state.selected(), None);
+    }
+}
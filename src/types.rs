@@ -0,0 +1,68 @@
+use ignore::types::{Types, TypesBuilder};
+
+/// Built-in file type database (the same one ripgrep ships) extended with
+/// user definitions of the form `name:glob` given to `--type-add`.
+pub fn types_builder(definitions: &[String]) -> Result<TypesBuilder, ignore::Error> {
+    let mut builder = TypesBuilder::new();
+    builder.add_defaults();
+    for definition in definitions {
+        builder.add_def(definition)?;
+    }
+    Ok(builder)
+}
+
+/// Builds the matcher for `--type` / `--type-not`. Unknown type names are an error.
+pub fn build_types(definitions: &[String], selected: &[String], negated: &[String]) -> Result<Types, ignore::Error> {
+    let mut builder = types_builder(definitions)?;
+    for name in selected {
+        builder.select(name);
+    }
+    for name in negated {
+        builder.negate(name);
+    }
+    builder.build()
+}
+
+/// The names of the known types, sorted, for the completion of `--type`.
+pub fn type_names(definitions: &[String]) -> Result<Vec<String>, ignore::Error> {
+    Ok(types_builder(definitions)?.definitions().into_iter().map(|definition| definition.name().to_string()).collect())
+}
+
+/// One `name: glob, glob` line per type, sorted by name, for `--type-list`.
+pub fn format_type_list(definitions: &[String]) -> Result<String, ignore::Error> {
+    let builder = types_builder(definitions)?;
+    let mut list = String::new();
+    for definition in builder.definitions() {
+        list.push_str(&format!("{}: {}\n", definition.name(), definition.globs().join(", ")));
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_build_types_select_and_add() {
+        let types = build_types(&["proto:*.proto".to_string()], &["rust".to_string(), "proto".to_string()], &[]).unwrap();
+        assert!(types.matched(Path::new("main.rs"), false).is_whitelist());
+        assert!(types.matched(Path::new("api.proto"), false).is_whitelist());
+        assert!(types.matched(Path::new("app.js"), false).is_ignore());
+    }
+
+    #[test]
+    fn test_build_types_negate_and_unknown() {
+        let types = build_types(&[], &[], &["js".to_string()]).unwrap();
+        assert!(types.matched(Path::new("app.js"), false).is_ignore());
+        assert!(types.matched(Path::new("main.rs"), false).is_none());
+        assert!(build_types(&[], &["no-such-type".to_string()], &[]).is_err());
+    }
+
+    #[test]
+    fn test_format_type_list() {
+        let list = format_type_list(&["proto:*.proto".to_string()]).unwrap();
+        assert!(list.lines().any(|line| line == "proto: *.proto"));
+        assert!(list.lines().any(|line| line.starts_with("rust: ")));
+    }
+}
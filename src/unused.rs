@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::Args as ClapArgs;
+use ignore::WalkBuilder;
+use indicatif::ProgressBar;
+use regex::Regex;
+
+use crate::error::FinderError;
+use crate::filters::FileFilter;
+use crate::{SearchOptions, SearchResult};
+
+/// Arguments of `finder unused`.
+#[derive(ClapArgs, Debug)]
+pub struct UnusedArgs {
+    /// The path(s) to search in (files or directories)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Regex matching a definition, with the identifier in its first capture group (or in a group named `id`)
+    #[arg(long, value_name = "REGEX")]
+    pub defs: String,
+
+    /// Regex matching a usage, with `{}` standing for the identifier, e.g. `t\("{}"\)`
+    #[arg(long, value_name = "TEMPLATE", default_value = r"\b{}\b")]
+    pub uses: String,
+}
+
+/// An identifier and a line where it appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub id: String,
+    pub path: PathBuf,
+    pub line_number: usize,
+}
+
+/// The identifiers captured by `regex` in `line`: the group named `id`, or
+/// the first one.
+pub fn identifiers<'a>(regex: &Regex, line: &'a str) -> impl Iterator<Item = &'a str> {
+    regex.captures_iter(line).filter_map(|captures| captures.name("id").or_else(|| captures.get(1))).map(|id| id.as_str())
+}
+
+/// The identifiers defined on the lines matched by `defs`.
+pub fn definitions(defs: &Regex, results: &[SearchResult]) -> Vec<Occurrence> {
+    results.iter()
+        .flat_map(|result| identifiers(defs, &result.line).map(|id| Occurrence {
+            id: id.to_string(),
+            path: result.path.clone(),
+            line_number: result.line_number,
+        }))
+        .collect()
+}
+
+/// Builds the usage regex: `template` with `{}` replaced by an alternation
+/// of all the identifiers, captured as `id`.
+pub fn usage_regex(template: &str, definitions: &[Occurrence]) -> Result<Regex, String> {
+    if template.matches("{}").count() != 1 {
+        return Err(format!("the usage template `{}` must contain `{{}}` exactly once", template));
+    }
+    let mut ids: Vec<&str> = definitions.iter().map(|definition| definition.id.as_str()).collect();
+    ids.sort();
+    ids.dedup();
+    // Longest first, so that `FLAG_A` is not matched as `FLAG` followed by `_A`.
+    ids.sort_by_key(|id| std::cmp::Reverse(id.len()));
+    let alternation = ids.iter().map(|id| regex::escape(id)).collect::<Vec<_>>().join("|");
+    Regex::new(&template.replace("{}", &format!("(?P<id>{})", alternation))).map_err(|e| e.to_string())
+}
+
+/// The uses of the identifiers on the lines matched by `uses`, leaving out
+/// each identifier's own definition lines.
+pub fn usages(definitions: &[Occurrence], uses: &Regex, results: &[SearchResult]) -> Vec<Occurrence> {
+    let sites: HashSet<(&str, &PathBuf, usize)> = definitions.iter()
+        .map(|definition| (definition.id.as_str(), &definition.path, definition.line_number))
+        .collect();
+    results.iter()
+        .flat_map(|result| identifiers(uses, &result.line)
+            .filter(|id| !sites.contains(&(*id, &result.path, result.line_number)))
+            .map(|id| Occurrence { id: id.to_string(), path: result.path.clone(), line_number: result.line_number }))
+        .collect()
+}
+
+/// The definitions whose identifier is not used anywhere but on a
+/// definition line of its own.
+pub fn unused<'a>(definitions: &'a [Occurrence], usages: &[Occurrence]) -> Vec<&'a Occurrence> {
+    let used: HashSet<&str> = usages.iter().map(|usage| usage.id.as_str()).collect();
+    definitions.iter().filter(|definition| !used.contains(definition.id.as_str())).collect()
+}
+
+/// Searches `paths` for the definitions matched by `defs`, then for the
+/// usages of their identifiers through the `uses` template.
+pub fn find_occurrences(paths: &[PathBuf], defs: &str, uses: &str) -> Result<(Vec<Occurrence>, Vec<Occurrence>), FinderError> {
+    let defs = Regex::new(defs)?;
+    if defs.captures_len() < 2 {
+        return Err("the --defs regex needs a capture group around the identifier".into());
+    }
+    let mut walk_builder = WalkBuilder::new(&paths[0]);
+    for path in &paths[1..] {
+        walk_builder.add(path);
+    }
+    let search = |regex: &Regex| {
+        let outcome = crate::search_walk(
+            &walk_builder,
+            std::slice::from_ref(regex),
+            &SearchOptions::default(),
+            &FileFilter::default(),
+            &ProgressBar::hidden(),
+        );
+        crate::warn_unreadable(&outcome.errors);
+        outcome.results
+    };
+
+    let mut definitions = definitions(&defs, &search(&defs));
+    if definitions.is_empty() {
+        return Ok((definitions, Vec::new()));
+    }
+    let uses = usage_regex(uses, &definitions).map_err(FinderError::Usage)?;
+    let mut usages = usages(&definitions, &uses, &search(&uses));
+    definitions.sort_by(|a, b| a.path.cmp(&b.path).then(a.line_number.cmp(&b.line_number)));
+    usages.sort_by(|a, b| a.path.cmp(&b.path).then(a.line_number.cmp(&b.line_number)));
+    Ok((definitions, usages))
+}
+
+pub fn run(args: UnusedArgs) -> Result<(), FinderError> {
+    let (definitions, usages) = find_occurrences(&args.paths, &args.defs, &args.uses)?;
+    if definitions.is_empty() {
+        eprintln!("No definition matched --defs.");
+        return Ok(());
+    }
+    let unused = unused(&definitions, &usages);
+
+    let mut out = io::stdout().lock();
+    for definition in &unused {
+        writeln!(out, "{}:{}:{}", definition.path.display(), definition.line_number, definition.id)?;
+    }
+    let distinct: HashSet<&str> = definitions.iter().map(|definition| definition.id.as_str()).collect();
+    eprintln!("{} of {} definitions are unused.", unused.len(), distinct.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, line_number: usize, line: &str) -> SearchResult {
+        SearchResult::new(path, line_number, line, 0)
+    }
+
+    #[test]
+    fn test_unused_definitions() {
+        let defs = Regex::new(r#"^\s*"(\w+)":"#).unwrap();
+        let definitions = definitions(&defs, &[
+            result("en.json", 2, r#"  "greeting": "Hello","#),
+            result("en.json", 3, r#"  "greeting_long": "Hello there","#),
+            result("en.json", 4, r#"  "farewell": "Bye""#),
+        ]);
+        let ids: Vec<&str> = definitions.iter().map(|definition| definition.id.as_str()).collect();
+        assert_eq!(ids, vec!["greeting", "greeting_long", "farewell"]);
+
+        let uses = usage_regex(r#"t\("{}"\)"#, &definitions).unwrap();
+        let usages = usages(&definitions, &uses, &[
+            result("app.js", 10, r#"title = t("greeting_long") + t("farewell");"#),
+        ]);
+        assert_eq!(usages.len(), 2);
+        let unused = unused(&definitions, &usages);
+        let ids: Vec<&str> = unused.iter().map(|definition| definition.id.as_str()).collect();
+        assert_eq!(ids, vec!["greeting"]);
+
+        assert!(usage_regex("no placeholder", &definitions).is_err());
+    }
+
+    #[test]
+    fn test_definition_line_is_not_a_use() {
+        let defs = Regex::new(r"const (?P<id>FLAG_\w+)").unwrap();
+        let results = [result("flags.rs", 1, "pub const FLAG_BETA: bool = false;")];
+        let definitions = definitions(&defs, &results);
+        let uses = usage_regex(r"\b{}\b", &definitions).unwrap();
+        assert!(usages(&definitions, &uses, &results).is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use ignore::gitignore::Gitignore;
+use ignore::overrides::Override;
+use ignore::types::Types;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::SearchResult;
+
+/// Decides whether a changed file belongs to the search, mirroring the
+/// walk: `--include` globs, `--type` filters, hidden files and the roots'
+/// `.gitignore`.
+pub struct WatchFilter {
+    pub overrides: Option<Override>,
+    pub types: Option<Types>,
+    gitignores: Vec<(PathBuf, Gitignore)>,
+}
+
+impl WatchFilter {
+    pub fn new(roots: &[PathBuf], overrides: Option<Override>, types: Option<Types>) -> WatchFilter {
+        let gitignores = roots.iter()
+            .filter(|root| root.is_dir())
+            .map(|root| (root.clone(), Gitignore::new(root.join(".gitignore")).0))
+            .collect();
+        WatchFilter { overrides, types, gitignores }
+    }
+
+    pub fn accepts(&self, path: &Path) -> bool {
+        if self.overrides.as_ref().is_some_and(|o| o.matched(path, false).is_ignore())
+            || self.types.as_ref().is_some_and(|t| t.matched(path, false).is_ignore())
+        {
+            return false;
+        }
+        for (root, gitignore) in &self.gitignores {
+            let Ok(relative) = path.strip_prefix(root) else { continue };
+            let hidden = relative.components().any(|c| matches!(c, Component::Normal(name) if name.to_string_lossy().starts_with('.')));
+            if hidden || gitignore.matched_path_or_any_parents(path, false).is_ignore() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Matches already reported per file, so that re-searching a file that grew
+/// only prints what is new.
+#[derive(Default)]
+pub struct SeenMatches(HashMap<PathBuf, HashSet<(PathBuf, usize, String)>>);
+
+fn key(result: &SearchResult) -> (PathBuf, usize, String) {
+    (result.path.clone(), result.line_number, result.line.clone())
+}
+
+/// The searched file a result came from: the result's own path, or the
+/// archive part of an `archive.zip!inner/path.txt` entry path.
+fn containing_file(path: &Path, files: &HashSet<&Path>) -> PathBuf {
+    if files.contains(path) {
+        return path.to_path_buf();
+    }
+    let display = path.to_string_lossy();
+    display.match_indices('!')
+        .map(|(index, _)| Path::new(&display[..index]))
+        .find(|candidate| files.contains(candidate))
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+impl SeenMatches {
+    /// Remembers the results of the initial search over `files`. Matches
+    /// found inside an archive are attributed to the archive itself.
+    pub fn record(&mut self, files: &[PathBuf], results: &[SearchResult]) {
+        let files: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+        for result in results {
+            let file = containing_file(&result.path, &files);
+            self.0.entry(file).or_default().insert(key(result));
+        }
+    }
+
+    /// Replaces what is known about `file` with `results` and returns the
+    /// results that were not reported before.
+    pub fn fresh(&mut self, file: &Path, results: Vec<SearchResult>) -> Vec<SearchResult> {
+        let previous = self.0.insert(file.to_path_buf(), results.iter().map(key).collect()).unwrap_or_default();
+        results.into_iter().filter(|result| !previous.contains(&key(result))).collect()
+    }
+}
+
+/// Watches `roots` recursively and calls `on_change` with the paths that
+/// were created, modified, renamed or removed, which may no longer exist
+/// or be directories. Events arriving close together are batched so a
+/// burst of writes to one file triggers a single call. Never returns unless
+/// the watcher fails.
+pub fn watch_files<F: FnMut(&[PathBuf])>(roots: &[PathBuf], mut on_change: F) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for root in roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+
+    let mut changed = BTreeSet::new();
+    while let Ok(event) = rx.recv() {
+        collect(event, &mut changed)?;
+        while let Ok(event) = rx.recv_timeout(Duration::from_millis(100)) {
+            collect(event, &mut changed)?;
+        }
+        let paths: Vec<PathBuf> = std::mem::take(&mut changed).into_iter().collect();
+        if !paths.is_empty() {
+            on_change(&paths);
+        }
+    }
+    Ok(())
+}
+
+fn collect(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) -> notify::Result<()> {
+    let event = event?;
+    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+        changed.extend(event.paths);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ignore::overrides::OverrideBuilder;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn result(line_number: usize, line: &str) -> SearchResult {
+        SearchResult { pattern: "ERROR".to_string(), ..SearchResult::new("app.log", line_number, line, 0) }
+    }
+
+    #[test]
+    fn test_seen_matches_reports_only_new_results() {
+        let mut seen = SeenMatches::default();
+        let file = Path::new("app.log");
+        seen.record(&[file.to_path_buf()], &[result(1, "ERROR a")]);
+        let fresh = seen.fresh(file, vec![result(1, "ERROR a"), result(5, "ERROR b")]);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].line_number, 5);
+        // After a rotation the same line number with new content is new again.
+        let fresh = seen.fresh(file, vec![result(1, "ERROR c")]);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].line, "ERROR c");
+    }
+
+    #[test]
+    fn test_seen_matches_attributes_archive_entries_to_archive() {
+        let mut seen = SeenMatches::default();
+        let archive = PathBuf::from("logs.zip");
+        let mut entry = result(1, "ERROR a");
+        entry.path = crate::archive::entry_path(&archive, "inner/app.log");
+        seen.record(std::slice::from_ref(&archive), std::slice::from_ref(&entry));
+        let fresh = seen.fresh(&archive, vec![entry, result(2, "ERROR b")]);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_watch_filter() {
+        let test_dir = tempdir().unwrap();
+        let root = test_dir.path().to_path_buf();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        let mut overrides = OverrideBuilder::new(".");
+        overrides.add("*.log").unwrap();
+        let filter = WatchFilter::new(std::slice::from_ref(&root), Some(overrides.build().unwrap()), None);
+
+        assert!(filter.accepts(&root.join("logs/app.log")));
+        assert!(!filter.accepts(&root.join("notes.txt")));
+        assert!(!filter.accepts(&root.join("target/build.log")));
+        assert!(!filter.accepts(&root.join(".git/logs/HEAD.log")));
+
+        test_dir.close().unwrap();
+    }
+}
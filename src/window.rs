@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+use crate::engine::Matcher;
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while index < text.len() && !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index.min(text.len())
+}
+
+/// Matches of `re` in `content` found through windows of `window` bytes,
+/// each starting half a window after the previous one, so that any match
+/// of up to half a window is seen whole and none is longer than a window.
+/// Unlike a search over the whole content, a runaway pattern such as
+/// `(?s)BEGIN.*END` cannot swallow the rest of a large file.
+///
+/// The text before a window stays visible to anchors and word boundaries.
+/// Matches that overlap an earlier, longer one are dropped.
+pub fn window_spans<M: Matcher + ?Sized>(re: &M, content: &str, window: usize) -> Vec<Range<usize>> {
+    let step = (window / 2).max(1);
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut start: usize = 0;
+    loop {
+        let end = ceil_char_boundary(content, start.saturating_add(window));
+        let haystack = &content[..end];
+        let mut at = start;
+        while let Some(span) = re.find_at(haystack, at) {
+            if span.is_empty() {
+                // Step over the character after an empty match.
+                at = span.end + haystack[span.end..].chars().next().map_or(1, char::len_utf8);
+                if at > end {
+                    break;
+                }
+                continue;
+            }
+            at = span.end;
+            spans.push(span);
+        }
+        if end == content.len() {
+            break;
+        }
+        start = ceil_char_boundary(content, start + step);
+    }
+
+    spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+    let mut kept: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+    for span in spans {
+        if kept.last().is_none_or(|last| span.start >= last.end) {
+            kept.push(span);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_window_spans() {
+        let key = "-----BEGIN KEY-----\nMIIB\nAAAA\n-----END KEY-----\n";
+        let content = format!("{}{}\n{}", "x".repeat(300), key, "y".repeat(300));
+        let re = Regex::new(r"(?s)-----BEGIN KEY-----.*?-----END KEY-----").unwrap();
+        let spans = window_spans(&re, &content, 128);
+        assert_eq!(spans, vec![300..300 + key.len() - 1]);
+        // Longer than half a window: only seen if a window happens to hold it.
+        assert!(window_spans(&re, &content, 32).is_empty());
+
+        // A greedy pattern is cut at the end of the window it started in.
+        let greedy = Regex::new(r"(?s)x.*").unwrap();
+        let spans = window_spans(&greedy, &content, 100);
+        assert_eq!(spans.first(), Some(&(0..100)));
+        assert!(spans.iter().all(|span| span.len() <= 100));
+
+        // No false `^` at a window start in the middle of a line.
+        let anchored = Regex::new(r"(?m)^y").unwrap();
+        assert_eq!(window_spans(&anchored, &content, 64).len(), 1);
+
+        let text = "é".repeat(100);
+        assert_eq!(window_spans(&Regex::new("éé").unwrap(), &text, 7).len(), 50);
+    }
+}
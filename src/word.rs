@@ -0,0 +1,248 @@
+use std::ops::Range;
+
+use regex_automata::meta;
+use regex_automata::util::syntax;
+use regex_automata::{Anchored, Input};
+use regex_syntax::hir::{self, Hir, HirKind};
+
+use crate::engine::{self, Matcher, next_char_boundary};
+use crate::error::FinderError;
+
+/// Which characters make up a word for `-w`.
+#[derive(Debug, Clone)]
+pub enum WordChars {
+    /// Unicode letters and digits. `_` is a separator, so `id` matches in
+    /// `user_id` but not in `uuid`.
+    Alphanumeric,
+    /// A user supplied regex character class, e.g. `[\w$]`.
+    Class {
+        /// The class as written.
+        source: String,
+        class: hir::Class,
+    },
+}
+
+impl WordChars {
+    /// Builds the word character set from a `--word-chars` class such as
+    /// `[A-Za-z0-9_]`. A single character counts as a class of one.
+    pub fn from_class(source: &str) -> Result<WordChars, FinderError> {
+        let hir = regex_syntax::Parser::new().parse(source)
+            .map_err(|e| FinderError::Usage(format!("--word-chars {}: {}", source, e)))?;
+        let class = match hir.kind() {
+            HirKind::Class(class) => class.clone(),
+            HirKind::Literal(hir::Literal(bytes)) => match std::str::from_utf8(bytes).ok().and_then(|s| s.parse::<char>().ok()) {
+                Some(c) => hir::Class::Unicode(hir::ClassUnicode::new([hir::ClassUnicodeRange::new(c, c)])),
+                None => return Err(FinderError::Usage(format!("--word-chars {}: not a character class", source))),
+            },
+            _ => return Err(FinderError::Usage(format!("--word-chars {}: not a character class, such as `[A-Za-z0-9_]`", source))),
+        };
+        Ok(WordChars::Class { source: source.to_string(), class })
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            WordChars::Alphanumeric => c.is_alphanumeric(),
+            WordChars::Class { class: hir::Class::Unicode(class), .. } => class.ranges().iter().any(|range| range.start() <= c && c <= range.end()),
+            WordChars::Class { class: hir::Class::Bytes(class), .. } => {
+                u8::try_from(c).is_ok_and(|b| class.ranges().iter().any(|range| range.start() <= b && b <= range.end()))
+            }
+        }
+    }
+
+    /// The word characters as a regex class.
+    fn class(&self) -> &str {
+        match self {
+            WordChars::Alphanumeric => r"[\p{Alphabetic}\p{N}]",
+            WordChars::Class { source, .. } => source,
+        }
+    }
+
+    /// A regex class of the characters that are not word characters, kept
+    /// case-sensitive whatever the flags of the pattern around it.
+    fn negated_class(&self) -> String {
+        match self {
+            WordChars::Alphanumeric => r"[^\p{Alphabetic}\p{N}]".to_string(),
+            WordChars::Class { class, .. } => {
+                let mut negated = class.clone();
+                negated.negate();
+                format!("(?-i:{})", Hir::class(negated))
+            }
+        }
+    }
+}
+
+/// Rewrites `pattern` so that it only matches where it is followed by the
+/// end of the text or a character that is not a word character, which it
+/// consumes: the empty group after the pattern marks where the match of
+/// `pattern` itself ends.
+fn bounded_pattern(pattern: &str, word_chars: &WordChars) -> String {
+    format!(r"(?:{})()(?:\z|{})", pattern, word_chars.negated_class())
+}
+
+/// Compiles the pattern that retries rejected candidates with the flags of
+/// the inner matcher, or `None` when the rewritten pattern does not compile.
+pub fn compile_bounded(pattern: &str, word_chars: &WordChars, ignore_case: bool, multiline: bool, unicode: bool) -> Option<meta::Regex> {
+    let bounded = bounded_pattern(pattern, word_chars);
+    let bounded = if unicode { bounded } else { engine::ascii_pattern(&bounded) };
+    meta::Regex::builder()
+        .syntax(syntax::Config::new().unicode(unicode).case_insensitive(ignore_case).multi_line(multiline).crlf(multiline))
+        .build(&bounded)
+        .ok()
+}
+
+/// Rewrites `pattern` for PCRE2 so that it only matches between characters
+/// that are not word characters.
+pub fn look_around_pattern(pattern: &str, word_chars: &WordChars) -> String {
+    let class = word_chars.class();
+    format!("(?<!{})(?:{})(?!{})", class, pattern, class)
+}
+
+/// Restricts another matcher to matches that are not directly preceded or
+/// followed by a word character.
+pub struct WordMatcher<M> {
+    inner: M,
+    word_chars: WordChars,
+    /// The pattern compiled by `compile_bounded`, to retry a candidate
+    /// glued to a word character with the other ways the pattern can match
+    /// at the same start, as `foobar` for `foo|foobar`.
+    bounded: Option<meta::Regex>,
+}
+
+impl<M: Matcher> WordMatcher<M> {
+    pub fn new(inner: M, word_chars: WordChars) -> WordMatcher<M> {
+        WordMatcher { inner, word_chars, bounded: None }
+    }
+
+    /// Retries rejected candidates with `bounded`, the pattern compiled by
+    /// `compile_bounded`.
+    pub fn with_bounded(self, bounded: meta::Regex) -> WordMatcher<M> {
+        WordMatcher { bounded: Some(bounded), ..self }
+    }
+
+    fn is_word_boundary(&self, haystack: &str, span: &Range<usize>) -> bool {
+        let before = haystack[..span.start].chars().next_back();
+        let after = haystack[span.end..].chars().next();
+        !before.is_some_and(|c| self.word_chars.contains(c)) && !after.is_some_and(|c| self.word_chars.contains(c))
+    }
+
+    /// The match of the bounded pattern that starts right at `start`, up to
+    /// the end of the pattern itself, if it is not preceded by a word
+    /// character either.
+    fn bounded_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        let bounded = self.bounded.as_ref()?;
+        // Anchored, so that a rejected candidate does not scan the rest of
+        // the text, which is the whole file with -U.
+        let input = Input::new(haystack).range(start..).anchored(Anchored::Yes);
+        let mut captures = bounded.create_captures();
+        bounded.search_captures(&input, &mut captures);
+        let span = start..captures.get_group(bounded.captures_len() - 1)?.end;
+        self.is_word_boundary(haystack, &span).then_some(span)
+    }
+}
+
+impl<M: Matcher> Matcher for WordMatcher<M> {
+    fn as_str(&self) -> &str {
+        self.inner.as_str()
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while start <= haystack.len() {
+            let Some(span) = self.find_at(haystack, start) else { break };
+            start = if span.is_empty() { next_char_boundary(haystack, span.end) } else { span.end };
+            spans.push(span);
+        }
+        spans
+    }
+
+    fn find_at(&self, haystack: &str, mut start: usize) -> Option<Range<usize>> {
+        // A candidate glued to a word character is rejected, and the search
+        // resumes just after its start so shorter or later matches get a chance.
+        while start <= haystack.len() {
+            let span = self.inner.find_at(haystack, start)?;
+            if self.is_word_boundary(haystack, &span) {
+                return Some(span);
+            }
+            if let Some(span) = self.bounded_at(haystack, span.start) {
+                return Some(span);
+            }
+            start = next_char_boundary(haystack, span.start);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    fn word_with(pattern: &str, word_chars: WordChars) -> WordMatcher<Regex> {
+        let bounded = compile_bounded(pattern, &word_chars, false, false, true).unwrap();
+        WordMatcher::new(Regex::new(pattern).unwrap(), word_chars).with_bounded(bounded)
+    }
+
+    fn word(pattern: &str) -> WordMatcher<Regex> {
+        word_with(pattern, WordChars::Alphanumeric)
+    }
+
+    #[test]
+    fn test_identifier_boundaries() {
+        let matcher = word("id");
+        assert!(!matcher.is_match("let uuid = 1;"));
+        assert!(matcher.is_match("user_id"));
+        assert!(matcher.is_match("id"));
+        assert_eq!(matcher.find_spans("uuid id idx user_id"), vec![5..7, 17..19]);
+    }
+
+    #[test]
+    fn test_unicode_letters_are_word_chars() {
+        assert!(!word("ete").is_match("été"));
+        assert!(word("été").is_match("l'été dernier"));
+    }
+
+    #[test]
+    fn test_retries_after_rejected_candidate() {
+        // The leftmost candidate "ab" is glued to "x"; the later one is not.
+        assert_eq!(word("ab").find_spans("xab ab"), vec![4..6]);
+    }
+
+    #[test]
+    fn test_retries_other_alternatives_at_same_start() {
+        // `foo` comes first in the alternation but is glued to `bar`.
+        assert_eq!(word("foo|foobar").find_spans("foobar foo foobarbaz"), vec![0..6, 7..10]);
+        assert_eq!(word("fo|foo!?").find_spans("foo!"), vec![0..4]);
+        assert!(!word("foo|foobar").is_match("xfoobar"));
+        let classic = word_with("foo|foobar", WordChars::from_class("[A-Za-z0-9_]").unwrap());
+        assert!(!classic.is_match("foobar_x"));
+        assert_eq!(classic.find_spans("foobar-x"), vec![0..6]);
+    }
+
+    #[test]
+    fn test_custom_word_chars() {
+        let classic = WordMatcher::new(Regex::new("id").unwrap(), WordChars::from_class("[A-Za-z0-9_]").unwrap());
+        assert!(!classic.is_match("user_id"));
+        assert!(classic.is_match("user-id"));
+    }
+
+    #[test]
+    fn test_word_chars_class() {
+        let dollar = WordChars::from_class(r"[\w$]").unwrap();
+        assert!(dollar.contains('$') && dollar.contains('_') && !dollar.contains('-'));
+        assert!(WordChars::from_class("[_]").unwrap().contains('_'));
+        assert!(WordChars::from_class(r"\w|\$").is_err());
+        assert!(WordChars::from_class("[a-z]|_").is_err());
+        assert!(WordChars::from_class("[a-z").is_err());
+        // The class stays case-sensitive in a case-insensitive pattern.
+        let lower = WordChars::from_class("[a-z]").unwrap();
+        let matcher = WordMatcher::new(Regex::new("(?i)foo|foobar").unwrap(), lower.clone())
+            .with_bounded(compile_bounded("foo|foobar", &lower, true, false, true).unwrap());
+        assert_eq!(matcher.find_spans("FOObar FOOBAR"), vec![0..6, 7..10]);
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::Args as ClapArgs;
+
+use crate::error::FinderError;
+use crate::json::json_string;
+use crate::unused::{self, Occurrence};
+
+/// How `finder xref` exports its table.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum XrefFormat {
+    /// One JSON object per identifier, with its definitions and usages
+    #[default]
+    Json,
+    /// One `id,kind,path,line` row per occurrence
+    Csv,
+}
+
+/// Arguments of `finder xref`.
+#[derive(ClapArgs, Debug)]
+pub struct XrefArgs {
+    /// The path(s) to search in (files or directories)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Regex matching a definition, with the identifier in its first capture group (or in a group named `id`)
+    #[arg(long, value_name = "REGEX")]
+    pub defs: String,
+
+    /// Regex matching a usage, with `{}` standing for the identifier
+    #[arg(long, value_name = "TEMPLATE", default_value = r"\b{}\b")]
+    pub uses: String,
+
+    /// Export format
+    #[arg(long, value_enum, default_value_t = XrefFormat::Json)]
+    pub format: XrefFormat,
+}
+
+/// The definitions and usages of one identifier.
+#[derive(Debug, Default)]
+pub struct Entry<'a> {
+    pub definitions: Vec<&'a Occurrence>,
+    pub usages: Vec<&'a Occurrence>,
+}
+
+/// Groups the occurrences by identifier, in alphabetical order.
+pub fn cross_reference<'a>(definitions: &'a [Occurrence], usages: &'a [Occurrence]) -> BTreeMap<&'a str, Entry<'a>> {
+    let mut table: BTreeMap<&str, Entry> = BTreeMap::new();
+    for definition in definitions {
+        table.entry(&definition.id).or_default().definitions.push(definition);
+    }
+    for usage in usages {
+        table.entry(&usage.id).or_default().usages.push(usage);
+    }
+    table
+}
+
+fn json_locations(occurrences: &[&Occurrence]) -> String {
+    let locations: Vec<String> = occurrences.iter()
+        .map(|occurrence| format!(
+            "{{\"path\":{},\"line\":{}}}",
+            json_string(&occurrence.path.to_string_lossy()),
+            occurrence.line_number,
+        ))
+        .collect();
+    format!("[{}]", locations.join(","))
+}
+
+/// Writes the table as a JSON array, one object per identifier.
+pub fn write_json(out: &mut dyn Write, table: &BTreeMap<&str, Entry>) -> io::Result<()> {
+    let entries: Vec<String> = table.iter()
+        .map(|(id, entry)| format!(
+            "{{\"id\":{},\"definitions\":{},\"usages\":{}}}",
+            json_string(id),
+            json_locations(&entry.definitions),
+            json_locations(&entry.usages),
+        ))
+        .collect();
+    writeln!(out, "[{}]", entries.join(","))
+}
+
+/// Quotes a CSV field if it needs to be (RFC 4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the table as CSV with a header row, definitions before usages.
+pub fn write_csv(out: &mut dyn Write, table: &BTreeMap<&str, Entry>) -> io::Result<()> {
+    writeln!(out, "id,kind,path,line")?;
+    for (id, entry) in table {
+        let rows = entry.definitions.iter().map(|occurrence| ("definition", occurrence))
+            .chain(entry.usages.iter().map(|occurrence| ("usage", occurrence)));
+        for (kind, occurrence) in rows {
+            writeln!(
+                out,
+                "{},{},{},{}",
+                csv_field(id),
+                kind,
+                csv_field(&occurrence.path.to_string_lossy()),
+                occurrence.line_number,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: XrefArgs) -> Result<(), FinderError> {
+    let (definitions, usages) = unused::find_occurrences(&args.paths, &args.defs, &args.uses)?;
+    let table = cross_reference(&definitions, &usages);
+    let mut out = io::stdout().lock();
+    match args.format {
+        XrefFormat::Json => write_json(&mut out, &table)?,
+        XrefFormat::Csv => write_csv(&mut out, &table)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occurrence(id: &str, path: &str, line_number: usize) -> Occurrence {
+        Occurrence { id: id.to_string(), path: PathBuf::from(path), line_number }
+    }
+
+    #[test]
+    fn test_cross_reference_exports() {
+        let definitions = [occurrence("beta", "flags.rs", 2), occurrence("alpha", "flags.rs", 1)];
+        let usages = [occurrence("alpha", "a,b.rs", 7)];
+        let table = cross_reference(&definitions, &usages);
+
+        let mut out = Vec::new();
+        write_json(&mut out, &table).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"[{"id":"alpha","definitions":[{"path":"flags.rs","line":1}],"usages":[{"path":"a,b.rs","line":7}]},"#,
+            r#"{"id":"beta","definitions":[{"path":"flags.rs","line":2}],"usages":[]}]"#,
+            "\n",
+        ));
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &table).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+id,kind,path,line
+alpha,definition,flags.rs,1
+alpha,usage,\"a,b.rs\",7
+beta,definition,flags.rs,2
+");
+    }
+}